@@ -0,0 +1,5 @@
+create table if not exists user_password_resets (
+    id uuid default gen_random_uuid() primary key,
+    user_id bigint not null references users (id) on delete cascade,
+    created_at timestamp not null default now()
+);
@@ -0,0 +1,7 @@
+create table if not exists oidc_clients (
+    id uuid default gen_random_uuid() primary key,
+    service_name text not null references services (name) on delete cascade,
+    client_secret_hash text not null,
+    redirect_uris text[] not null,
+    created_at timestamp not null default now()
+);
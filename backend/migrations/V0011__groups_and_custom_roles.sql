@@ -0,0 +1,18 @@
+create table if not exists roles (
+    name text primary key,
+    permissions jsonb not null
+);
+
+alter table users add column custom_role text references roles (name) on delete set null;
+
+create table if not exists groups (
+    name text primary key
+);
+
+create table if not exists group_members (
+    group_name text not null references groups (name) on delete cascade,
+    user_id bigint not null references users (id) on delete cascade,
+    primary key (group_name, user_id)
+);
+
+alter table services add column access_list jsonb;
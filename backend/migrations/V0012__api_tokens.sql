@@ -0,0 +1,10 @@
+create table if not exists api_tokens (
+    id uuid default gen_random_uuid() primary key,
+    user_id bigint not null references users (id) on delete cascade,
+    name text not null,
+    token_hash text not null,
+    permissions jsonb not null default '[]',
+    expires_at timestamp not null,
+    last_used_at timestamp,
+    created_at timestamp not null default now()
+);
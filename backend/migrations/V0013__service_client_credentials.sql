@@ -0,0 +1 @@
+alter table services add column if not exists client_secret text not null default replace(gen_random_uuid()::text || gen_random_uuid()::text, '-', '');
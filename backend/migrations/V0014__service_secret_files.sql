@@ -0,0 +1,2 @@
+alter table services add column if not exists internal_secrets_as_files boolean not null default false;
+alter table services add column if not exists internal_secrets_owner_id integer;
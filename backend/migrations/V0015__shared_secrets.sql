@@ -0,0 +1,9 @@
+create table if not exists shared_secrets (
+    name text not null,
+    version integer not null,
+    value text not null,
+    created_at timestamp not null default now(),
+    primary key (name, version)
+);
+
+alter table services add column shared_secrets jsonb not null default '[]';
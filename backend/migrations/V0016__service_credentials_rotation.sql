@@ -0,0 +1,2 @@
+alter table services add column if not exists postgres_login_username text;
+alter table services add column if not exists credentials_rotated_at timestamp not null default now();
@@ -0,0 +1,13 @@
+create type backup_storage as enum ('Local', 'S3');
+create type backup_trigger as enum ('Scheduled', 'Manual', 'PreRestore');
+
+create table if not exists backups (
+    id uuid default gen_random_uuid() primary key,
+    service_name text not null references services (name) on delete cascade,
+    storage backup_storage not null,
+    trigger backup_trigger not null,
+    size_bytes bigint not null,
+    created_at timestamp not null default now()
+);
+
+alter table services add column backup_policy jsonb;
@@ -0,0 +1,15 @@
+create table if not exists volume_snapshots (
+    id uuid default gen_random_uuid() primary key,
+    service_name text not null references services (name) on delete cascade,
+    volume_path text not null,
+    storage backup_storage not null,
+    size_bytes bigint not null,
+    created_at timestamp not null default now()
+);
+
+create table if not exists deleted_volumes (
+    volume_id text primary key,
+    service_name text not null,
+    volume_path text not null,
+    deleted_at timestamp not null default now()
+);
@@ -0,0 +1,7 @@
+create table if not exists shared_volumes (
+    name text primary key,
+    created_at timestamp not null default now()
+);
+
+alter table services add column shared_volumes jsonb not null default '[]';
+alter table services add column bind_mounts jsonb not null default '[]';
@@ -0,0 +1,7 @@
+create table if not exists registry_credentials (
+    registry text primary key,
+    username text not null,
+    password text not null,
+    created_at timestamp not null default now(),
+    last_modified_at timestamp not null default now()
+);
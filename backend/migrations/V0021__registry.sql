@@ -0,0 +1,25 @@
+create table if not exists registry_blobs (
+    repository text not null,
+    digest text not null,
+    size_bytes bigint not null,
+    created_at timestamp not null default now(),
+    primary key (repository, digest)
+);
+
+create table if not exists registry_manifests (
+    repository text not null,
+    digest text not null,
+    media_type text not null,
+    referenced_digests text[] not null default '{}',
+    created_at timestamp not null default now(),
+    primary key (repository, digest)
+);
+
+create table if not exists registry_tags (
+    repository text not null,
+    tag text not null,
+    digest text not null,
+    updated_at timestamp not null default now(),
+    primary key (repository, tag),
+    foreign key (repository, digest) references registry_manifests (repository, digest) on delete cascade
+);
@@ -0,0 +1 @@
+alter table services add column deploy_policy jsonb;
@@ -0,0 +1 @@
+alter table services add column source_repository jsonb;
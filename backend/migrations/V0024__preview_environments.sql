@@ -0,0 +1,8 @@
+create table if not exists preview_environments (
+    name text primary key references services (name) on delete cascade,
+    parent_service_name text not null references services (name) on delete cascade,
+    pull_request integer not null,
+    created_at timestamp not null default now(),
+    last_deployed_at timestamp not null default now(),
+    unique (parent_service_name, pull_request)
+);
@@ -0,0 +1,22 @@
+create type image_pull_policy as enum ('Always', 'IfNotPresent');
+create type deployment_trigger as enum ('Manual', 'Ci', 'Preview', 'ImageUpdate');
+
+alter table services add column pull_policy image_pull_policy not null default 'Always';
+alter table services add column image_update_policy jsonb;
+
+create table if not exists deployments (
+    id uuid default gen_random_uuid() primary key,
+    service_name text not null references services (name) on delete cascade,
+    image_sha text not null,
+    previous_image_sha text,
+    trigger deployment_trigger not null,
+    created_at timestamp not null default now()
+);
+
+create table if not exists tracked_image_tags (
+    service_name text primary key references services (name) on delete cascade,
+    tag text not null,
+    image_sha text,
+    error text,
+    checked_at timestamp not null default now()
+);
@@ -0,0 +1 @@
+alter table services add column private_network jsonb;
@@ -0,0 +1 @@
+alter table services add column sidecars jsonb not null default '[]';
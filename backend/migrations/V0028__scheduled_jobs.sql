@@ -0,0 +1,16 @@
+create type job_run_status as enum ('Running', 'Succeeded', 'Failed', 'TimedOut', 'Cancelled', 'Skipped');
+
+alter table services add column scheduled_jobs jsonb not null default '[]';
+
+create table if not exists job_runs (
+    id uuid default gen_random_uuid() primary key,
+    service_name text not null references services (name) on delete cascade,
+    job_name text not null,
+    status job_run_status not null,
+    exit_code integer,
+    logs text,
+    error text,
+    scheduled_at timestamp,
+    started_at timestamp not null default now(),
+    finished_at timestamp
+);
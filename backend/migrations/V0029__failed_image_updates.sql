@@ -0,0 +1 @@
+alter table tracked_image_tags add column failed_image_sha text;
@@ -0,0 +1,2 @@
+alter table backups drop constraint if exists backups_service_name_fkey;
+alter table volume_snapshots drop constraint if exists volume_snapshots_service_name_fkey;
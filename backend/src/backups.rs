@@ -0,0 +1,296 @@
+use uuid::Uuid;
+
+use crate::deployments::redeploy_service;
+use crate::error::Error;
+use crate::managers::backup::models::BackupTrigger;
+use crate::managers::db::models::{Backup, VolumeSnapshot};
+use crate::models::ByteStream;
+use crate::state::AppState;
+
+static POSTGRES_CONTAINER_NAME: &str = "kiwi-postgres";
+
+pub async fn create_service_backup(
+    state: &AppState,
+    name: &str,
+    trigger: BackupTrigger,
+) -> Result<Backup, Error> {
+    let service = state
+        .db_manager
+        .get_service_data(name)
+        .await?
+        .ok_or(Error::service_not_found())?;
+    let database_name = service.internal_configuration.postgres_username;
+
+    let archive = state
+        .container_manager
+        .stream_command_output(
+            POSTGRES_CONTAINER_NAME,
+            vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                "pg_dump --username=\"$POSTGRES_USER\" --format=custom --no-owner --no-privileges --dbname=\"$1\"".to_string(),
+                "sh".to_string(),
+                database_name,
+            ],
+        )
+        .await?;
+
+    let id = Uuid::new_v4();
+    let storage_key = Backup::get_storage_key(name, &id);
+    let size_bytes = state.backup_manager.store(&storage_key, archive).await? as i64;
+    let backup = state
+        .db_manager
+        .create_backup(
+            &id,
+            name,
+            &state.backup_manager.storage_kind(),
+            &trigger,
+            size_bytes,
+        )
+        .await;
+
+    match backup {
+        Ok(backup) => {
+            tracing::info!("created backup {} of service {}", backup.id, name);
+            Ok(backup)
+        }
+        Err(error) => {
+            state.backup_manager.delete(&storage_key).await?;
+            Err(error)
+        }
+    }
+}
+
+pub async fn delete_service_backup(state: &AppState, backup: &Backup) -> Result<(), Error> {
+    if backup.storage != state.backup_manager.storage_kind() {
+        return Err(Error::backup_storage_mismatch(&backup.storage.to_string()));
+    }
+
+    state.backup_manager.delete(&backup.storage_key()).await?;
+    state.db_manager.delete_backup(&backup.id).await?;
+
+    Ok(())
+}
+
+pub async fn apply_backup_retention(
+    state: &AppState,
+    name: &str,
+    retention_count: u32,
+) -> Result<(), Error> {
+    let expired_backups = state
+        .db_manager
+        .get_expired_backups(name, retention_count as i64)
+        .await?;
+    for backup in expired_backups.iter() {
+        delete_service_backup(state, backup).await?;
+    }
+
+    if !expired_backups.is_empty() {
+        tracing::info!(
+            "removed {} expired backups of service {}",
+            expired_backups.len(),
+            name
+        );
+    }
+
+    Ok(())
+}
+
+pub async fn restore_service_backup(
+    state: &AppState,
+    name: &str,
+    backup: &Backup,
+) -> Result<Backup, Error> {
+    if backup.storage != state.backup_manager.storage_kind() {
+        return Err(Error::backup_storage_mismatch(&backup.storage.to_string()));
+    }
+
+    let pre_restore_backup = create_service_backup(state, name, BackupTrigger::PreRestore).await?;
+    let archive = state.backup_manager.read(&backup.storage_key()).await?;
+    let service = state
+        .db_manager
+        .get_service_data(name)
+        .await?
+        .ok_or(Error::service_not_found())?;
+
+    state
+        .container_manager
+        .stop_and_remove_container(name)
+        .await?;
+    let restore_result = state
+        .container_manager
+        .execute_command(
+            POSTGRES_CONTAINER_NAME,
+            vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                "pg_restore --username=\"$POSTGRES_USER\" --clean --if-exists --no-owner --no-privileges --single-transaction --role=\"$2\" --dbname=\"$1\"".to_string(),
+                "sh".to_string(),
+                service.internal_configuration.postgres_username.clone(),
+                service.internal_configuration.postgres_username.clone(),
+            ],
+            Some(archive),
+        )
+        .await;
+    // Redeploying can fail as well, which would hide why the restore failed
+    if let Err(error) = &restore_result {
+        tracing::error!(
+            "failed to restore backup {} of service {}: {}",
+            backup.id,
+            name,
+            error
+        );
+    }
+    redeploy_service(state, &service.container_configuration).await?;
+    restore_result?;
+
+    tracing::info!("restored backup {} of service {}", backup.id, name);
+
+    Ok(pre_restore_backup)
+}
+
+pub async fn create_volume_snapshot(
+    state: &AppState,
+    name: &str,
+    volume_path: &str,
+) -> Result<VolumeSnapshot, Error> {
+    let service = state
+        .db_manager
+        .get_service_data(name)
+        .await?
+        .ok_or(Error::service_not_found())?;
+    let volume_path = volume_path.to_string();
+    if !service
+        .container_configuration
+        .stateful_volume_paths
+        .contains(&volume_path)
+    {
+        return Err(Error::volume_not_found(&volume_path));
+    }
+
+    let archive = state
+        .container_manager
+        .export_volume(
+            &service
+                .container_configuration
+                .get_stateful_volume_id(&volume_path),
+        )
+        .await?;
+    store_volume_snapshot(state, name, &volume_path, archive).await
+}
+
+pub async fn store_volume_snapshot(
+    state: &AppState,
+    name: &str,
+    volume_path: &str,
+    archive: ByteStream,
+) -> Result<VolumeSnapshot, Error> {
+    let id = Uuid::new_v4();
+    let storage_key = VolumeSnapshot::get_storage_key(name, &id);
+    let size_bytes = state.backup_manager.store(&storage_key, archive).await? as i64;
+    let volume_snapshot = state
+        .db_manager
+        .create_volume_snapshot(
+            &id,
+            name,
+            volume_path,
+            &state.backup_manager.storage_kind(),
+            size_bytes,
+        )
+        .await;
+
+    match volume_snapshot {
+        Ok(volume_snapshot) => {
+            tracing::info!(
+                "created snapshot {} of volume {} of service {}",
+                volume_snapshot.id,
+                volume_path,
+                name
+            );
+            Ok(volume_snapshot)
+        }
+        Err(error) => {
+            state.backup_manager.delete(&storage_key).await?;
+            Err(error)
+        }
+    }
+}
+
+pub async fn delete_volume_snapshot(
+    state: &AppState,
+    volume_snapshot: &VolumeSnapshot,
+) -> Result<(), Error> {
+    if volume_snapshot.storage != state.backup_manager.storage_kind() {
+        return Err(Error::backup_storage_mismatch(
+            &volume_snapshot.storage.to_string(),
+        ));
+    }
+
+    state
+        .backup_manager
+        .delete(&volume_snapshot.storage_key())
+        .await?;
+    state
+        .db_manager
+        .delete_volume_snapshot(&volume_snapshot.id)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn restore_volume_snapshot(
+    state: &AppState,
+    name: &str,
+    volume_snapshot: &VolumeSnapshot,
+) -> Result<VolumeSnapshot, Error> {
+    if volume_snapshot.storage != state.backup_manager.storage_kind() {
+        return Err(Error::backup_storage_mismatch(
+            &volume_snapshot.storage.to_string(),
+        ));
+    }
+
+    let pre_restore_snapshot =
+        create_volume_snapshot(state, name, &volume_snapshot.volume_path).await?;
+    let archive = state
+        .backup_manager
+        .read(&volume_snapshot.storage_key())
+        .await?;
+    let service = state
+        .db_manager
+        .get_service_data(name)
+        .await?
+        .ok_or(Error::service_not_found())?;
+
+    state
+        .container_manager
+        .stop_and_remove_container(name)
+        .await?;
+    let import_result = state
+        .container_manager
+        .import_volume(
+            &service
+                .container_configuration
+                .get_stateful_volume_id(&volume_snapshot.volume_path),
+            archive,
+        )
+        .await;
+    if let Err(error) = &import_result {
+        tracing::error!(
+            "failed to restore volume snapshot {} of service {}: {}",
+            volume_snapshot.id,
+            name,
+            error
+        );
+    }
+    redeploy_service(state, &service.container_configuration).await?;
+    import_result?;
+
+    tracing::info!(
+        "restored snapshot {} of volume {} of service {}",
+        volume_snapshot.id,
+        volume_snapshot.volume_path,
+        name
+    );
+
+    Ok(pre_restore_snapshot)
+}
@@ -0,0 +1,751 @@
+use std::collections::{HashMap, HashSet};
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::deployments::{
+    deploy_new_service, find_free_port, get_used_service_ports, tear_down_service,
+};
+use crate::error::Error;
+use crate::managers::container::models::{
+    BindMount, ContainerConfiguration, DeploymentTrigger, EnvironmentVariable, ExposedPort,
+    ImagePullPolicy, ImageSha, ImageUpdateMode, ImageUpdatePolicy, PrivateNetworkMembership,
+    SharedVolumeMount, default_image_update_check_interval_minutes, get_shared_volume_id,
+};
+use crate::models::UserRole;
+use crate::state::AppState;
+
+static FIRST_COMPOSE_PORT: u16 = 41000;
+static LAST_COMPOSE_PORT: u16 = 41999;
+static DEFAULT_INTERNAL_PORT: u16 = 80;
+static DEFAULT_IMAGE_TAG: &str = "latest";
+static MAX_SERVICE_NAME_LENGTH: usize = 32;
+static SUPPORTED_TOP_LEVEL_KEYS: [&str; 5] = ["name", "version", "services", "volumes", "networks"];
+static SUPPORTED_SERVICE_KEYS: [&str; 7] = [
+    "image",
+    "environment",
+    "ports",
+    "expose",
+    "volumes",
+    "depends_on",
+    "networks",
+];
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UnsupportedComposeFeature {
+    pub service: Option<String>,
+    pub feature: String,
+    pub reason: String,
+}
+
+pub struct ComposeProject {
+    pub services: Vec<ContainerConfiguration>,
+    pub shared_volumes: Vec<String>,
+    pub unsupported_features: Vec<UnsupportedComposeFeature>,
+}
+
+enum ComposeVolume {
+    Named {
+        source: String,
+        target: String,
+        read_only: bool,
+    },
+    Anonymous {
+        target: String,
+    },
+    Bind {
+        source: String,
+        target: String,
+        read_only: bool,
+    },
+    Unsupported {
+        kind: String,
+    },
+}
+
+pub fn parse_compose_file(compose_file: &str) -> Result<Value, Error> {
+    serde_yaml::from_str(compose_file)
+        .map_err(|error| Error::invalid_compose_file(&format!("cannot parse YAML, {}", error)))
+}
+
+/// Services keep reaching each other by their compose names on the private network of the project.
+pub async fn translate_compose_file(
+    state: &AppState,
+    project_name: Option<String>,
+    compose_file: &Value,
+) -> Result<ComposeProject, Error> {
+    let compose_file = compose_file
+        .as_object()
+        .ok_or(Error::invalid_compose_file("expected a mapping"))?;
+    let project_name = project_name
+        .or(compose_file
+            .get("name")
+            .and_then(Value::as_str)
+            .map(str::to_string))
+        .ok_or(Error::invalid_compose_file("missing project name"))?;
+    if !Regex::new(r"^[a-zA-Z0-9][a-zA-Z0-9_-]*$")?.is_match(&project_name) {
+        return Err(Error::invalid_compose_file("invalid project name"));
+    }
+    let services = compose_file
+        .get("services")
+        .and_then(Value::as_object)
+        .ok_or(Error::invalid_compose_file("missing services"))?;
+
+    let mut unsupported_features = vec![];
+    for key in compose_file.keys() {
+        if !SUPPORTED_TOP_LEVEL_KEYS.contains(&key.as_str()) && !key.starts_with("x-") {
+            unsupported_features.push(UnsupportedComposeFeature {
+                service: None,
+                feature: key.clone(),
+                reason: "top-level key is not supported and was ignored".to_string(),
+            });
+        }
+    }
+    for (network_name, network) in get_object(compose_file, "networks") {
+        if network_name != "default" || !is_empty_definition(network) {
+            unsupported_features.push(UnsupportedComposeFeature {
+                service: None,
+                feature: format!("networks.{}", network_name),
+                reason: "all services of the project share a single private network".to_string(),
+            });
+        }
+    }
+    for (volume_name, volume) in get_object(compose_file, "volumes") {
+        let has_options = volume.as_object().is_some_and(|volume| {
+            volume
+                .keys()
+                .any(|key| key != "name" && !key.starts_with("x-"))
+        });
+        if has_options {
+            unsupported_features.push(UnsupportedComposeFeature {
+                service: None,
+                feature: format!("volumes.{}", volume_name),
+                reason: "volume drivers and external volumes are not supported, a new empty volume is used"
+                    .to_string(),
+            });
+        }
+    }
+
+    let mut named_volume_users: HashMap<String, usize> = HashMap::new();
+    for service in services.values() {
+        let service_volume_names: HashSet<String> = get_array(service, "volumes")
+            .iter()
+            .filter_map(|volume| match parse_volume(volume) {
+                Some(ComposeVolume::Named { source, .. }) => Some(source),
+                _ => None,
+            })
+            .collect();
+        for volume_name in service_volume_names {
+            *named_volume_users.entry(volume_name).or_default() += 1;
+        }
+    }
+
+    let mut used_ports = get_used_service_ports(state).await?;
+    let mut published_ports = HashSet::new();
+    let mut shared_volumes = HashSet::new();
+    let mut configurations = vec![];
+    for service_name in sort_by_dependencies(services)? {
+        let service = &services[&service_name];
+        let mut report = |feature: String, reason: &str| {
+            unsupported_features.push(UnsupportedComposeFeature {
+                service: Some(service_name.clone()),
+                feature,
+                reason: reason.to_string(),
+            });
+        };
+
+        let name = format!("{}-{}", project_name, service_name);
+        if name.len() > MAX_SERVICE_NAME_LENGTH {
+            return Err(Error::invalid_compose_file(&format!(
+                "service name {} is longer than {} characters",
+                name, MAX_SERVICE_NAME_LENGTH
+            )));
+        }
+        for key in service.as_object().into_iter().flat_map(Map::keys) {
+            if !SUPPORTED_SERVICE_KEYS.contains(&key.as_str()) && !key.starts_with("x-") {
+                report(key.clone(), "not supported, ignored");
+            }
+        }
+        let Some(image) = service.get("image").and_then(Value::as_str) else {
+            report(
+                "image".to_string(),
+                "services must use an image pushed to a registry, skipped",
+            );
+            continue;
+        };
+
+        let (image_name, image_sha, image_update_policy) = resolve_image(state, image).await?;
+
+        let mut environment_variables = vec![];
+        match service.get("environment") {
+            Some(Value::Object(variables)) => {
+                for (variable_name, value) in variables {
+                    match value {
+                        Value::String(value) => environment_variables
+                            .push(EnvironmentVariable::new(variable_name, value.clone())),
+                        Value::Number(_) | Value::Bool(_) => environment_variables
+                            .push(EnvironmentVariable::new(variable_name, value.to_string())),
+                        _ => report(
+                            format!("environment.{}", variable_name),
+                            "values taken from the host environment are not supported, skipped",
+                        ),
+                    }
+                }
+            }
+            Some(Value::Array(variables)) => {
+                for variable in variables.iter().filter_map(Value::as_str) {
+                    match variable.split_once('=') {
+                        Some((variable_name, value)) => environment_variables
+                            .push(EnvironmentVariable::new(variable_name, value.to_string())),
+                        None => report(
+                            format!("environment.{}", variable),
+                            "values taken from the host environment are not supported, skipped",
+                        ),
+                    }
+                }
+            }
+            _ => {}
+        }
+        for environment_variable in environment_variables.iter() {
+            if environment_variable.value.contains("${") {
+                report(
+                    format!("environment.{}", environment_variable.name),
+                    "variable interpolation is not supported, run docker compose config first",
+                );
+            }
+        }
+
+        let mut ports = vec![];
+        for port in get_array(service, "ports") {
+            match parse_port(port) {
+                Ok(port) => ports.push(port),
+                Err(reason) => report(format!("ports.{}", display_value(port)), reason),
+            }
+        }
+        if ports.len() > 1 {
+            report(
+                "ports".to_string(),
+                "only the first port is exposed, through the service domain",
+            );
+        }
+        let (published_port, internal_port) = match ports.first() {
+            Some(port) => *port,
+            None => match get_array(service, "expose")
+                .first()
+                .and_then(|port| display_value(port).parse::<u16>().ok())
+            {
+                Some(internal_port) => (None, internal_port),
+                None => {
+                    report(
+                        "ports".to_string(),
+                        "no port is published, the service is exposed on port 80",
+                    );
+                    (None, DEFAULT_INTERNAL_PORT)
+                }
+            },
+        };
+        let external_port = match published_port {
+            Some(published_port) => {
+                if !published_ports.insert(published_port) {
+                    return Err(Error::invalid_compose_file(&format!(
+                        "port {} is published by several services",
+                        published_port
+                    )));
+                }
+                published_port
+            }
+            None => find_free_port(FIRST_COMPOSE_PORT..=LAST_COMPOSE_PORT, &used_ports)
+                .ok_or(Error::no_free_compose_port())?,
+        };
+        used_ports.insert(external_port);
+
+        let mut stateful_volume_paths = vec![];
+        let mut shared_volume_mounts = vec![];
+        let mut bind_mounts = vec![];
+        for volume in get_array(service, "volumes") {
+            match parse_volume(volume) {
+                Some(ComposeVolume::Named {
+                    source,
+                    target,
+                    read_only,
+                }) => {
+                    let is_shared = named_volume_users
+                        .get(&source)
+                        .is_some_and(|users| *users > 1);
+                    if is_shared || read_only {
+                        let shared_volume_name = format!("{}-{}", project_name, source);
+                        if shared_volume_name.len() > MAX_SERVICE_NAME_LENGTH {
+                            return Err(Error::invalid_compose_file(&format!(
+                                "volume name {} is longer than {} characters",
+                                shared_volume_name, MAX_SERVICE_NAME_LENGTH
+                            )));
+                        }
+                        shared_volumes.insert(shared_volume_name.clone());
+                        shared_volume_mounts.push(SharedVolumeMount {
+                            name: shared_volume_name,
+                            path: target,
+                            read_only,
+                        });
+                    } else {
+                        stateful_volume_paths.push(target);
+                    }
+                }
+                Some(ComposeVolume::Anonymous { target }) => stateful_volume_paths.push(target),
+                Some(ComposeVolume::Bind {
+                    source,
+                    target,
+                    read_only,
+                }) => {
+                    if source.starts_with('/') {
+                        bind_mounts.push(BindMount {
+                            host_path: source,
+                            path: target,
+                            read_only,
+                        });
+                    } else {
+                        report(
+                            format!("volumes.{}", source),
+                            "relative host paths are not supported, run docker compose config first",
+                        );
+                    }
+                }
+                Some(ComposeVolume::Unsupported { kind }) => report(
+                    format!("volumes.{}", display_value(volume)),
+                    &format!("{} volumes are not supported, skipped", kind),
+                ),
+                None => report(
+                    format!("volumes.{}", display_value(volume)),
+                    "cannot parse volume, skipped",
+                ),
+            }
+        }
+
+        for (dependency, condition) in get_dependencies(service) {
+            if !services.contains_key(&dependency) {
+                report(
+                    format!("depends_on.{}", dependency),
+                    "unknown service, ignored",
+                );
+            } else if condition.is_some_and(|condition| condition != "service_started") {
+                report(
+                    format!("depends_on.{}", dependency),
+                    "services are started in dependency order, without waiting for them to be healthy",
+                );
+            }
+        }
+        let has_custom_networks = match service.get("networks") {
+            Some(Value::Array(networks)) => networks
+                .iter()
+                .any(|network| network.as_str() != Some("default")),
+            Some(Value::Object(networks)) => networks.iter().any(|(network_name, network)| {
+                network_name != "default" || !is_empty_definition(network)
+            }),
+            _ => false,
+        };
+        if has_custom_networks {
+            report(
+                "networks".to_string(),
+                "all services of the project share a single private network",
+            );
+        }
+
+        configurations.push(ContainerConfiguration {
+            name,
+            image_name: Some(image_name),
+            image_sha,
+            exposed_port: ExposedPort {
+                internal: internal_port,
+                external: external_port,
+            },
+            environment_variables,
+            secrets: vec![],
+            shared_secrets: vec![],
+            internal_secrets: vec![],
+            stateful_volume_paths,
+            github_repository: None,
+            source_repository: None,
+            required_role: Some(UserRole::Admin),
+            access_list: None,
+            internal_secrets_as_files: false,
+            internal_secrets_owner_id: None,
+            backup_policy: None,
+            shared_volumes: shared_volume_mounts,
+            bind_mounts,
+            deploy_policy: None,
+            pull_policy: ImagePullPolicy::Always,
+            image_update_policy,
+            private_network: Some(PrivateNetworkMembership {
+                name: project_name.clone(),
+                alias: service_name.clone(),
+            }),
+            sidecars: vec![],
+            scheduled_jobs: vec![],
+            registry_credentials: None,
+        });
+    }
+
+    let mut shared_volumes: Vec<String> = shared_volumes.into_iter().collect();
+    shared_volumes.sort();
+
+    Ok(ComposeProject {
+        services: configurations,
+        shared_volumes,
+        unsupported_features,
+    })
+}
+
+async fn resolve_image(
+    state: &AppState,
+    image: &str,
+) -> Result<(String, ImageSha, Option<ImageUpdatePolicy>), Error> {
+    if let Some((image_name, digest)) = image.split_once("@sha256:") {
+        return Ok((
+            image_name.to_string(),
+            ImageSha::new(digest.to_string())?,
+            None,
+        ));
+    }
+
+    let (image_name, tag) = match image.rsplit_once(':') {
+        Some((image_name, tag)) if !tag.contains('/') => (image_name, tag),
+        _ => (image, DEFAULT_IMAGE_TAG),
+    };
+    let image_reference = format!("{}:{}", image_name, tag);
+    let credentials = state
+        .db_manager
+        .get_image_registry_credentials(image_name)
+        .await?;
+    let image_sha = state
+        .container_manager
+        .get_registry_image_sha(&image_reference, credentials.as_ref())
+        .await?;
+
+    Ok((
+        image_name.to_string(),
+        image_sha,
+        Some(ImageUpdatePolicy {
+            tag: tag.to_string(),
+            mode: ImageUpdateMode::Notify,
+            check_interval_minutes: default_image_update_check_interval_minutes(),
+        }),
+    ))
+}
+
+/// When a service fails to deploy, everything created so far is removed so that the import can be
+/// retried from scratch.
+pub async fn create_compose_project(
+    state: &AppState,
+    project: &ComposeProject,
+    new_shared_volumes: &[String],
+) -> Result<(), Error> {
+    let mut created_shared_volumes = vec![];
+    let mut creation_result = Ok(());
+    for shared_volume in new_shared_volumes {
+        creation_result = create_shared_volume(state, shared_volume).await;
+        if creation_result.is_err() {
+            break;
+        }
+        created_shared_volumes.push(shared_volume);
+    }
+    if creation_result.is_ok() {
+        for configuration in project.services.iter() {
+            creation_result = deploy_new_service(state, configuration, DeploymentTrigger::Manual)
+                .await
+                .map(|_| ());
+            if creation_result.is_err() {
+                break;
+            }
+        }
+    }
+
+    if let Err(error) = creation_result {
+        tracing::warn!("failed to import compose project, rolling back: {}", error);
+        // A service failing to start is already created, so every service is looked up
+        for configuration in project.services.iter().rev() {
+            let is_created = state
+                .db_manager
+                .get_service_data(&configuration.name)
+                .await?
+                .is_some();
+            if is_created
+                && let Err(error) = tear_down_service(state, &configuration.name, true).await
+            {
+                tracing::error!(
+                    "failed to tear down imported service {}: {}",
+                    configuration.name,
+                    error
+                );
+            }
+        }
+        for shared_volume in created_shared_volumes {
+            if let Err(error) = remove_shared_volume(state, shared_volume).await {
+                tracing::error!(
+                    "failed to remove imported shared volume {}: {}",
+                    shared_volume,
+                    error
+                );
+            }
+        }
+        return Err(error);
+    }
+
+    Ok(())
+}
+
+async fn create_shared_volume(state: &AppState, name: &str) -> Result<(), Error> {
+    state
+        .container_manager
+        .create_volume_if_missing(&get_shared_volume_id(name))
+        .await?;
+    state.db_manager.create_shared_volume(name).await?;
+    Ok(())
+}
+
+async fn remove_shared_volume(state: &AppState, name: &str) -> Result<(), Error> {
+    state.db_manager.delete_shared_volume(name).await?;
+    state
+        .container_manager
+        .remove_volume(&get_shared_volume_id(name))
+        .await
+}
+
+fn sort_by_dependencies(services: &Map<String, Value>) -> Result<Vec<String>, Error> {
+    let mut sorted_service_names: Vec<String> = vec![];
+    while sorted_service_names.len() < services.len() {
+        let ready_service_names: Vec<String> = services
+            .iter()
+            .filter(|(service_name, _)| !sorted_service_names.contains(service_name))
+            .filter(|(_, service)| {
+                get_dependencies(service).iter().all(|(dependency, _)| {
+                    sorted_service_names.contains(dependency) || !services.contains_key(dependency)
+                })
+            })
+            .map(|(service_name, _)| service_name.clone())
+            .collect();
+        if ready_service_names.is_empty() {
+            return Err(Error::invalid_compose_file(
+                "services have circular dependencies",
+            ));
+        }
+        sorted_service_names.extend(ready_service_names);
+    }
+
+    Ok(sorted_service_names)
+}
+
+fn get_dependencies(service: &Value) -> Vec<(String, Option<String>)> {
+    match service.get("depends_on") {
+        Some(Value::Array(dependencies)) => dependencies
+            .iter()
+            .filter_map(Value::as_str)
+            .map(|dependency| (dependency.to_string(), None))
+            .collect(),
+        Some(Value::Object(dependencies)) => dependencies
+            .iter()
+            .map(|(dependency, definition)| {
+                let condition = definition
+                    .get("condition")
+                    .and_then(Value::as_str)
+                    .map(str::to_string);
+                (dependency.clone(), condition)
+            })
+            .collect(),
+        _ => vec![],
+    }
+}
+
+fn parse_port(port: &Value) -> Result<(Option<u16>, u16), &'static str> {
+    let (host_ip, published, target, protocol) = match port {
+        Value::Object(port) => (
+            port.get("host_ip").map(display_value),
+            port.get("published").map(display_value),
+            port.get("target").map(display_value).unwrap_or_default(),
+            port.get("protocol").map(display_value),
+        ),
+        _ => {
+            let port = display_value(port);
+            let (port, protocol) = match port.split_once('/') {
+                Some((port, protocol)) => (port.to_string(), Some(protocol.to_string())),
+                None => (port, None),
+            };
+            let mut parts: Vec<String> = port.rsplitn(3, ':').map(str::to_string).collect();
+            parts.reverse();
+            match parts.as_slice() {
+                [target] => (None, None, target.clone(), protocol),
+                [published, target] => (None, Some(published.clone()), target.clone(), protocol),
+                [host_ip, published, target] => (
+                    Some(host_ip.clone()),
+                    Some(published.clone()),
+                    target.clone(),
+                    protocol,
+                ),
+                _ => return Err("cannot parse port, skipped"),
+            }
+        }
+    };
+
+    if protocol.is_some_and(|protocol| protocol != "tcp") {
+        return Err("only TCP ports are supported, skipped");
+    }
+    if host_ip.is_some_and(|host_ip| !host_ip.is_empty() && host_ip != "127.0.0.1") {
+        return Err("ports are always bound to 127.0.0.1 and served through the service domain");
+    }
+    if target.contains('-') || published.as_ref().is_some_and(|port| port.contains('-')) {
+        return Err("port ranges are not supported, skipped");
+    }
+    let target = target
+        .parse::<u16>()
+        .map_err(|_| "cannot parse port, skipped")?;
+    let published = match published.filter(|published| !published.is_empty()) {
+        Some(published) => Some(
+            published
+                .parse::<u16>()
+                .map_err(|_| "cannot parse port, skipped")?,
+        ),
+        None => None,
+    };
+
+    Ok((published, target))
+}
+
+fn parse_volume(volume: &Value) -> Option<ComposeVolume> {
+    let (kind, source, target, read_only) = match volume {
+        Value::String(volume) => {
+            let parts: Vec<&str> = volume.split(':').collect();
+            match parts.as_slice() {
+                [target] => {
+                    return Some(ComposeVolume::Anonymous {
+                        target: target.to_string(),
+                    });
+                }
+                [source, target] | [source, target, _] => {
+                    let read_only = parts
+                        .get(2)
+                        .is_some_and(|mode| mode.split(',').any(|option| option == "ro"));
+                    let kind = if source.starts_with(['/', '.', '~']) {
+                        "bind"
+                    } else {
+                        "volume"
+                    };
+                    (
+                        kind.to_string(),
+                        Some(source.to_string()),
+                        target.to_string(),
+                        read_only,
+                    )
+                }
+                _ => return None,
+            }
+        }
+        Value::Object(volume) => (
+            volume.get("type").and_then(Value::as_str)?.to_string(),
+            volume
+                .get("source")
+                .and_then(Value::as_str)
+                .filter(|source| !source.is_empty())
+                .map(str::to_string),
+            volume.get("target").and_then(Value::as_str)?.to_string(),
+            volume
+                .get("read_only")
+                .and_then(Value::as_bool)
+                .unwrap_or_default(),
+        ),
+        _ => return None,
+    };
+
+    Some(match (kind.as_str(), source) {
+        ("volume", Some(source)) => ComposeVolume::Named {
+            source,
+            target,
+            read_only,
+        },
+        ("volume", None) => ComposeVolume::Anonymous { target },
+        ("bind", Some(source)) => ComposeVolume::Bind {
+            source,
+            target,
+            read_only,
+        },
+        _ => ComposeVolume::Unsupported { kind },
+    })
+}
+
+fn get_object<'a>(definition: &'a Map<String, Value>, key: &str) -> Vec<(&'a String, &'a Value)> {
+    definition
+        .get(key)
+        .and_then(Value::as_object)
+        .map(|object| object.iter().collect())
+        .unwrap_or_default()
+}
+
+fn get_array<'a>(definition: &'a Value, key: &str) -> &'a [Value] {
+    definition
+        .get(key)
+        .and_then(Value::as_array)
+        .map(Vec::as_slice)
+        .unwrap_or_default()
+}
+
+fn is_empty_definition(definition: &Value) -> bool {
+    match definition {
+        Value::Null => true,
+        Value::Object(definition) => definition
+            .keys()
+            .all(|key| key == "name" || key.starts_with("x-")),
+        _ => false,
+    }
+}
+
+fn display_value(value: &Value) -> String {
+    match value {
+        Value::String(value) => value.clone(),
+        _ => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn parses_yaml_and_json_compose_files() {
+        let yaml_compose_file = "
+name: blog
+services:
+  web:
+    image: nginx:1.27
+    ports:
+      - mode: ingress
+        target: 80
+        published: \"8080\"
+";
+        let json_compose_file = r#"{"name": "blog", "services": {"web": {"image": "nginx:1.27",
+            "ports": [{"mode": "ingress", "target": 80, "published": "8080"}]}}}"#;
+        let expected_compose_file = json!({
+            "name": "blog",
+            "services": {
+                "web": {
+                    "image": "nginx:1.27",
+                    "ports": [{ "mode": "ingress", "target": 80, "published": "8080" }],
+                },
+            },
+        });
+
+        assert_eq!(
+            parse_compose_file(yaml_compose_file).unwrap(),
+            expected_compose_file
+        );
+        assert_eq!(
+            parse_compose_file(json_compose_file).unwrap(),
+            expected_compose_file
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_compose_file() {
+        assert!(parse_compose_file("services: [web").is_err());
+    }
+}
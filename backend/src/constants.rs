@@ -3,3 +3,12 @@ pub static REFRESH_TOKEN_COOKIE_NAME: &str = "__kiwi_refresh_token";
 pub static LOGOUT_REFRESH_TOKEN_COPY_NAME: &str = "__kiwi_logout_refresh_token_copy";
 pub static KIWI_USER_ID_HEADER_NAME: &str = "X-Kiwi-User-Id";
 pub static KIWI_USERNAME_HEADER_NAME: &str = "X-Kiwi-Username";
+pub static KIWI_USER_ROLE_HEADER_NAME: &str = "X-Kiwi-User-Role";
+pub static KIWI_USER_CUSTOM_ROLE_HEADER_NAME: &str = "X-Kiwi-User-Custom-Role";
+pub static KIWI_USER_GROUPS_HEADER_NAME: &str = "X-Kiwi-User-Groups";
+pub static KIWI_USER_PERMISSIONS_HEADER_NAME: &str = "X-Kiwi-User-Permissions";
+pub static KIWI_IDENTITY_ASSERTION_HEADER_NAME: &str = "X-Kiwi-Identity-Assertion";
+pub static KIWI_CALLING_SERVICE_HEADER_NAME: &str = "X-Kiwi-Calling-Service";
+pub static SERVICE_ACCESS_TOKEN_PREFIX: &str = "kiwi_service_";
+pub static INTERNAL_SECRETS_FILES_PATH: &str = "/run/secrets/kiwi";
+pub static RESERVED_SERVICE_NAMES: [&str; 5] = ["admin", "auth", "ci", "registry", "status"];
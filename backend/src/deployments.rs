@@ -0,0 +1,489 @@
+use std::collections::HashSet;
+use std::ops::RangeInclusive;
+
+use crate::backups::{delete_service_backup, delete_volume_snapshot};
+use crate::error::Error;
+use crate::managers::container::ContainerManager;
+use crate::managers::container::models::{
+    ContainerConfiguration, DeploymentTrigger, ImageSha, ImageUpdateMode,
+};
+use crate::managers::db::models::{ServiceData, TrackedImageTag};
+use crate::managers::secrets::models::Secret;
+use crate::state::AppState;
+
+static FIRST_ROTATION_PORT: u16 = 42000;
+static LAST_ROTATION_PORT: u16 = 42999;
+
+pub async fn deploy_new_service(
+    state: &AppState,
+    configuration: &ContainerConfiguration,
+    trigger: DeploymentTrigger,
+) -> Result<ServiceData, Error> {
+    let postgres_username = Secret::default().get();
+    let postgres_password = Secret::default().get();
+    let redis_username = Secret::default().get();
+    let redis_password = Secret::default().get();
+    let client_secret = Secret::default().get();
+
+    state
+        .redis_manager
+        .create_user(&redis_username, &redis_password)
+        .await?;
+    state
+        .redis_manager
+        .purge_service_port(&configuration.name)
+        .await?;
+    state
+        .redis_manager
+        .purge_service_authorisation(&configuration.name)
+        .await?;
+    let service = state
+        .db_manager
+        .create_service(
+            configuration,
+            &postgres_username,
+            &postgres_password,
+            &redis_username,
+            &redis_password,
+            &client_secret,
+        )
+        .await;
+
+    match service {
+        Ok(service) => {
+            state
+                .db_manager
+                .unmark_volumes_as_deleted(&service.container_configuration)
+                .await?;
+            state
+                .container_manager
+                .start_container(&service.container_configuration)
+                .await?;
+            state
+                .container_manager
+                .create_and_attach_network_for_container(&service.container_configuration)
+                .await?;
+            state
+                .db_manager
+                .create_deployment(
+                    &configuration.name,
+                    &configuration.image_sha.get_value(),
+                    None,
+                    &trigger,
+                )
+                .await?;
+            Ok(service)
+        }
+        Err(error) => {
+            state.redis_manager.delete_user(&redis_username).await?;
+            Err(error)
+        }
+    }
+}
+
+/// Volumes are only marked as deleted, to be purged after the grace period, and backups are kept
+/// unless `delete_backups` is set.
+pub async fn tear_down_service(
+    state: &AppState,
+    name: &str,
+    delete_backups: bool,
+) -> Result<(), Error> {
+    let service = state
+        .db_manager
+        .get_service_data(name)
+        .await?
+        .ok_or(Error::container_not_found())?;
+
+    state
+        .container_manager
+        .stop_and_remove_job_containers(name)
+        .await?;
+    state
+        .container_manager
+        .stop_and_remove_container(name)
+        .await?;
+    state
+        .db_manager
+        .mark_volumes_as_deleted(
+            &service.container_configuration,
+            &service.container_configuration.stateful_volume_paths,
+        )
+        .await?;
+    state.container_manager.prune_unused_images().await?;
+    if delete_backups {
+        for backup in state.db_manager.get_service_backups(name).await? {
+            if let Err(error) = delete_service_backup(state, &backup).await {
+                tracing::warn!("failed to delete backup {}: {}", backup.id, error);
+            }
+        }
+        for volume_snapshot in state.db_manager.get_service_volume_snapshots(name).await? {
+            if let Err(error) = delete_volume_snapshot(state, &volume_snapshot).await {
+                tracing::warn!(
+                    "failed to delete volume snapshot {}: {}",
+                    volume_snapshot.id,
+                    error
+                );
+            }
+        }
+    }
+    state
+        .redis_manager
+        .delete_user(&service.internal_configuration.redis_username)
+        .await?;
+    state.redis_manager.purge_service_port(name).await?;
+    state
+        .redis_manager
+        .purge_service_authorisation(name)
+        .await?;
+    state
+        .db_manager
+        .delete_service(
+            &service.container_configuration.name,
+            &service.internal_configuration.postgres_username,
+            &service.internal_configuration.postgres_login_username,
+        )
+        .await?;
+
+    Ok(())
+}
+
+pub async fn get_used_service_ports(state: &AppState) -> Result<HashSet<u16>, Error> {
+    Ok(state
+        .db_manager
+        .get_services_data()
+        .await?
+        .iter()
+        .map(|service| service.container_configuration.exposed_port.external)
+        .collect())
+}
+
+pub fn find_free_port(ports: RangeInclusive<u16>, used_ports: &HashSet<u16>) -> Option<u16> {
+    ports
+        .into_iter()
+        .find(|port| !used_ports.contains(port) && ContainerManager::is_local_port_free(port))
+}
+
+pub async fn redeploy_service(
+    state: &AppState,
+    configuration: &ContainerConfiguration,
+) -> Result<(), Error> {
+    state
+        .container_manager
+        .stop_and_remove_container(&configuration.name)
+        .await?;
+    state
+        .container_manager
+        .start_container(configuration)
+        .await?;
+    state
+        .container_manager
+        .create_and_attach_network_for_container(configuration)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn deploy_image(
+    state: &AppState,
+    service: &ServiceData,
+    image_sha: ImageSha,
+    trigger: DeploymentTrigger,
+) -> Result<(), Error> {
+    let previous_image_sha = service.container_configuration.image_sha.get_value();
+    let mut new_configuration = service.container_configuration.clone();
+    new_configuration.image_sha = image_sha;
+
+    let updated_service = state
+        .db_manager
+        .update_service(service, &new_configuration)
+        .await?;
+    if let Err(error) = redeploy_service(state, &updated_service.container_configuration).await {
+        tracing::warn!(
+            "failed to deploy service {}, rolling back: {}",
+            new_configuration.name,
+            error
+        );
+        state
+            .db_manager
+            .update_service(&updated_service, &service.container_configuration)
+            .await?;
+        redeploy_service(state, &service.container_configuration).await?;
+        return Err(error);
+    }
+    state
+        .db_manager
+        .create_deployment(
+            &new_configuration.name,
+            &new_configuration.image_sha.get_value(),
+            Some(&previous_image_sha),
+            &trigger,
+        )
+        .await?;
+
+    Ok(())
+}
+
+pub async fn check_image_update(state: &AppState, name: &str) -> Result<TrackedImageTag, Error> {
+    let service = state
+        .db_manager
+        .get_service_data(name)
+        .await?
+        .ok_or(Error::service_not_found())?;
+    let container_configuration = &service.container_configuration;
+    let (Some(image_update_policy), Some(image_reference)) = (
+        container_configuration.image_update_policy.clone(),
+        container_configuration.get_tracked_image_reference(),
+    ) else {
+        return Err(Error::image_update_policy_not_set());
+    };
+
+    let previous_tracked_image_tag = state.db_manager.get_tracked_image_tag(name).await?;
+    let resolution_result = state
+        .container_manager
+        .get_registry_image_sha(
+            &image_reference,
+            container_configuration.registry_credentials.as_ref(),
+        )
+        .await;
+    let (image_sha, error) = match &resolution_result {
+        Ok(image_sha) => (Some(image_sha.get_value()), None),
+        Err(error) => (None, Some(error.message.clone())),
+    };
+    let tracked_image_tag = state
+        .db_manager
+        .save_tracked_image_tag(
+            name,
+            &image_update_policy.tag,
+            image_sha.as_deref(),
+            error.as_deref(),
+        )
+        .await?;
+
+    let image_sha = resolution_result?;
+    if image_sha.get_value() == container_configuration.image_sha.get_value() {
+        return Ok(tracked_image_tag);
+    }
+    match image_update_policy.mode {
+        ImageUpdateMode::Deploy => {
+            if !is_deployable_image_update(&tracked_image_tag, &image_sha) {
+                tracing::info!(
+                    "skipped deploying {} to service {} as sha256:{} failed before",
+                    image_reference,
+                    name,
+                    image_sha.get_value()
+                );
+                return Ok(tracked_image_tag);
+            }
+            let failed_image_sha = image_sha.get_value();
+            if let Err(error) =
+                deploy_image(state, &service, image_sha, DeploymentTrigger::ImageUpdate).await
+            {
+                state
+                    .db_manager
+                    .save_failed_image_sha(name, &failed_image_sha)
+                    .await?;
+                return Err(error);
+            }
+            tracing::info!("deployed {} to service {}", image_reference, name);
+        }
+        ImageUpdateMode::Notify => {
+            let is_new_image = previous_tracked_image_tag
+                .and_then(|tracked_image_tag| tracked_image_tag.image_sha)
+                .is_none_or(|previous_image_sha| previous_image_sha != image_sha.get_value());
+            if is_new_image {
+                tracing::info!(
+                    "update available for service {}: {} points to sha256:{}",
+                    name,
+                    image_reference,
+                    image_sha.get_value()
+                );
+            }
+        }
+    }
+
+    Ok(tracked_image_tag)
+}
+
+/// An image that failed to deploy is skipped until the tag moves, instead of being redeployed at
+/// every check.
+fn is_deployable_image_update(tracked_image_tag: &TrackedImageTag, image_sha: &ImageSha) -> bool {
+    tracked_image_tag.failed_image_sha.as_deref() != Some(image_sha.get_value().as_str())
+}
+
+pub async fn deploy_tracked_image(state: &AppState, name: &str) -> Result<(), Error> {
+    let service = state
+        .db_manager
+        .get_service_data(name)
+        .await?
+        .ok_or(Error::service_not_found())?;
+    let image_sha = state
+        .db_manager
+        .get_tracked_image_tag(name)
+        .await?
+        .and_then(|tracked_image_tag| tracked_image_tag.image_sha)
+        .ok_or(Error::tracked_image_not_found())?;
+    if image_sha == service.container_configuration.image_sha.get_value() {
+        return Ok(());
+    }
+
+    deploy_image(
+        state,
+        &service,
+        ImageSha::new(image_sha)?,
+        DeploymentTrigger::ImageUpdate,
+    )
+    .await
+}
+
+/// The replacement container runs next to the current one, on another port, and the previous
+/// credentials stay valid until the proxy has switched over to it.
+pub async fn rotate_service_credentials(state: &AppState, name: &str) -> Result<(), Error> {
+    let service = state
+        .db_manager
+        .get_service_data(name)
+        .await?
+        .ok_or(Error::service_not_found())?;
+    let redis_username = &service.internal_configuration.redis_username;
+    let postgres_username = &service.internal_configuration.postgres_username;
+    let postgres_login_username = Secret::default().get();
+    let postgres_password = Secret::default().get();
+    let redis_password = Secret::default().get();
+    let used_ports = get_used_service_ports(state).await?;
+    let external_port = find_free_port(FIRST_ROTATION_PORT..=LAST_ROTATION_PORT, &used_ports)
+        .ok_or(Error::no_free_rotation_port())?;
+
+    state
+        .redis_manager
+        .add_user_password(redis_username, &redis_password)
+        .await?;
+    let rotated_service = state
+        .db_manager
+        .rotate_service_credentials(
+            &service,
+            &postgres_login_username,
+            &postgres_password,
+            &redis_password,
+            external_port,
+        )
+        .await;
+    let rotated_service = match rotated_service {
+        Ok(rotated_service) => rotated_service,
+        Err(error) => {
+            state
+                .redis_manager
+                .remove_user_password(redis_username, &redis_password)
+                .await?;
+            return Err(error);
+        }
+    };
+
+    let replaced_containers = match state
+        .container_manager
+        .start_replacement_container(&rotated_service.container_configuration)
+        .await
+    {
+        Ok(replaced_containers) => replaced_containers,
+        Err(error) => {
+            tracing::warn!(
+                "failed to redeploy service {} with rotated credentials, revoking them: {}",
+                name,
+                error
+            );
+            state
+                .db_manager
+                .restore_service_credentials(&service)
+                .await?;
+            state
+                .redis_manager
+                .remove_user_password(redis_username, &redis_password)
+                .await?;
+            state
+                .db_manager
+                .revoke_service_postgres_login(postgres_username, &Some(postgres_login_username))
+                .await?;
+            return Err(error);
+        }
+    };
+    state
+        .redis_manager
+        .store_service_port(name, external_port as i32)
+        .await?;
+    state
+        .container_manager
+        .remove_replaced_containers(&replaced_containers)
+        .await?;
+
+    state
+        .redis_manager
+        .remove_user_password(
+            redis_username,
+            &service.internal_configuration.redis_password,
+        )
+        .await?;
+    state
+        .db_manager
+        .revoke_service_postgres_login(
+            postgres_username,
+            &service.internal_configuration.postgres_login_username,
+        )
+        .await?;
+
+    tracing::info!("rotated credentials of service {}", name);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDateTime;
+
+    use super::*;
+
+    fn tracked_image_tag(
+        image_sha: &ImageSha,
+        failed_image_sha: Option<&ImageSha>,
+    ) -> TrackedImageTag {
+        TrackedImageTag {
+            service_name: "service".to_string(),
+            tag: "latest".to_string(),
+            image_sha: Some(image_sha.get_value()),
+            error: None,
+            failed_image_sha: failed_image_sha.map(ImageSha::get_value),
+            checked_at: NaiveDateTime::default(),
+        }
+    }
+
+    fn image_sha(digit: char) -> ImageSha {
+        ImageSha::new(digit.to_string().repeat(64)).unwrap()
+    }
+
+    #[test]
+    fn deploys_image_without_failures() {
+        let new_image_sha = image_sha('a');
+        let tracked_image_tag = tracked_image_tag(&new_image_sha, None);
+        assert!(is_deployable_image_update(
+            &tracked_image_tag,
+            &new_image_sha
+        ));
+    }
+
+    #[test]
+    fn skips_image_that_failed_to_deploy() {
+        let failed_image_sha = image_sha('a');
+        let tracked_image_tag = tracked_image_tag(&failed_image_sha, Some(&failed_image_sha));
+        assert!(!is_deployable_image_update(
+            &tracked_image_tag,
+            &failed_image_sha
+        ));
+    }
+
+    #[test]
+    fn deploys_image_once_tag_moves_past_failure() {
+        let new_image_sha = image_sha('b');
+        let tracked_image_tag = tracked_image_tag(&new_image_sha, Some(&image_sha('a')));
+        assert!(is_deployable_image_update(
+            &tracked_image_tag,
+            &new_image_sha
+        ));
+    }
+}
@@ -52,6 +52,34 @@ impl Error {
         }
     }
 
+    pub fn instance_already_initialised() -> Self {
+        Self {
+            code: StatusCode::INTERNAL_SERVER_ERROR,
+            message: "instance backups can only be imported into a new instance".to_string(),
+        }
+    }
+
+    pub fn missing_instance_backup_passphrase() -> Self {
+        Self {
+            code: StatusCode::INTERNAL_SERVER_ERROR,
+            message: "instance backup passphrase is missing".to_string(),
+        }
+    }
+
+    pub fn invalid_instance_backup() -> Self {
+        Self {
+            code: StatusCode::INTERNAL_SERVER_ERROR,
+            message: "instance backup is invalid".to_string(),
+        }
+    }
+
+    pub fn unsupported_instance_backup_version(version: u32) -> Self {
+        Self {
+            code: StatusCode::INTERNAL_SERVER_ERROR,
+            message: format!("instance backup version {} is not supported", version),
+        }
+    }
+
     pub fn internal_authorisation_failure() -> Self {
         Self {
             code: StatusCode::INTERNAL_SERVER_ERROR,
@@ -161,6 +189,15 @@ impl From<std::io::Error> for Error {
     }
 }
 
+impl From<axum::Error> for Error {
+    fn from(error: axum::Error) -> Self {
+        Self {
+            code: StatusCode::BAD_REQUEST,
+            message: error.to_string(),
+        }
+    }
+}
+
 impl From<serde_json::Error> for Error {
     fn from(error: serde_json::Error) -> Self {
         Self {
@@ -0,0 +1,593 @@
+use std::time::Duration;
+
+use axum::body::Bytes;
+use chrono::Utc;
+use futures::channel::mpsc::{Sender, channel};
+use futures::sink::SinkExt;
+use futures::{StreamExt, TryStreamExt, join};
+use tar::{EntryType, Header};
+use tokio::fs::{File, create_dir_all, remove_file, try_exists};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::time::sleep;
+use tokio_util::io::ReaderStream;
+use uuid::Uuid;
+
+use crate::error::Error;
+use crate::managers::backup::write_to_file;
+use crate::managers::container::ContainerManager;
+use crate::managers::container::models::get_shared_volume_id;
+use crate::managers::db::DbManager;
+use crate::managers::encryption::{PassphraseOpener, PassphraseSealer};
+use crate::managers::redis::RedisManager;
+use crate::models::{ByteStream, InstanceBackup};
+use crate::settings::Settings;
+use crate::state::AppState;
+
+static INSTANCE_BACKUP_VERSION: u32 = 2;
+static POSTGRES_CONTAINER_NAME: &str = "kiwi-postgres";
+static MANIFEST_ENTRY_PATH: &str = "manifest.json";
+static DATABASE_DUMP_ENTRY_PATH: &str = "database.sql";
+static TAR_BLOCK_LENGTH: usize = 512;
+static READ_LENGTH: usize = 64 * 1024;
+
+/// The returned file is already removed, so that the archive is gone once read.
+pub async fn export_instance(
+    state: &AppState,
+    passphrase: &str,
+    folder_path: &str,
+) -> Result<File, Error> {
+    create_dir_all(folder_path).await?;
+    let path = format!("{}/instance-export-{}.backup", folder_path, Uuid::new_v4());
+    let file = async {
+        write_instance_backup(state, passphrase, &path).await?;
+        Ok::<File, Error>(File::open(&path).await?)
+    }
+    .await;
+    remove_file_if_exists(&path).await?;
+
+    file
+}
+
+async fn write_instance_backup(
+    state: &AppState,
+    passphrase: &str,
+    path: &str,
+) -> Result<(), Error> {
+    let secrets = state.secrets_manager.lock().await.secrets();
+    let (tls_private_key, tls_public_certificate) = state
+        .lets_encrypt_manager
+        .lock()
+        .await
+        .get_tls_files()
+        .await?;
+
+    let mut volume_ids = vec![];
+    for service in state.db_manager.get_services_data().await? {
+        let configuration = &service.container_configuration;
+        for path in configuration.stateful_volume_paths.iter() {
+            volume_ids.push(configuration.get_stateful_volume_id(path));
+        }
+    }
+    for shared_volume in state.db_manager.get_shared_volumes().await? {
+        volume_ids.push(get_shared_volume_id(&shared_volume.name));
+    }
+
+    let instance_backup = InstanceBackup {
+        version: INSTANCE_BACKUP_VERSION,
+        created_at: Utc::now().naive_utc(),
+        master_key: state.encryption_manager.export_master_key(),
+        secrets,
+        tls_private_key,
+        tls_public_certificate,
+        volume_ids,
+    };
+    let mut writer = InstanceBackupWriter::create(path, passphrase).await?;
+    let manifest = serde_json::to_vec(&instance_backup)?;
+    writer
+        .append_entry(
+            MANIFEST_ENTRY_PATH,
+            manifest.len() as u64,
+            futures::stream::once(async { Ok(Bytes::from(manifest)) }).boxed(),
+        )
+        .await?;
+
+    let database_dump = state
+        .container_manager
+        .stream_command_output(
+            POSTGRES_CONTAINER_NAME,
+            vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                "pg_dumpall --username=\"$POSTGRES_USER\" --clean --if-exists".to_string(),
+            ],
+        )
+        .await?;
+    writer
+        .append_spooled_entry(DATABASE_DUMP_ENTRY_PATH, database_dump)
+        .await?;
+
+    for (index, volume_id) in instance_backup.volume_ids.iter().enumerate() {
+        let archive = state.container_manager.export_volume(volume_id).await?;
+        writer
+            .append_spooled_entry(&get_volume_entry_path(index), archive)
+            .await?;
+    }
+    writer.finish().await?;
+
+    tracing::info!(
+        "exported instance with {} volumes",
+        instance_backup.volume_ids.len()
+    );
+
+    Ok(())
+}
+
+/// Importing is only allowed into a new instance, so that an existing one never gets overwritten.
+pub async fn read_instance_backup(
+    settings: &Settings,
+) -> Result<Option<(InstanceBackup, InstanceBackupReader)>, Error> {
+    let Some(path) = &settings.instance_backup_import_path else {
+        return Ok(None);
+    };
+    if try_exists(settings.secrets_file_path()).await? {
+        return Err(Error::instance_already_initialised());
+    }
+    let passphrase = settings
+        .instance_backup_passphrase
+        .as_ref()
+        .ok_or(Error::missing_instance_backup_passphrase())?;
+
+    let mut reader = InstanceBackupReader::open(path, passphrase).await?;
+    let size = reader.next_entry(MANIFEST_ENTRY_PATH).await?;
+    let instance_backup: InstanceBackup =
+        serde_json::from_slice(&reader.read_entry_content(size).await?)?;
+    if instance_backup.version != INSTANCE_BACKUP_VERSION {
+        return Err(Error::unsupported_instance_backup_version(
+            instance_backup.version,
+        ));
+    }
+
+    tracing::info!(
+        "importing instance backup created at {}",
+        instance_backup.created_at
+    );
+
+    Ok(Some((instance_backup, reader)))
+}
+
+pub async fn restore_tls_files(
+    settings: &Settings,
+    instance_backup: &InstanceBackup,
+) -> Result<(), Error> {
+    for (path, content) in [
+        (
+            settings.tls_private_key_path(),
+            &instance_backup.tls_private_key,
+        ),
+        (
+            settings.tls_public_certificate_path(),
+            &instance_backup.tls_public_certificate,
+        ),
+    ] {
+        let mut file = File::create(path).await?;
+        file.write_all(content.as_bytes()).await?;
+        file.flush().await?;
+    }
+
+    Ok(())
+}
+
+/// Runs before migrations, and stops at the first failing statement.
+pub async fn restore_database(
+    container_manager: &ContainerManager,
+    reader: &mut InstanceBackupReader,
+) -> Result<(), Error> {
+    for tentative_count in 1..=10 {
+        tracing::info!("waiting for db to be ready, attempt {}/10", tentative_count);
+        let readiness_check_result = container_manager
+            .execute_command(
+                POSTGRES_CONTAINER_NAME,
+                vec![
+                    "sh".to_string(),
+                    "-c".to_string(),
+                    // The entrypoint runs a temporary server listening on the socket only while
+                    // initialising, so checking over TCP waits for the real one
+                    "pg_isready --host=127.0.0.1 --username=\"$POSTGRES_USER\"".to_string(),
+                ],
+                None,
+            )
+            .await;
+
+        match readiness_check_result {
+            Ok(_) => break,
+            Err(_) if tentative_count < 10 => {
+                sleep(Duration::from_secs(2)).await;
+            }
+            Err(error) => {
+                return Err(error);
+            }
+        }
+    }
+
+    let size = reader.next_entry(DATABASE_DUMP_ENTRY_PATH).await?;
+    reader
+        .restore_entry(size, |database_dump| {
+            container_manager.execute_command(
+                POSTGRES_CONTAINER_NAME,
+                vec![
+                    "sh".to_string(),
+                    "-c".to_string(),
+                    // The dump drops and creates every role, which can only fail for the one
+                    // running the restore, as it already exists and is in use
+                    "grep -v -x -F -e \"DROP ROLE IF EXISTS \\\"$POSTGRES_USER\\\";\" -e \"DROP ROLE IF EXISTS $POSTGRES_USER;\" -e \"CREATE ROLE \\\"$POSTGRES_USER\\\";\" -e \"CREATE ROLE $POSTGRES_USER;\" | psql --username=\"$POSTGRES_USER\" --dbname=postgres --quiet -v ON_ERROR_STOP=1".to_string(),
+                ],
+                Some(database_dump),
+            )
+        })
+        .await?;
+
+    tracing::info!("restored db from instance backup");
+
+    Ok(())
+}
+
+pub async fn restore_volumes(
+    container_manager: &ContainerManager,
+    reader: &mut InstanceBackupReader,
+    instance_backup: &InstanceBackup,
+) -> Result<(), Error> {
+    for (index, volume_id) in instance_backup.volume_ids.iter().enumerate() {
+        let size = reader.next_entry(&get_volume_entry_path(index)).await?;
+        reader
+            .restore_entry(size, |archive| {
+                container_manager.import_volume(volume_id, archive)
+            })
+            .await?;
+    }
+
+    tracing::info!(
+        "restored {} volumes from instance backup",
+        instance_backup.volume_ids.len()
+    );
+
+    Ok(())
+}
+
+pub async fn restore_redis_users(
+    db_manager: &DbManager,
+    redis_manager: &RedisManager,
+) -> Result<(), Error> {
+    for service in db_manager.get_services_data().await? {
+        redis_manager
+            .create_user(
+                &service.internal_configuration.redis_username,
+                &service.internal_configuration.redis_password,
+            )
+            .await?;
+    }
+
+    Ok(())
+}
+
+struct InstanceBackupWriter {
+    path: String,
+    file: File,
+    sealer: PassphraseSealer,
+}
+
+impl InstanceBackupWriter {
+    async fn create(path: &str, passphrase: &str) -> Result<Self, Error> {
+        let (sealer, header) = PassphraseSealer::new(passphrase)?;
+        let mut file = File::create(path).await?;
+        file.write_all(&header).await?;
+
+        Ok(Self {
+            path: path.to_string(),
+            file,
+            sealer,
+        })
+    }
+
+    async fn write(&mut self, data: &[u8]) -> Result<(), Error> {
+        let sealed_data = self.sealer.update(data)?;
+        self.file.write_all(&sealed_data).await?;
+
+        Ok(())
+    }
+
+    async fn append_entry(
+        &mut self,
+        entry_path: &str,
+        size: u64,
+        mut content: ByteStream,
+    ) -> Result<(), Error> {
+        let mut header = Header::new_ustar();
+        header.set_path(entry_path)?;
+        header.set_entry_type(EntryType::Regular);
+        header.set_size(size);
+        header.set_mode(0o600);
+        header.set_mtime(Utc::now().timestamp() as u64);
+        header.set_cksum();
+        self.write(header.as_bytes()).await?;
+
+        let mut written_size = 0;
+        while let Some(chunk) = content.next().await {
+            let chunk = chunk?;
+            written_size += chunk.len() as u64;
+            self.write(&chunk).await?;
+        }
+        if written_size != size {
+            return Err(Error::invalid_instance_backup());
+        }
+
+        self.write(&vec![0; get_padding_length(size)]).await
+    }
+
+    async fn append_spooled_entry(
+        &mut self,
+        entry_path: &str,
+        content: ByteStream,
+    ) -> Result<(), Error> {
+        let spool_path = format!("{}.part", self.path);
+        let append_result = async {
+            let size = write_to_file(&spool_path, content).await?;
+            let spooled_content = ReaderStream::new(File::open(&spool_path).await?)
+                .map_err(Error::from)
+                .boxed();
+            self.append_entry(entry_path, size, spooled_content).await
+        }
+        .await;
+        remove_file_if_exists(&spool_path).await?;
+
+        append_result
+    }
+
+    async fn finish(mut self) -> Result<(), Error> {
+        self.write(&vec![0; 2 * TAR_BLOCK_LENGTH]).await?;
+        let sealed_data = self.sealer.finish()?;
+        self.file.write_all(&sealed_data).await?;
+        self.file.flush().await?;
+
+        Ok(())
+    }
+}
+
+pub struct InstanceBackupReader {
+    file: File,
+    opener: Option<PassphraseOpener>,
+    buffer: Vec<u8>,
+}
+
+impl InstanceBackupReader {
+    async fn open(path: &str, passphrase: &str) -> Result<Self, Error> {
+        let mut file = File::open(path).await?;
+        let mut header = vec![0; PassphraseOpener::header_length()];
+        file.read_exact(&mut header)
+            .await
+            .map_err(|_| Error::invalid_instance_backup())?;
+
+        Ok(Self {
+            file,
+            opener: Some(PassphraseOpener::new(passphrase, &header)?),
+            buffer: vec![],
+        })
+    }
+
+    async fn read(&mut self, max_length: usize) -> Result<Vec<u8>, Error> {
+        while self.buffer.is_empty() {
+            let mut sealed_data = vec![0; READ_LENGTH];
+            let length = self.file.read(&mut sealed_data).await?;
+            self.buffer = match (length, self.opener.take()) {
+                (_, None) => return Err(Error::invalid_instance_backup()),
+                (0, Some(opener)) => opener.finish()?,
+                (_, Some(mut opener)) => {
+                    let data = opener.update(&sealed_data[..length])?;
+                    self.opener = Some(opener);
+                    data
+                }
+            };
+        }
+        let length = max_length.min(self.buffer.len());
+
+        Ok(self.buffer.drain(..length).collect())
+    }
+
+    async fn read_exact(&mut self, length: usize) -> Result<Vec<u8>, Error> {
+        let mut data = Vec::with_capacity(length);
+        while data.len() < length {
+            data.extend(self.read(length - data.len()).await?);
+        }
+
+        Ok(data)
+    }
+
+    async fn next_entry(&mut self, entry_path: &str) -> Result<u64, Error> {
+        let block = self.read_exact(TAR_BLOCK_LENGTH).await?;
+        let header = Header::from_byte_slice(&block);
+        if header.path()?.to_str() != Some(entry_path) {
+            return Err(Error::invalid_instance_backup());
+        }
+
+        Ok(header.entry_size()?)
+    }
+
+    async fn read_entry_content(&mut self, size: u64) -> Result<Vec<u8>, Error> {
+        let content = self.read_exact(size as usize).await?;
+        self.read_exact(get_padding_length(size)).await?;
+
+        Ok(content)
+    }
+
+    async fn restore_entry<T, F>(
+        &mut self,
+        size: u64,
+        restore: impl FnOnce(ByteStream) -> F,
+    ) -> Result<T, Error>
+    where
+        F: Future<Output = Result<T, Error>>,
+    {
+        let (sender, receiver) = channel(4);
+        let (restore_result, forward_result) = join!(
+            restore(receiver.boxed()),
+            self.forward_entry_content(size, sender)
+        );
+        let restored_value = restore_result?;
+        forward_result?;
+
+        Ok(restored_value)
+    }
+
+    async fn forward_entry_content(
+        &mut self,
+        size: u64,
+        mut sender: Sender<Result<Bytes, Error>>,
+    ) -> Result<(), Error> {
+        let mut remaining_size = size;
+        while remaining_size > 0 {
+            let chunk = match self
+                .read(remaining_size.min(READ_LENGTH as u64) as usize)
+                .await
+            {
+                Ok(chunk) => chunk,
+                Err(error) => {
+                    let _ = sender.send(Err(error.clone())).await;
+                    return Err(error);
+                }
+            };
+            remaining_size -= chunk.len() as u64;
+            // The content was not fully consumed, so the next entries cannot be reached
+            if sender.send(Ok(chunk.into())).await.is_err() {
+                return Err(Error::invalid_instance_backup());
+            }
+        }
+        self.read_exact(get_padding_length(size)).await?;
+
+        Ok(())
+    }
+}
+
+fn get_volume_entry_path(index: usize) -> String {
+    format!("volumes/{}.tar", index)
+}
+
+fn get_padding_length(size: u64) -> usize {
+    let block_length = TAR_BLOCK_LENGTH as u64;
+    ((block_length - size % block_length) % block_length) as usize
+}
+
+async fn remove_file_if_exists(path: &str) -> Result<(), Error> {
+    match remove_file(path).await {
+        Err(error) if error.kind() != std::io::ErrorKind::NotFound => Err(error.into()),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::stream;
+
+    use super::*;
+
+    fn build_stream(chunks: Vec<Vec<u8>>) -> ByteStream {
+        stream::iter(chunks.into_iter().map(|chunk| Ok(Bytes::from(chunk)))).boxed()
+    }
+
+    async fn write_archive(path: &str, passphrase: &str) {
+        let mut writer = InstanceBackupWriter::create(path, passphrase)
+            .await
+            .unwrap();
+        writer
+            .append_entry(MANIFEST_ENTRY_PATH, 2, build_stream(vec![b"{}".to_vec()]))
+            .await
+            .unwrap();
+        writer
+            .append_spooled_entry(
+                DATABASE_DUMP_ENTRY_PATH,
+                build_stream(vec![vec![1; READ_LENGTH], vec![2; 1000]]),
+            )
+            .await
+            .unwrap();
+        writer
+            .append_spooled_entry(&get_volume_entry_path(0), build_stream(vec![]))
+            .await
+            .unwrap();
+        writer.finish().await.unwrap();
+    }
+
+    fn get_archive_path() -> String {
+        std::env::temp_dir()
+            .join(format!("{}.backup", Uuid::new_v4()))
+            .to_string_lossy()
+            .to_string()
+    }
+
+    #[tokio::test]
+    async fn reads_written_archives() {
+        let path = get_archive_path();
+        write_archive(&path, "passphrase").await;
+
+        let mut reader = InstanceBackupReader::open(&path, "passphrase")
+            .await
+            .unwrap();
+        let size = reader.next_entry(MANIFEST_ENTRY_PATH).await.unwrap();
+        assert_eq!(reader.read_entry_content(size).await.unwrap(), b"{}");
+
+        let size = reader.next_entry(DATABASE_DUMP_ENTRY_PATH).await.unwrap();
+        let database_dump = reader
+            .restore_entry(size, |content| content.try_collect::<Vec<Bytes>>())
+            .await
+            .unwrap()
+            .concat();
+        assert_eq!(database_dump.len(), READ_LENGTH + 1000);
+        assert_eq!(database_dump[READ_LENGTH..], [2; 1000]);
+
+        let size = reader.next_entry(&get_volume_entry_path(0)).await.unwrap();
+        assert_eq!(size, 0);
+        assert!(!try_exists(format!("{}.part", path)).await.unwrap());
+
+        remove_file(&path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn rejects_unexpected_entries() {
+        let path = get_archive_path();
+        write_archive(&path, "passphrase").await;
+
+        let mut reader = InstanceBackupReader::open(&path, "passphrase")
+            .await
+            .unwrap();
+        assert!(reader.next_entry(DATABASE_DUMP_ENTRY_PATH).await.is_err());
+
+        remove_file(&path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn stops_restoring_when_the_archive_is_truncated() {
+        let path = get_archive_path();
+        write_archive(&path, "passphrase").await;
+        let file = File::options().write(true).open(&path).await.unwrap();
+        let length = file.metadata().await.unwrap().len();
+        file.set_len(length - 2000).await.unwrap();
+
+        let mut reader = InstanceBackupReader::open(&path, "passphrase")
+            .await
+            .unwrap();
+        let size = reader.next_entry(MANIFEST_ENTRY_PATH).await.unwrap();
+        reader.read_entry_content(size).await.unwrap();
+        let size = reader.next_entry(DATABASE_DUMP_ENTRY_PATH).await.unwrap();
+        let restore_result = reader
+            .restore_entry(size, |content| content.try_collect::<Vec<Bytes>>())
+            .await;
+        assert!(restore_result.is_err());
+
+        remove_file(&path).await.unwrap();
+    }
+
+    #[test]
+    fn pads_entries_to_whole_blocks() {
+        assert_eq!(get_padding_length(0), 0);
+        assert_eq!(get_padding_length(1), 511);
+        assert_eq!(get_padding_length(512), 0);
+        assert_eq!(get_padding_length(1000), 24);
+    }
+}
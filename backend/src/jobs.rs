@@ -0,0 +1,185 @@
+use chrono::{NaiveDateTime, Utc};
+use tokio::time::{Duration, timeout};
+use uuid::Uuid;
+
+use crate::error::Error;
+use crate::managers::container::models::{JobConcurrencyPolicy, JobRunStatus, ScheduledJob};
+use crate::managers::db::models::{JobRun, ServiceData};
+use crate::state::AppState;
+
+/// Occurrences missed while Kiwi was down are caught up with a single run.
+pub async fn run_due_scheduled_job(
+    state: &AppState,
+    service: &ServiceData,
+    job: &ScheduledJob,
+) -> Result<(), Error> {
+    let name = &service.container_configuration.name;
+    let last_scheduled_at = state
+        .db_manager
+        .get_last_job_scheduled_at(name, &job.name)
+        .await?
+        // Jobs are added by editing the service
+        .unwrap_or(service.last_modified_at);
+    let now = Utc::now();
+    let scheduled_at = job
+        .get_schedule()?
+        .after(&last_scheduled_at.and_utc())
+        .take_while(|occurrence| *occurrence <= now)
+        .last();
+
+    if let Some(scheduled_at) = scheduled_at {
+        run_scheduled_job(state, service, job, Some(scheduled_at.naive_utc())).await?;
+    }
+
+    Ok(())
+}
+
+/// `Forbid` skips the occurrence while a run is in progress, `Replace` stops the running ones first
+/// and `Allow` runs them alongside.
+pub async fn run_scheduled_job(
+    state: &AppState,
+    service: &ServiceData,
+    job: &ScheduledJob,
+    scheduled_at: Option<NaiveDateTime>,
+) -> Result<JobRun, Error> {
+    let configuration = &service.container_configuration;
+    let running_job_runs = state
+        .db_manager
+        .get_running_job_runs(&configuration.name, &job.name)
+        .await?;
+    if !running_job_runs.is_empty() {
+        match job.concurrency_policy {
+            JobConcurrencyPolicy::Allow => {}
+            JobConcurrencyPolicy::Forbid => {
+                tracing::info!(
+                    "skipped job {} of service {} as it's still running",
+                    job.name,
+                    configuration.name
+                );
+                return state
+                    .db_manager
+                    .create_job_run(
+                        &configuration.name,
+                        &job.name,
+                        &JobRunStatus::Skipped,
+                        scheduled_at,
+                    )
+                    .await;
+            }
+            JobConcurrencyPolicy::Replace => {
+                for job_run in running_job_runs {
+                    state.db_manager.cancel_job_run(&job_run.id).await?;
+                    state
+                        .container_manager
+                        .stop_job_container(
+                            &configuration.get_job_container_name(&job.name, &job_run.id),
+                        )
+                        .await?;
+                }
+            }
+        }
+    }
+
+    let job_run = state
+        .db_manager
+        .create_job_run(
+            &configuration.name,
+            &job.name,
+            &JobRunStatus::Running,
+            scheduled_at,
+        )
+        .await?;
+    let container_name = configuration.get_job_container_name(&job.name, &job_run.id);
+    if let Err(error) = state
+        .container_manager
+        .start_job_container(configuration, &container_name, job.command.clone())
+        .await
+    {
+        state
+            .db_manager
+            .finish_job_run(
+                &job_run.id,
+                &JobRunStatus::Failed,
+                None,
+                None,
+                Some(&error.message),
+            )
+            .await?;
+        return Err(error);
+    }
+    tracing::info!(
+        "started job {} of service {} as run {}",
+        job.name,
+        configuration.name,
+        job_run.id
+    );
+
+    let state = state.clone();
+    let timeout_seconds = job.timeout_seconds;
+    tokio::spawn(async move {
+        if let Err(error) =
+            wait_for_job_run(&state, &job_run.id, &container_name, timeout_seconds).await
+        {
+            tracing::error!("failed to finish job run {}: {}", job_run.id, error);
+        }
+    });
+
+    Ok(job_run)
+}
+
+async fn wait_for_job_run(
+    state: &AppState,
+    id: &Uuid,
+    container_name: &str,
+    timeout_seconds: u32,
+) -> Result<(), Error> {
+    let wait_result = match timeout(
+        Duration::from_secs(timeout_seconds as u64),
+        state.container_manager.wait_for_container(container_name),
+    )
+    .await
+    {
+        Ok(wait_result) => wait_result.map(|exit_code| {
+            let status = if exit_code == 0 {
+                JobRunStatus::Succeeded
+            } else {
+                JobRunStatus::Failed
+            };
+            (status, exit_code)
+        }),
+        Err(_) => {
+            state
+                .container_manager
+                .stop_job_container(container_name)
+                .await?;
+            state
+                .container_manager
+                .wait_for_container(container_name)
+                .await
+                .map(|exit_code| (JobRunStatus::TimedOut, exit_code))
+        }
+    };
+    let logs = state
+        .container_manager
+        .get_job_container_logs(container_name)
+        .await;
+    state
+        .container_manager
+        .stop_and_remove_job_container(container_name)
+        .await?;
+
+    match (wait_result, logs) {
+        (Ok((status, exit_code)), Ok(logs)) => {
+            state
+                .db_manager
+                .finish_job_run(id, &status, Some(exit_code as i32), Some(&logs), None)
+                .await
+        }
+        (Err(error), _) | (_, Err(error)) => {
+            state
+                .db_manager
+                .finish_job_run(id, &JobRunStatus::Failed, None, None, Some(&error.message))
+                .await
+        }
+    }
+}
@@ -1,13 +1,20 @@
 use std::sync::Arc;
 
 use crate::error::Error;
+use crate::instance_backups::{
+    read_instance_backup, restore_database, restore_redis_users, restore_tls_files, restore_volumes,
+};
 use crate::logger::Logger;
+use crate::managers::backup::BackupManager;
 use crate::managers::crypto::CryptoManager;
 use crate::managers::dynamic_dns::DynamicDnsManager;
+use crate::managers::encryption::EncryptionManager;
 use crate::managers::lets_encrypt::LetsEncryptManager;
 use crate::managers::local_http::LocalHttpManager;
 use crate::managers::oidc::OidcManager;
+use crate::managers::oidc_provider::OidcProviderManager;
 use crate::managers::redis::RedisManager;
+use crate::managers::registry::RegistryManager;
 use crate::managers::secrets::SecretsManager;
 use crate::models::ServerAction;
 use crate::server::Server;
@@ -29,13 +36,19 @@ use tower_http::cors::CorsLayer;
 use tower_http::set_header::SetResponseHeaderLayer;
 use tower_http::trace::TraceLayer;
 
+mod backups;
+mod compose_import;
 mod constants;
+mod deployments;
 mod error;
 mod extractors;
+mod instance_backups;
+mod jobs;
 mod logger;
 mod managers;
 mod middlewares;
 mod models;
+mod previews;
 mod routes;
 mod server;
 mod services;
@@ -49,9 +62,28 @@ async fn main() -> Result<(), Error> {
 
     Logger::new(&settings).init();
 
-    let mut secrets_manager = SecretsManager::new_with_loaded_or_created_secrets(&settings).await?;
-    let container_manager = ContainerManager::new().await?;
-    let oidc_manager = OidcManager::new().await?;
+    let (instance_backup, mut instance_backup_reader) =
+        match read_instance_backup(&settings).await? {
+            Some((instance_backup, reader)) => (Some(instance_backup), Some(reader)),
+            None => (None, None),
+        };
+
+    let mut encryption_manager = EncryptionManager::new(&settings).await?;
+    if let Some(instance_backup) = &instance_backup {
+        encryption_manager =
+            encryption_manager.with_previous_master_key(&instance_backup.master_key)?;
+    }
+    let mut secrets_manager =
+        SecretsManager::new_with_loaded_or_created_secrets(&settings, encryption_manager.clone())
+            .await?;
+    if let Some(instance_backup) = &instance_backup {
+        secrets_manager
+            .replace_secrets(instance_backup.secrets.clone())
+            .await?;
+        restore_tls_files(&settings, instance_backup).await?;
+    }
+    let container_manager = ContainerManager::new(&settings).await?;
+    let oidc_manager = OidcManager::new(&settings).await?;
 
     let crypto_pepper = secrets_manager.crypto_pepper();
     let db_admin_username = secrets_manager.db_admin_username();
@@ -60,6 +92,18 @@ async fn main() -> Result<(), Error> {
 
     let crypto_manager = CryptoManager::new(&crypto_pepper)?;
 
+    let oidc_signing_key = match secrets_manager.oidc_signing_key() {
+        Some(signing_key) => signing_key,
+        None => {
+            let signing_key = OidcProviderManager::generate_signing_key()?;
+            secrets_manager
+                .set_oidc_signing_key(signing_key.clone())
+                .await?;
+            signing_key
+        }
+    };
+    let oidc_provider_manager = OidcProviderManager::new(&oidc_signing_key)?;
+
     let db_container_configuration =
         ContainerConfiguration::get_postgres_configuration(&db_admin_username, &db_admin_password)?;
     let redis_container_configuration =
@@ -70,10 +114,25 @@ async fn main() -> Result<(), Error> {
             .start_container(&container_configuration)
             .await?;
     }
+    if let (Some(instance_backup), Some(reader)) = (&instance_backup, &mut instance_backup_reader) {
+        restore_database(&container_manager, reader).await?;
+        restore_volumes(&container_manager, reader, instance_backup).await?;
+    }
 
-    let db_manager = DbManager::new(&db_admin_username, &db_admin_password).await?;
+    let db_manager = DbManager::new(
+        &db_admin_username,
+        &db_admin_password,
+        encryption_manager.clone(),
+    )
+    .await?;
     let redis_manager = RedisManager::new(&redis_admin_password).await?;
+    if instance_backup.is_some() {
+        restore_redis_users(&db_manager, &redis_manager).await?;
+        tracing::info!("instance backup imported");
+    }
     let local_http_manager = LocalHttpManager::new(&settings)?;
+    let backup_manager = BackupManager::new(&settings)?;
+    let registry_manager = RegistryManager::new(&settings)?;
     let dynamic_dns_manager = match secrets_manager.dynamic_dns_api_configuration() {
         Some(configuration) => Arc::new(Mutex::new(Some(
             DynamicDnsManager::new(&configuration).await?,
@@ -112,6 +171,24 @@ async fn main() -> Result<(), Error> {
                 error
             );
         }
+        // Their runs are failed below, nothing waits for them anymore
+        if let Err(error) = container_manager
+            .stop_and_remove_job_containers(&service.container_configuration.name)
+            .await
+        {
+            tracing::error!(
+                "failed to remove job containers of {}: {}",
+                service.container_configuration.name,
+                error
+            );
+        }
+    }
+    let interrupted_job_runs_count = db_manager.fail_interrupted_job_runs().await?;
+    if interrupted_job_runs_count > 0 {
+        tracing::warn!(
+            "marked {} job runs interrupted by the restart as failed",
+            interrupted_job_runs_count
+        );
     }
     secrets_manager
         .set_lets_encrypt_credentials(lets_encrypt_manager.lock().await.get_credentials())
@@ -141,9 +218,13 @@ async fn main() -> Result<(), Error> {
         redis_manager,
         local_http_manager,
         oidc_manager,
-        dynamic_dns_manager: dynamic_dns_manager.clone(),
+        oidc_provider_manager,
+        dynamic_dns_manager,
         secrets_manager: Arc::new(Mutex::new(secrets_manager)),
-        lets_encrypt_manager: lets_encrypt_manager.clone(),
+        lets_encrypt_manager,
+        backup_manager,
+        encryption_manager,
+        registry_manager,
     };
 
     let app = create_router(&settings)
@@ -162,10 +243,10 @@ async fn main() -> Result<(), Error> {
             state.clone(),
             authentication_middleware,
         ))
-        .with_state(state);
+        .with_state(state.clone());
 
     let server = Server::new(&settings);
-    let worker = Worker::new(dynamic_dns_manager, lets_encrypt_manager);
+    let worker = Worker::new(state, &settings);
 
     loop {
         let server_action = select! {
@@ -0,0 +1,35 @@
+use crate::error::Error;
+use axum::http::StatusCode;
+
+impl Error {
+    pub fn invalid_backup_storage_configuration() -> Self {
+        Self {
+            code: StatusCode::INTERNAL_SERVER_ERROR,
+            message: "s3 backup storage requires endpoint, bucket and credentials".to_string(),
+        }
+    }
+
+    pub fn backup_storage_request_failed(status: u16) -> Self {
+        Self {
+            code: StatusCode::BAD_GATEWAY,
+            message: format!("backup storage request failed with status {}", status),
+        }
+    }
+
+    pub fn backup_storage_mismatch(storage: &str) -> Self {
+        Self {
+            code: StatusCode::CONFLICT,
+            message: format!(
+                "backup is kept in {} storage, which is not configured",
+                storage
+            ),
+        }
+    }
+
+    pub fn invalid_backup_storage_response() -> Self {
+        Self {
+            code: StatusCode::BAD_GATEWAY,
+            message: "backup storage sent an unexpected response".to_string(),
+        }
+    }
+}
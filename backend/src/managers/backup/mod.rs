@@ -0,0 +1,533 @@
+use axum::body::Bytes;
+use chrono::Utc;
+use futures::{StreamExt, TryStreamExt};
+use hmac::{Hmac, Mac};
+use models::{BackupStorageKind, S3Configuration};
+use reqwest::header::ETAG;
+use reqwest::{Client, Method, Response};
+use sha2::{Digest, Sha256};
+use tokio::fs::{File, create_dir_all, remove_file, rename};
+use tokio::io::AsyncWriteExt;
+use tokio_util::io::ReaderStream;
+use urlencoding::encode;
+
+use crate::error::Error;
+use crate::managers::crypto::hex_encode;
+use crate::models::ByteStream;
+use crate::settings::Settings;
+
+pub mod error;
+pub mod models;
+
+// S3 requires parts of at least 5 MiB, besides the last one
+static S3_PART_SIZE: usize = 16 * 1024 * 1024;
+
+#[derive(Clone)]
+pub struct BackupManager {
+    client: Client,
+    folder_path: String,
+    s3_configuration: Option<S3Configuration>,
+}
+
+impl BackupManager {
+    pub fn new(settings: &Settings) -> Result<Self, Error> {
+        let s3_configuration = match (
+            &settings.backups_s3_endpoint,
+            &settings.backups_s3_bucket,
+            &settings.backups_s3_access_key_id,
+            &settings.backups_s3_secret_access_key,
+        ) {
+            (None, None, _, _) => None,
+            (Some(endpoint), Some(bucket), Some(access_key_id), Some(secret_access_key)) => {
+                Some(S3Configuration {
+                    endpoint: endpoint
+                        .parse()
+                        .map_err(|_| Error::invalid_backup_storage_configuration())?,
+                    bucket: bucket.clone(),
+                    region: settings.backups_s3_region.clone(),
+                    access_key_id: access_key_id.clone(),
+                    secret_access_key: secret_access_key.clone(),
+                })
+            }
+            _ => return Err(Error::invalid_backup_storage_configuration()),
+        };
+        let backup_manager = Self {
+            client: Client::builder().build()?,
+            folder_path: settings.backups_folder_path(),
+            s3_configuration,
+        };
+
+        tracing::info!(
+            "backup manager initialised with {} storage",
+            backup_manager.storage_kind()
+        );
+
+        Ok(backup_manager)
+    }
+
+    pub fn folder_path(&self) -> &str {
+        &self.folder_path
+    }
+
+    pub fn storage_kind(&self) -> BackupStorageKind {
+        if self.s3_configuration.is_some() {
+            BackupStorageKind::S3
+        } else {
+            BackupStorageKind::Local
+        }
+    }
+
+    /// Nothing is stored if reading the data fails midway.
+    pub async fn store(&self, key: &str, data: ByteStream) -> Result<u64, Error> {
+        if let Some(s3_configuration) = &self.s3_configuration {
+            return self.store_in_s3(s3_configuration, key, data).await;
+        }
+
+        let path = format!("{}/{}", self.folder_path, key);
+        if let Some((folder_path, _)) = path.rsplit_once("/") {
+            create_dir_all(folder_path).await?;
+        }
+        let temporary_path = format!("{}.tmp", path);
+        match write_to_file(&temporary_path, data).await {
+            Ok(size) => {
+                rename(&temporary_path, &path).await?;
+                Ok(size)
+            }
+            Err(error) => {
+                remove_file(&temporary_path).await?;
+                Err(error)
+            }
+        }
+    }
+
+    pub async fn read(&self, key: &str) -> Result<ByteStream, Error> {
+        if let Some(s3_configuration) = &self.s3_configuration {
+            let response = self
+                .send_s3_request(s3_configuration, Method::GET, key, &[], Bytes::new())
+                .await?;
+            return Ok(response.bytes_stream().map_err(Error::from).boxed());
+        }
+
+        let file = File::open(format!("{}/{}", self.folder_path, key)).await?;
+
+        Ok(ReaderStream::new(file).map_err(Error::from).boxed())
+    }
+
+    pub async fn delete(&self, key: &str) -> Result<(), Error> {
+        if let Some(s3_configuration) = &self.s3_configuration {
+            self.send_s3_request(s3_configuration, Method::DELETE, key, &[], Bytes::new())
+                .await?;
+            return Ok(());
+        }
+
+        match remove_file(format!("{}/{}", self.folder_path, key)).await {
+            Err(error) if error.kind() != std::io::ErrorKind::NotFound => Err(error.into()),
+            _ => Ok(()),
+        }
+    }
+
+    async fn store_in_s3(
+        &self,
+        s3_configuration: &S3Configuration,
+        key: &str,
+        mut data: ByteStream,
+    ) -> Result<u64, Error> {
+        let (first_part, is_complete) = read_part(&mut data).await?;
+        if is_complete {
+            let size = first_part.len() as u64;
+            self.send_s3_request(s3_configuration, Method::PUT, key, &[], first_part)
+                .await?;
+            return Ok(size);
+        }
+
+        let response = self
+            .send_s3_request(
+                s3_configuration,
+                Method::POST,
+                key,
+                &[("uploads", "")],
+                Bytes::new(),
+            )
+            .await?;
+        let upload_id = get_xml_element(&response.text().await?, "UploadId")
+            .ok_or(Error::invalid_backup_storage_response())?;
+        let upload_result = self
+            .upload_s3_parts(s3_configuration, key, &upload_id, first_part, data)
+            .await;
+        if upload_result.is_err()
+            && let Err(error) = self
+                .send_s3_request(
+                    s3_configuration,
+                    Method::DELETE,
+                    key,
+                    &[("uploadId", &upload_id)],
+                    Bytes::new(),
+                )
+                .await
+        {
+            tracing::error!("failed to abort s3 upload of {}: {}", key, error);
+        }
+
+        upload_result
+    }
+
+    async fn upload_s3_parts(
+        &self,
+        s3_configuration: &S3Configuration,
+        key: &str,
+        upload_id: &str,
+        first_part: Bytes,
+        mut data: ByteStream,
+    ) -> Result<u64, Error> {
+        let mut etags = vec![];
+        let mut size = 0;
+        let (mut part, mut is_complete) = (first_part, false);
+        loop {
+            if !part.is_empty() {
+                size += part.len() as u64;
+                let part_number = (etags.len() + 1).to_string();
+                let response = self
+                    .send_s3_request(
+                        s3_configuration,
+                        Method::PUT,
+                        key,
+                        &[("partNumber", &part_number), ("uploadId", upload_id)],
+                        part,
+                    )
+                    .await?;
+                let etag = response
+                    .headers()
+                    .get(ETAG)
+                    .and_then(|etag| etag.to_str().ok())
+                    .ok_or(Error::invalid_backup_storage_response())?;
+                etags.push(etag.to_string());
+            }
+            if is_complete {
+                break;
+            }
+            (part, is_complete) = read_part(&mut data).await?;
+        }
+
+        let parts: String = etags
+            .iter()
+            .enumerate()
+            .map(|(index, etag)| {
+                format!(
+                    "<Part><PartNumber>{}</PartNumber><ETag>{}</ETag></Part>",
+                    index + 1,
+                    etag
+                )
+            })
+            .collect();
+        let response = self
+            .send_s3_request(
+                s3_configuration,
+                Method::POST,
+                key,
+                &[("uploadId", upload_id)],
+                format!(
+                    "<CompleteMultipartUpload>{}</CompleteMultipartUpload>",
+                    parts
+                )
+                .into(),
+            )
+            .await?;
+        // Completion failures can come with a 200 status, once the parts are assembled
+        if get_xml_element(&response.text().await?, "Code").is_some() {
+            return Err(Error::invalid_backup_storage_response());
+        }
+
+        Ok(size)
+    }
+
+    async fn send_s3_request(
+        &self,
+        s3_configuration: &S3Configuration,
+        method: Method,
+        key: &str,
+        query: &[(&str, &str)],
+        body: Bytes,
+    ) -> Result<Response, Error> {
+        let mut url = s3_configuration.endpoint.clone();
+        url.path_segments_mut()
+            .map_err(|_| Error::invalid_backup_storage_configuration())?
+            .pop_if_empty()
+            .push(&s3_configuration.bucket)
+            .extend(key.split("/"));
+        let mut query: Vec<String> = query
+            .iter()
+            .map(|(name, value)| format!("{}={}", encode(name), encode(value)))
+            .collect();
+        query.sort();
+        let canonical_query = query.join("&");
+        url.set_query(Some(canonical_query.as_str()).filter(|query| !query.is_empty()));
+        let host = match url.port() {
+            Some(port) => format!("{}:{}", url.host_str().unwrap_or_default(), port),
+            None => url.host_str().unwrap_or_default().to_string(),
+        };
+
+        let now = Utc::now();
+        let date = now.format("%Y%m%d").to_string();
+        let timestamp = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let payload_hash = hex_encode(&Sha256::digest(&body));
+        let canonical_request = format!(
+            "{}\n{}\n{}\nhost:{}\nx-amz-content-sha256:{}\nx-amz-date:{}\n\nhost;x-amz-content-sha256;x-amz-date\n{}",
+            method,
+            url.path(),
+            canonical_query,
+            host,
+            payload_hash,
+            timestamp,
+            payload_hash
+        );
+        let scope = format!("{}/{}/s3/aws4_request", date, s3_configuration.region);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            timestamp,
+            scope,
+            hex_encode(&Sha256::digest(canonical_request.as_bytes()))
+        );
+        let signing_key = [
+            date.as_str(),
+            s3_configuration.region.as_str(),
+            "s3",
+            "aws4_request",
+        ]
+        .iter()
+        .try_fold(
+            format!("AWS4{}", s3_configuration.secret_access_key).into_bytes(),
+            |key, part| hmac_sha256(&key, part.as_bytes()),
+        )?;
+        let signature = hex_encode(&hmac_sha256(&signing_key, string_to_sign.as_bytes())?);
+        let authorization = format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders=host;x-amz-content-sha256;x-amz-date, Signature={}",
+            s3_configuration.access_key_id, scope, signature
+        );
+
+        let response = self
+            .client
+            .request(method, url)
+            .header("x-amz-content-sha256", payload_hash)
+            .header("x-amz-date", timestamp)
+            .header("authorization", authorization)
+            .body(body)
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(Error::backup_storage_request_failed(
+                response.status().as_u16(),
+            ));
+        }
+
+        Ok(response)
+    }
+}
+
+pub async fn write_to_file(path: &str, mut data: ByteStream) -> Result<u64, Error> {
+    let mut file = File::create(path).await?;
+    let mut size = 0;
+    while let Some(chunk) = data.next().await {
+        let chunk = chunk?;
+        file.write_all(&chunk).await?;
+        size += chunk.len() as u64;
+    }
+    file.flush().await?;
+
+    Ok(size)
+}
+
+async fn read_part(data: &mut ByteStream) -> Result<(Bytes, bool), Error> {
+    let mut part = Vec::with_capacity(S3_PART_SIZE);
+    while part.len() < S3_PART_SIZE {
+        match data.next().await {
+            Some(chunk) => part.extend_from_slice(&chunk?),
+            None => return Ok((part.into(), true)),
+        }
+    }
+
+    Ok((part.into(), false))
+}
+
+fn get_xml_element(xml: &str, name: &str) -> Option<String> {
+    let (_, content) = xml.split_once(&format!("<{}>", name))?;
+    let (value, _) = content.split_once(&format!("</{}>", name))?;
+    Some(value.to_string())
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key)
+        .map_err(|_| Error::invalid_backup_storage_configuration())?;
+    mac.update(data);
+    Ok(mac.finalize().into_bytes().to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use axum::Router;
+    use axum::extract::{DefaultBodyLimit, RawQuery, State};
+    use axum::http::HeaderMap;
+    use futures::stream;
+    use tokio::net::TcpListener;
+    use uuid::Uuid;
+
+    use tokio::fs::remove_dir_all;
+
+    use super::*;
+
+    type S3Requests = Arc<Mutex<Vec<(Method, String, usize)>>>;
+
+    async fn handle_s3_request(
+        State(requests): State<S3Requests>,
+        method: Method,
+        RawQuery(query): RawQuery,
+        body: Bytes,
+    ) -> (HeaderMap, String) {
+        let query = query.unwrap_or_default();
+        requests
+            .lock()
+            .unwrap()
+            .push((method.clone(), query.clone(), body.len()));
+
+        let mut headers = HeaderMap::new();
+        if method == Method::PUT {
+            headers.insert(ETAG, "\"etag\"".parse().unwrap());
+        }
+        let body = if method == Method::POST && query == "uploads=" {
+            "<InitiateMultipartUploadResult><UploadId>upload</UploadId></InitiateMultipartUploadResult>"
+        } else {
+            ""
+        };
+
+        (headers, body.to_string())
+    }
+
+    async fn build_s3_backup_manager() -> (BackupManager, S3Requests) {
+        let requests = S3Requests::default();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let router = Router::new()
+            .fallback(handle_s3_request)
+            .layer(DefaultBodyLimit::disable())
+            .with_state(requests.clone());
+        tokio::spawn(async move { axum::serve(listener, router).await });
+
+        let backup_manager = BackupManager {
+            client: Client::new(),
+            folder_path: String::new(),
+            s3_configuration: Some(S3Configuration {
+                endpoint: format!("http://{}", address).parse().unwrap(),
+                bucket: "backups".to_string(),
+                region: "us-east-1".to_string(),
+                access_key_id: "access".to_string(),
+                secret_access_key: "secret".to_string(),
+            }),
+        };
+
+        (backup_manager, requests)
+    }
+
+    fn build_stream(chunks: Vec<Result<Vec<u8>, Error>>) -> ByteStream {
+        stream::iter(chunks.into_iter().map(|chunk| chunk.map(Bytes::from))).boxed()
+    }
+
+    #[tokio::test]
+    async fn stores_and_reads_local_backups() {
+        let backup_manager = BackupManager {
+            client: Client::new(),
+            folder_path: std::env::temp_dir()
+                .join(Uuid::new_v4().to_string())
+                .to_string_lossy()
+                .to_string(),
+            s3_configuration: None,
+        };
+        let data = build_stream(vec![Ok(b"first ".to_vec()), Ok(b"second".to_vec())]);
+
+        assert_eq!(
+            backup_manager.store("service/backup", data).await.unwrap(),
+            12
+        );
+        let content: Vec<Bytes> = backup_manager
+            .read("service/backup")
+            .await
+            .unwrap()
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(content.concat(), b"first second");
+
+        let data = build_stream(vec![Ok(b"first".to_vec()), Err(Error::backup_not_found())]);
+        assert!(backup_manager.store("service/failed", data).await.is_err());
+        assert!(backup_manager.read("service/failed").await.is_err());
+        assert!(backup_manager.read("service/failed.tmp").await.is_err());
+
+        remove_dir_all(&backup_manager.folder_path).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn stores_small_s3_backups_in_a_single_request() {
+        let (backup_manager, requests) = build_s3_backup_manager().await;
+        let data = build_stream(vec![Ok(vec![0; 10]), Ok(vec![0; 5])]);
+
+        assert_eq!(
+            backup_manager.store("service/backup", data).await.unwrap(),
+            15
+        );
+        assert_eq!(
+            *requests.lock().unwrap(),
+            vec![(Method::PUT, String::new(), 15)]
+        );
+    }
+
+    #[tokio::test]
+    async fn stores_large_s3_backups_in_parts() {
+        let (backup_manager, requests) = build_s3_backup_manager().await;
+        let data = build_stream(vec![Ok(vec![0; S3_PART_SIZE]), Ok(vec![0; 10])]);
+
+        assert_eq!(
+            backup_manager.store("service/backup", data).await.unwrap(),
+            S3_PART_SIZE as u64 + 10
+        );
+        let requests = requests.lock().unwrap();
+        assert_eq!(
+            requests[..3],
+            [
+                (Method::POST, "uploads=".to_string(), 0),
+                (
+                    Method::PUT,
+                    "partNumber=1&uploadId=upload".to_string(),
+                    S3_PART_SIZE
+                ),
+                (Method::PUT, "partNumber=2&uploadId=upload".to_string(), 10),
+            ]
+        );
+        assert_eq!(requests[3].0, Method::POST);
+        assert_eq!(requests[3].1, "uploadId=upload");
+        assert_eq!(requests.len(), 4);
+    }
+
+    #[tokio::test]
+    async fn aborts_failed_s3_uploads() {
+        let (backup_manager, requests) = build_s3_backup_manager().await;
+        let data = build_stream(vec![
+            Ok(vec![0; S3_PART_SIZE]),
+            Err(Error::backup_not_found()),
+        ]);
+
+        assert!(backup_manager.store("service/backup", data).await.is_err());
+        let requests = requests.lock().unwrap();
+        assert_eq!(
+            requests.last(),
+            Some(&(Method::DELETE, "uploadId=upload".to_string(), 0))
+        );
+    }
+
+    #[test]
+    fn gets_xml_elements() {
+        let xml = "<Result><UploadId>abc</UploadId></Result>";
+
+        assert_eq!(get_xml_element(xml, "UploadId"), Some("abc".to_string()));
+        assert_eq!(get_xml_element(xml, "Code"), None);
+    }
+}
@@ -0,0 +1,52 @@
+use std::fmt::Display;
+
+use postgres_types::{FromSql, ToSql};
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, FromSql, ToSql, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[postgres(name = "backup_storage")]
+pub enum BackupStorageKind {
+    Local,
+    S3,
+}
+
+impl Display for BackupStorageKind {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Local => write!(formatter, "local"),
+            Self::S3 => write!(formatter, "s3"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, FromSql, ToSql, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[postgres(name = "backup_trigger")]
+pub enum BackupTrigger {
+    Scheduled,
+    Manual,
+    PreRestore,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupPolicy {
+    pub interval_hours: u32,
+    pub retention_count: u32,
+}
+
+impl BackupPolicy {
+    pub fn is_valid(&self) -> bool {
+        self.interval_hours > 0 && self.retention_count > 0
+    }
+}
+
+#[derive(Clone)]
+pub struct S3Configuration {
+    pub endpoint: reqwest::Url,
+    pub bucket: String,
+    pub region: String,
+    pub access_key_id: String,
+    pub secret_access_key: String,
+}
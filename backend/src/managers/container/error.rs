@@ -36,4 +36,49 @@ impl Error {
             message: format!("cannot delete container {}", name),
         }
     }
+
+    pub fn container_command_failed(name: &str, message: &str) -> Self {
+        Self {
+            code: StatusCode::INTERNAL_SERVER_ERROR,
+            message: format!("command failed in container {}: {}", name, message),
+        }
+    }
+
+    pub fn bind_mount_not_allowed(host_path: &str) -> Self {
+        Self {
+            code: StatusCode::BAD_REQUEST,
+            message: format!("bind mount of {} is not allowed", host_path),
+        }
+    }
+
+    pub fn invalid_allowed_bind_mount_path(path: &str) -> Self {
+        Self {
+            code: StatusCode::INTERNAL_SERVER_ERROR,
+            message: format!(
+                "allowed bind mount path {:?} must be an absolute path other than /",
+                path
+            ),
+        }
+    }
+
+    pub fn registry_image_digest_not_found(image_reference: &str) -> Self {
+        Self {
+            code: StatusCode::BAD_GATEWAY,
+            message: format!("registry did not return a digest for {}", image_reference),
+        }
+    }
+
+    pub fn invalid_job_schedule(schedule: &str) -> Self {
+        Self {
+            code: StatusCode::BAD_REQUEST,
+            message: format!("invalid cron schedule {}", schedule),
+        }
+    }
+
+    pub fn container_exit_code_not_found(name: &str) -> Self {
+        Self {
+            code: StatusCode::INTERNAL_SERVER_ERROR,
+            message: format!("exit code of container {} not found", name),
+        }
+    }
 }
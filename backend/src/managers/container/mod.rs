@@ -1,10 +1,21 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::fs::Permissions;
 use std::net::{Ipv4Addr, SocketAddrV4, TcpListener};
+use std::os::unix::fs::{PermissionsExt, chown};
 
 use crate::error::Error;
-use crate::managers::container::models::Log;
+use crate::managers::container::models::{
+    BindMount, EnvironmentVariable, ImageLoadMessage, ImagePullPolicy, ImageSha, Log,
+    RegistryCredentials, SecretFile, Sidecar,
+};
+use crate::models::ByteStream;
+use crate::settings::Settings;
+use axum::body::Bytes;
+use bollard::body_try_stream;
 use bollard::container::LogOutput;
+use bollard::exec::{StartExecOptions, StartExecResults};
 use bollard::query_parameters::{InspectNetworkOptions, LogsOptionsBuilder, PruneImagesOptions};
+use bollard::secret::ExecConfig;
 #[allow(deprecated)]
 use bollard::volume::CreateVolumeOptions;
 #[allow(deprecated)]
@@ -14,29 +25,65 @@ use bollard::{
     Docker,
     network::{ConnectNetworkOptions, CreateNetworkOptions, DisconnectNetworkOptions},
     query_parameters::{
-        CreateContainerOptionsBuilder, CreateImageOptionsBuilder, ListContainersOptionsBuilder,
-        ListNetworksOptions, RemoveContainerOptionsBuilder, StartContainerOptions,
-        StopContainerOptions,
+        CreateContainerOptionsBuilder, CreateImageOptionsBuilder,
+        DownloadFromContainerOptionsBuilder, InspectContainerOptions, ListContainersOptionsBuilder,
+        ListImagesOptionsBuilder, ListNetworksOptions, RemoveContainerOptionsBuilder,
+        RemoveImageOptions, RenameContainerOptionsBuilder, StartContainerOptions,
+        StopContainerOptions, UploadToContainerOptionsBuilder, WaitContainerOptions,
+    },
+    secret::{
+        ContainerCreateBody, ContainerSummaryStateEnum, EndpointSettings, HostConfig, Network,
+        PortBinding,
     },
-    secret::{ContainerCreateBody, ContainerSummaryStateEnum, HostConfig, Network, PortBinding},
 };
 use chrono::NaiveDateTime;
 use futures::TryStreamExt;
+use futures::channel::mpsc::channel;
+use futures::join;
+use futures::sink::SinkExt;
 use futures::stream::StreamExt;
 use models::ContainerConfiguration;
 use reqwest::header::CONTENT_TYPE;
+use tokio::fs::{
+    OpenOptions, create_dir_all, read_dir, remove_dir_all, set_permissions, try_exists,
+};
+use tokio::io::AsyncWriteExt;
 use tokio::time::{Duration, sleep};
+use uuid::Uuid;
 
 pub mod error;
 pub mod models;
 
+static VOLUME_HELPER_MOUNT_PATH: &str = "/volume";
+static MAX_JOB_LOG_LINES: u32 = 1000;
+
+enum ServiceContainer<'a> {
+    Main,
+    Sidecar(&'a Sidecar),
+    Job {
+        container_name: &'a str,
+        command: Option<Vec<String>>,
+    },
+}
+
 #[derive(Clone)]
 pub struct ContainerManager {
     client: Docker,
+    secret_files_path: String,
+    secret_files_host_path: String,
+    allowed_bind_mount_paths: Vec<String>,
 }
 
 impl ContainerManager {
-    pub async fn new() -> Result<Self, Error> {
+    pub async fn new(settings: &Settings) -> Result<Self, Error> {
+        if let Some(path) = settings
+            .allowed_bind_mount_paths
+            .iter()
+            .find(|path| !BindMount::is_allowed_path(path))
+        {
+            return Err(Error::invalid_allowed_bind_mount_path(path));
+        }
+
         let client = Docker::connect_with_local_defaults()?;
 
         let _connection_test = client.version().await?;
@@ -111,13 +158,148 @@ impl ContainerManager {
             tracing::info!("reset docker status: removed {} networks", networks.len());
         }
 
-        Ok(Self { client })
+        create_dir_all(&settings.secret_files_path).await?;
+        let mut secret_files_folders = read_dir(&settings.secret_files_path).await?;
+        while let Some(folder) = secret_files_folders.next_entry().await? {
+            remove_dir_all(folder.path()).await?;
+        }
+        set_permissions(&settings.secret_files_path, Permissions::from_mode(0o700)).await?;
+        tracing::info!("reset secret files in {}", settings.secret_files_path);
+
+        Ok(Self {
+            client,
+            secret_files_path: settings.secret_files_path.clone(),
+            secret_files_host_path: settings.secret_files_host_path(),
+            allowed_bind_mount_paths: settings.allowed_bind_mount_paths.clone(),
+        })
     }
 
     pub async fn start_container(
         &self,
         configuration: &ContainerConfiguration,
     ) -> Result<(), Error> {
+        self.prepare_volumes(configuration).await?;
+
+        self.create_and_start_container(configuration, &ServiceContainer::Main)
+            .await?;
+        for sidecar in configuration.sidecars.iter() {
+            if let Err(error) = self
+                .create_and_start_container(configuration, &ServiceContainer::Sidecar(sidecar))
+                .await
+            {
+                // The containers of a service are only ever deployed together
+                self.stop_and_remove_service_containers(configuration)
+                    .await?;
+                return Err(error);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The running containers are renamed out of the way and keep serving until removed with
+    /// `remove_replaced_containers`.
+    pub async fn start_replacement_container(
+        &self,
+        configuration: &ContainerConfiguration,
+    ) -> Result<Vec<(String, String)>, Error> {
+        let mut container_names = vec![configuration.name.clone()];
+        container_names.extend(
+            self.get_sidecar_container_names(&configuration.name)
+                .await?,
+        );
+
+        let mut replaced_containers = vec![];
+        for container_name in container_names {
+            let replaced_container_name =
+                format!("{}.replaced-{}", container_name, Uuid::new_v4().simple());
+            let options = RenameContainerOptionsBuilder::new()
+                .name(&replaced_container_name)
+                .build();
+            if let Err(error) = self.client.rename_container(&container_name, options).await {
+                self.rename_replaced_containers_back(&replaced_containers)
+                    .await?;
+                return Err(error.into());
+            }
+            replaced_containers.push((container_name, replaced_container_name));
+        }
+
+        if let Err(error) = self.start_container(configuration).await {
+            self.rename_replaced_containers_back(&replaced_containers)
+                .await?;
+            return Err(error);
+        }
+        if let Err(error) = self.attach_service_containers(configuration).await {
+            self.restore_replaced_containers(configuration, &replaced_containers)
+                .await?;
+            return Err(error);
+        }
+
+        Ok(replaced_containers)
+    }
+
+    pub async fn restore_replaced_containers(
+        &self,
+        configuration: &ContainerConfiguration,
+        replaced_containers: &[(String, String)],
+    ) -> Result<(), Error> {
+        self.stop_and_remove_service_containers(configuration)
+            .await?;
+        self.rename_replaced_containers_back(replaced_containers)
+            .await
+    }
+
+    pub async fn remove_replaced_containers(
+        &self,
+        replaced_containers: &[(String, String)],
+    ) -> Result<(), Error> {
+        for (_, replaced_container_name) in replaced_containers {
+            self.stop_and_remove_single_container(replaced_container_name)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn rename_replaced_containers_back(
+        &self,
+        replaced_containers: &[(String, String)],
+    ) -> Result<(), Error> {
+        for (container_name, replaced_container_name) in replaced_containers {
+            let options = RenameContainerOptionsBuilder::new()
+                .name(container_name)
+                .build();
+            self.client
+                .rename_container(replaced_container_name, options)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// The container is kept once exited, to read its exit code and logs.
+    pub async fn start_job_container(
+        &self,
+        configuration: &ContainerConfiguration,
+        container_name: &str,
+        command: Option<Vec<String>>,
+    ) -> Result<(), Error> {
+        self.prepare_volumes(configuration).await?;
+
+        let job = ServiceContainer::Job {
+            container_name,
+            command,
+        };
+        if let Err(error) = self.create_and_start_container(configuration, &job).await {
+            self.stop_and_remove_single_container(container_name)
+                .await?;
+            return Err(error);
+        }
+
+        Ok(())
+    }
+
+    async fn prepare_volumes(&self, configuration: &ContainerConfiguration) -> Result<(), Error> {
         let volumes: Vec<(String, String)> = configuration
             .stateful_volume_paths
             .iter()
@@ -125,47 +307,81 @@ impl ContainerManager {
             .collect();
 
         for (volume_id, _) in volumes.iter() {
-            let volume_details = self.client.inspect_volume(volume_id).await;
-            let needs_creation = match volume_details {
-                Err(bollard::errors::Error::DockerResponseServerError {
-                    status_code: 404,
-                    message: _,
-                }) => Ok(true),
-                Err(error) => Err(error),
-                Ok(_) => Ok(false),
-            }?;
-
-            if needs_creation {
-                #[allow(deprecated)]
-                let options = CreateVolumeOptions {
-                    name: volume_id.clone(),
-                    ..Default::default()
-                };
-                self.client.create_volume(options).await?;
-                tracing::info!("volume {} created", volume_id);
-            } else {
-                tracing::info!("volume {} already exists", volume_id);
-            }
+            self.create_volume_if_missing(volume_id).await?;
+        }
+        for shared_volume in configuration.shared_volumes.iter() {
+            self.create_volume_if_missing(&shared_volume.get_volume_id())
+                .await?;
+        }
+        // The allow-list may have changed since the service was configured
+        if let Some(bind_mount) = configuration
+            .bind_mounts
+            .iter()
+            .find(|bind_mount| !self.allows_bind_mount(bind_mount))
+        {
+            return Err(Error::bind_mount_not_allowed(&bind_mount.host_path));
         }
 
-        let is_remote_image = configuration.image_name.is_some();
-        let image_tag = if let Some(image_name) = &configuration.image_name {
-            format!(
-                "{}@sha256:{}",
-                image_name,
-                configuration.image_sha.get_value()
-            )
+        Ok(())
+    }
+
+    async fn create_and_start_container(
+        &self,
+        configuration: &ContainerConfiguration,
+        container: &ServiceContainer<'_>,
+    ) -> Result<(), Error> {
+        let container_name = match container {
+            ServiceContainer::Main => configuration.name.clone(),
+            ServiceContainer::Sidecar(sidecar) => configuration.get_sidecar_container_name(sidecar),
+            ServiceContainer::Job { container_name, .. } => container_name.to_string(),
+        };
+        let sidecar = match container {
+            ServiceContainer::Sidecar(sidecar) => Some(*sidecar),
+            _ => None,
+        };
+        let (image_name, image_sha, registry_credentials) = match sidecar.and_then(|sidecar| {
+            sidecar
+                .image_sha
+                .as_ref()
+                .map(|image_sha| (sidecar, image_sha))
+        }) {
+            Some((sidecar, image_sha)) => (
+                &sidecar.image_name,
+                image_sha,
+                &sidecar.registry_credentials,
+            ),
+            None => (
+                &configuration.image_name,
+                &configuration.image_sha,
+                &configuration.registry_credentials,
+            ),
+        };
+
+        let is_remote_image = image_name.is_some();
+        let image_tag = if let Some(image_name) = image_name {
+            format!("{}@sha256:{}", image_name, image_sha.get_value())
         } else {
-            format!("sha256:{}", configuration.image_sha.get_value())
+            format!("sha256:{}", image_sha.get_value())
         };
 
-        if is_remote_image {
+        // Jobs run the image the service was started from
+        let is_job = matches!(container, ServiceContainer::Job { .. });
+        let is_image_present = (configuration.pull_policy == ImagePullPolicy::IfNotPresent
+            || is_job)
+            && self.client.inspect_image(&image_tag).await.is_ok();
+
+        if is_image_present {
+            tracing::info!("skipped pulling image {} as it's present", image_tag);
+        } else if is_remote_image {
             let create_image_options = CreateImageOptionsBuilder::new()
                 .from_image(&image_tag)
                 .build();
+            let credentials = registry_credentials
+                .as_ref()
+                .map(RegistryCredentials::to_docker_credentials);
             let mut image_pull_stream =
                 self.client
-                    .create_image(Some(create_image_options), None, None);
+                    .create_image(Some(create_image_options), None, credentials);
 
             tracing::info!("started pulling image {}", image_tag);
 
@@ -179,46 +395,86 @@ impl ContainerManager {
         }
 
         let options = CreateContainerOptionsBuilder::new()
-            .name(&configuration.name)
+            .name(&container_name)
             .build();
 
-        let env_vars: Vec<String> = configuration
+        let environment_variables: Vec<&EnvironmentVariable> = configuration
             .environment_variables
             .iter()
             .chain(&configuration.secrets)
             .chain(&configuration.internal_secrets)
-            .map(|env_var| format!("{}={}", env_var.name, env_var.value))
+            .chain(
+                sidecar
+                    .map(|sidecar| sidecar.environment_variables.as_slice())
+                    .unwrap_or_default(),
+            )
             .collect();
-        let port_bindings: HashMap<String, Option<Vec<PortBinding>>> =
-            [configuration.exposed_port.clone()]
-                .iter()
-                .map(|port| {
-                    (
-                        port.internal.to_string(),
-                        Some(vec![PortBinding {
-                            host_ip: Some("127.0.0.1".to_string()),
-                            host_port: Some(port.external.to_string()),
-                        }]),
-                    )
-                })
-                .collect();
-        let exposed_ports: HashMap<String, HashMap<(), ()>> = [configuration.exposed_port.clone()]
+        let env_vars: Vec<String> = environment_variables
+            .iter()
+            .map(|env_var| match &env_var.file {
+                Some(file) => format!("{}={}", env_var.file_environment_variable_name(), file.path),
+                None => format!("{}={}", env_var.name, env_var.value),
+            })
+            .collect();
+        // Only the main container is exposed through the proxy
+        let published_ports = match container {
+            ServiceContainer::Main => vec![configuration.exposed_port.clone()],
+            _ => vec![],
+        };
+        let command = match container {
+            ServiceContainer::Main => None,
+            ServiceContainer::Sidecar(sidecar) => sidecar.command.clone(),
+            ServiceContainer::Job { command, .. } => command.clone(),
+        };
+        let port_bindings: HashMap<String, Option<Vec<PortBinding>>> = published_ports
+            .iter()
+            .map(|port| {
+                (
+                    port.internal.to_string(),
+                    Some(vec![PortBinding {
+                        host_ip: Some("127.0.0.1".to_string()),
+                        host_port: Some(port.external.to_string()),
+                    }]),
+                )
+            })
+            .collect();
+        let exposed_ports: HashMap<String, HashMap<(), ()>> = published_ports
             .iter()
             .map(|port| (port.internal.to_string(), HashMap::new()))
             .collect();
-        let volume_bindings = configuration
+        let mut volume_bindings: Vec<String> = configuration
             .stateful_volume_paths
             .iter()
             .map(|path| format!("{}:{}", configuration.get_stateful_volume_id(path), path))
             .collect();
+        volume_bindings.extend(configuration.shared_volumes.iter().map(|shared_volume| {
+            get_volume_binding(
+                &shared_volume.get_volume_id(),
+                &shared_volume.path,
+                shared_volume.read_only,
+            )
+        }));
+        volume_bindings.extend(configuration.bind_mounts.iter().map(|bind_mount| {
+            get_volume_binding(
+                &bind_mount.host_path,
+                &bind_mount.path,
+                bind_mount.read_only,
+            )
+        }));
+        volume_bindings.extend(
+            self.write_secret_files(&container_name, &environment_variables)
+                .await?,
+        );
 
         let configuration_body = ContainerCreateBody {
+            cmd: command,
             env: Some(env_vars),
             exposed_ports: Some(exposed_ports),
             host_config: Some(HostConfig {
-                auto_remove: Some(true),
+                auto_remove: Some(!is_job),
                 port_bindings: Some(port_bindings),
                 binds: Some(volume_bindings),
+                network_mode: is_job.then(|| configuration.name.clone()),
                 ..Default::default()
             }),
             image: Some(image_tag),
@@ -230,10 +486,10 @@ impl ContainerManager {
             .await?;
 
         self.client
-            .start_container(&configuration.name, None::<StartContainerOptions>)
+            .start_container(&container_name, None::<StartContainerOptions>)
             .await?;
 
-        tracing::info!("container {} started", configuration.name);
+        tracing::info!("container {} started", container_name);
 
         Ok(())
     }
@@ -252,10 +508,13 @@ impl ContainerManager {
         };
         self.client.create_network(options).await?;
 
-        for container_name in ["kiwi-postgres", "kiwi-redis", &configuration.name] {
+        let mut container_names = vec!["kiwi-postgres".to_string(), "kiwi-redis".to_string()];
+        // Jobs still running across a redeployment get their network back
+        container_names.extend(self.get_job_container_names(&configuration.name).await?);
+        for container_name in container_names.iter() {
             #[allow(deprecated)]
             let options = ConnectNetworkOptions {
-                container: container_name,
+                container: container_name.as_str(),
                 ..Default::default()
             };
             self.client
@@ -263,9 +522,66 @@ impl ContainerManager {
                 .await?;
         }
 
+        self.attach_service_containers(configuration).await
+    }
+
+    async fn attach_service_containers(
+        &self,
+        configuration: &ContainerConfiguration,
+    ) -> Result<(), Error> {
+        for container_name in self.get_service_container_names(configuration) {
+            #[allow(deprecated)]
+            let options = ConnectNetworkOptions {
+                container: container_name.as_str(),
+                ..Default::default()
+            };
+            self.client
+                .connect_network(configuration.name.as_str(), options)
+                .await?;
+        }
+
+        if let Some(private_network) = &configuration.private_network {
+            let network_id = private_network.get_network_id();
+            self.create_network_if_missing(&network_id).await?;
+            #[allow(deprecated)]
+            let options = ConnectNetworkOptions {
+                container: configuration.name.as_str(),
+                endpoint_config: EndpointSettings {
+                    aliases: Some(vec![private_network.alias.clone()]),
+                    ..Default::default()
+                },
+            };
+            self.client
+                .connect_network(network_id.as_str(), options)
+                .await?;
+        }
+
         Ok(())
     }
 
+    async fn create_network_if_missing(&self, name: &str) -> Result<(), Error> {
+        match self
+            .client
+            .inspect_network(name, None::<InspectNetworkOptions>)
+            .await
+        {
+            Ok(_) => Ok(()),
+            Err(bollard::errors::Error::DockerResponseServerError {
+                status_code: 404,
+                message: _,
+            }) => {
+                #[allow(deprecated)]
+                let options = CreateNetworkOptions {
+                    name,
+                    ..Default::default()
+                };
+                self.client.create_network(options).await?;
+                Ok(())
+            }
+            Err(error) => Err(error)?,
+        }
+    }
+
     pub async fn get_container_status(&self, name: &str) -> Result<String, Error> {
         let status = self
             .get_container_status_enum(name)
@@ -300,6 +616,39 @@ impl ContainerManager {
 
     pub async fn stop_and_remove_container(&self, name: &str) -> Result<(), Error> {
         self.detach_and_remove_any_network(name).await?;
+        for sidecar_container_name in self.get_sidecar_container_names(name).await? {
+            self.stop_and_remove_single_container(&sidecar_container_name)
+                .await?;
+        }
+
+        self.stop_and_remove_single_container(name).await
+    }
+
+    /// Leaves the network and the other containers of the service alone.
+    async fn stop_and_remove_service_containers(
+        &self,
+        configuration: &ContainerConfiguration,
+    ) -> Result<(), Error> {
+        for container_name in self.get_service_container_names(configuration) {
+            self.stop_and_remove_single_container(&container_name)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    fn get_service_container_names(&self, configuration: &ContainerConfiguration) -> Vec<String> {
+        let mut container_names = vec![configuration.name.clone()];
+        container_names.extend(
+            configuration
+                .sidecars
+                .iter()
+                .map(|sidecar| configuration.get_sidecar_container_name(sidecar)),
+        );
+        container_names
+    }
+
+    async fn stop_and_remove_single_container(&self, name: &str) -> Result<(), Error> {
         let status = self.get_container_status_enum(name).await;
 
         match status {
@@ -315,29 +664,447 @@ impl ContainerManager {
         }
 
         remove_container(&self.client, name).await?;
+        self.remove_secret_files(name).await?;
+
+        Ok(())
+    }
+
+    pub async fn wait_for_container(&self, name: &str) -> Result<i64, Error> {
+        let mut wait_stream = self
+            .client
+            .wait_container(name, None::<WaitContainerOptions>);
+        while let Some(wait_result) = wait_stream.next().await {
+            match wait_result {
+                // Non-zero exit codes are reported as errors, read below
+                Ok(_) | Err(bollard::errors::Error::DockerContainerWaitError { .. }) => {}
+                Err(error) => return Err(error.into()),
+            }
+        }
+
+        self.client
+            .inspect_container(name, None::<InspectContainerOptions>)
+            .await?
+            .state
+            .and_then(|state| state.exit_code)
+            .ok_or(Error::container_exit_code_not_found(name))
+    }
+
+    pub async fn get_job_container_logs(&self, name: &str) -> Result<String, Error> {
+        let options = LogsOptionsBuilder::new()
+            .stdout(true)
+            .stderr(true)
+            .tail(&MAX_JOB_LOG_LINES.to_string())
+            .build();
+        let logs_raw: Vec<LogOutput> = self.client.logs(name, Some(options)).try_collect().await?;
+
+        Ok(logs_raw.iter().map(LogOutput::to_string).collect())
+    }
+
+    pub async fn stop_job_container(&self, name: &str) -> Result<(), Error> {
+        match self
+            .client
+            .stop_container(name, None::<StopContainerOptions>)
+            .await
+        {
+            Ok(_)
+            | Err(bollard::errors::Error::DockerResponseServerError {
+                status_code: 304 | 404,
+                message: _,
+            }) => Ok(()),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    pub async fn stop_and_remove_job_container(&self, name: &str) -> Result<(), Error> {
+        self.stop_and_remove_single_container(name).await
+    }
+
+    pub async fn stop_and_remove_job_containers(&self, name: &str) -> Result<(), Error> {
+        for job_container_name in self.get_job_container_names(name).await? {
+            self.stop_and_remove_single_container(&job_container_name)
+                .await?;
+        }
 
         Ok(())
     }
 
-    pub async fn remove_volumes(
+    async fn get_sidecar_container_names(&self, name: &str) -> Result<Vec<String>, Error> {
+        Ok(self
+            .get_auxiliary_container_names(name)
+            .await?
+            .into_iter()
+            .filter(|(_, is_job)| !is_job)
+            .map(|(container_name, _)| container_name)
+            .collect())
+    }
+
+    async fn get_job_container_names(&self, name: &str) -> Result<Vec<String>, Error> {
+        Ok(self
+            .get_auxiliary_container_names(name)
+            .await?
+            .into_iter()
+            .filter(|(_, is_job)| *is_job)
+            .map(|(container_name, _)| container_name)
+            .collect())
+    }
+
+    /// Jobs are named `<service>.<job>.<run>` and sidecars `<service>.<sidecar>`.
+    async fn get_auxiliary_container_names(
         &self,
-        configuration: &ContainerConfiguration,
-    ) -> Result<(), Error> {
-        let volume_ids: Vec<String> = configuration
-            .stateful_volume_paths
+        name: &str,
+    ) -> Result<Vec<(String, bool)>, Error> {
+        let list_options = ListContainersOptionsBuilder::new().all(true).build();
+        let containers = self.client.list_containers(Some(list_options)).await?;
+        let prefix = format!("{}.", name);
+
+        Ok(containers
+            .into_iter()
+            .flat_map(|container| container.names.unwrap_or_default())
+            .filter_map(|container_name| {
+                let container_name = container_name.trim_start_matches('/');
+                let suffix = container_name.strip_prefix(&prefix)?;
+                Some((container_name.to_string(), suffix.contains('.')))
+            })
+            .collect())
+    }
+
+    async fn write_secret_files(
+        &self,
+        container_name: &str,
+        environment_variables: &[&EnvironmentVariable],
+    ) -> Result<Vec<String>, Error> {
+        self.remove_secret_files(container_name).await?;
+
+        let secret_files: Vec<(&String, &SecretFile)> = environment_variables
             .iter()
-            .map(|path| configuration.get_stateful_volume_id(path))
+            .filter_map(|env_var| env_var.file.as_ref().map(|file| (&env_var.value, file)))
             .collect();
+        if secret_files.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let folder_path = format!("{}/{}", self.secret_files_path, container_name);
+        create_dir_all(&folder_path).await?;
+        set_permissions(&folder_path, Permissions::from_mode(0o700)).await?;
 
-        for volume_id in volume_ids {
+        let mut bindings = vec![];
+        for (index, (value, file)) in secret_files.into_iter().enumerate() {
+            let file_path = format!("{}/{}", folder_path, index);
+            let mut secret_file = OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .mode(0o600)
+                .open(&file_path)
+                .await?;
+            secret_file.write_all(value.as_bytes()).await?;
+            secret_file.flush().await?;
+            set_permissions(&file_path, Permissions::from_mode(file.mode)).await?;
+            if let Some(owner_id) = file.owner_id {
+                chown(&file_path, Some(owner_id), Some(owner_id))?;
+            }
+
+            bindings.push(format!(
+                "{}/{}/{}:{}:ro",
+                self.secret_files_host_path, container_name, index, file.path
+            ));
+        }
+
+        tracing::info!(
+            "wrote {} secret files for container {}",
+            bindings.len(),
+            container_name
+        );
+
+        Ok(bindings)
+    }
+
+    async fn remove_secret_files(&self, name: &str) -> Result<(), Error> {
+        let folder_path = format!("{}/{}", self.secret_files_path, name);
+        if try_exists(&folder_path).await? {
+            remove_dir_all(&folder_path).await?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn remove_volume(&self, volume_id: &str) -> Result<(), Error> {
+        #[allow(deprecated)]
+        let options = RemoveVolumeOptions { force: true };
+        match self.client.remove_volume(volume_id, Some(options)).await {
+            Ok(_)
+            | Err(bollard::errors::Error::DockerResponseServerError {
+                status_code: 404,
+                message: _,
+            }) => Ok(()),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    pub fn allows_bind_mount(&self, bind_mount: &BindMount) -> bool {
+        bind_mount.is_within(&self.allowed_bind_mount_paths)
+    }
+
+    pub async fn create_volume_if_missing(&self, volume_id: &str) -> Result<(), Error> {
+        let volume_details = self.client.inspect_volume(volume_id).await;
+        let needs_creation = match volume_details {
+            Err(bollard::errors::Error::DockerResponseServerError {
+                status_code: 404,
+                message: _,
+            }) => Ok(true),
+            Err(error) => Err(error),
+            Ok(_) => Ok(false),
+        }?;
+
+        if needs_creation {
             #[allow(deprecated)]
-            let options = RemoveVolumeOptions { force: true };
-            self.client.remove_volume(&volume_id, Some(options)).await?;
+            let options = CreateVolumeOptions {
+                name: volume_id.to_string(),
+                ..Default::default()
+            };
+            self.client.create_volume(options).await?;
+            tracing::info!("volume {} created", volume_id);
+        } else {
+            tracing::info!("volume {} already exists", volume_id);
+        }
+
+        Ok(())
+    }
+
+    pub async fn export_volume(&self, volume_id: &str) -> Result<ByteStream, Error> {
+        let helper_name = self.create_volume_helper_container(volume_id).await?;
+        let options = DownloadFromContainerOptionsBuilder::new()
+            .path(&format!("{}/.", VOLUME_HELPER_MOUNT_PATH))
+            .build();
+        let mut archive = self
+            .client
+            .download_from_container(&helper_name, Some(options));
+
+        let client = self.client.clone();
+        let (mut sender, receiver) = channel::<Result<Bytes, Error>>(4);
+        tokio::spawn(async move {
+            while let Some(chunk) = archive.next().await {
+                let is_error = chunk.is_err();
+                if sender.send(chunk.map_err(Error::from)).await.is_err() || is_error {
+                    break;
+                }
+            }
+            if let Err(error) = remove_container(&client, &helper_name).await {
+                tracing::error!(
+                    "failed to remove volume helper container {}: {}",
+                    helper_name,
+                    error
+                );
+            }
+        });
+
+        Ok(receiver.boxed())
+    }
+
+    /// The archive is extracted into a temporary volume first, so that the volume is left untouched
+    /// when the archive is invalid or cut short.
+    pub async fn import_volume(&self, volume_id: &str, archive: ByteStream) -> Result<(), Error> {
+        let import_volume_id = format!("{}-import-{}", volume_id, Uuid::new_v4().simple());
+        self.create_volume_if_missing(&import_volume_id).await?;
+        if let Err(error) = self.upload_to_volume(&import_volume_id, archive).await {
+            self.remove_volume(&import_volume_id).await?;
+            return Err(error);
         }
 
+        self.remove_volume(volume_id).await?;
+        self.create_volume_if_missing(volume_id).await?;
+        let helper_name = self
+            .create_volume_helper_container(&import_volume_id)
+            .await?;
+        let options = DownloadFromContainerOptionsBuilder::new()
+            .path(&format!("{}/.", VOLUME_HELPER_MOUNT_PATH))
+            .build();
+        let imported_archive = self
+            .client
+            .download_from_container(&helper_name, Some(options))
+            .map_err(Error::from)
+            .boxed();
+        let copy_result = self.upload_to_volume(volume_id, imported_archive).await;
+        remove_container(&self.client, &helper_name).await?;
+        if let Err(error) = copy_result {
+            tracing::error!(
+                "failed to copy the imported content into volume {}, it's kept in volume {}",
+                volume_id,
+                import_volume_id
+            );
+            return Err(error);
+        }
+        self.remove_volume(&import_volume_id).await?;
+
+        tracing::info!("volume {} imported", volume_id);
+
+        Ok(())
+    }
+
+    async fn upload_to_volume(&self, volume_id: &str, archive: ByteStream) -> Result<(), Error> {
+        let helper_name = self.create_volume_helper_container(volume_id).await?;
+        let options = UploadToContainerOptionsBuilder::new()
+            .path(VOLUME_HELPER_MOUNT_PATH)
+            .build();
+        let upload_result = self
+            .client
+            .upload_to_container(
+                &helper_name,
+                Some(options),
+                body_try_stream(archive.map_err(std::io::Error::other)),
+            )
+            .await;
+        remove_container(&self.client, &helper_name).await?;
+        upload_result?;
+
         Ok(())
     }
 
+    async fn create_volume_helper_container(&self, volume_id: &str) -> Result<String, Error> {
+        let image = self
+            .client
+            .inspect_container("kiwi-postgres", None::<InspectContainerOptions>)
+            .await?
+            .image
+            .ok_or(Error::container_id_not_found())?;
+        let helper_name = format!("kiwi-volume-helper-{}", Uuid::new_v4().simple());
+        let options = CreateContainerOptionsBuilder::new()
+            .name(&helper_name)
+            .build();
+        let configuration_body = ContainerCreateBody {
+            host_config: Some(HostConfig {
+                binds: Some(vec![format!("{}:{}", volume_id, VOLUME_HELPER_MOUNT_PATH)]),
+                ..Default::default()
+            }),
+            image: Some(image),
+            ..Default::default()
+        };
+        self.client
+            .create_container(Some(options), configuration_body)
+            .await?;
+
+        Ok(helper_name)
+    }
+
+    pub async fn execute_command(
+        &self,
+        name: &str,
+        command: Vec<String>,
+        input: Option<ByteStream>,
+    ) -> Result<Vec<u8>, Error> {
+        let exec_configuration = ExecConfig {
+            attach_stdin: Some(input.is_some()),
+            attach_stdout: Some(true),
+            attach_stderr: Some(true),
+            cmd: Some(command),
+            ..Default::default()
+        };
+        let exec = self.client.create_exec(name, exec_configuration).await?;
+
+        let mut output_bytes = vec![];
+        let mut error_bytes = vec![];
+        if let StartExecResults::Attached {
+            mut output,
+            input: mut stdin,
+        } = self
+            .client
+            .start_exec(&exec.id, None::<StartExecOptions>)
+            .await?
+        {
+            let write_input = async {
+                if let Some(mut input) = input {
+                    while let Some(chunk) = input.next().await {
+                        stdin.write_all(&chunk?).await?;
+                    }
+                    stdin.shutdown().await?;
+                }
+                Ok::<(), Error>(())
+            };
+            let read_output = async {
+                while let Some(log) = output.next().await {
+                    match log? {
+                        LogOutput::StdOut { message } => output_bytes.extend_from_slice(&message),
+                        LogOutput::StdErr { message } => error_bytes.extend_from_slice(&message),
+                        _ => {}
+                    }
+                }
+                Ok::<(), Error>(())
+            };
+            let (write_result, read_result) = join!(write_input, read_output);
+            write_result?;
+            read_result?;
+        }
+
+        let exit_code = self.client.inspect_exec(&exec.id).await?.exit_code;
+        if exit_code != Some(0) {
+            return Err(Error::container_command_failed(
+                name,
+                String::from_utf8_lossy(&error_bytes).trim(),
+            ));
+        }
+
+        Ok(output_bytes)
+    }
+
+    pub async fn stream_command_output(
+        &self,
+        name: &str,
+        command: Vec<String>,
+    ) -> Result<ByteStream, Error> {
+        let exec_configuration = ExecConfig {
+            attach_stdout: Some(true),
+            attach_stderr: Some(true),
+            cmd: Some(command),
+            ..Default::default()
+        };
+        let exec = self.client.create_exec(name, exec_configuration).await?;
+        let StartExecResults::Attached { mut output, .. } = self
+            .client
+            .start_exec(&exec.id, None::<StartExecOptions>)
+            .await?
+        else {
+            return Err(Error::container_command_failed(name, "output is detached"));
+        };
+
+        let client = self.client.clone();
+        let name = name.to_string();
+        let (mut sender, receiver) = channel::<Result<Bytes, Error>>(4);
+        tokio::spawn(async move {
+            let mut error_bytes = vec![];
+            while let Some(log) = output.next().await {
+                let chunk = match log {
+                    Ok(LogOutput::StdOut { message }) => Ok(message),
+                    Ok(LogOutput::StdErr { message }) => {
+                        error_bytes.extend_from_slice(&message);
+                        continue;
+                    }
+                    Ok(_) => continue,
+                    Err(error) => Err(error.into()),
+                };
+                let is_error = chunk.is_err();
+                if sender.send(chunk).await.is_err() || is_error {
+                    return;
+                }
+            }
+
+            let exit_code = match client.inspect_exec(&exec.id).await {
+                Ok(exec_details) => exec_details.exit_code,
+                Err(error) => {
+                    let _ = sender.send(Err(error.into())).await;
+                    return;
+                }
+            };
+            if exit_code != Some(0) {
+                let error = Error::container_command_failed(
+                    &name,
+                    String::from_utf8_lossy(&error_bytes).trim(),
+                );
+                let _ = sender.send(Err(error)).await;
+            }
+        });
+
+        Ok(receiver.boxed())
+    }
+
     pub fn is_local_port_free(port: &u16) -> bool {
         let ipv4 = SocketAddrV4::new(Ipv4Addr::LOCALHOST, *port);
         TcpListener::bind(ipv4).is_ok()
@@ -408,12 +1175,52 @@ impl ContainerManager {
         Ok(container.state)
     }
 
+    pub async fn get_image_ids(&self) -> Result<HashSet<String>, Error> {
+        let options = ListImagesOptionsBuilder::new().all(true).build();
+        Ok(self
+            .client
+            .list_images(Some(options))
+            .await?
+            .into_iter()
+            .map(|image| image.id)
+            .collect())
+    }
+
+    pub async fn remove_images(&self, image_ids: &[String]) -> Result<(), Error> {
+        for image_id in image_ids {
+            self.client
+                .remove_image(image_id, None::<RemoveImageOptions>, None)
+                .await?;
+        }
+        Ok(())
+    }
+
     pub async fn prune_unused_images(&self) -> Result<(), Error> {
         self.client.prune_images(None::<PruneImagesOptions>).await?;
         Ok(())
     }
 
-    pub async fn load_image_tarball(&self, tarball: Vec<u8>) -> Result<(), Error> {
+    pub async fn get_registry_image_sha(
+        &self,
+        image_reference: &str,
+        credentials: Option<&RegistryCredentials>,
+    ) -> Result<ImageSha, Error> {
+        let distribution = self
+            .client
+            .inspect_registry_image(
+                image_reference,
+                credentials.map(RegistryCredentials::to_docker_credentials),
+            )
+            .await?;
+        let digest = distribution
+            .descriptor
+            .digest
+            .ok_or(Error::registry_image_digest_not_found(image_reference))?;
+
+        ImageSha::new(digest.trim_start_matches("sha256:").to_string())
+    }
+
+    pub async fn load_image_tarball(&self, tarball: reqwest::Body) -> Result<Vec<String>, Error> {
         let client = reqwest::Client::builder()
             .unix_socket("/var/run/docker.sock")
             .build()?;
@@ -424,11 +1231,38 @@ impl ContainerManager {
             .send()
             .await?;
 
-        if response.status().is_success() {
-            Ok(())
-        } else {
-            Err(Error::could_not_load_docker_image())
+        if !response.status().is_success() {
+            return Err(Error::could_not_load_docker_image());
         }
+
+        // Docker answers 200 as soon as it starts reading, failures come within the stream
+        let mut image_ids = vec![];
+        for line in response.text().await?.lines() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let message: ImageLoadMessage = serde_json::from_str(line)?;
+            if let Some(error) = message.error {
+                tracing::error!("docker failed to load image tarball: {}", error);
+                return Err(Error::could_not_load_docker_image());
+            }
+            let Some(stream) = message.stream else {
+                continue;
+            };
+            if let Some(image_id) = stream.trim().strip_prefix("Loaded image ID: ") {
+                image_ids.push(image_id.to_string());
+            } else if let Some(image_name) = stream.trim().strip_prefix("Loaded image: ") {
+                let image_id = self
+                    .client
+                    .inspect_image(image_name)
+                    .await?
+                    .id
+                    .ok_or(Error::could_not_load_docker_image())?;
+                image_ids.push(image_id);
+            }
+        }
+
+        Ok(image_ids)
     }
 }
 
@@ -452,3 +1286,11 @@ async fn remove_container(client: &Docker, name_or_id: &str) -> Result<(), Error
     }
     Err(Error::cannot_delete_container(name_or_id))
 }
+
+fn get_volume_binding(source: &str, path: &str, read_only: bool) -> String {
+    if read_only {
+        format!("{}:{}:ro", source, path)
+    } else {
+        format!("{}:{}", source, path)
+    }
+}
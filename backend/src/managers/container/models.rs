@@ -1,15 +1,25 @@
-use std::fmt::Display;
+use std::fmt::{Debug, Display};
+use std::str::FromStr;
 
+use bollard::auth::DockerCredentials;
 use bollard::container::LogOutput;
+use cron::Schedule;
+use postgres_types::{FromSql, ToSql};
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 use sha256::digest;
+use uuid::Uuid;
 
 use crate::error::Error;
+use crate::managers::backup::models::BackupPolicy;
 use crate::managers::db::constants::DATABASE_NAME;
+use crate::managers::oidc::models::{DeploymentClaims, GITHUB_ISSUER_NAME};
 use crate::models::UserRole;
 
-#[derive(Clone, Serialize, Deserialize)]
+static DOCKER_HUB_REGISTRY: &str = "docker.io";
+static DEFAULT_DEPLOYMENT_REF: &str = "refs/heads/main";
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct ImageSha {
     value: String,
@@ -51,6 +61,214 @@ impl ExposedPort {
 pub struct EnvironmentVariable {
     pub name: String,
     pub value: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<SecretFile>,
+}
+
+impl EnvironmentVariable {
+    pub fn new(name: &str, value: String) -> Self {
+        Self {
+            name: name.to_string(),
+            value,
+            file: None,
+        }
+    }
+
+    pub fn file_environment_variable_name(&self) -> String {
+        format!("{}_FILE", self.name)
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SecretFile {
+    pub path: String,
+    #[serde(default = "default_secret_file_mode")]
+    pub mode: u32,
+    #[serde(default)]
+    pub owner_id: Option<u32>,
+}
+
+impl SecretFile {
+    pub fn is_valid(&self) -> bool {
+        is_valid_absolute_path(&self.path) && self.mode & !0o777 == 0
+    }
+}
+
+pub fn default_secret_file_mode() -> u32 {
+    0o400
+}
+
+pub fn is_valid_absolute_path(path: &str) -> bool {
+    path.starts_with('/')
+        && path.len() > 1
+        && !path.ends_with('/')
+        && !path.split('/').any(|part| part == "..")
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SharedSecretReference {
+    pub name: String,
+    pub secret_name: String,
+    #[serde(default)]
+    pub version: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<SecretFile>,
+}
+
+impl SharedSecretReference {
+    pub fn follows_latest_version_of(&self, secret_name: &str) -> bool {
+        self.secret_name == secret_name && self.version.is_none()
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SharedVolumeMount {
+    pub name: String,
+    pub path: String,
+    #[serde(default)]
+    pub read_only: bool,
+}
+
+impl SharedVolumeMount {
+    pub fn get_volume_id(&self) -> String {
+        get_shared_volume_id(&self.name)
+    }
+}
+
+pub fn get_shared_volume_id(name: &str) -> String {
+    format!("kiwi-shared-{}", name)
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PrivateNetworkMembership {
+    pub name: String,
+    pub alias: String,
+}
+
+impl PrivateNetworkMembership {
+    pub fn is_valid(&self) -> bool {
+        Regex::new(r"^[a-zA-Z0-9][a-zA-Z0-9_.-]{0,62}$").is_ok_and(|name_regex| {
+            name_regex.is_match(&self.name) && name_regex.is_match(&self.alias)
+        })
+    }
+
+    pub fn get_network_id(&self) -> String {
+        format!("kiwi-private-{}", self.name)
+    }
+}
+
+/// Without `image_sha`, a sidecar runs the image of the service and follows its deployments.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Sidecar {
+    pub name: String,
+    #[serde(default)]
+    pub image_name: Option<String>,
+    #[serde(default)]
+    pub image_sha: Option<ImageSha>,
+    #[serde(default)]
+    pub command: Option<Vec<String>>,
+    #[serde(default)]
+    pub environment_variables: Vec<EnvironmentVariable>,
+    #[serde(skip)]
+    pub registry_credentials: Option<RegistryCredentials>,
+}
+
+impl Sidecar {
+    pub fn is_valid(&self) -> bool {
+        Regex::new(r"^[a-zA-Z0-9][a-zA-Z0-9_-]{0,31}$")
+            .is_ok_and(|name_regex| name_regex.is_match(&self.name))
+            && (self.image_name.is_none() || self.image_sha.is_some())
+            && self
+                .command
+                .as_ref()
+                .is_none_or(|command| !command.is_empty())
+            && self
+                .environment_variables
+                .iter()
+                .all(|env_var| env_var.file.is_none())
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct BindMount {
+    pub host_path: String,
+    pub path: String,
+    #[serde(default)]
+    pub read_only: bool,
+}
+
+impl BindMount {
+    pub fn is_allowed_path(allowed_path: &str) -> bool {
+        is_valid_absolute_path(allowed_path.trim_end_matches('/'))
+    }
+
+    pub fn is_within(&self, allowed_paths: &[String]) -> bool {
+        is_valid_absolute_path(&self.host_path)
+            && allowed_paths.iter().any(|allowed_path| {
+                let allowed_path = allowed_path.trim_end_matches('/');
+                self.host_path == allowed_path
+                    || self.host_path.starts_with(&format!("{}/", allowed_path))
+            })
+    }
+}
+
+#[derive(Clone)]
+pub struct RegistryCredentials {
+    pub registry: String,
+    pub username: String,
+    pub password: String,
+}
+
+impl Debug for RegistryCredentials {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        formatter
+            .debug_struct("RegistryCredentials")
+            .field("registry", &self.registry)
+            .field("username", &self.username)
+            .finish_non_exhaustive()
+    }
+}
+
+impl RegistryCredentials {
+    pub fn to_docker_credentials(&self) -> DockerCredentials {
+        DockerCredentials {
+            username: Some(self.username.clone()),
+            password: Some(self.password.clone()),
+            serveraddress: Some(self.server_address()),
+            ..Default::default()
+        }
+    }
+
+    pub fn server_address(&self) -> String {
+        if self.registry == DOCKER_HUB_REGISTRY {
+            "https://index.docker.io/v1/".to_string()
+        } else {
+            self.registry.clone()
+        }
+    }
+}
+
+pub fn get_registry_host(image_name: &str) -> String {
+    match image_name.split_once('/') {
+        Some((host, _)) if host.contains('.') || host.contains(':') || host == "localhost" => {
+            normalise_registry_host(host)
+        }
+        _ => DOCKER_HUB_REGISTRY.to_string(),
+    }
+}
+
+pub fn normalise_registry_host(host: &str) -> String {
+    let host = host.to_lowercase();
+    match host.as_str() {
+        "index.docker.io" | "registry-1.docker.io" => DOCKER_HUB_REGISTRY.to_string(),
+        _ => host,
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, PartialEq)]
@@ -88,6 +306,309 @@ impl Display for GithubRepository {
     }
 }
 
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SourceRepository {
+    pub issuer: String,
+    pub path: String,
+}
+
+impl SourceRepository {
+    pub fn is_valid(&self) -> bool {
+        Regex::new(r"^[a-zA-Z0-9._-]+(/[a-zA-Z0-9._-]+)+$")
+            .is_ok_and(|path_regex| path_regex.is_match(&self.path))
+    }
+}
+
+impl From<&GithubRepository> for SourceRepository {
+    fn from(value: &GithubRepository) -> Self {
+        Self {
+            issuer: GITHUB_ISSUER_NAME.to_string(),
+            path: value.to_string(),
+        }
+    }
+}
+
+/// Without a policy, only `refs/heads/main` can deploy.
+#[derive(Clone, Serialize, Deserialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct DeployPolicy {
+    /// `*` matches within a path segment, `**` across segments.
+    #[serde(default)]
+    pub allowed_refs: Vec<String>,
+    pub environment: Option<String>,
+    pub workflow_path: Option<String>,
+    #[serde(default)]
+    pub allowed_actors: Vec<String>,
+    /// Once pinned, a new repository taking over the name is rejected.
+    pub repository_id: Option<String>,
+    #[serde(default)]
+    pub preview_refs: Vec<String>,
+    /// Previews run untrusted code, so they never get the secrets of the service.
+    #[serde(default)]
+    pub preview_shared_secrets: Vec<SharedSecretReference>,
+}
+
+impl DeployPolicy {
+    pub fn is_valid(&self) -> bool {
+        self.allowed_refs
+            .iter()
+            .chain(&self.preview_refs)
+            .all(|pattern| !pattern.trim().is_empty())
+            && self
+                .workflow_path
+                .as_ref()
+                .is_none_or(|workflow_path| !workflow_path.trim().is_empty())
+            && self.repository_id.as_ref().is_none_or(|repository_id| {
+                !repository_id.is_empty() && repository_id.chars().all(|c| c.is_ascii_digit())
+            })
+    }
+
+    pub fn matches_repository(
+        &self,
+        repository: &SourceRepository,
+        claims: &DeploymentClaims,
+    ) -> bool {
+        repository.issuer == claims.issuer
+            && match &self.repository_id {
+                Some(repository_id) => claims.repository_id.as_ref() == Some(repository_id),
+                None => claims.repository == repository.path,
+            }
+    }
+
+    pub fn authorises(
+        &self,
+        repository: &SourceRepository,
+        claims: &DeploymentClaims,
+    ) -> Result<(), Error> {
+        if !self.matches_repository(repository, claims) {
+            return Err(Error::invalid_repo_for_service());
+        }
+
+        let is_allowed_ref = if self.allowed_refs.is_empty() {
+            claims.reference == DEFAULT_DEPLOYMENT_REF
+        } else {
+            self.allowed_refs
+                .iter()
+                .any(|pattern| matches_glob(pattern, &claims.reference))
+        };
+        if !is_allowed_ref {
+            return Err(Error::invalid_branch());
+        }
+
+        if let Some(environment) = &self.environment
+            && claims.environment.as_ref() != Some(environment)
+        {
+            return Err(Error::invalid_deployment_environment());
+        }
+
+        self.authorises_workflow(claims)
+    }
+
+    /// A run triggered by a pull request can only deploy the preview of that pull request.
+    pub fn authorises_preview(
+        &self,
+        repository: &SourceRepository,
+        claims: &DeploymentClaims,
+        pull_request: Option<i32>,
+    ) -> Result<(), Error> {
+        if !self.matches_repository(repository, claims) {
+            return Err(Error::invalid_repo_for_service());
+        }
+
+        if self.preview_refs.is_empty() {
+            return Err(Error::previews_disabled());
+        }
+        if !self
+            .preview_refs
+            .iter()
+            .any(|pattern| matches_glob(pattern, &claims.reference))
+        {
+            return Err(Error::invalid_branch());
+        }
+        if let (Some(pull_request), Some(reference_pull_request)) =
+            (pull_request, get_pull_request_number(&claims.reference))
+            && pull_request != reference_pull_request
+        {
+            return Err(Error::pull_request_mismatch());
+        }
+
+        self.authorises_workflow(claims)
+    }
+
+    fn authorises_workflow(&self, claims: &DeploymentClaims) -> Result<(), Error> {
+        if let Some(workflow_path) = &self.workflow_path
+            && claims.get_workflow_path() != Some(workflow_path.trim_start_matches("./"))
+        {
+            return Err(Error::invalid_deployment_workflow());
+        }
+
+        if !self.allowed_actors.is_empty() && !self.allowed_actors.contains(&claims.actor) {
+            return Err(Error::actor_not_allowed_to_deploy());
+        }
+
+        Ok(())
+    }
+}
+
+pub fn get_pull_request_number(reference: &str) -> Option<i32> {
+    match reference.split('/').collect::<Vec<&str>>().as_slice() {
+        ["refs", "pull" | "merge-requests", number, ..] => number.parse().ok(),
+        _ => None,
+    }
+}
+
+pub fn matches_glob(pattern: &str, value: &str) -> bool {
+    let mut regex_pattern = String::from("^");
+    let mut characters = pattern.chars().peekable();
+    while let Some(character) = characters.next() {
+        match character {
+            '*' if characters.peek() == Some(&'*') => {
+                characters.next();
+                regex_pattern.push_str(".*");
+            }
+            '*' => regex_pattern.push_str("[^/]*"),
+            '?' => regex_pattern.push_str("[^/]"),
+            _ => regex_pattern.push_str(&regex::escape(&character.to_string())),
+        }
+    }
+    regex_pattern.push('$');
+
+    Regex::new(&regex_pattern).is_ok_and(|regex| regex.is_match(value))
+}
+
+#[derive(Clone, Debug, FromSql, ToSql, PartialEq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+#[postgres(name = "image_pull_policy")]
+pub enum ImagePullPolicy {
+    #[default]
+    Always,
+    IfNotPresent,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ImageUpdateMode {
+    Notify,
+    Deploy,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ImageUpdatePolicy {
+    pub tag: String,
+    pub mode: ImageUpdateMode,
+    #[serde(default = "default_image_update_check_interval_minutes")]
+    pub check_interval_minutes: u32,
+}
+
+impl ImageUpdatePolicy {
+    pub fn is_valid(&self) -> bool {
+        self.check_interval_minutes > 0
+            && Regex::new(r"^[a-zA-Z0-9_][a-zA-Z0-9_.-]{0,127}$")
+                .is_ok_and(|tag_regex| tag_regex.is_match(&self.tag))
+    }
+}
+
+pub fn default_image_update_check_interval_minutes() -> u32 {
+    60
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum JobConcurrencyPolicy {
+    Allow,
+    #[default]
+    Forbid,
+    Replace,
+}
+
+/// When the job is still running at the next occurrence, the concurrency policy either runs it
+/// alongside, skips the occurrence or replaces the running one.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduledJob {
+    pub name: String,
+    pub schedule: String,
+    #[serde(default)]
+    pub command: Option<Vec<String>>,
+    #[serde(default = "default_job_timeout_seconds")]
+    pub timeout_seconds: u32,
+    #[serde(default)]
+    pub concurrency_policy: JobConcurrencyPolicy,
+}
+
+impl ScheduledJob {
+    pub fn is_valid(&self) -> bool {
+        Regex::new(r"^[a-zA-Z0-9][a-zA-Z0-9_-]{0,31}$")
+            .is_ok_and(|name_regex| name_regex.is_match(&self.name))
+            && self.schedule.split_whitespace().count() == 5
+            // Numbered days of the week start from Sunday = 1, unlike crontab, so only names are
+            // accepted to avoid running jobs a day early
+            && self
+                .schedule
+                .split_whitespace()
+                .last()
+                .is_some_and(|days_of_week| !days_of_week.contains(|c: char| c.is_ascii_digit()))
+            && self.get_schedule().is_ok()
+            && self.timeout_seconds > 0
+            && self
+                .command
+                .as_ref()
+                .is_none_or(|command| !command.is_empty())
+    }
+
+    pub fn get_schedule(&self) -> Result<Schedule, Error> {
+        Schedule::from_str(&format!("0 {}", self.schedule))
+            .map_err(|_| Error::invalid_job_schedule(&self.schedule))
+    }
+}
+
+pub fn default_job_timeout_seconds() -> u32 {
+    3600
+}
+
+#[derive(Clone, Debug, FromSql, ToSql, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[postgres(name = "job_run_status")]
+pub enum JobRunStatus {
+    Running,
+    Succeeded,
+    Failed,
+    TimedOut,
+    Cancelled,
+    Skipped,
+}
+
+#[derive(Clone, Debug, FromSql, ToSql, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[postgres(name = "deployment_trigger")]
+pub enum DeploymentTrigger {
+    Manual,
+    Ci,
+    Preview,
+    ImageUpdate,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ServiceAccessList {
+    pub usernames: Vec<String>,
+    pub groups: Vec<String>,
+    #[serde(default)]
+    pub services: Vec<String>,
+}
+
+impl ServiceAccessList {
+    pub fn allows(&self, username: &String, groups: &[String]) -> bool {
+        self.usernames.contains(username) || groups.iter().any(|group| self.groups.contains(group))
+    }
+
+    pub fn allows_service(&self, service_name: &String) -> bool {
+        self.services.contains(service_name)
+    }
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ContainerConfiguration {
@@ -97,10 +618,39 @@ pub struct ContainerConfiguration {
     pub exposed_port: ExposedPort,
     pub environment_variables: Vec<EnvironmentVariable>,
     pub secrets: Vec<EnvironmentVariable>,
+    #[serde(default)]
+    pub shared_secrets: Vec<SharedSecretReference>,
     pub internal_secrets: Vec<EnvironmentVariable>,
     pub stateful_volume_paths: Vec<String>,
     pub github_repository: Option<GithubRepository>,
+    #[serde(default)]
+    pub source_repository: Option<SourceRepository>,
     pub required_role: Option<UserRole>,
+    pub access_list: Option<ServiceAccessList>,
+    #[serde(default)]
+    pub internal_secrets_as_files: bool,
+    #[serde(default)]
+    pub internal_secrets_owner_id: Option<u32>,
+    #[serde(default)]
+    pub backup_policy: Option<BackupPolicy>,
+    #[serde(default)]
+    pub shared_volumes: Vec<SharedVolumeMount>,
+    #[serde(default)]
+    pub bind_mounts: Vec<BindMount>,
+    #[serde(default)]
+    pub deploy_policy: Option<DeployPolicy>,
+    #[serde(default)]
+    pub pull_policy: ImagePullPolicy,
+    #[serde(default)]
+    pub image_update_policy: Option<ImageUpdatePolicy>,
+    #[serde(default)]
+    pub private_network: Option<PrivateNetworkMembership>,
+    #[serde(default)]
+    pub sidecars: Vec<Sidecar>,
+    #[serde(default)]
+    pub scheduled_jobs: Vec<ScheduledJob>,
+    #[serde(skip)]
+    pub registry_credentials: Option<RegistryCredentials>,
 }
 
 impl ContainerConfiguration {
@@ -115,24 +665,33 @@ impl ContainerConfiguration {
                 "bcb90dc18910057ff49ce2ea157d8a0d534964090d39af959df41083f18c3318".to_string(),
             )?, // 17.5-alpine3.22
             exposed_port: ExposedPort::symmetric(5432),
-            environment_variables: vec![EnvironmentVariable {
-                name: "POSTGRES_DB".to_string(),
-                value: DATABASE_NAME.to_string(),
-            }],
+            environment_variables: vec![EnvironmentVariable::new(
+                "POSTGRES_DB",
+                DATABASE_NAME.to_string(),
+            )],
             secrets: vec![],
+            shared_secrets: vec![],
             internal_secrets: vec![
-                EnvironmentVariable {
-                    name: "POSTGRES_USER".to_string(),
-                    value: admin_username.to_string(),
-                },
-                EnvironmentVariable {
-                    name: "POSTGRES_PASSWORD".to_string(),
-                    value: admin_password.to_string(),
-                },
+                EnvironmentVariable::new("POSTGRES_USER", admin_username.to_string()),
+                EnvironmentVariable::new("POSTGRES_PASSWORD", admin_password.to_string()),
             ],
             stateful_volume_paths: vec!["/var/lib/postgresql/data".to_string()],
             github_repository: None,
+            source_repository: None,
             required_role: None,
+            access_list: None,
+            internal_secrets_as_files: false,
+            internal_secrets_owner_id: None,
+            backup_policy: None,
+            shared_volumes: vec![],
+            bind_mounts: vec![],
+            deploy_policy: None,
+            pull_policy: ImagePullPolicy::Always,
+            image_update_policy: None,
+            private_network: None,
+            sidecars: vec![],
+            scheduled_jobs: vec![],
+            registry_credentials: None,
         })
     }
 
@@ -146,16 +705,54 @@ impl ContainerConfiguration {
             exposed_port: ExposedPort::symmetric(6379),
             environment_variables: vec![],
             secrets: vec![],
-            internal_secrets: vec![EnvironmentVariable {
-                name: "REDIS_PASSWORD".to_string(),
-                value: admin_password.to_string(),
-            }],
+            shared_secrets: vec![],
+            internal_secrets: vec![EnvironmentVariable::new(
+                "REDIS_PASSWORD",
+                admin_password.to_string(),
+            )],
             stateful_volume_paths: vec!["/bitnami/redis/data".to_string()],
             github_repository: None,
+            source_repository: None,
             required_role: None,
+            access_list: None,
+            internal_secrets_as_files: false,
+            internal_secrets_owner_id: None,
+            backup_policy: None,
+            shared_volumes: vec![],
+            bind_mounts: vec![],
+            deploy_policy: None,
+            pull_policy: ImagePullPolicy::Always,
+            image_update_policy: None,
+            private_network: None,
+            sidecars: vec![],
+            scheduled_jobs: vec![],
+            registry_credentials: None,
         })
     }
 
+    pub fn get_linked_repository(&self) -> Option<SourceRepository> {
+        self.source_repository
+            .clone()
+            .or(self.github_repository.as_ref().map(SourceRepository::from))
+    }
+
+    pub fn get_tracked_image_reference(&self) -> Option<String> {
+        match (&self.image_name, &self.image_update_policy) {
+            (Some(image_name), Some(image_update_policy)) => {
+                Some(format!("{}:{}", image_name, image_update_policy.tag))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn get_sidecar_container_name(&self, sidecar: &Sidecar) -> String {
+        format!("{}.{}", self.name, sidecar.name)
+    }
+
+    pub fn get_job_container_name(&self, job_name: &str, job_run_id: &Uuid) -> String {
+        format!("{}.{}.{}", self.name, job_name, job_run_id.simple())
+    }
+
     pub fn get_stateful_volume_id(&self, path: &String) -> String {
         let raw_id = format!("{}-{}", self.name, path);
         let hashed_id = digest(raw_id);
@@ -194,3 +791,124 @@ impl From<LogOutput> for Log {
         }
     }
 }
+
+#[derive(Deserialize)]
+pub struct ImageLoadMessage {
+    pub stream: Option<String>,
+    pub error: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bind_mount(host_path: &str) -> BindMount {
+        BindMount {
+            host_path: host_path.to_string(),
+            path: "/data".to_string(),
+            read_only: false,
+        }
+    }
+
+    #[test]
+    fn bind_mount_is_within_allowed_path() {
+        let allowed_paths = vec!["/data".to_string(), "/srv/shared/".to_string()];
+        assert!(bind_mount("/data").is_within(&allowed_paths));
+        assert!(bind_mount("/data/uploads").is_within(&allowed_paths));
+        assert!(bind_mount("/srv/shared").is_within(&allowed_paths));
+        assert!(bind_mount("/srv/shared/files").is_within(&allowed_paths));
+        assert!(!bind_mount("/etc").is_within(&allowed_paths));
+        assert!(!bind_mount("/srv").is_within(&allowed_paths));
+    }
+
+    #[test]
+    fn bind_mount_is_not_within_prefix_sibling() {
+        let allowed_paths = vec!["/data".to_string()];
+        assert!(!bind_mount("/database").is_within(&allowed_paths));
+        assert!(!bind_mount("/data-backup/files").is_within(&allowed_paths));
+        assert!(!bind_mount("/dat").is_within(&allowed_paths));
+    }
+
+    #[test]
+    fn bind_mount_is_not_within_through_parent_segments() {
+        let allowed_paths = vec!["/data".to_string()];
+        assert!(!bind_mount("/data/../etc").is_within(&allowed_paths));
+        assert!(!bind_mount("/data/uploads/").is_within(&allowed_paths));
+        assert!(!bind_mount("data/uploads").is_within(&allowed_paths));
+        assert!(!bind_mount("/data").is_within(&[]));
+    }
+
+    #[test]
+    fn rejects_invalid_allowed_bind_mount_paths() {
+        assert!(BindMount::is_allowed_path("/data"));
+        assert!(BindMount::is_allowed_path("/data/"));
+        assert!(!BindMount::is_allowed_path(""));
+        assert!(!BindMount::is_allowed_path("/"));
+        assert!(!BindMount::is_allowed_path("//"));
+        assert!(!BindMount::is_allowed_path("data"));
+        assert!(!BindMount::is_allowed_path("/data/../etc"));
+    }
+
+    #[test]
+    fn glob_star_matches_within_segment() {
+        assert!(matches_glob("refs/tags/v*", "refs/tags/v1.2.0"));
+        assert!(matches_glob("refs/tags/v*", "refs/tags/v"));
+        assert!(!matches_glob("refs/tags/v*", "refs/tags/v1/hotfix"));
+        assert!(matches_glob("refs/pull/*/merge", "refs/pull/42/merge"));
+        assert!(!matches_glob(
+            "refs/pull/*/merge",
+            "refs/pull/42/head/merge"
+        ));
+    }
+
+    #[test]
+    fn glob_double_star_matches_across_segments() {
+        assert!(matches_glob("refs/heads/**", "refs/heads/feature/login"));
+        assert!(matches_glob("refs/heads/**", "refs/heads/main"));
+        assert!(!matches_glob("refs/heads/**", "refs/tags/v1"));
+    }
+
+    #[test]
+    fn glob_question_mark_matches_single_character() {
+        assert!(matches_glob("refs/tags/v?", "refs/tags/v1"));
+        assert!(!matches_glob("refs/tags/v?", "refs/tags/v10"));
+        assert!(!matches_glob("refs/tags/v?", "refs/tags/v"));
+        assert!(!matches_glob("refs/heads/a?b", "refs/heads/a/b"));
+    }
+
+    #[test]
+    fn glob_escapes_regex_metacharacters() {
+        assert!(matches_glob(
+            "refs/heads/release+1.0",
+            "refs/heads/release+1.0"
+        ));
+        assert!(!matches_glob(
+            "refs/heads/release+1.0",
+            "refs/heads/releaseee1x0"
+        ));
+        assert!(matches_glob("refs/heads/[main]", "refs/heads/[main]"));
+        assert!(!matches_glob("refs/heads/[main]", "refs/heads/m"));
+        assert!(matches_glob("refs/heads/(a|b)", "refs/heads/(a|b)"));
+        assert!(!matches_glob("refs/heads/(a|b)", "refs/heads/a"));
+        assert!(!matches_glob("refs/heads/^main$", "refs/heads/main"));
+    }
+
+    #[test]
+    fn glob_matches_whole_ref() {
+        assert!(matches_glob("refs/heads/main", "refs/heads/main"));
+        assert!(!matches_glob("refs/heads/main", "refs/heads/main-2"));
+        assert!(!matches_glob("heads/main", "refs/heads/main"));
+    }
+
+    #[test]
+    fn deploy_policy_rejects_empty_repository_id() {
+        let deploy_policy = |repository_id: &str| DeployPolicy {
+            repository_id: Some(repository_id.to_string()),
+            ..Default::default()
+        };
+        assert!(deploy_policy("123456").is_valid());
+        assert!(!deploy_policy("").is_valid());
+        assert!(!deploy_policy("12a").is_valid());
+        assert!(DeployPolicy::default().is_valid());
+    }
+}
@@ -7,6 +7,9 @@ use crate::error::Error;
 
 mod error;
 
+static HASH_ALGORITHM: argon2::Algorithm = argon2::Algorithm::Argon2id;
+static HASH_VERSION: argon2::Version = argon2::Version::V0x13;
+
 #[derive(Clone)]
 pub struct CryptoManager {
     pepper: String,
@@ -38,11 +41,31 @@ impl CryptoManager {
         Ok(matches)
     }
 
+    pub fn needs_rehash(&self, hashed_text: &str) -> Result<bool, Error> {
+        let password_hash = PasswordHash::new(hashed_text)?;
+        let is_current_algorithm = password_hash.algorithm == HASH_ALGORITHM.ident()
+            && password_hash.version == Some(HASH_VERSION.into());
+        let hasher = self.get_hasher()?;
+        let current_params = hasher.params();
+        let is_current_params = Params::try_from(&password_hash)
+            .map(|params| {
+                params.m_cost() == current_params.m_cost()
+                    && params.t_cost() == current_params.t_cost()
+                    && params.p_cost() == current_params.p_cost()
+                    && params.output_len().unwrap_or(Params::DEFAULT_OUTPUT_LEN)
+                        == current_params
+                            .output_len()
+                            .unwrap_or(Params::DEFAULT_OUTPUT_LEN)
+            })
+            .unwrap_or(false);
+        Ok(!is_current_algorithm || !is_current_params)
+    }
+
     fn get_hasher(&self) -> Result<Argon2<'_>, Error> {
         Ok(Argon2::new_with_secret(
             self.pepper.as_bytes(),
-            argon2::Algorithm::Argon2id,
-            argon2::Version::V0x13,
+            HASH_ALGORITHM,
+            HASH_VERSION,
             Params::DEFAULT,
         )?)
     }
@@ -54,3 +77,7 @@ impl CryptoManager {
         Ok(())
     }
 }
+
+pub fn hex_encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
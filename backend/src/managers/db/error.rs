@@ -8,4 +8,11 @@ impl Error {
             message: "database connection test failed".to_string(),
         }
     }
+
+    pub fn shared_secret_not_found(name: &str) -> Self {
+        Self {
+            code: StatusCode::NOT_FOUND,
+            message: format!("shared secret {} not found", name),
+        }
+    }
 }
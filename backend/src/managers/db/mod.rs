@@ -1,6 +1,7 @@
 use std::{ops::DerefMut, time::Duration};
 
 use crate::error::Error;
+use crate::managers::encryption::EncryptionManager;
 use constants::{APPLICATION_NAME, DATABASE_NAME, HOST, MAX_POOL_SIZE, PORT};
 use deadpool_postgres::{Config, ManagerConfig, Pool, PoolConfig, RecyclingMethod, Runtime};
 use refinery::embed_migrations;
@@ -15,10 +16,15 @@ pub mod queries;
 #[derive(Clone)]
 pub struct DbManager {
     connection_pool: Pool,
+    encryption_manager: EncryptionManager,
 }
 
 impl DbManager {
-    pub async fn new(admin_username: &str, admin_password: &str) -> Result<Self, Error> {
+    pub async fn new(
+        admin_username: &str,
+        admin_password: &str,
+        encryption_manager: EncryptionManager,
+    ) -> Result<Self, Error> {
         let mut config = Config::new();
         config.dbname = Some(DATABASE_NAME.to_string());
         config.application_name = Some(APPLICATION_NAME.to_string());
@@ -34,6 +40,7 @@ impl DbManager {
         let pool = config.create_pool(Some(Runtime::Tokio1), NoTls)?;
         let db_manager = Self {
             connection_pool: pool.clone(),
+            encryption_manager,
         };
 
         for tentative_count in 1..=5 {
@@ -58,9 +65,17 @@ impl DbManager {
         let migrations_report = migrations::runner().run_async(connection).await?;
         let applied_migrations_count = migrations_report.applied_migrations().len();
 
+        let reencrypted_services_count = db_manager.reencrypt_services_secrets().await?;
+        let reencrypted_shared_secrets_count = db_manager.reencrypt_shared_secrets().await?;
+        let reencrypted_registry_passwords_count =
+            db_manager.reencrypt_registry_credentials().await?;
+
         tracing::info!(
-            "db manager initialised, applied {} migrations",
-            applied_migrations_count
+            "db manager initialised, applied {} migrations, re-encrypted secrets of {} services, {} shared secrets and {} registry passwords",
+            applied_migrations_count,
+            reencrypted_services_count,
+            reencrypted_shared_secrets_count,
+            reencrypted_registry_passwords_count
         );
         Ok(db_manager)
     }
@@ -4,17 +4,24 @@ use serde::{Deserialize, Serialize};
 use tokio_postgres::Row;
 use uuid::Uuid;
 
+use crate::constants::INTERNAL_SECRETS_FILES_PATH;
 use crate::error::Error;
+use crate::managers::backup::models::{BackupPolicy, BackupStorageKind, BackupTrigger};
 use crate::managers::container::models::{
-    ContainerConfiguration, EnvironmentVariable, ExposedPort, GithubRepository, ImageSha,
+    BindMount, ContainerConfiguration, DeployPolicy, DeploymentTrigger, EnvironmentVariable,
+    ExposedPort, GithubRepository, ImageSha, ImageUpdatePolicy, JobRunStatus,
+    PrivateNetworkMembership, ScheduledJob, SecretFile, ServiceAccessList, SharedSecretReference,
+    SharedVolumeMount, Sidecar, SourceRepository, default_secret_file_mode,
 };
-use crate::models::UserRole;
+use crate::managers::encryption::EncryptionManager;
+use crate::models::{AccessLevel, AdminArea, Permission, UserAuthorisation, UserRole};
 
 pub struct UserData {
     pub id: i64,
     pub password_hash: String,
     pub role: UserRole,
     pub username: String,
+    pub custom_role: Option<String>,
 }
 
 impl TryFrom<Row> for UserData {
@@ -26,6 +33,72 @@ impl TryFrom<Row> for UserData {
             password_hash: value.try_get("password_hash")?,
             role: value.try_get("role")?,
             username: value.try_get("username")?,
+            custom_role: value.try_get("custom_role")?,
+        })
+    }
+}
+
+impl TryFrom<Row> for UserAuthorisation {
+    type Error = Error;
+
+    fn try_from(value: Row) -> Result<Self, Self::Error> {
+        let role: UserRole = value.try_get("role")?;
+        let permissions = match role {
+            UserRole::Admin => AdminArea::all()
+                .into_iter()
+                .map(|area| Permission {
+                    area,
+                    access_level: AccessLevel::Write,
+                })
+                .collect(),
+            UserRole::Customer => value
+                .try_get::<&str, Option<Json<Vec<Permission>>>>("permissions")?
+                .map(|permissions| permissions.0)
+                .unwrap_or_default(),
+        };
+
+        Ok(Self {
+            custom_role: value.try_get("custom_role")?,
+            permissions,
+            groups: value.try_get("groups")?,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CustomRole {
+    pub name: String,
+    pub permissions: Vec<Permission>,
+}
+
+impl TryFrom<Row> for CustomRole {
+    type Error = Error;
+
+    fn try_from(value: Row) -> Result<Self, Self::Error> {
+        Ok(Self {
+            name: value.try_get("name")?,
+            permissions: value
+                .try_get::<&str, Json<Vec<Permission>>>("permissions")?
+                .0,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Group {
+    pub name: String,
+    pub usernames: Vec<String>,
+}
+
+impl TryFrom<Row> for Group {
+    type Error = Error;
+
+    fn try_from(value: Row) -> Result<Self, Self::Error> {
+        Ok(Self {
+            name: value.try_get("name")?,
+            usernames: value.try_get("usernames")?,
         })
     }
 }
@@ -46,11 +119,373 @@ impl TryFrom<Row> for UserInvitation {
     }
 }
 
+pub struct UserPasswordReset {
+    pub id: Uuid,
+    pub user_id: i64,
+}
+
+impl TryFrom<Row> for UserPasswordReset {
+    type Error = Error;
+
+    fn try_from(value: Row) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: value.try_get("id")?,
+            user_id: value.try_get("user_id")?,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OidcClient {
+    pub id: Uuid,
+    pub service_name: String,
+    #[serde(skip)]
+    pub client_secret_hash: String,
+    pub redirect_uris: Vec<String>,
+    pub created_at: NaiveDateTime,
+}
+
+impl TryFrom<Row> for OidcClient {
+    type Error = Error;
+
+    fn try_from(value: Row) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: value.try_get("id")?,
+            service_name: value.try_get("service_name")?,
+            client_secret_hash: value.try_get("client_secret_hash")?,
+            redirect_uris: value.try_get("redirect_uris")?,
+            created_at: value.try_get("created_at")?,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiToken {
+    pub id: Uuid,
+    #[serde(skip)]
+    pub user_id: i64,
+    pub name: String,
+    #[serde(skip)]
+    pub token_hash: String,
+    pub permissions: Vec<Permission>,
+    pub expires_at: NaiveDateTime,
+    pub last_used_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+}
+
+impl TryFrom<Row> for ApiToken {
+    type Error = Error;
+
+    fn try_from(value: Row) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: value.try_get("id")?,
+            user_id: value.try_get("user_id")?,
+            name: value.try_get("name")?,
+            token_hash: value.try_get("token_hash")?,
+            permissions: value
+                .try_get::<&str, Json<Vec<Permission>>>("permissions")?
+                .0,
+            expires_at: value.try_get("expires_at")?,
+            last_used_at: value.try_get("last_used_at")?,
+            created_at: value.try_get("created_at")?,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SharedSecret {
+    pub name: String,
+    pub version: i32,
+    pub created_at: NaiveDateTime,
+}
+
+impl TryFrom<Row> for SharedSecret {
+    type Error = Error;
+
+    fn try_from(value: Row) -> Result<Self, Self::Error> {
+        Ok(Self {
+            name: value.try_get("name")?,
+            version: value.try_get("version")?,
+            created_at: value.try_get("created_at")?,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RegistryCredential {
+    pub registry: String,
+    pub username: String,
+    pub created_at: NaiveDateTime,
+    pub last_modified_at: NaiveDateTime,
+}
+
+impl TryFrom<Row> for RegistryCredential {
+    type Error = Error;
+
+    fn try_from(value: Row) -> Result<Self, Self::Error> {
+        Ok(Self {
+            registry: value.try_get("registry")?,
+            username: value.try_get("username")?,
+            created_at: value.try_get("created_at")?,
+            last_modified_at: value.try_get("last_modified_at")?,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SharedVolume {
+    pub name: String,
+    pub created_at: NaiveDateTime,
+}
+
+impl TryFrom<Row> for SharedVolume {
+    type Error = Error;
+
+    fn try_from(value: Row) -> Result<Self, Self::Error> {
+        Ok(Self {
+            name: value.try_get("name")?,
+            created_at: value.try_get("created_at")?,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PreviewEnvironment {
+    pub name: String,
+    pub parent_service_name: String,
+    pub pull_request: i32,
+    pub created_at: NaiveDateTime,
+    pub last_deployed_at: NaiveDateTime,
+}
+
+impl TryFrom<Row> for PreviewEnvironment {
+    type Error = Error;
+
+    fn try_from(value: Row) -> Result<Self, Self::Error> {
+        Ok(Self {
+            name: value.try_get("name")?,
+            parent_service_name: value.try_get("parent_service_name")?,
+            pull_request: value.try_get("pull_request")?,
+            created_at: value.try_get("created_at")?,
+            last_deployed_at: value.try_get("last_deployed_at")?,
+        })
+    }
+}
+
+pub struct RegistryManifest {
+    pub digest: String,
+    pub media_type: String,
+}
+
+impl TryFrom<Row> for RegistryManifest {
+    type Error = Error;
+
+    fn try_from(value: Row) -> Result<Self, Self::Error> {
+        Ok(Self {
+            digest: value.try_get("digest")?,
+            media_type: value.try_get("media_type")?,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Backup {
+    pub id: Uuid,
+    pub service_name: String,
+    pub storage: BackupStorageKind,
+    pub trigger: BackupTrigger,
+    pub size_bytes: i64,
+    pub created_at: NaiveDateTime,
+}
+
+impl Backup {
+    pub fn get_storage_key(service_name: &str, id: &Uuid) -> String {
+        format!("{}/{}.dump", service_name, id)
+    }
+
+    pub fn storage_key(&self) -> String {
+        Self::get_storage_key(&self.service_name, &self.id)
+    }
+}
+
+impl TryFrom<Row> for Backup {
+    type Error = Error;
+
+    fn try_from(value: Row) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: value.try_get("id")?,
+            service_name: value.try_get("service_name")?,
+            storage: value.try_get("storage")?,
+            trigger: value.try_get("trigger")?,
+            size_bytes: value.try_get("size_bytes")?,
+            created_at: value.try_get("created_at")?,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VolumeSnapshot {
+    pub id: Uuid,
+    pub service_name: String,
+    pub volume_path: String,
+    pub storage: BackupStorageKind,
+    pub size_bytes: i64,
+    pub created_at: NaiveDateTime,
+}
+
+impl VolumeSnapshot {
+    pub fn get_storage_key(service_name: &str, id: &Uuid) -> String {
+        format!("{}/volumes/{}.tar", service_name, id)
+    }
+
+    pub fn storage_key(&self) -> String {
+        Self::get_storage_key(&self.service_name, &self.id)
+    }
+}
+
+impl TryFrom<Row> for VolumeSnapshot {
+    type Error = Error;
+
+    fn try_from(value: Row) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: value.try_get("id")?,
+            service_name: value.try_get("service_name")?,
+            volume_path: value.try_get("volume_path")?,
+            storage: value.try_get("storage")?,
+            size_bytes: value.try_get("size_bytes")?,
+            created_at: value.try_get("created_at")?,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Deployment {
+    pub id: Uuid,
+    pub service_name: String,
+    pub image_sha: String,
+    pub previous_image_sha: Option<String>,
+    pub trigger: DeploymentTrigger,
+    pub created_at: NaiveDateTime,
+}
+
+impl TryFrom<Row> for Deployment {
+    type Error = Error;
+
+    fn try_from(value: Row) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: value.try_get("id")?,
+            service_name: value.try_get("service_name")?,
+            image_sha: value.try_get("image_sha")?,
+            previous_image_sha: value.try_get("previous_image_sha")?,
+            trigger: value.try_get("trigger")?,
+            created_at: value.try_get("created_at")?,
+        })
+    }
+}
+
+/// `scheduled_at` is unset for runs started on demand.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JobRun {
+    pub id: Uuid,
+    pub service_name: String,
+    pub job_name: String,
+    pub status: JobRunStatus,
+    pub exit_code: Option<i32>,
+    pub error: Option<String>,
+    pub scheduled_at: Option<NaiveDateTime>,
+    pub started_at: NaiveDateTime,
+    pub finished_at: Option<NaiveDateTime>,
+}
+
+impl TryFrom<Row> for JobRun {
+    type Error = Error;
+
+    fn try_from(value: Row) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: value.try_get("id")?,
+            service_name: value.try_get("service_name")?,
+            job_name: value.try_get("job_name")?,
+            status: value.try_get("status")?,
+            exit_code: value.try_get("exit_code")?,
+            error: value.try_get("error")?,
+            scheduled_at: value.try_get("scheduled_at")?,
+            started_at: value.try_get("started_at")?,
+            finished_at: value.try_get("finished_at")?,
+        })
+    }
+}
+
+/// `image_sha` keeps the last resolved image when the latest check failed.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrackedImageTag {
+    pub service_name: String,
+    pub tag: String,
+    pub image_sha: Option<String>,
+    pub error: Option<String>,
+    pub failed_image_sha: Option<String>,
+    pub checked_at: NaiveDateTime,
+}
+
+impl TryFrom<Row> for TrackedImageTag {
+    type Error = Error;
+
+    fn try_from(value: Row) -> Result<Self, Self::Error> {
+        Ok(Self {
+            service_name: value.try_get("service_name")?,
+            tag: value.try_get("tag")?,
+            image_sha: value.try_get("image_sha")?,
+            error: value.try_get("error")?,
+            failed_image_sha: value.try_get("failed_image_sha")?,
+            checked_at: value.try_get("checked_at")?,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeletedVolume {
+    pub volume_id: String,
+    pub service_name: String,
+    pub volume_path: String,
+    pub deleted_at: NaiveDateTime,
+}
+
+impl TryFrom<Row> for DeletedVolume {
+    type Error = Error;
+
+    fn try_from(value: Row) -> Result<Self, Self::Error> {
+        Ok(Self {
+            volume_id: value.try_get("volume_id")?,
+            service_name: value.try_get("service_name")?,
+            volume_path: value.try_get("volume_path")?,
+            deleted_at: value.try_get("deleted_at")?,
+        })
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct InternalServiceConfiguration {
     pub redis_username: String,
     pub postgres_username: String,
+    pub client_secret: String,
+    #[serde(skip)]
+    pub redis_password: String,
+    #[serde(skip)]
+    pub postgres_password: String,
+    #[serde(skip)]
+    pub postgres_login_username: Option<String>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -60,13 +495,15 @@ pub struct ServiceData {
     pub created_at: NaiveDateTime,
     pub last_modified_at: NaiveDateTime,
     pub last_deployed_at: NaiveDateTime,
+    pub credentials_rotated_at: NaiveDateTime,
     pub internal_configuration: InternalServiceConfiguration,
 }
 
-impl TryFrom<Row> for ServiceData {
-    type Error = Error;
-
-    fn try_from(value: Row) -> Result<Self, Self::Error> {
+impl ServiceData {
+    pub fn try_from_encrypted_row(
+        value: Row,
+        encryption_manager: &EncryptionManager,
+    ) -> Result<Self, Error> {
         let exposed_port_vec = value.try_get::<&str, Vec<i32>>("exposed_port")?;
         let exposed_port: ExposedPort = ExposedPort {
             internal: *(exposed_port_vec.first().ok_or(Error::serialisation())?) as u16,
@@ -74,10 +511,17 @@ impl TryFrom<Row> for ServiceData {
         };
 
         let postgres_username: String = value.try_get("postgres_username")?;
-        let postgres_password: String = value.try_get("postgres_password")?;
+        let postgres_login_username: Option<String> = value.try_get("postgres_login_username")?;
+        let postgres_password = encryption_manager.decrypt(value.try_get("postgres_password")?)?;
         let redis_username: String = value.try_get("redis_username")?;
-        let redis_password: String = value.try_get("redis_password")?;
+        let redis_password = encryption_manager.decrypt(value.try_get("redis_password")?)?;
         let redis_prefix = format!("{}:", redis_username);
+        let name: String = value.try_get("name")?;
+        let client_secret = encryption_manager.decrypt(value.try_get("client_secret")?)?;
+        let internal_secrets_as_files: bool = value.try_get("internal_secrets_as_files")?;
+        let internal_secrets_owner_id = value
+            .try_get::<&str, Option<i32>>("internal_secrets_owner_id")?
+            .map(|owner_id| owner_id as u32);
         let github_repository =
             if let Some(repo) = value.try_get::<&str, Option<String>>("github_repository")? {
                 Some(GithubRepository::try_from(repo)?)
@@ -87,7 +531,7 @@ impl TryFrom<Row> for ServiceData {
 
         Ok(Self {
             container_configuration: ContainerConfiguration {
-                name: value.try_get("name")?,
+                name: name.clone(),
                 image_name: value.try_get("image_name")?,
                 image_sha: ImageSha::new(value.try_get("image_sha")?)?,
                 exposed_port,
@@ -96,48 +540,121 @@ impl TryFrom<Row> for ServiceData {
                     .0,
                 secrets: value
                     .try_get::<&str, Json<Vec<EnvironmentVariable>>>("secrets")?
+                    .0
+                    .into_iter()
+                    .map(|secret| {
+                        Ok(EnvironmentVariable {
+                            value: encryption_manager.decrypt(&secret.value)?,
+                            ..secret
+                        })
+                    })
+                    .collect::<Result<Vec<EnvironmentVariable>, Error>>()?,
+                shared_secrets: value
+                    .try_get::<&str, Json<Vec<SharedSecretReference>>>("shared_secrets")?
                     .0,
-                internal_secrets: vec![
-                    EnvironmentVariable {
-                        name: "KIWI_POSTGRES_URI".to_string(),
-                        value: format!(
+                internal_secrets: [
+                    EnvironmentVariable::new(
+                        "KIWI_POSTGRES_URI",
+                        format!(
                             "postgresql://{}:{}@kiwi-postgres:5432/{}",
-                            postgres_username, postgres_password, postgres_username
+                            postgres_login_username
+                                .as_ref()
+                                .unwrap_or(&postgres_username),
+                            postgres_password,
+                            postgres_username
                         ),
-                    },
-                    EnvironmentVariable {
-                        name: "KIWI_REDIS_URI".to_string(),
-                        value: format!(
+                    ),
+                    EnvironmentVariable::new(
+                        "KIWI_REDIS_URI",
+                        format!(
                             "redis://{}:{}:kiwi-redis:6379",
-                            redis_username, redis_password
+                            redis_username, &redis_password
                         ),
-                    },
-                    EnvironmentVariable {
-                        name: "KIWI_REDIS_PREFIX".to_string(),
-                        value: redis_prefix,
-                    },
-                ],
+                    ),
+                    EnvironmentVariable::new("KIWI_REDIS_PREFIX", redis_prefix),
+                    EnvironmentVariable::new("KIWI_CLIENT_ID", name.clone()),
+                    EnvironmentVariable::new("KIWI_CLIENT_SECRET", client_secret.clone()),
+                ]
+                .into_iter()
+                .map(|secret| {
+                    if internal_secrets_as_files {
+                        EnvironmentVariable {
+                            file: Some(SecretFile {
+                                path: format!(
+                                    "{}/{}",
+                                    INTERNAL_SECRETS_FILES_PATH,
+                                    secret.name.to_lowercase()
+                                ),
+                                mode: default_secret_file_mode(),
+                                owner_id: internal_secrets_owner_id,
+                            }),
+                            ..secret
+                        }
+                    } else {
+                        secret
+                    }
+                })
+                .collect(),
                 stateful_volume_paths: value.try_get("stateful_volume_paths")?,
                 github_repository,
+                source_repository: value
+                    .try_get::<&str, Option<Json<SourceRepository>>>("source_repository")?
+                    .map(|source_repository| source_repository.0),
                 required_role: value.try_get("required_role")?,
+                access_list: value
+                    .try_get::<&str, Option<Json<ServiceAccessList>>>("access_list")?
+                    .map(|access_list| access_list.0),
+                internal_secrets_as_files,
+                internal_secrets_owner_id,
+                backup_policy: value
+                    .try_get::<&str, Option<Json<BackupPolicy>>>("backup_policy")?
+                    .map(|backup_policy| backup_policy.0),
+                shared_volumes: value
+                    .try_get::<&str, Json<Vec<SharedVolumeMount>>>("shared_volumes")?
+                    .0,
+                bind_mounts: value
+                    .try_get::<&str, Json<Vec<BindMount>>>("bind_mounts")?
+                    .0,
+                deploy_policy: value
+                    .try_get::<&str, Option<Json<DeployPolicy>>>("deploy_policy")?
+                    .map(|deploy_policy| deploy_policy.0),
+                pull_policy: value.try_get("pull_policy")?,
+                image_update_policy: value
+                    .try_get::<&str, Option<Json<ImageUpdatePolicy>>>("image_update_policy")?
+                    .map(|image_update_policy| image_update_policy.0),
+                private_network: value
+                    .try_get::<&str, Option<Json<PrivateNetworkMembership>>>("private_network")?
+                    .map(|private_network| private_network.0),
+                sidecars: value.try_get::<&str, Json<Vec<Sidecar>>>("sidecars")?.0,
+                scheduled_jobs: value
+                    .try_get::<&str, Json<Vec<ScheduledJob>>>("scheduled_jobs")?
+                    .0,
+                registry_credentials: None,
             },
             created_at: value.try_get("created_at")?,
             last_modified_at: value.try_get("last_modified_at")?,
             last_deployed_at: value.try_get("last_deployed_at")?,
+            credentials_rotated_at: value.try_get("credentials_rotated_at")?,
             internal_configuration: InternalServiceConfiguration {
                 redis_username,
                 postgres_username,
+                client_secret,
+                redis_password,
+                postgres_password,
+                postgres_login_username,
             },
         })
     }
-}
 
-impl ServiceData {
     pub fn with_redacted_internal_secrets(mut self) -> Self {
         self.container_configuration.internal_secrets = vec![];
         self.internal_configuration = InternalServiceConfiguration {
             redis_username: String::new(),
             postgres_username: String::new(),
+            client_secret: String::new(),
+            redis_password: String::new(),
+            postgres_password: String::new(),
+            postgres_login_username: None,
         };
         self
     }
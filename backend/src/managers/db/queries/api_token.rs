@@ -0,0 +1,87 @@
+use chrono::NaiveDateTime;
+use postgres_types::Json;
+use uuid::Uuid;
+
+use crate::error::Error;
+use crate::managers::db::{DbManager, models::ApiToken};
+use crate::models::Permission;
+
+impl DbManager {
+    pub async fn get_api_token(&self, id: &Uuid) -> Result<Option<ApiToken>, Error> {
+        let client = self.connection_pool.get().await?;
+        let statement = client
+            .prepare_cached("SELECT * FROM api_tokens WHERE id = $1 AND expires_at > now()")
+            .await?;
+        let api_token = client
+            .query_opt(&statement, &[id])
+            .await?
+            .map(ApiToken::try_from)
+            .transpose()?;
+        Ok(api_token)
+    }
+
+    pub async fn get_user_api_tokens(&self, user_id: &i64) -> Result<Vec<ApiToken>, Error> {
+        let client = self.connection_pool.get().await?;
+        let statement = client
+            .prepare_cached("SELECT * FROM api_tokens WHERE user_id = $1 ORDER BY created_at")
+            .await?;
+        let api_tokens: Result<Vec<ApiToken>, Error> = client
+            .query(&statement, &[user_id])
+            .await?
+            .into_iter()
+            .map(ApiToken::try_from)
+            .collect();
+        api_tokens
+    }
+
+    pub async fn create_api_token(
+        &self,
+        user_id: &i64,
+        name: &String,
+        token_hash: &String,
+        permissions: &[Permission],
+        expires_at: &NaiveDateTime,
+    ) -> Result<ApiToken, Error> {
+        let client = self.connection_pool.get().await?;
+        let statement = client
+            .prepare_cached(
+                "INSERT INTO api_tokens (user_id, name, token_hash, permissions, expires_at) VALUES ($1, $2, $3, $4, $5) RETURNING *",
+            )
+            .await?;
+        let api_token_raw = client
+            .query_one(
+                &statement,
+                &[user_id, name, token_hash, &Json(permissions), expires_at],
+            )
+            .await?;
+        let api_token = ApiToken::try_from(api_token_raw)?;
+        Ok(api_token)
+    }
+
+    pub async fn mark_api_token_as_used(&self, id: &Uuid) -> Result<(), Error> {
+        let client = self.connection_pool.get().await?;
+        let statement = client
+            .prepare_cached("UPDATE api_tokens SET last_used_at = now() WHERE id = $1")
+            .await?;
+        client.execute(&statement, &[id]).await?;
+        Ok(())
+    }
+
+    pub async fn delete_api_token(&self, user_id: &i64, id: &Uuid) -> Result<bool, Error> {
+        let client = self.connection_pool.get().await?;
+        let statement = client
+            .prepare_cached("DELETE FROM api_tokens WHERE user_id = $1 AND id = $2")
+            .await?;
+        let deleted_rows = client.execute(&statement, &[user_id, id]).await?;
+        Ok(deleted_rows > 0)
+    }
+
+    pub async fn delete_user_api_tokens(&self, user_id: &i64) -> Result<(), Error> {
+        let client = self.connection_pool.get().await?;
+        let statement = client
+            .prepare_cached("DELETE FROM api_tokens WHERE user_id = $1")
+            .await?;
+        client.execute(&statement, &[user_id]).await?;
+        Ok(())
+    }
+}
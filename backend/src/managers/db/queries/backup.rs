@@ -0,0 +1,108 @@
+use uuid::Uuid;
+
+use crate::error::Error;
+use crate::managers::backup::models::{BackupStorageKind, BackupTrigger};
+use crate::managers::db::{DbManager, models::Backup};
+
+impl DbManager {
+    pub async fn get_service_backups(&self, service_name: &str) -> Result<Vec<Backup>, Error> {
+        let client = self.connection_pool.get().await?;
+        let statement = client
+            .prepare_cached(
+                "SELECT * FROM backups WHERE service_name = $1 ORDER BY created_at DESC",
+            )
+            .await?;
+        let backups: Result<Vec<Backup>, Error> = client
+            .query(&statement, &[&service_name])
+            .await?
+            .into_iter()
+            .map(Backup::try_from)
+            .collect();
+        backups
+    }
+
+    pub async fn get_backup(&self, service_name: &str, id: &Uuid) -> Result<Option<Backup>, Error> {
+        let client = self.connection_pool.get().await?;
+        let statement = client
+            .prepare_cached("SELECT * FROM backups WHERE service_name = $1 AND id = $2")
+            .await?;
+        let backup = client
+            .query_opt(&statement, &[&service_name, id])
+            .await?
+            .map(Backup::try_from)
+            .transpose()?;
+        Ok(backup)
+    }
+
+    pub async fn create_backup(
+        &self,
+        id: &Uuid,
+        service_name: &str,
+        storage: &BackupStorageKind,
+        trigger: &BackupTrigger,
+        size_bytes: i64,
+    ) -> Result<Backup, Error> {
+        let client = self.connection_pool.get().await?;
+        let statement = client
+            .prepare_cached(
+                "INSERT INTO backups (id, service_name, storage, trigger, size_bytes) VALUES ($1, $2, $3, $4, $5) RETURNING *",
+            )
+            .await?;
+        let backup_row = client
+            .query_one(
+                &statement,
+                &[id, &service_name, storage, trigger, &size_bytes],
+            )
+            .await?;
+        Backup::try_from(backup_row)
+    }
+
+    pub async fn delete_backup(&self, id: &Uuid) -> Result<(), Error> {
+        let client = self.connection_pool.get().await?;
+        let statement = client
+            .prepare_cached("DELETE FROM backups WHERE id = $1")
+            .await?;
+        client.execute(&statement, &[id]).await?;
+        Ok(())
+    }
+
+    pub async fn get_expired_backups(
+        &self,
+        service_name: &str,
+        retention_count: i64,
+    ) -> Result<Vec<Backup>, Error> {
+        let client = self.connection_pool.get().await?;
+        let statement = client
+            .prepare_cached(
+                "SELECT * FROM backups WHERE service_name = $1 AND trigger = 'Scheduled' ORDER BY created_at DESC OFFSET $2",
+            )
+            .await?;
+        let backups: Result<Vec<Backup>, Error> = client
+            .query(&statement, &[&service_name, &retention_count])
+            .await?
+            .into_iter()
+            .map(Backup::try_from)
+            .collect();
+        backups
+    }
+
+    pub async fn get_services_due_for_backup(&self) -> Result<Vec<String>, Error> {
+        let client = self.connection_pool.get().await?;
+        let statement = client
+            .prepare_cached(
+                "SELECT name FROM services WHERE backup_policy IS NOT NULL AND coalesce(
+                    (SELECT max(created_at) FROM backups WHERE service_name = services.name AND trigger = 'Scheduled'),
+                    '-infinity'
+                ) < now() - make_interval(hours => (backup_policy->>'intervalHours')::integer)
+                ORDER BY name",
+            )
+            .await?;
+        let service_names: Result<Vec<String>, Error> = client
+            .query(&statement, &[])
+            .await?
+            .into_iter()
+            .map(|row| Ok(row.try_get("name")?))
+            .collect();
+        service_names
+    }
+}
@@ -0,0 +1,47 @@
+use crate::error::Error;
+use crate::managers::container::models::DeploymentTrigger;
+use crate::managers::db::DbManager;
+use crate::managers::db::models::Deployment;
+
+impl DbManager {
+    pub async fn get_service_deployments(
+        &self,
+        service_name: &str,
+    ) -> Result<Vec<Deployment>, Error> {
+        let client = self.connection_pool.get().await?;
+        let statement = client
+            .prepare_cached(
+                "SELECT * FROM deployments WHERE service_name = $1 ORDER BY created_at DESC",
+            )
+            .await?;
+        let deployments: Result<Vec<Deployment>, Error> = client
+            .query(&statement, &[&service_name])
+            .await?
+            .into_iter()
+            .map(Deployment::try_from)
+            .collect();
+        deployments
+    }
+
+    pub async fn create_deployment(
+        &self,
+        service_name: &str,
+        image_sha: &str,
+        previous_image_sha: Option<&str>,
+        trigger: &DeploymentTrigger,
+    ) -> Result<Deployment, Error> {
+        let client = self.connection_pool.get().await?;
+        let statement = client
+            .prepare_cached(
+                "INSERT INTO deployments (service_name, image_sha, previous_image_sha, trigger) VALUES ($1, $2, $3, $4) RETURNING *",
+            )
+            .await?;
+        let deployment_row = client
+            .query_one(
+                &statement,
+                &[&service_name, &image_sha, &previous_image_sha, trigger],
+            )
+            .await?;
+        Deployment::try_from(deployment_row)
+    }
+}
@@ -0,0 +1,158 @@
+use chrono::NaiveDateTime;
+use uuid::Uuid;
+
+use crate::error::Error;
+use crate::managers::container::models::JobRunStatus;
+use crate::managers::db::DbManager;
+use crate::managers::db::models::JobRun;
+
+static MAX_LISTED_JOB_RUNS: i64 = 100;
+
+impl DbManager {
+    pub async fn get_service_job_runs(
+        &self,
+        service_name: &str,
+        job_name: Option<&str>,
+    ) -> Result<Vec<JobRun>, Error> {
+        let client = self.connection_pool.get().await?;
+        let statement = client
+            .prepare_cached(
+                "SELECT * FROM job_runs WHERE service_name = $1 AND ($2::text IS NULL OR job_name = $2) ORDER BY started_at DESC LIMIT $3",
+            )
+            .await?;
+        let job_runs: Result<Vec<JobRun>, Error> = client
+            .query(
+                &statement,
+                &[&service_name, &job_name, &MAX_LISTED_JOB_RUNS],
+            )
+            .await?
+            .into_iter()
+            .map(JobRun::try_from)
+            .collect();
+        job_runs
+    }
+
+    pub async fn get_running_job_runs(
+        &self,
+        service_name: &str,
+        job_name: &str,
+    ) -> Result<Vec<JobRun>, Error> {
+        let client = self.connection_pool.get().await?;
+        let statement = client
+            .prepare_cached(
+                "SELECT * FROM job_runs WHERE service_name = $1 AND job_name = $2 AND status = 'Running'",
+            )
+            .await?;
+        let job_runs: Result<Vec<JobRun>, Error> = client
+            .query(&statement, &[&service_name, &job_name])
+            .await?
+            .into_iter()
+            .map(JobRun::try_from)
+            .collect();
+        job_runs
+    }
+
+    pub async fn get_job_run_logs(
+        &self,
+        service_name: &str,
+        id: &Uuid,
+    ) -> Result<Option<String>, Error> {
+        let client = self.connection_pool.get().await?;
+        let statement = client
+            .prepare_cached(
+                "SELECT coalesce(logs, '') AS logs FROM job_runs WHERE service_name = $1 AND id = $2",
+            )
+            .await?;
+        match client.query_opt(&statement, &[&service_name, id]).await? {
+            Some(row) => Ok(Some(row.try_get("logs")?)),
+            None => Ok(None),
+        }
+    }
+
+    pub async fn get_last_job_scheduled_at(
+        &self,
+        service_name: &str,
+        job_name: &str,
+    ) -> Result<Option<NaiveDateTime>, Error> {
+        let client = self.connection_pool.get().await?;
+        let statement = client
+            .prepare_cached(
+                "SELECT max(scheduled_at) AS scheduled_at FROM job_runs WHERE service_name = $1 AND job_name = $2",
+            )
+            .await?;
+        let row = client
+            .query_one(&statement, &[&service_name, &job_name])
+            .await?;
+        Ok(row.try_get("scheduled_at")?)
+    }
+
+    pub async fn create_job_run(
+        &self,
+        service_name: &str,
+        job_name: &str,
+        status: &JobRunStatus,
+        scheduled_at: Option<NaiveDateTime>,
+    ) -> Result<JobRun, Error> {
+        let client = self.connection_pool.get().await?;
+        let statement = client
+            .prepare_cached(
+                "INSERT INTO job_runs (service_name, job_name, status, scheduled_at, finished_at) VALUES ($1, $2, $3, $4, CASE WHEN $3 = 'Running'::job_run_status THEN NULL ELSE now() END) RETURNING *",
+            )
+            .await?;
+        let job_run_row = client
+            .query_one(
+                &statement,
+                &[&service_name, &job_name, status, &scheduled_at],
+            )
+            .await?;
+        JobRun::try_from(job_run_row)
+    }
+
+    pub async fn cancel_job_run(&self, id: &Uuid) -> Result<(), Error> {
+        let client = self.connection_pool.get().await?;
+        let statement = client
+            .prepare_cached(
+                "UPDATE job_runs SET status = 'Cancelled' WHERE id = $1 AND status = 'Running'",
+            )
+            .await?;
+        client.execute(&statement, &[id]).await?;
+        Ok(())
+    }
+
+    pub async fn finish_job_run(
+        &self,
+        id: &Uuid,
+        status: &JobRunStatus,
+        exit_code: Option<i32>,
+        logs: Option<&str>,
+        error: Option<&str>,
+    ) -> Result<(), Error> {
+        let client = self.connection_pool.get().await?;
+        let statement = client
+            .prepare_cached(
+                "UPDATE job_runs SET
+                    status = CASE WHEN status = 'Running' THEN $2 ELSE status END,
+                    exit_code = $3,
+                    logs = $4,
+                    error = $5,
+                    finished_at = now()
+                WHERE id = $1",
+            )
+            .await?;
+        client
+            .execute(&statement, &[id, status, &exit_code, &logs, &error])
+            .await?;
+        Ok(())
+    }
+
+    /// Their containers must be removed beforehand, as no one waits for them anymore.
+    pub async fn fail_interrupted_job_runs(&self) -> Result<u64, Error> {
+        let client = self.connection_pool.get().await?;
+        let statement = client
+            .prepare_cached(
+                "UPDATE job_runs SET status = 'Failed', error = 'interrupted by a restart', finished_at = now() WHERE status = 'Running'",
+            )
+            .await?;
+        Ok(client.execute(&statement, &[]).await?)
+    }
+}
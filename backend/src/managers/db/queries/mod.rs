@@ -1,2 +1,14 @@
+pub mod api_token;
+pub mod backup;
+pub mod deployment;
+pub mod job_run;
+pub mod oidc_client;
+pub mod preview_environment;
+pub mod registry;
+pub mod registry_credential;
 pub mod service;
+pub mod shared_secret;
+pub mod shared_volume;
+pub mod tracked_image_tag;
 pub mod user;
+pub mod volume;
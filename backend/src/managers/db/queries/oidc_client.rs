@@ -0,0 +1,67 @@
+use uuid::Uuid;
+
+use crate::error::Error;
+use crate::managers::db::{DbManager, models::OidcClient};
+
+impl DbManager {
+    pub async fn get_oidc_client(&self, id: &Uuid) -> Result<Option<OidcClient>, Error> {
+        let client = self.connection_pool.get().await?;
+        let statement = client
+            .prepare_cached("SELECT * FROM oidc_clients WHERE id = $1")
+            .await?;
+        let oidc_client = client
+            .query_opt(&statement, &[id])
+            .await?
+            .map(OidcClient::try_from)
+            .transpose()?;
+        Ok(oidc_client)
+    }
+
+    pub async fn get_service_oidc_clients(
+        &self,
+        service_name: &String,
+    ) -> Result<Vec<OidcClient>, Error> {
+        let client = self.connection_pool.get().await?;
+        let statement = client
+            .prepare_cached("SELECT * FROM oidc_clients WHERE service_name = $1")
+            .await?;
+        let oidc_clients: Result<Vec<OidcClient>, Error> = client
+            .query(&statement, &[service_name])
+            .await?
+            .into_iter()
+            .map(OidcClient::try_from)
+            .collect();
+        oidc_clients
+    }
+
+    pub async fn create_oidc_client(
+        &self,
+        service_name: &String,
+        client_secret_hash: &String,
+        redirect_uris: &Vec<String>,
+    ) -> Result<OidcClient, Error> {
+        let client = self.connection_pool.get().await?;
+        let statement = client
+            .prepare_cached(
+                "INSERT INTO oidc_clients (service_name, client_secret_hash, redirect_uris) VALUES ($1, $2, $3) RETURNING *",
+            )
+            .await?;
+        let oidc_client_raw = client
+            .query_one(
+                &statement,
+                &[service_name, client_secret_hash, redirect_uris],
+            )
+            .await?;
+        let oidc_client = OidcClient::try_from(oidc_client_raw)?;
+        Ok(oidc_client)
+    }
+
+    pub async fn delete_oidc_client(&self, service_name: &String, id: &Uuid) -> Result<(), Error> {
+        let client = self.connection_pool.get().await?;
+        let statement = client
+            .prepare_cached("DELETE FROM oidc_clients WHERE service_name = $1 AND id = $2")
+            .await?;
+        client.execute(&statement, &[service_name, id]).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,75 @@
+use crate::error::Error;
+use crate::managers::db::DbManager;
+use crate::managers::db::models::PreviewEnvironment;
+
+impl DbManager {
+    pub async fn get_preview_environment(
+        &self,
+        name: &str,
+    ) -> Result<Option<PreviewEnvironment>, Error> {
+        let client = self.connection_pool.get().await?;
+        let statement = client
+            .prepare_cached("SELECT * FROM preview_environments WHERE name = $1")
+            .await?;
+        let row = client.query_opt(&statement, &[&name]).await?;
+        row.map(PreviewEnvironment::try_from).transpose()
+    }
+
+    pub async fn get_service_preview_environments(
+        &self,
+        parent_service_name: &str,
+    ) -> Result<Vec<PreviewEnvironment>, Error> {
+        let client = self.connection_pool.get().await?;
+        let statement = client
+            .prepare_cached(
+                "SELECT * FROM preview_environments WHERE parent_service_name = $1 ORDER BY pull_request",
+            )
+            .await?;
+        let preview_environments: Result<Vec<PreviewEnvironment>, Error> = client
+            .query(&statement, &[&parent_service_name])
+            .await?
+            .into_iter()
+            .map(PreviewEnvironment::try_from)
+            .collect();
+        preview_environments
+    }
+
+    pub async fn save_preview_environment(
+        &self,
+        name: &str,
+        parent_service_name: &str,
+        pull_request: i32,
+    ) -> Result<PreviewEnvironment, Error> {
+        let client = self.connection_pool.get().await?;
+        let statement = client
+            .prepare_cached(
+                "INSERT INTO preview_environments (name, parent_service_name, pull_request) VALUES ($1, $2, $3)
+                ON CONFLICT (name) DO UPDATE SET last_deployed_at = now()
+                RETURNING *",
+            )
+            .await?;
+        let preview_environment_row = client
+            .query_one(&statement, &[&name, &parent_service_name, &pull_request])
+            .await?;
+        PreviewEnvironment::try_from(preview_environment_row)
+    }
+
+    pub async fn get_expired_preview_environments(
+        &self,
+        time_to_live_hours: i32,
+    ) -> Result<Vec<PreviewEnvironment>, Error> {
+        let client = self.connection_pool.get().await?;
+        let statement = client
+            .prepare_cached(
+                "SELECT * FROM preview_environments WHERE last_deployed_at < now() - make_interval(hours => $1)",
+            )
+            .await?;
+        let preview_environments: Result<Vec<PreviewEnvironment>, Error> = client
+            .query(&statement, &[&time_to_live_hours])
+            .await?
+            .into_iter()
+            .map(PreviewEnvironment::try_from)
+            .collect();
+        preview_environments
+    }
+}
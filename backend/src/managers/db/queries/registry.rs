@@ -0,0 +1,186 @@
+use std::collections::HashSet;
+
+use crate::error::Error;
+use crate::managers::db::DbManager;
+use crate::managers::db::models::RegistryManifest;
+
+impl DbManager {
+    pub async fn registry_blob_exists(
+        &self,
+        repository: &str,
+        digest: &str,
+    ) -> Result<bool, Error> {
+        let client = self.connection_pool.get().await?;
+        let statement = client
+            .prepare_cached(
+                "SELECT 1 FROM registry_blobs WHERE repository = $1 AND digest = $2
+                UNION ALL
+                SELECT 1 FROM registry_manifests WHERE repository = $1 AND digest = $2",
+            )
+            .await?;
+        let rows = client.query(&statement, &[&repository, &digest]).await?;
+        Ok(!rows.is_empty())
+    }
+
+    pub async fn link_registry_blob(
+        &self,
+        repository: &str,
+        digest: &str,
+        size_bytes: i64,
+    ) -> Result<(), Error> {
+        let client = self.connection_pool.get().await?;
+        let statement = client
+            .prepare_cached(
+                "INSERT INTO registry_blobs (repository, digest, size_bytes) VALUES ($1, $2, $3)
+                ON CONFLICT (repository, digest) DO NOTHING",
+            )
+            .await?;
+        client
+            .execute(&statement, &[&repository, &digest, &size_bytes])
+            .await?;
+        Ok(())
+    }
+
+    pub async fn get_registry_manifest(
+        &self,
+        repository: &str,
+        digest: &str,
+    ) -> Result<Option<RegistryManifest>, Error> {
+        let client = self.connection_pool.get().await?;
+        let statement = client
+            .prepare_cached(
+                "SELECT * FROM registry_manifests WHERE repository = $1 AND digest = $2",
+            )
+            .await?;
+        let row = client
+            .query_opt(&statement, &[&repository, &digest])
+            .await?;
+        row.map(RegistryManifest::try_from).transpose()
+    }
+
+    pub async fn get_tagged_registry_manifest(
+        &self,
+        repository: &str,
+        tag: &str,
+    ) -> Result<Option<RegistryManifest>, Error> {
+        let client = self.connection_pool.get().await?;
+        let statement = client
+            .prepare_cached(
+                "SELECT m.* FROM registry_tags t
+                JOIN registry_manifests m ON m.repository = t.repository AND m.digest = t.digest
+                WHERE t.repository = $1 AND t.tag = $2",
+            )
+            .await?;
+        let row = client.query_opt(&statement, &[&repository, &tag]).await?;
+        row.map(RegistryManifest::try_from).transpose()
+    }
+
+    pub async fn put_registry_manifest(
+        &self,
+        repository: &str,
+        digest: &str,
+        media_type: &str,
+        referenced_digests: &[String],
+        tag: Option<&str>,
+    ) -> Result<(), Error> {
+        let mut client = self.connection_pool.get().await?;
+        let transaction = client.transaction().await?;
+        let manifest_statement = transaction
+            .prepare_cached(
+                "INSERT INTO registry_manifests (repository, digest, media_type, referenced_digests)
+                VALUES ($1, $2, $3, $4)
+                ON CONFLICT (repository, digest) DO UPDATE SET
+                media_type = excluded.media_type, referenced_digests = excluded.referenced_digests",
+            )
+            .await?;
+        transaction
+            .execute(
+                &manifest_statement,
+                &[&repository, &digest, &media_type, &referenced_digests],
+            )
+            .await?;
+        if let Some(tag) = tag {
+            let tag_statement = transaction
+                .prepare_cached(
+                    "INSERT INTO registry_tags (repository, tag, digest) VALUES ($1, $2, $3)
+                    ON CONFLICT (repository, tag) DO UPDATE SET
+                    digest = excluded.digest, updated_at = now()",
+                )
+                .await?;
+            transaction
+                .execute(&tag_statement, &[&repository, &tag, &digest])
+                .await?;
+        }
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    pub async fn delete_registry_manifest(
+        &self,
+        repository: &str,
+        digest: &str,
+    ) -> Result<bool, Error> {
+        let client = self.connection_pool.get().await?;
+        let statement = client
+            .prepare_cached("DELETE FROM registry_manifests WHERE repository = $1 AND digest = $2")
+            .await?;
+        let deleted_rows_count = client.execute(&statement, &[&repository, &digest]).await?;
+        Ok(deleted_rows_count > 0)
+    }
+
+    pub async fn get_registry_tags(
+        &self,
+        repository: &str,
+        limit: i64,
+        last: &str,
+    ) -> Result<Vec<String>, Error> {
+        let client = self.connection_pool.get().await?;
+        let statement = client
+            .prepare_cached(
+                "SELECT tag FROM registry_tags WHERE repository = $1 AND tag > $2
+                ORDER BY tag LIMIT $3",
+            )
+            .await?;
+        let tags: Result<Vec<String>, Error> = client
+            .query(&statement, &[&repository, &last, &limit])
+            .await?
+            .into_iter()
+            .map(|row| Ok(row.try_get("tag")?))
+            .collect();
+        tags
+    }
+
+    pub async fn prune_registry_blobs(
+        &self,
+        grace_period_minutes: i32,
+    ) -> Result<HashSet<String>, Error> {
+        let client = self.connection_pool.get().await?;
+        let prune_statement = client
+            .prepare_cached(
+                "DELETE FROM registry_blobs b
+                WHERE b.created_at < now() - make_interval(mins => $1)
+                AND NOT EXISTS (
+                    SELECT 1 FROM registry_manifests m
+                    WHERE m.repository = b.repository AND b.digest = ANY(m.referenced_digests)
+                )",
+            )
+            .await?;
+        client
+            .execute(&prune_statement, &[&grace_period_minutes])
+            .await?;
+
+        let digests_statement = client
+            .prepare_cached(
+                "SELECT digest FROM registry_blobs
+                UNION SELECT digest FROM registry_manifests",
+            )
+            .await?;
+        let digests: Result<HashSet<String>, Error> = client
+            .query(&digests_statement, &[])
+            .await?
+            .into_iter()
+            .map(|row| Ok(row.try_get("digest")?))
+            .collect();
+        digests
+    }
+}
@@ -0,0 +1,129 @@
+use crate::error::Error;
+use crate::managers::container::models::{RegistryCredentials, get_registry_host};
+use crate::managers::db::DbManager;
+use crate::managers::db::models::{RegistryCredential, ServiceData};
+
+impl DbManager {
+    pub async fn get_registry_credentials(&self) -> Result<Vec<RegistryCredential>, Error> {
+        let client = self.connection_pool.get().await?;
+        let statement = client
+            .prepare_cached(
+                "SELECT registry, username, created_at, last_modified_at FROM registry_credentials ORDER BY registry",
+            )
+            .await?;
+        let registry_credentials: Result<Vec<RegistryCredential>, Error> = client
+            .query(&statement, &[])
+            .await?
+            .into_iter()
+            .map(RegistryCredential::try_from)
+            .collect();
+        registry_credentials
+    }
+
+    pub async fn upsert_registry_credential(
+        &self,
+        registry: &str,
+        username: &str,
+        password: &str,
+    ) -> Result<RegistryCredential, Error> {
+        let encrypted_password = self.encryption_manager.encrypt(password)?;
+
+        let client = self.connection_pool.get().await?;
+        let statement = client
+            .prepare_cached(
+                "INSERT INTO registry_credentials (registry, username, password) VALUES ($1, $2, $3)
+                ON CONFLICT (registry) DO UPDATE SET
+                    username = excluded.username,
+                    password = excluded.password,
+                    last_modified_at = now()
+                RETURNING registry, username, created_at, last_modified_at",
+            )
+            .await?;
+        let registry_credential_row = client
+            .query_one(&statement, &[&registry, &username, &encrypted_password])
+            .await?;
+        RegistryCredential::try_from(registry_credential_row)
+    }
+
+    pub async fn delete_registry_credential(&self, registry: &str) -> Result<bool, Error> {
+        let client = self.connection_pool.get().await?;
+        let statement = client
+            .prepare_cached("DELETE FROM registry_credentials WHERE registry = $1")
+            .await?;
+        let deleted_rows_count = client.execute(&statement, &[&registry]).await?;
+        Ok(deleted_rows_count > 0)
+    }
+
+    pub(super) async fn with_resolved_registry_credentials(
+        &self,
+        mut service: ServiceData,
+    ) -> Result<ServiceData, Error> {
+        for sidecar in service.container_configuration.sidecars.iter_mut() {
+            if let Some(image_name) = &sidecar.image_name {
+                sidecar.registry_credentials =
+                    self.get_image_registry_credentials(image_name).await?;
+            }
+        }
+        let Some(image_name) = &service.container_configuration.image_name else {
+            return Ok(service);
+        };
+        service.container_configuration.registry_credentials =
+            self.get_image_registry_credentials(image_name).await?;
+
+        Ok(service)
+    }
+
+    pub async fn get_image_registry_credentials(
+        &self,
+        image_name: &str,
+    ) -> Result<Option<RegistryCredentials>, Error> {
+        let registry = get_registry_host(image_name);
+
+        let client = self.connection_pool.get().await?;
+        let statement = client
+            .prepare_cached(
+                "SELECT username, password FROM registry_credentials WHERE registry = $1",
+            )
+            .await?;
+        match client.query_opt(&statement, &[&registry]).await? {
+            Some(row) => {
+                let password: String = row.try_get("password")?;
+                Ok(Some(RegistryCredentials {
+                    username: row.try_get("username")?,
+                    password: self.encryption_manager.decrypt(&password)?,
+                    registry,
+                }))
+            }
+            None => Ok(None),
+        }
+    }
+
+    pub async fn reencrypt_registry_credentials(&self) -> Result<usize, Error> {
+        let mut client = self.connection_pool.get().await?;
+        let transaction = client.transaction().await?;
+
+        let statement = transaction
+            .prepare_cached("SELECT registry, password FROM registry_credentials FOR UPDATE")
+            .await?;
+        let rows = transaction.query(&statement, &[]).await?;
+        let statement = transaction
+            .prepare_cached("UPDATE registry_credentials SET password = $1 WHERE registry = $2")
+            .await?;
+
+        let mut reencrypted_passwords_count = 0;
+        for row in rows {
+            let registry: String = row.try_get("registry")?;
+            let password: String = row.try_get("password")?;
+
+            if let Some(reencrypted_password) = self.encryption_manager.reencrypt(&password)? {
+                transaction
+                    .execute(&statement, &[&reencrypted_password, &registry])
+                    .await?;
+                reencrypted_passwords_count += 1;
+            }
+        }
+
+        transaction.commit().await?;
+        Ok(reencrypted_passwords_count)
+    }
+}
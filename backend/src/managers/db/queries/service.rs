@@ -1,7 +1,7 @@
 use postgres_types::Json;
 
 use crate::error::Error;
-use crate::managers::container::models::ContainerConfiguration;
+use crate::managers::container::models::{ContainerConfiguration, EnvironmentVariable};
 use crate::managers::db::DbManager;
 use crate::managers::db::models::ServiceData;
 
@@ -9,13 +9,14 @@ impl DbManager {
     pub async fn get_services_data(&self) -> Result<Vec<ServiceData>, Error> {
         let client = self.connection_pool.get().await?;
         let statement = client.prepare_cached("SELECT * FROM services").await?;
-        let services: Result<Vec<ServiceData>, Error> = client
-            .query(&statement, &[])
-            .await?
-            .into_iter()
-            .map(ServiceData::try_from)
-            .collect();
-        services
+        let rows = client.query(&statement, &[]).await?;
+
+        let mut services = vec![];
+        for row in rows {
+            let service = ServiceData::try_from_encrypted_row(row, &self.encryption_manager)?;
+            services.push(self.with_resolved_references(service).await?);
+        }
+        Ok(services)
     }
 
     pub async fn get_service_data(&self, name: &str) -> Result<Option<ServiceData>, Error> {
@@ -26,9 +27,13 @@ impl DbManager {
         let service = client
             .query_opt(&statement, &[&name])
             .await?
-            .map(ServiceData::try_from)
-            .and_then(Result::ok);
-        Ok(service)
+            .map(|row| ServiceData::try_from_encrypted_row(row, &self.encryption_manager))
+            .transpose()?;
+
+        match service {
+            Some(service) => Ok(Some(self.with_resolved_references(service).await?)),
+            None => Ok(None),
+        }
     }
 
     pub async fn create_service(
@@ -37,14 +42,32 @@ impl DbManager {
         postgres_username: &String,
         postgres_password: &String,
         redis_username: &String,
-        redis_password: &String,
+        redis_password: &str,
+        client_secret: &str,
     ) -> Result<ServiceData, Error> {
         let exposed_port: Vec<i32> = vec![
             configuration.exposed_port.internal as i32,
             configuration.exposed_port.external as i32,
         ];
         let environment_variables = Json(configuration.environment_variables.clone());
-        let secrets = Json(configuration.secrets.clone());
+        let secrets = Json(self.encrypt_secrets(&configuration.secrets)?);
+        let access_list = configuration.access_list.clone().map(Json);
+        let shared_secrets = Json(configuration.shared_secrets.clone());
+        let backup_policy = configuration.backup_policy.clone().map(Json);
+        let shared_volumes = Json(configuration.shared_volumes.clone());
+        let bind_mounts = Json(configuration.bind_mounts.clone());
+        let deploy_policy = configuration.deploy_policy.clone().map(Json);
+        let source_repository = configuration.source_repository.clone().map(Json);
+        let image_update_policy = configuration.image_update_policy.clone().map(Json);
+        let private_network = configuration.private_network.clone().map(Json);
+        let sidecars = Json(configuration.sidecars.clone());
+        let scheduled_jobs = Json(configuration.scheduled_jobs.clone());
+        let encrypted_postgres_password = self.encryption_manager.encrypt(postgres_password)?;
+        let encrypted_redis_password = self.encryption_manager.encrypt(redis_password)?;
+        let encrypted_client_secret = self.encryption_manager.encrypt(client_secret)?;
+        let internal_secrets_owner_id = configuration
+            .internal_secrets_owner_id
+            .map(|owner_id| owner_id as i32);
 
         let mut client = self.connection_pool.get().await?;
         let transaction = client.transaction().await?;
@@ -64,9 +87,25 @@ impl DbManager {
                 redis_username,
                 redis_password,
                 github_repository,
-                required_role
+                required_role,
+                access_list,
+                client_secret,
+                internal_secrets_as_files,
+                internal_secrets_owner_id,
+                shared_secrets,
+                backup_policy,
+                shared_volumes,
+                bind_mounts,
+                deploy_policy,
+                source_repository,
+                pull_policy,
+                image_update_policy,
+                private_network,
+                sidecars,
+                scheduled_jobs
             ) VALUES (
-                $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13
+                $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18,
+                $19, $20, $21, $22, $23, $24, $25, $26, $27, $28
             ) RETURNING
                 name,
                 image_name,
@@ -83,7 +122,24 @@ impl DbManager {
                 last_modified_at,
                 last_deployed_at,
                 github_repository,
-                required_role",
+                required_role,
+                access_list,
+                client_secret,
+                internal_secrets_as_files,
+                internal_secrets_owner_id,
+                shared_secrets,
+                postgres_login_username,
+                credentials_rotated_at,
+                backup_policy,
+                shared_volumes,
+                bind_mounts,
+                deploy_policy,
+                source_repository,
+                pull_policy,
+                image_update_policy,
+                private_network,
+                sidecars,
+                scheduled_jobs",
             )
             .await?;
         let service_row = transaction
@@ -98,18 +154,34 @@ impl DbManager {
                     &secrets,
                     &configuration.stateful_volume_paths,
                     &postgres_username,
-                    &postgres_password,
+                    &encrypted_postgres_password,
                     &redis_username,
-                    &redis_password,
+                    &encrypted_redis_password,
                     &configuration
                         .github_repository
                         .clone()
                         .map(|repo| repo.to_string()),
                     &configuration.required_role,
+                    &access_list,
+                    &encrypted_client_secret,
+                    &configuration.internal_secrets_as_files,
+                    &internal_secrets_owner_id,
+                    &shared_secrets,
+                    &backup_policy,
+                    &shared_volumes,
+                    &bind_mounts,
+                    &deploy_policy,
+                    &source_repository,
+                    &configuration.pull_policy,
+                    &image_update_policy,
+                    &private_network,
+                    &sidecars,
+                    &scheduled_jobs,
                 ],
             )
             .await?;
-        let service = ServiceData::try_from(service_row)?;
+        let service = ServiceData::try_from_encrypted_row(service_row, &self.encryption_manager)?;
+        let service = self.with_resolved_references(service).await?;
 
         let query_string = format!(
             "CREATE ROLE \"{}\" NOSUPERUSER NOCREATEDB NOCREATEROLE NOINHERIT LOGIN ENCRYPTED PASSWORD '{}'",
@@ -133,6 +205,7 @@ impl DbManager {
         &self,
         name: &String,
         postgres_username: &String,
+        postgres_login_username: &Option<String>,
     ) -> Result<(), Error> {
         let mut client = self.connection_pool.get().await?;
         let transaction = client.transaction().await?;
@@ -142,6 +215,12 @@ impl DbManager {
             .await?;
         transaction.execute(&statement, &[name]).await?;
 
+        if let Some(postgres_login_username) = postgres_login_username {
+            let query_string = format!("DROP ROLE \"{}\"", postgres_login_username);
+            let statement = transaction.prepare_cached(&query_string).await?;
+            transaction.execute(&statement, &[]).await?;
+        }
+
         let query_string = format!("DROP ROLE \"{}\"", postgres_username);
         let statement = transaction.prepare_cached(&query_string).await?;
         transaction.execute(&statement, &[]).await?;
@@ -165,7 +244,21 @@ impl DbManager {
             new_configuration.exposed_port.external as i32,
         ];
         let environment_variables = Json(new_configuration.environment_variables.clone());
-        let secrets = Json(new_configuration.secrets.clone());
+        let secrets = Json(self.encrypt_secrets(&new_configuration.secrets)?);
+        let access_list = new_configuration.access_list.clone().map(Json);
+        let shared_secrets = Json(new_configuration.shared_secrets.clone());
+        let backup_policy = new_configuration.backup_policy.clone().map(Json);
+        let shared_volumes = Json(new_configuration.shared_volumes.clone());
+        let bind_mounts = Json(new_configuration.bind_mounts.clone());
+        let deploy_policy = new_configuration.deploy_policy.clone().map(Json);
+        let source_repository = new_configuration.source_repository.clone().map(Json);
+        let image_update_policy = new_configuration.image_update_policy.clone().map(Json);
+        let private_network = new_configuration.private_network.clone().map(Json);
+        let sidecars = Json(new_configuration.sidecars.clone());
+        let scheduled_jobs = Json(new_configuration.scheduled_jobs.clone());
+        let internal_secrets_owner_id = new_configuration
+            .internal_secrets_owner_id
+            .map(|owner_id| owner_id as i32);
 
         let client = self.connection_pool.get().await?;
 
@@ -181,9 +274,23 @@ impl DbManager {
                     stateful_volume_paths = $7,
                     github_repository = $8,
                     required_role = $9,
+                    access_list = $10,
+                    internal_secrets_as_files = $11,
+                    internal_secrets_owner_id = $12,
+                    shared_secrets = $13,
+                    backup_policy = $14,
+                    shared_volumes = $15,
+                    bind_mounts = $16,
+                    deploy_policy = $17,
+                    source_repository = $18,
+                    pull_policy = $19,
+                    image_update_policy = $20,
+                    private_network = $21,
+                    sidecars = $22,
+                    scheduled_jobs = $23,
                     last_modified_at = now(),
                     last_deployed_at = now()
-                WHERE name = $10
+                WHERE name = $24
                 RETURNING
                     name,
                     image_name,
@@ -200,7 +307,24 @@ impl DbManager {
                     last_modified_at,
                     last_deployed_at,
                     github_repository,
-                    required_role",
+                    required_role,
+                    access_list,
+                client_secret,
+                internal_secrets_as_files,
+                internal_secrets_owner_id,
+                shared_secrets,
+                postgres_login_username,
+                credentials_rotated_at,
+                backup_policy,
+                shared_volumes,
+                bind_mounts,
+                deploy_policy,
+                source_repository,
+                pull_policy,
+                image_update_policy,
+                private_network,
+                sidecars,
+                scheduled_jobs",
             )
             .await?;
         let service_row = client
@@ -219,15 +343,181 @@ impl DbManager {
                         .clone()
                         .map(|repo| repo.to_string()),
                     &new_configuration.required_role,
+                    &access_list,
+                    &new_configuration.internal_secrets_as_files,
+                    &internal_secrets_owner_id,
+                    &shared_secrets,
+                    &backup_policy,
+                    &shared_volumes,
+                    &bind_mounts,
+                    &deploy_policy,
+                    &source_repository,
+                    &new_configuration.pull_policy,
+                    &image_update_policy,
+                    &private_network,
+                    &sidecars,
+                    &scheduled_jobs,
                     &old_service.container_configuration.name,
                 ],
             )
             .await?;
-        let service = ServiceData::try_from(service_row)?;
+        let service = ServiceData::try_from_encrypted_row(service_row, &self.encryption_manager)?;
+        let service = self.with_resolved_references(service).await?;
 
         Ok(service)
     }
 
+    /// The new Postgres login role acts as the database owner role, while the previous one stays
+    /// valid until revoked with `revoke_service_postgres_login`.
+    pub async fn rotate_service_credentials(
+        &self,
+        service: &ServiceData,
+        postgres_login_username: &str,
+        postgres_password: &str,
+        redis_password: &str,
+        external_port: u16,
+    ) -> Result<ServiceData, Error> {
+        let encrypted_postgres_password = self.encryption_manager.encrypt(postgres_password)?;
+        let encrypted_redis_password = self.encryption_manager.encrypt(redis_password)?;
+        let postgres_username = &service.internal_configuration.postgres_username;
+
+        let mut client = self.connection_pool.get().await?;
+        let transaction = client.transaction().await?;
+
+        for query_string in [
+            format!(
+                "CREATE ROLE \"{}\" NOSUPERUSER NOCREATEDB NOCREATEROLE INHERIT LOGIN ENCRYPTED PASSWORD '{}'",
+                postgres_login_username, postgres_password
+            ),
+            format!(
+                "GRANT \"{}\" TO \"{}\"",
+                postgres_username, postgres_login_username
+            ),
+            format!(
+                "ALTER ROLE \"{}\" SET role = \"{}\"",
+                postgres_login_username, postgres_username
+            ),
+        ] {
+            let statement = transaction.prepare_cached(&query_string).await?;
+            transaction.execute(&statement, &[]).await?;
+        }
+
+        let statement = transaction
+            .prepare_cached(
+                "UPDATE services SET
+                    postgres_login_username = $1,
+                    postgres_password = $2,
+                    redis_password = $3,
+                    exposed_port = $4,
+                    credentials_rotated_at = now()
+                WHERE name = $5
+                RETURNING *",
+            )
+            .await?;
+        let exposed_port: Vec<i32> = vec![
+            service.container_configuration.exposed_port.internal as i32,
+            external_port as i32,
+        ];
+        let service_row = transaction
+            .query_one(
+                &statement,
+                &[
+                    &postgres_login_username,
+                    &encrypted_postgres_password,
+                    &encrypted_redis_password,
+                    &exposed_port,
+                    &service.container_configuration.name,
+                ],
+            )
+            .await?;
+        transaction.commit().await?;
+
+        let service = ServiceData::try_from_encrypted_row(service_row, &self.encryption_manager)?;
+        let service = self.with_resolved_references(service).await?;
+
+        Ok(service)
+    }
+
+    pub async fn restore_service_credentials(&self, service: &ServiceData) -> Result<(), Error> {
+        let internal_configuration = &service.internal_configuration;
+        let encrypted_postgres_password = self
+            .encryption_manager
+            .encrypt(&internal_configuration.postgres_password)?;
+        let encrypted_redis_password = self
+            .encryption_manager
+            .encrypt(&internal_configuration.redis_password)?;
+        let exposed_port: Vec<i32> = vec![
+            service.container_configuration.exposed_port.internal as i32,
+            service.container_configuration.exposed_port.external as i32,
+        ];
+
+        let client = self.connection_pool.get().await?;
+        let statement = client
+            .prepare_cached(
+                "UPDATE services SET
+                    postgres_login_username = $1,
+                    postgres_password = $2,
+                    redis_password = $3,
+                    exposed_port = $4,
+                    credentials_rotated_at = $5
+                WHERE name = $6",
+            )
+            .await?;
+        client
+            .execute(
+                &statement,
+                &[
+                    &internal_configuration.postgres_login_username,
+                    &encrypted_postgres_password,
+                    &encrypted_redis_password,
+                    &exposed_port,
+                    &service.credentials_rotated_at,
+                    &service.container_configuration.name,
+                ],
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn revoke_service_postgres_login(
+        &self,
+        postgres_username: &str,
+        previous_postgres_login_username: &Option<String>,
+    ) -> Result<(), Error> {
+        let client = self.connection_pool.get().await?;
+
+        let query_string = match previous_postgres_login_username {
+            Some(previous_postgres_login_username) => {
+                format!("DROP ROLE \"{}\"", previous_postgres_login_username)
+            }
+            None => format!("ALTER ROLE \"{}\" NOLOGIN PASSWORD NULL", postgres_username),
+        };
+        let statement = client.prepare_cached(&query_string).await?;
+        client.execute(&statement, &[]).await?;
+
+        Ok(())
+    }
+
+    pub async fn get_services_due_for_credentials_rotation(
+        &self,
+        interval_days: i32,
+    ) -> Result<Vec<String>, Error> {
+        let client = self.connection_pool.get().await?;
+        let statement = client
+            .prepare_cached(
+                "SELECT name FROM services WHERE credentials_rotated_at < now() - make_interval(days => $1) ORDER BY credentials_rotated_at",
+            )
+            .await?;
+        let service_names: Result<Vec<String>, Error> = client
+            .query(&statement, &[&interval_days])
+            .await?
+            .into_iter()
+            .map(|row| Ok(row.try_get("name")?))
+            .collect();
+        service_names
+    }
+
     pub async fn get_service_port(&self, name: &String) -> Result<Option<i32>, Error> {
         let client = self.connection_pool.get().await?;
 
@@ -243,4 +533,90 @@ impl DbManager {
             Ok(None)
         }
     }
+
+    pub async fn reencrypt_services_secrets(&self) -> Result<usize, Error> {
+        let mut client = self.connection_pool.get().await?;
+        let transaction = client.transaction().await?;
+
+        let statement = transaction
+            .prepare_cached(
+                "SELECT name, postgres_password, redis_password, client_secret, secrets FROM services FOR UPDATE",
+            )
+            .await?;
+        let rows = transaction.query(&statement, &[]).await?;
+        let statement = transaction
+            .prepare_cached(
+                "UPDATE services SET postgres_password = $1, redis_password = $2, client_secret = $3, secrets = $4 WHERE name = $5",
+            )
+            .await?;
+
+        let mut reencrypted_services_count = 0;
+        for row in rows {
+            let name: String = row.try_get("name")?;
+            let mut is_reencrypted = false;
+            let mut reencrypt = |value: String| -> Result<String, Error> {
+                match self.encryption_manager.reencrypt(&value)? {
+                    Some(reencrypted_value) => {
+                        is_reencrypted = true;
+                        Ok(reencrypted_value)
+                    }
+                    None => Ok(value),
+                }
+            };
+
+            let postgres_password = reencrypt(row.try_get("postgres_password")?)?;
+            let redis_password = reencrypt(row.try_get("redis_password")?)?;
+            let client_secret = reencrypt(row.try_get("client_secret")?)?;
+            let secrets = row
+                .try_get::<&str, Json<Vec<EnvironmentVariable>>>("secrets")?
+                .0
+                .into_iter()
+                .map(|secret| {
+                    Ok(EnvironmentVariable {
+                        value: reencrypt(secret.value)?,
+                        ..secret
+                    })
+                })
+                .collect::<Result<Vec<EnvironmentVariable>, Error>>()?;
+
+            if is_reencrypted {
+                transaction
+                    .execute(
+                        &statement,
+                        &[
+                            &postgres_password,
+                            &redis_password,
+                            &client_secret,
+                            &Json(secrets),
+                            &name,
+                        ],
+                    )
+                    .await?;
+                reencrypted_services_count += 1;
+            }
+        }
+
+        transaction.commit().await?;
+        Ok(reencrypted_services_count)
+    }
+
+    fn encrypt_secrets(
+        &self,
+        secrets: &[EnvironmentVariable],
+    ) -> Result<Vec<EnvironmentVariable>, Error> {
+        secrets
+            .iter()
+            .map(|secret| {
+                Ok(EnvironmentVariable {
+                    value: self.encryption_manager.encrypt(&secret.value)?,
+                    ..secret.clone()
+                })
+            })
+            .collect()
+    }
+
+    async fn with_resolved_references(&self, service: ServiceData) -> Result<ServiceData, Error> {
+        let service = self.with_resolved_shared_secrets(service).await?;
+        self.with_resolved_registry_credentials(service).await
+    }
 }
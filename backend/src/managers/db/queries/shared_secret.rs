@@ -0,0 +1,160 @@
+use postgres_types::Json;
+
+use crate::error::Error;
+use crate::managers::container::models::EnvironmentVariable;
+use crate::managers::db::DbManager;
+use crate::managers::db::models::{ServiceData, SharedSecret};
+
+impl DbManager {
+    pub async fn get_shared_secrets(&self) -> Result<Vec<SharedSecret>, Error> {
+        let client = self.connection_pool.get().await?;
+        let statement = client
+            .prepare_cached(
+                "SELECT name, version, created_at FROM shared_secrets ORDER BY name, version",
+            )
+            .await?;
+        let shared_secrets: Result<Vec<SharedSecret>, Error> = client
+            .query(&statement, &[])
+            .await?
+            .into_iter()
+            .map(SharedSecret::try_from)
+            .collect();
+        shared_secrets
+    }
+
+    pub async fn create_shared_secret_version(
+        &self,
+        name: &String,
+        value: &str,
+    ) -> Result<SharedSecret, Error> {
+        let encrypted_value = self.encryption_manager.encrypt(value)?;
+
+        let mut client = self.connection_pool.get().await?;
+        let transaction = client.transaction().await?;
+
+        let statement = transaction
+            .prepare_cached("SELECT pg_advisory_xact_lock(hashtext($1))")
+            .await?;
+        transaction.execute(&statement, &[name]).await?;
+
+        let statement = transaction
+            .prepare_cached(
+                "INSERT INTO shared_secrets (name, version, value)
+                SELECT $1, coalesce(max(version), 0) + 1, $2 FROM shared_secrets WHERE name = $1
+                RETURNING name, version, created_at",
+            )
+            .await?;
+        let shared_secret_row = transaction
+            .query_one(&statement, &[name, &encrypted_value])
+            .await?;
+        transaction.commit().await?;
+
+        SharedSecret::try_from(shared_secret_row)
+    }
+
+    pub async fn shared_secret_exists(
+        &self,
+        name: &String,
+        version: &Option<i32>,
+    ) -> Result<bool, Error> {
+        let client = self.connection_pool.get().await?;
+        let statement = client
+            .prepare_cached(
+                "SELECT 1 FROM shared_secrets WHERE name = $1 AND ($2::integer IS NULL OR version = $2) LIMIT 1",
+            )
+            .await?;
+        let row = client.query_opt(&statement, &[name, version]).await?;
+        Ok(row.is_some())
+    }
+
+    pub async fn delete_shared_secret(&self, name: &String) -> Result<bool, Error> {
+        let client = self.connection_pool.get().await?;
+        let statement = client
+            .prepare_cached("DELETE FROM shared_secrets WHERE name = $1")
+            .await?;
+        let deleted_rows_count = client.execute(&statement, &[name]).await?;
+        Ok(deleted_rows_count > 0)
+    }
+
+    pub async fn get_shared_secret_dependent_services(
+        &self,
+        name: &str,
+    ) -> Result<Vec<String>, Error> {
+        let client = self.connection_pool.get().await?;
+        let statement = client
+            .prepare_cached("SELECT name FROM services WHERE shared_secrets @> $1 OR deploy_policy -> 'previewSharedSecrets' @> $1 ORDER BY name")
+            .await?;
+        let reference = Json(serde_json::json!([{ "secretName": name }]));
+        let service_names: Result<Vec<String>, Error> = client
+            .query(&statement, &[&reference])
+            .await?
+            .into_iter()
+            .map(|row| Ok(row.try_get("name")?))
+            .collect();
+        service_names
+    }
+
+    pub(super) async fn with_resolved_shared_secrets(
+        &self,
+        mut service: ServiceData,
+    ) -> Result<ServiceData, Error> {
+        if service.container_configuration.shared_secrets.is_empty() {
+            return Ok(service);
+        }
+
+        let client = self.connection_pool.get().await?;
+        let statement = client
+            .prepare_cached(
+                "SELECT value FROM shared_secrets WHERE name = $1 AND ($2::integer IS NULL OR version = $2) ORDER BY version DESC LIMIT 1",
+            )
+            .await?;
+
+        for reference in service.container_configuration.shared_secrets.iter() {
+            let value: String = client
+                .query_opt(&statement, &[&reference.secret_name, &reference.version])
+                .await?
+                .ok_or(Error::shared_secret_not_found(&reference.secret_name))?
+                .try_get("value")?;
+            service
+                .container_configuration
+                .internal_secrets
+                .push(EnvironmentVariable {
+                    name: reference.name.clone(),
+                    value: self.encryption_manager.decrypt(&value)?,
+                    file: reference.file.clone(),
+                });
+        }
+
+        Ok(service)
+    }
+
+    pub async fn reencrypt_shared_secrets(&self) -> Result<usize, Error> {
+        let mut client = self.connection_pool.get().await?;
+        let transaction = client.transaction().await?;
+
+        let statement = transaction
+            .prepare_cached("SELECT name, version, value FROM shared_secrets FOR UPDATE")
+            .await?;
+        let rows = transaction.query(&statement, &[]).await?;
+        let statement = transaction
+            .prepare_cached("UPDATE shared_secrets SET value = $1 WHERE name = $2 AND version = $3")
+            .await?;
+
+        let mut reencrypted_secrets_count = 0;
+        for row in rows {
+            let name: String = row.try_get("name")?;
+            let version: i32 = row.try_get("version")?;
+            let value: String = row.try_get("value")?;
+
+            if let Some(reencrypted_value) = self.encryption_manager.reencrypt(&value)? {
+                transaction
+                    .execute(&statement, &[&reencrypted_value, &name, &version])
+                    .await?;
+                reencrypted_secrets_count += 1;
+            }
+        }
+
+        transaction.commit().await?;
+        Ok(reencrypted_secrets_count)
+    }
+}
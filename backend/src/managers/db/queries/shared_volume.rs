@@ -0,0 +1,70 @@
+use postgres_types::Json;
+
+use crate::error::Error;
+use crate::managers::db::DbManager;
+use crate::managers::db::models::SharedVolume;
+
+impl DbManager {
+    pub async fn get_shared_volumes(&self) -> Result<Vec<SharedVolume>, Error> {
+        let client = self.connection_pool.get().await?;
+        let statement = client
+            .prepare_cached("SELECT * FROM shared_volumes ORDER BY name")
+            .await?;
+        let shared_volumes: Result<Vec<SharedVolume>, Error> = client
+            .query(&statement, &[])
+            .await?
+            .into_iter()
+            .map(SharedVolume::try_from)
+            .collect();
+        shared_volumes
+    }
+
+    pub async fn shared_volume_exists(&self, name: &str) -> Result<bool, Error> {
+        let client = self.connection_pool.get().await?;
+        let statement = client
+            .prepare_cached("SELECT 1 FROM shared_volumes WHERE name = $1")
+            .await?;
+        let row = client.query_opt(&statement, &[&name]).await?;
+        Ok(row.is_some())
+    }
+
+    pub async fn create_shared_volume(&self, name: &str) -> Result<SharedVolume, Error> {
+        let client = self.connection_pool.get().await?;
+        let statement = client
+            .prepare_cached(
+                "INSERT INTO shared_volumes (name) VALUES ($1)
+                ON CONFLICT (name) DO UPDATE SET name = excluded.name
+                RETURNING *",
+            )
+            .await?;
+        let shared_volume_row = client.query_one(&statement, &[&name]).await?;
+        SharedVolume::try_from(shared_volume_row)
+    }
+
+    pub async fn delete_shared_volume(&self, name: &str) -> Result<bool, Error> {
+        let client = self.connection_pool.get().await?;
+        let statement = client
+            .prepare_cached("DELETE FROM shared_volumes WHERE name = $1")
+            .await?;
+        let deleted_rows_count = client.execute(&statement, &[&name]).await?;
+        Ok(deleted_rows_count > 0)
+    }
+
+    pub async fn get_shared_volume_dependent_services(
+        &self,
+        name: &str,
+    ) -> Result<Vec<String>, Error> {
+        let client = self.connection_pool.get().await?;
+        let statement = client
+            .prepare_cached("SELECT name FROM services WHERE shared_volumes @> $1 ORDER BY name")
+            .await?;
+        let mount = Json(serde_json::json!([{ "name": name }]));
+        let service_names: Result<Vec<String>, Error> = client
+            .query(&statement, &[&mount])
+            .await?
+            .into_iter()
+            .map(|row| Ok(row.try_get("name")?))
+            .collect();
+        service_names
+    }
+}
@@ -0,0 +1,92 @@
+use crate::error::Error;
+use crate::managers::db::DbManager;
+use crate::managers::db::models::TrackedImageTag;
+
+impl DbManager {
+    pub async fn get_tracked_image_tag(
+        &self,
+        service_name: &str,
+    ) -> Result<Option<TrackedImageTag>, Error> {
+        let client = self.connection_pool.get().await?;
+        let statement = client
+            .prepare_cached("SELECT * FROM tracked_image_tags WHERE service_name = $1")
+            .await?;
+        let row = client.query_opt(&statement, &[&service_name]).await?;
+        row.map(TrackedImageTag::try_from).transpose()
+    }
+
+    pub async fn save_tracked_image_tag(
+        &self,
+        service_name: &str,
+        tag: &str,
+        image_sha: Option<&str>,
+        error: Option<&str>,
+    ) -> Result<TrackedImageTag, Error> {
+        let client = self.connection_pool.get().await?;
+        let statement = client
+            .prepare_cached(
+                "INSERT INTO tracked_image_tags (service_name, tag, image_sha, error) VALUES ($1, $2, $3, $4)
+                ON CONFLICT (service_name) DO UPDATE SET
+                    tag = excluded.tag,
+                    image_sha = CASE
+                        WHEN excluded.tag = tracked_image_tags.tag
+                        THEN coalesce(excluded.image_sha, tracked_image_tags.image_sha)
+                        ELSE excluded.image_sha
+                    END,
+                    error = excluded.error,
+                    failed_image_sha = CASE
+                        WHEN excluded.tag = tracked_image_tags.tag
+                        THEN tracked_image_tags.failed_image_sha
+                    END,
+                    checked_at = now()
+                RETURNING *",
+            )
+            .await?;
+        let tracked_image_tag_row = client
+            .query_one(&statement, &[&service_name, &tag, &image_sha, &error])
+            .await?;
+        TrackedImageTag::try_from(tracked_image_tag_row)
+    }
+
+    pub async fn save_failed_image_sha(
+        &self,
+        service_name: &str,
+        failed_image_sha: &str,
+    ) -> Result<(), Error> {
+        let client = self.connection_pool.get().await?;
+        let statement = client
+            .prepare_cached(
+                "UPDATE tracked_image_tags SET failed_image_sha = $2 WHERE service_name = $1",
+            )
+            .await?;
+        client
+            .execute(&statement, &[&service_name, &failed_image_sha])
+            .await?;
+        Ok(())
+    }
+
+    pub async fn get_services_due_for_image_update_check(&self) -> Result<Vec<String>, Error> {
+        let client = self.connection_pool.get().await?;
+        let statement = client
+            .prepare_cached(
+                "SELECT services.name FROM services
+                LEFT JOIN tracked_image_tags ON tracked_image_tags.service_name = services.name
+                WHERE services.image_update_policy IS NOT NULL
+                    AND (
+                        tracked_image_tags.checked_at IS NULL
+                        OR tracked_image_tags.checked_at < now() - make_interval(
+                            mins => (services.image_update_policy->>'checkIntervalMinutes')::int
+                        )
+                    )
+                ORDER BY tracked_image_tags.checked_at NULLS FIRST",
+            )
+            .await?;
+        let service_names: Result<Vec<String>, Error> = client
+            .query(&statement, &[])
+            .await?
+            .into_iter()
+            .map(|row| Ok(row.try_get("name")?))
+            .collect();
+        service_names
+    }
+}
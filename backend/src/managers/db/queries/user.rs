@@ -1,11 +1,13 @@
+use deadpool_postgres::Transaction;
+use postgres_types::Json;
 use uuid::Uuid;
 
 use crate::error::Error;
 use crate::managers::db::{
     DbManager,
-    models::{UserData, UserInvitation},
+    models::{CustomRole, Group, UserData, UserInvitation, UserPasswordReset},
 };
-use crate::models::UserRole;
+use crate::models::{Permission, UserAuthorisation, UserRole};
 
 impl DbManager {
     pub async fn get_user_data(&self, username: &String) -> Result<Option<UserData>, Error> {
@@ -115,7 +117,7 @@ impl DbManager {
         let user_data = match invitation {
             None => None,
             Some(invitation) => {
-                let statement = transaction.prepare_cached("INSERT INTO users (username, password_hash, role) VALUES ($1, $2, $3) RETURNING id, password_hash, role, username").await?;
+                let statement = transaction.prepare_cached("INSERT INTO users (username, password_hash, role) VALUES ($1, $2, $3) RETURNING id, password_hash, role, username, custom_role").await?;
                 let user_data_raw = transaction
                     .query_one(&statement, &[&username, &password_hash, &invitation.role])
                     .await?;
@@ -141,4 +143,280 @@ impl DbManager {
         let invitation = UserInvitation::try_from(invitation_raw)?;
         Ok(invitation)
     }
+
+    pub async fn update_user_password_hash(
+        &self,
+        user_id: &i64,
+        password_hash: &String,
+    ) -> Result<(), Error> {
+        let client = self.connection_pool.get().await?;
+        let statement = client
+            .prepare_cached("UPDATE users SET password_hash = $1 WHERE id = $2")
+            .await?;
+        client
+            .execute(&statement, &[password_hash, user_id])
+            .await?;
+        Ok(())
+    }
+
+    pub async fn create_user_password_reset(
+        &self,
+        username: &String,
+    ) -> Result<Option<UserPasswordReset>, Error> {
+        let client = self.connection_pool.get().await?;
+        let statement = client
+            .prepare_cached(
+                "INSERT INTO user_password_resets (user_id) SELECT id FROM users WHERE username = $1 RETURNING id, user_id",
+            )
+            .await?;
+        let password_reset = client
+            .query_opt(&statement, &[username])
+            .await?
+            .map(UserPasswordReset::try_from)
+            .transpose()?;
+        Ok(password_reset)
+    }
+
+    pub async fn reset_user_password(
+        &self,
+        password_reset_id: &Uuid,
+        password_hash: &String,
+    ) -> Result<Option<UserData>, Error> {
+        let mut client = self.connection_pool.get().await?;
+        let transaction = client.transaction().await?;
+        let statement = transaction
+            .prepare_cached(
+                "SELECT id, user_id FROM user_password_resets WHERE id = $1 AND created_at > now() - interval '1 day' FOR UPDATE",
+            )
+            .await?;
+        let password_reset: Option<UserPasswordReset> = transaction
+            .query_opt(&statement, &[&password_reset_id])
+            .await?
+            .map(UserPasswordReset::try_from)
+            .transpose()?;
+        let user_data = match password_reset {
+            None => None,
+            Some(password_reset) => {
+                let statement = transaction.prepare_cached("UPDATE users SET password_hash = $1 WHERE id = $2 RETURNING id, password_hash, role, username, custom_role").await?;
+                let user_data_raw = transaction
+                    .query_one(&statement, &[&password_hash, &password_reset.user_id])
+                    .await?;
+                let user_data = UserData::try_from(user_data_raw)?;
+                let statement = transaction
+                    .prepare_cached("DELETE FROM user_password_resets WHERE user_id = $1")
+                    .await?;
+                transaction
+                    .execute(&statement, &[&password_reset.user_id])
+                    .await?;
+
+                Some(user_data)
+            }
+        };
+        transaction.commit().await?;
+        Ok(user_data)
+    }
+
+    pub async fn get_user_authorisation(
+        &self,
+        user_id: &i64,
+    ) -> Result<Option<UserAuthorisation>, Error> {
+        let client = self.connection_pool.get().await?;
+        let statement = client
+            .prepare_cached(
+                "SELECT
+                    users.role,
+                    users.custom_role,
+                    roles.permissions,
+                    array(
+                        SELECT group_name FROM group_members WHERE user_id = users.id ORDER BY group_name
+                    ) AS groups
+                FROM users LEFT JOIN roles ON roles.name = users.custom_role
+                WHERE users.id = $1",
+            )
+            .await?;
+        let user_authorisation = client
+            .query_opt(&statement, &[user_id])
+            .await?
+            .map(UserAuthorisation::try_from)
+            .transpose()?;
+        Ok(user_authorisation)
+    }
+
+    pub async fn set_user_custom_role(
+        &self,
+        username: &String,
+        custom_role: &Option<String>,
+    ) -> Result<Option<i64>, Error> {
+        let client = self.connection_pool.get().await?;
+        let statement = client
+            .prepare_cached("UPDATE users SET custom_role = $1 WHERE username = $2 RETURNING id")
+            .await?;
+        let user_id = client
+            .query_opt(&statement, &[custom_role, username])
+            .await?
+            .map(|row| row.try_get("id"))
+            .transpose()?;
+        Ok(user_id)
+    }
+
+    pub async fn get_custom_roles(&self) -> Result<Vec<CustomRole>, Error> {
+        let client = self.connection_pool.get().await?;
+        let statement = client
+            .prepare_cached("SELECT * FROM roles ORDER BY name")
+            .await?;
+        let roles: Result<Vec<CustomRole>, Error> = client
+            .query(&statement, &[])
+            .await?
+            .into_iter()
+            .map(CustomRole::try_from)
+            .collect();
+        roles
+    }
+
+    pub async fn upsert_custom_role(
+        &self,
+        name: &String,
+        permissions: &[Permission],
+    ) -> Result<Vec<i64>, Error> {
+        let mut client = self.connection_pool.get().await?;
+        let transaction = client.transaction().await?;
+        let statement = transaction
+            .prepare_cached(
+                "INSERT INTO roles (name, permissions) VALUES ($1, $2) ON CONFLICT (name) DO UPDATE SET permissions = excluded.permissions",
+            )
+            .await?;
+        transaction
+            .execute(&statement, &[name, &Json(permissions)])
+            .await?;
+        let user_ids = Self::get_custom_role_user_ids(&transaction, name).await?;
+        transaction.commit().await?;
+        Ok(user_ids)
+    }
+
+    pub async fn delete_custom_role(&self, name: &String) -> Result<Vec<i64>, Error> {
+        let mut client = self.connection_pool.get().await?;
+        let transaction = client.transaction().await?;
+        let user_ids = Self::get_custom_role_user_ids(&transaction, name).await?;
+        let statement = transaction
+            .prepare_cached("DELETE FROM roles WHERE name = $1")
+            .await?;
+        transaction.execute(&statement, &[name]).await?;
+        transaction.commit().await?;
+        Ok(user_ids)
+    }
+
+    async fn get_custom_role_user_ids(
+        transaction: &Transaction<'_>,
+        name: &String,
+    ) -> Result<Vec<i64>, Error> {
+        let statement = transaction
+            .prepare_cached("SELECT id FROM users WHERE custom_role = $1")
+            .await?;
+        let user_ids: Result<Vec<i64>, Error> = transaction
+            .query(&statement, &[name])
+            .await?
+            .into_iter()
+            .map(|row| row.try_get("id").map_err(Error::from))
+            .collect();
+        user_ids
+    }
+
+    pub async fn get_groups(&self) -> Result<Vec<Group>, Error> {
+        let client = self.connection_pool.get().await?;
+        let statement = client
+            .prepare_cached(
+                "SELECT
+                    groups.name,
+                    array(
+                        SELECT users.username FROM group_members
+                        JOIN users ON users.id = group_members.user_id
+                        WHERE group_members.group_name = groups.name
+                        ORDER BY users.username
+                    ) AS usernames
+                FROM groups ORDER BY groups.name",
+            )
+            .await?;
+        let groups: Result<Vec<Group>, Error> = client
+            .query(&statement, &[])
+            .await?
+            .into_iter()
+            .map(Group::try_from)
+            .collect();
+        groups
+    }
+
+    pub async fn create_group(&self, name: &String) -> Result<(), Error> {
+        let client = self.connection_pool.get().await?;
+        let statement = client
+            .prepare_cached("INSERT INTO groups (name) VALUES ($1) ON CONFLICT DO NOTHING")
+            .await?;
+        client.execute(&statement, &[name]).await?;
+        Ok(())
+    }
+
+    pub async fn delete_group(&self, name: &String) -> Result<Vec<i64>, Error> {
+        let mut client = self.connection_pool.get().await?;
+        let transaction = client.transaction().await?;
+        let statement = transaction
+            .prepare_cached("SELECT user_id FROM group_members WHERE group_name = $1")
+            .await?;
+        let user_ids: Result<Vec<i64>, Error> = transaction
+            .query(&statement, &[name])
+            .await?
+            .into_iter()
+            .map(|row| row.try_get("user_id").map_err(Error::from))
+            .collect();
+        let statement = transaction
+            .prepare_cached("DELETE FROM groups WHERE name = $1")
+            .await?;
+        transaction.execute(&statement, &[name]).await?;
+        transaction.commit().await?;
+        user_ids
+    }
+
+    pub async fn add_group_member(
+        &self,
+        group_name: &String,
+        username: &String,
+    ) -> Result<Option<i64>, Error> {
+        let client = self.connection_pool.get().await?;
+        let statement = client
+            .prepare_cached(
+                "INSERT INTO group_members (group_name, user_id)
+                SELECT groups.name, users.id FROM groups, users
+                WHERE groups.name = $1 AND users.username = $2
+                ON CONFLICT DO NOTHING
+                RETURNING user_id",
+            )
+            .await?;
+        let user_id = client
+            .query_opt(&statement, &[group_name, username])
+            .await?
+            .map(|row| row.try_get("user_id"))
+            .transpose()?;
+        Ok(user_id)
+    }
+
+    pub async fn remove_group_member(
+        &self,
+        group_name: &String,
+        username: &String,
+    ) -> Result<Option<i64>, Error> {
+        let client = self.connection_pool.get().await?;
+        let statement = client
+            .prepare_cached(
+                "DELETE FROM group_members USING users
+                WHERE group_members.user_id = users.id
+                AND group_members.group_name = $1
+                AND users.username = $2
+                RETURNING group_members.user_id",
+            )
+            .await?;
+        let user_id = client
+            .query_opt(&statement, &[group_name, username])
+            .await?
+            .map(|row| row.try_get("user_id"))
+            .transpose()?;
+        Ok(user_id)
+    }
 }
@@ -0,0 +1,173 @@
+use uuid::Uuid;
+
+use crate::error::Error;
+use crate::managers::backup::models::BackupStorageKind;
+use crate::managers::container::models::ContainerConfiguration;
+use crate::managers::db::DbManager;
+use crate::managers::db::models::{DeletedVolume, VolumeSnapshot};
+
+impl DbManager {
+    pub async fn get_service_volume_snapshots(
+        &self,
+        service_name: &str,
+    ) -> Result<Vec<VolumeSnapshot>, Error> {
+        let client = self.connection_pool.get().await?;
+        let statement = client
+            .prepare_cached(
+                "SELECT * FROM volume_snapshots WHERE service_name = $1 ORDER BY created_at DESC",
+            )
+            .await?;
+        let volume_snapshots: Result<Vec<VolumeSnapshot>, Error> = client
+            .query(&statement, &[&service_name])
+            .await?
+            .into_iter()
+            .map(VolumeSnapshot::try_from)
+            .collect();
+        volume_snapshots
+    }
+
+    pub async fn get_volume_snapshot(
+        &self,
+        service_name: &str,
+        id: &Uuid,
+    ) -> Result<Option<VolumeSnapshot>, Error> {
+        let client = self.connection_pool.get().await?;
+        let statement = client
+            .prepare_cached("SELECT * FROM volume_snapshots WHERE service_name = $1 AND id = $2")
+            .await?;
+        let volume_snapshot = client
+            .query_opt(&statement, &[&service_name, id])
+            .await?
+            .map(VolumeSnapshot::try_from)
+            .transpose()?;
+        Ok(volume_snapshot)
+    }
+
+    pub async fn create_volume_snapshot(
+        &self,
+        id: &Uuid,
+        service_name: &str,
+        volume_path: &str,
+        storage: &BackupStorageKind,
+        size_bytes: i64,
+    ) -> Result<VolumeSnapshot, Error> {
+        let client = self.connection_pool.get().await?;
+        let statement = client
+            .prepare_cached(
+                "INSERT INTO volume_snapshots (id, service_name, volume_path, storage, size_bytes) VALUES ($1, $2, $3, $4, $5) RETURNING *",
+            )
+            .await?;
+        let volume_snapshot_row = client
+            .query_one(
+                &statement,
+                &[id, &service_name, &volume_path, storage, &size_bytes],
+            )
+            .await?;
+        VolumeSnapshot::try_from(volume_snapshot_row)
+    }
+
+    pub async fn delete_volume_snapshot(&self, id: &Uuid) -> Result<(), Error> {
+        let client = self.connection_pool.get().await?;
+        let statement = client
+            .prepare_cached("DELETE FROM volume_snapshots WHERE id = $1")
+            .await?;
+        client.execute(&statement, &[id]).await?;
+        Ok(())
+    }
+
+    pub async fn mark_volumes_as_deleted(
+        &self,
+        configuration: &ContainerConfiguration,
+        volume_paths: &[String],
+    ) -> Result<(), Error> {
+        let client = self.connection_pool.get().await?;
+        let statement = client
+            .prepare_cached(
+                "INSERT INTO deleted_volumes (volume_id, service_name, volume_path) VALUES ($1, $2, $3)
+                ON CONFLICT (volume_id) DO UPDATE SET deleted_at = now()",
+            )
+            .await?;
+
+        for volume_path in volume_paths {
+            let volume_id = configuration.get_stateful_volume_id(volume_path);
+            client
+                .execute(&statement, &[&volume_id, &configuration.name, volume_path])
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn unmark_volumes_as_deleted(
+        &self,
+        configuration: &ContainerConfiguration,
+    ) -> Result<(), Error> {
+        let volume_ids: Vec<String> = configuration
+            .stateful_volume_paths
+            .iter()
+            .map(|path| configuration.get_stateful_volume_id(path))
+            .collect();
+
+        let client = self.connection_pool.get().await?;
+        let statement = client
+            .prepare_cached("DELETE FROM deleted_volumes WHERE volume_id = ANY($1)")
+            .await?;
+        client.execute(&statement, &[&volume_ids]).await?;
+        Ok(())
+    }
+
+    pub async fn get_service_deleted_volumes(
+        &self,
+        service_name: &str,
+    ) -> Result<Vec<DeletedVolume>, Error> {
+        let client = self.connection_pool.get().await?;
+        let statement = client
+            .prepare_cached(
+                "SELECT * FROM deleted_volumes WHERE service_name = $1 ORDER BY deleted_at DESC",
+            )
+            .await?;
+        let deleted_volumes: Result<Vec<DeletedVolume>, Error> = client
+            .query(&statement, &[&service_name])
+            .await?
+            .into_iter()
+            .map(DeletedVolume::try_from)
+            .collect();
+        deleted_volumes
+    }
+
+    pub async fn get_expired_deleted_volumes(
+        &self,
+        grace_period_hours: i32,
+    ) -> Result<Vec<DeletedVolume>, Error> {
+        let client = self.connection_pool.get().await?;
+        let statement = client
+            .prepare_cached(
+                "SELECT * FROM deleted_volumes WHERE deleted_at < now() - make_interval(hours => $1)",
+            )
+            .await?;
+        let deleted_volumes: Result<Vec<DeletedVolume>, Error> = client
+            .query(&statement, &[&grace_period_hours])
+            .await?
+            .into_iter()
+            .map(DeletedVolume::try_from)
+            .collect();
+        deleted_volumes
+    }
+
+    pub async fn remove_deleted_volume(
+        &self,
+        service_name: &str,
+        volume_id: &str,
+    ) -> Result<bool, Error> {
+        let client = self.connection_pool.get().await?;
+        let statement = client
+            .prepare_cached(
+                "DELETE FROM deleted_volumes WHERE service_name = $1 AND volume_id = $2",
+            )
+            .await?;
+        let deleted_rows_count = client
+            .execute(&statement, &[&service_name, &volume_id])
+            .await?;
+        Ok(deleted_rows_count > 0)
+    }
+}
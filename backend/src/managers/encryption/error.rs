@@ -0,0 +1,35 @@
+use crate::error::Error;
+use axum::http::StatusCode;
+
+impl Error {
+    pub fn invalid_master_key() -> Self {
+        Self {
+            code: StatusCode::INTERNAL_SERVER_ERROR,
+            message: "master key is invalid".to_string(),
+        }
+    }
+
+    pub fn unknown_master_key(key_id: &str) -> Self {
+        Self {
+            code: StatusCode::INTERNAL_SERVER_ERROR,
+            message: format!(
+                "value is encrypted with unknown master key {}, provide it as previous master key",
+                key_id
+            ),
+        }
+    }
+
+    pub fn encryption_failure() -> Self {
+        Self {
+            code: StatusCode::INTERNAL_SERVER_ERROR,
+            message: "cannot encrypt value".to_string(),
+        }
+    }
+
+    pub fn decryption_failure() -> Self {
+        Self {
+            code: StatusCode::INTERNAL_SERVER_ERROR,
+            message: "cannot decrypt value".to_string(),
+        }
+    }
+}
@@ -0,0 +1,461 @@
+use std::io::ErrorKind;
+use std::sync::Arc;
+
+use aes_gcm::aead::stream::{DecryptorBE32, EncryptorBE32, Nonce as StreamNonce, StreamBE32};
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use argon2::{Argon2, Params};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use tokio::fs::{File, OpenOptions, create_dir_all, try_exists};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use crate::error::Error;
+use crate::managers::crypto::hex_encode;
+use crate::settings::Settings;
+
+mod error;
+
+static ENCRYPTED_VALUE_PREFIX: &str = "kiwi-enc:v1:";
+static PASSPHRASE_SEALED_PREFIX: &[u8] = b"kiwi-sealed:v2:";
+static KEY_LENGTH: usize = 32;
+static NONCE_LENGTH: usize = 12;
+static SALT_LENGTH: usize = 16;
+static STREAM_NONCE_LENGTH: usize = 7;
+static STREAM_CHUNK_LENGTH: usize = 64 * 1024;
+static TAG_LENGTH: usize = 16;
+
+struct MasterKey {
+    id: String,
+    key: Vec<u8>,
+    cipher: Aes256Gcm,
+}
+
+/// Every value gets its own data key, wrapped with the master key, so that rotating the master key
+/// only requires re-wrapping data keys.
+#[derive(Clone)]
+pub struct EncryptionManager {
+    current_master_key: Arc<MasterKey>,
+    previous_master_keys: Arc<Vec<MasterKey>>,
+}
+
+impl EncryptionManager {
+    pub async fn new(settings: &Settings) -> Result<Self, Error> {
+        let current_master_key = match &settings.master_passphrase {
+            Some(passphrase) => derive_master_key(settings, passphrase).await?,
+            None => load_or_create_master_key(&settings.master_key_file_path()).await?,
+        };
+
+        let mut previous_master_keys = vec![];
+        if let Some(passphrase) = &settings.previous_master_passphrase {
+            previous_master_keys.push(derive_master_key(settings, passphrase).await?);
+        }
+        if let Some(path) = &settings.previous_master_key_file_path {
+            previous_master_keys.push(load_master_key(path).await?);
+        }
+
+        tracing::info!(
+            "encryption manager initialised with master key {}",
+            current_master_key.id
+        );
+
+        Ok(Self {
+            current_master_key: Arc::new(current_master_key),
+            previous_master_keys: Arc::new(previous_master_keys),
+        })
+    }
+
+    pub fn encrypt(&self, plaintext: &str) -> Result<String, Error> {
+        let data_key = Aes256Gcm::generate_key(OsRng);
+        let data_cipher = Aes256Gcm::new(&data_key);
+        let sealed_value = seal(&data_cipher, plaintext.as_bytes())?;
+        let wrapped_data_key = seal(&self.current_master_key.cipher, &data_key)?;
+
+        Ok(format!(
+            "{}{}:{}:{}",
+            ENCRYPTED_VALUE_PREFIX,
+            self.current_master_key.id,
+            STANDARD.encode(wrapped_data_key),
+            STANDARD.encode(sealed_value)
+        ))
+    }
+
+    pub fn decrypt(&self, value: &str) -> Result<String, Error> {
+        let Some(envelope) = Envelope::parse(value)? else {
+            return Ok(value.to_string());
+        };
+
+        let data_key = self.unwrap_data_key(&envelope)?;
+        let data_cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&data_key));
+        let plaintext = open(&data_cipher, &envelope.sealed_value)?;
+
+        String::from_utf8(plaintext).map_err(|_| Error::decryption_failure())
+    }
+
+    pub fn reencrypt(&self, value: &str) -> Result<Option<String>, Error> {
+        let Some(envelope) = Envelope::parse(value)? else {
+            return self.encrypt(value).map(Some);
+        };
+        if envelope.key_id == self.current_master_key.id {
+            return Ok(None);
+        }
+
+        let data_key = self.unwrap_data_key(&envelope)?;
+        let wrapped_data_key = seal(&self.current_master_key.cipher, &data_key)?;
+
+        Ok(Some(format!(
+            "{}{}:{}:{}",
+            ENCRYPTED_VALUE_PREFIX,
+            self.current_master_key.id,
+            STANDARD.encode(wrapped_data_key),
+            STANDARD.encode(envelope.sealed_value)
+        )))
+    }
+
+    pub fn export_master_key(&self) -> String {
+        STANDARD.encode(&self.current_master_key.key)
+    }
+
+    pub fn with_previous_master_key(self, encoded_key: &str) -> Result<Self, Error> {
+        let key = STANDARD
+            .decode(encoded_key)
+            .map_err(|_| Error::invalid_master_key())?;
+        let master_key = create_master_key(&key)?;
+        if master_key.id == self.current_master_key.id {
+            return Ok(self);
+        }
+
+        let mut previous_master_keys: Vec<MasterKey> = self
+            .previous_master_keys
+            .iter()
+            .map(|previous_master_key| create_master_key(&previous_master_key.key))
+            .collect::<Result<Vec<MasterKey>, Error>>()?;
+        previous_master_keys.push(master_key);
+
+        Ok(Self {
+            current_master_key: self.current_master_key,
+            previous_master_keys: Arc::new(previous_master_keys),
+        })
+    }
+
+    fn unwrap_data_key(&self, envelope: &Envelope) -> Result<Vec<u8>, Error> {
+        let master_key = std::iter::once(self.current_master_key.as_ref())
+            .chain(self.previous_master_keys.iter())
+            .find(|master_key| master_key.id == envelope.key_id)
+            .ok_or(Error::unknown_master_key(&envelope.key_id))?;
+
+        open(&master_key.cipher, &envelope.wrapped_data_key)
+    }
+}
+
+struct Envelope {
+    key_id: String,
+    wrapped_data_key: Vec<u8>,
+    sealed_value: Vec<u8>,
+}
+
+impl Envelope {
+    fn parse(value: &str) -> Result<Option<Self>, Error> {
+        let Some(envelope) = value.strip_prefix(ENCRYPTED_VALUE_PREFIX) else {
+            return Ok(None);
+        };
+        let parts: Vec<&str> = envelope.split(":").collect();
+        let [key_id, wrapped_data_key, sealed_value] = parts.as_slice() else {
+            return Err(Error::decryption_failure());
+        };
+
+        Ok(Some(Self {
+            key_id: key_id.to_string(),
+            wrapped_data_key: STANDARD
+                .decode(wrapped_data_key)
+                .map_err(|_| Error::decryption_failure())?,
+            sealed_value: STANDARD
+                .decode(sealed_value)
+                .map_err(|_| Error::decryption_failure())?,
+        }))
+    }
+}
+
+/// Data is sealed chunk by chunk with the STREAM construction, which detects reordered, dropped or
+/// truncated chunks.
+pub struct PassphraseSealer {
+    encryptor: EncryptorBE32<Aes256Gcm>,
+    buffer: Vec<u8>,
+}
+
+impl PassphraseSealer {
+    pub fn new(passphrase: &str) -> Result<(Self, Vec<u8>), Error> {
+        let mut salt = vec![0u8; SALT_LENGTH];
+        rand::rng().fill_bytes(&mut salt);
+        let mut nonce = vec![0u8; STREAM_NONCE_LENGTH];
+        rand::rng().fill_bytes(&mut nonce);
+        let key = derive_key(passphrase, &salt)?;
+        let encryptor = EncryptorBE32::from_aead(
+            Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key)),
+            StreamNonce::<Aes256Gcm, StreamBE32<Aes256Gcm>>::from_slice(&nonce),
+        );
+        let sealer = Self {
+            encryptor,
+            buffer: vec![],
+        };
+
+        Ok((sealer, [PASSPHRASE_SEALED_PREFIX, &salt, &nonce].concat()))
+    }
+
+    pub fn update(&mut self, data: &[u8]) -> Result<Vec<u8>, Error> {
+        self.buffer.extend_from_slice(data);
+        let mut sealed_data = vec![];
+        while self.buffer.len() >= STREAM_CHUNK_LENGTH {
+            let chunk: Vec<u8> = self.buffer.drain(..STREAM_CHUNK_LENGTH).collect();
+            sealed_data.extend(
+                self.encryptor
+                    .encrypt_next(chunk.as_slice())
+                    .map_err(|_| Error::encryption_failure())?,
+            );
+        }
+
+        Ok(sealed_data)
+    }
+
+    /// The last chunk is always shorter than the others, so that truncation is detected.
+    pub fn finish(self) -> Result<Vec<u8>, Error> {
+        self.encryptor
+            .encrypt_last(self.buffer.as_slice())
+            .map_err(|_| Error::encryption_failure())
+    }
+}
+
+pub struct PassphraseOpener {
+    decryptor: DecryptorBE32<Aes256Gcm>,
+    buffer: Vec<u8>,
+}
+
+impl PassphraseOpener {
+    pub fn header_length() -> usize {
+        PASSPHRASE_SEALED_PREFIX.len() + SALT_LENGTH + STREAM_NONCE_LENGTH
+    }
+
+    pub fn new(passphrase: &str, header: &[u8]) -> Result<Self, Error> {
+        let header = header
+            .strip_prefix(PASSPHRASE_SEALED_PREFIX)
+            .filter(|header| header.len() == SALT_LENGTH + STREAM_NONCE_LENGTH)
+            .ok_or(Error::decryption_failure())?;
+        let (salt, nonce) = header.split_at(SALT_LENGTH);
+        let key = derive_key(passphrase, salt)?;
+        let decryptor = DecryptorBE32::from_aead(
+            Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key)),
+            StreamNonce::<Aes256Gcm, StreamBE32<Aes256Gcm>>::from_slice(nonce),
+        );
+
+        Ok(Self {
+            decryptor,
+            buffer: vec![],
+        })
+    }
+
+    pub fn update(&mut self, sealed_data: &[u8]) -> Result<Vec<u8>, Error> {
+        self.buffer.extend_from_slice(sealed_data);
+        let mut data = vec![];
+        while self.buffer.len() >= STREAM_CHUNK_LENGTH + TAG_LENGTH {
+            let chunk: Vec<u8> = self
+                .buffer
+                .drain(..STREAM_CHUNK_LENGTH + TAG_LENGTH)
+                .collect();
+            data.extend(
+                self.decryptor
+                    .decrypt_next(chunk.as_slice())
+                    .map_err(|_| Error::decryption_failure())?,
+            );
+        }
+
+        Ok(data)
+    }
+
+    pub fn finish(self) -> Result<Vec<u8>, Error> {
+        self.decryptor
+            .decrypt_last(self.buffer.as_slice())
+            .map_err(|_| Error::decryption_failure())
+    }
+}
+
+fn seal(cipher: &Aes256Gcm, plaintext: &[u8]) -> Result<Vec<u8>, Error> {
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, plaintext)
+        .map_err(|_| Error::encryption_failure())?;
+
+    Ok([nonce.as_slice(), &ciphertext].concat())
+}
+
+fn open(cipher: &Aes256Gcm, sealed_value: &[u8]) -> Result<Vec<u8>, Error> {
+    if sealed_value.len() < NONCE_LENGTH {
+        return Err(Error::decryption_failure());
+    }
+    let (nonce, ciphertext) = sealed_value.split_at(NONCE_LENGTH);
+
+    cipher
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| Error::decryption_failure())
+}
+
+fn create_master_key(key: &[u8]) -> Result<MasterKey, Error> {
+    if key.len() != KEY_LENGTH {
+        return Err(Error::invalid_master_key());
+    }
+    let id = hex_encode(&Sha256::digest(key)[..4]);
+
+    Ok(MasterKey {
+        id,
+        key: key.to_vec(),
+        cipher: Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key)),
+    })
+}
+
+async fn load_master_key(path: &str) -> Result<MasterKey, Error> {
+    let mut key_file = File::open(path).await?;
+    let mut raw_text = String::new();
+    key_file.read_to_string(&mut raw_text).await?;
+    let key = STANDARD
+        .decode(raw_text.trim())
+        .map_err(|_| Error::invalid_master_key())?;
+
+    create_master_key(&key)
+}
+
+async fn load_or_create_master_key(path: &str) -> Result<MasterKey, Error> {
+    if try_exists(path).await? {
+        return load_master_key(path).await;
+    }
+
+    let key = Aes256Gcm::generate_key(OsRng);
+    write_private_file(path, &STANDARD.encode(key)).await?;
+    tracing::warn!(
+        "master key generated at {}. keep a copy of it somewhere safe",
+        path
+    );
+
+    create_master_key(&key)
+}
+
+async fn derive_master_key(settings: &Settings, passphrase: &str) -> Result<MasterKey, Error> {
+    let salt_path = settings.master_key_salt_path();
+    let salt = match File::open(&salt_path).await {
+        Ok(mut salt_file) => {
+            let mut raw_text = String::new();
+            salt_file.read_to_string(&mut raw_text).await?;
+            STANDARD
+                .decode(raw_text.trim())
+                .map_err(|_| Error::invalid_master_key())?
+        }
+        Err(error) if error.kind() == ErrorKind::NotFound => {
+            let mut salt = vec![0u8; SALT_LENGTH];
+            rand::rng().fill_bytes(&mut salt);
+            write_private_file(&salt_path, &STANDARD.encode(&salt)).await?;
+            salt
+        }
+        Err(error) => return Err(error.into()),
+    };
+
+    create_master_key(&derive_key(passphrase, &salt)?)
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut key = vec![0u8; KEY_LENGTH];
+    Argon2::new(
+        argon2::Algorithm::Argon2id,
+        argon2::Version::V0x13,
+        Params::DEFAULT,
+    )
+    .hash_password_into(passphrase.as_bytes(), salt, &mut key)?;
+
+    Ok(key)
+}
+
+async fn write_private_file(path: &str, content: &str) -> Result<(), Error> {
+    let path_parts: Vec<&str> = path.split("/").collect();
+    let folder_path = path_parts[..path_parts.len() - 1].join("/");
+    create_dir_all(folder_path).await?;
+
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)
+        .await?;
+    file.write_all(content.as_bytes()).await?;
+    file.flush().await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seal_in_pieces(passphrase: &str, data: &[u8], piece_length: usize) -> Vec<u8> {
+        let (mut sealer, mut sealed_data) = PassphraseSealer::new(passphrase).unwrap();
+        for piece in data.chunks(piece_length) {
+            sealed_data.extend(sealer.update(piece).unwrap());
+        }
+        sealed_data.extend(sealer.finish().unwrap());
+        sealed_data
+    }
+
+    fn open(passphrase: &str, sealed_data: &[u8]) -> Result<Vec<u8>, Error> {
+        let (header, sealed_data) = sealed_data.split_at(PassphraseOpener::header_length());
+        let mut opener = PassphraseOpener::new(passphrase, header)?;
+        let mut data = vec![];
+        for piece in sealed_data.chunks(1000) {
+            data.extend(opener.update(piece)?);
+        }
+        data.extend(opener.finish()?);
+        Ok(data)
+    }
+
+    #[test]
+    fn opens_sealed_data_of_any_length() {
+        for length in [
+            0,
+            1,
+            STREAM_CHUNK_LENGTH - 1,
+            STREAM_CHUNK_LENGTH,
+            3 * STREAM_CHUNK_LENGTH + 5,
+        ] {
+            let data: Vec<u8> = (0..length).map(|index| index as u8).collect();
+            let sealed_data = seal_in_pieces("passphrase", &data, 10_000);
+
+            assert_eq!(open("passphrase", &sealed_data).unwrap(), data);
+        }
+    }
+
+    #[test]
+    fn rejects_wrong_passphrases() {
+        let sealed_data = seal_in_pieces("passphrase", b"data", 10);
+
+        assert!(open("other passphrase", &sealed_data).is_err());
+    }
+
+    #[test]
+    fn rejects_truncated_or_tampered_data() {
+        let data = vec![0; 2 * STREAM_CHUNK_LENGTH + 5];
+        let sealed_data = seal_in_pieces("passphrase", &data, STREAM_CHUNK_LENGTH);
+        let header_length = PassphraseOpener::header_length();
+        let sealed_chunk_length = STREAM_CHUNK_LENGTH + TAG_LENGTH;
+
+        let truncated_data = &sealed_data[..header_length + 2 * sealed_chunk_length];
+        assert!(open("passphrase", truncated_data).is_err());
+
+        let without_first_chunk = [
+            &sealed_data[..header_length],
+            &sealed_data[header_length + sealed_chunk_length..],
+        ]
+        .concat();
+        assert!(open("passphrase", &without_first_chunk).is_err());
+
+        let mut tampered_data = sealed_data.clone();
+        tampered_data[header_length + 10] ^= 1;
+        assert!(open("passphrase", &tampered_data).is_err());
+    }
+}
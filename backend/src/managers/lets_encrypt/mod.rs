@@ -106,6 +106,21 @@ impl LetsEncryptManager {
         })
     }
 
+    pub async fn get_tls_files(&self) -> Result<(String, String), Error> {
+        let mut tls_private_key = String::new();
+        File::open(&self.tls_private_key_path)
+            .await?
+            .read_to_string(&mut tls_private_key)
+            .await?;
+        let mut tls_public_certificate = String::new();
+        File::open(&self.tls_public_certificate_path)
+            .await?
+            .read_to_string(&mut tls_public_certificate)
+            .await?;
+
+        Ok((tls_private_key, tls_public_certificate))
+    }
+
     pub fn get_credentials(&self) -> String {
         self.serialised_credentials.clone()
     }
@@ -1,9 +1,13 @@
+pub mod backup;
 pub mod container;
 pub mod crypto;
 pub mod db;
 pub mod dynamic_dns;
+pub mod encryption;
 pub mod lets_encrypt;
 pub mod local_http;
 pub mod oidc;
+pub mod oidc_provider;
 pub mod redis;
+pub mod registry;
 pub mod secrets;
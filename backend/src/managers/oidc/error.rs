@@ -29,4 +29,28 @@ impl Error {
             message: "invalid jwt".to_string(),
         }
     }
+
+    pub fn untrusted_oidc_issuer() -> Self {
+        Self {
+            code: StatusCode::BAD_REQUEST,
+            message: "token issuer is not trusted".to_string(),
+        }
+    }
+
+    pub fn duplicate_oidc_issuer(name: &str) -> Self {
+        Self {
+            code: StatusCode::INTERNAL_SERVER_ERROR,
+            message: format!("oidc issuer {} is configured more than once", name),
+        }
+    }
+
+    pub fn invalid_oidc_issuer_name(name: &str) -> Self {
+        Self {
+            code: StatusCode::INTERNAL_SERVER_ERROR,
+            message: format!(
+                "oidc issuer name {} must only contain lowercase letters, digits and dashes",
+                name
+            ),
+        }
+    }
 }
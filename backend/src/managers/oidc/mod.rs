@@ -1,49 +1,142 @@
+use std::sync::Arc;
+
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
 use jsonwebtoken::{Algorithm, DecodingKey, Validation, decode, decode_header};
 use reqwest::Client;
+use reqwest::header::CACHE_CONTROL;
+use serde_json::Value;
+use tokio::fs::{create_dir_all, read_to_string, rename, write};
+use tokio::sync::Mutex;
+use tokio::time::{Duration, Instant};
 
 use crate::error::Error;
-use crate::managers::oidc::models::{GithubClaims, GithubJwksResponse, Jwk};
+use crate::managers::oidc::models::{
+    DeploymentClaims, Jwk, JwksCache, JwksResponse, OidcIssuer, OpenIdConfiguration,
+};
+use crate::settings::Settings;
 
 mod error;
-mod models;
+pub mod models;
+
+static DEFAULT_JWKS_TIME_TO_LIVE: Duration = Duration::from_secs(60 * 60);
+static MIN_JWKS_TIME_TO_LIVE: Duration = Duration::from_secs(60);
+static MAX_JWKS_TIME_TO_LIVE: Duration = Duration::from_secs(24 * 60 * 60);
+static ISSUER_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Clone)]
+struct TrustedIssuer {
+    configuration: OidcIssuer,
+    jwks: Arc<Mutex<JwksCache>>,
+}
 
 #[derive(Clone)]
 pub struct OidcManager {
-    github_jwks: Vec<Jwk>,
+    client: Client,
+    jwks_folder_path: String,
+    trusted_issuers: Vec<TrustedIssuer>,
 }
 
 impl OidcManager {
-    pub async fn new() -> Result<Self, Error> {
-        let client = Client::new();
-
-        let jwks: GithubJwksResponse = client
-            .get("https://token.actions.githubusercontent.com/.well-known/jwks")
-            .send()
-            .await?
-            .json()
-            .await?;
-
-        tracing::info!(
-            "initialised oidc manager with {} github jwks",
-            jwks.keys.len()
-        );
+    /// Unreachable issuers are not an error: the last keys fetched from them are used instead.
+    pub async fn new(settings: &Settings) -> Result<Self, Error> {
+        // Issuers are fetched while booting and while deployments wait, never for long
+        let client = Client::builder()
+            .connect_timeout(ISSUER_REQUEST_TIMEOUT)
+            .timeout(ISSUER_REQUEST_TIMEOUT)
+            .build()?;
+        let jwks_folder_path = settings.oidc_jwks_folder_path();
+        create_dir_all(&jwks_folder_path).await?;
+
+        let mut issuers = vec![OidcIssuer::github()];
+        if let Some(file_path) = &settings.oidc_issuers_file_path {
+            let configured_issuers: Vec<OidcIssuer> =
+                serde_json::from_str(&read_to_string(file_path).await?)?;
+            for issuer in configured_issuers {
+                // Names end up in the path of the file caching the keys of the issuer
+                if !issuer.has_valid_name() {
+                    return Err(Error::invalid_oidc_issuer_name(&issuer.name));
+                }
+                if issuers.iter().any(|other| other.name == issuer.name) {
+                    return Err(Error::duplicate_oidc_issuer(&issuer.name));
+                }
+                issuers.push(issuer);
+            }
+        }
+
+        let mut trusted_issuers = vec![];
+        for issuer in issuers {
+            let mut jwks = JwksCache::default();
+            match fetch_jwks(&client, &issuer).await {
+                Ok((keys, time_to_live)) => {
+                    jwks.replace_keys(keys, time_to_live);
+                    if let Err(error) = store_jwks(&jwks_folder_path, &issuer, &jwks.keys).await {
+                        tracing::error!("failed to store {} jwks: {}", issuer.name, error);
+                    }
+                }
+                Err(error) => {
+                    tracing::error!(
+                        "failed to fetch {} jwks, using the last known ones: {}",
+                        issuer.name,
+                        error
+                    );
+                    jwks.keys = load_jwks(&jwks_folder_path, &issuer)
+                        .await
+                        .unwrap_or_default();
+                }
+            }
+            tracing::info!(
+                "trusting oidc issuer {} with {} jwks",
+                issuer.name,
+                jwks.keys.len()
+            );
+            trusted_issuers.push(TrustedIssuer {
+                configuration: issuer,
+                jwks: Arc::new(Mutex::new(jwks)),
+            });
+        }
 
         Ok(Self {
-            github_jwks: jwks.keys,
+            client,
+            jwks_folder_path,
+            trusted_issuers,
         })
     }
 
-    pub async fn validate_github_oidc_token(&self, token: &str) -> Result<GithubClaims, Error> {
-        let kid = decode_header(token)
-            .map_err(|_| Error::invalid_header())?
-            .kid
-            .ok_or(Error::invalid_key_id())?;
+    pub fn is_trusted_issuer(&self, name: &str) -> bool {
+        self.trusted_issuers
+            .iter()
+            .any(|issuer| issuer.configuration.name == name)
+    }
+
+    pub async fn refresh_expired_jwks(&self) {
+        for trusted_issuer in &self.trusted_issuers {
+            let mut jwks = trusted_issuer.jwks.lock().await;
+            if jwks.needs_refresh() {
+                self.refresh_jwks(&trusted_issuer.configuration, &mut jwks)
+                    .await;
+            }
+        }
+    }
+
+    pub async fn validate_deployment_token(&self, token: &str) -> Result<DeploymentClaims, Error> {
+        let header = decode_header(token).map_err(|_| Error::invalid_header())?;
+        let kid = header.kid.ok_or(Error::invalid_key_id())?;
+        if !matches!(
+            header.alg,
+            Algorithm::RS256 | Algorithm::RS384 | Algorithm::RS512
+        ) {
+            return Err(Error::invalid_header());
+        }
 
-        let jwk = self
-            .github_jwks
+        let issuer = get_unverified_issuer(token)?;
+        let trusted_issuer = self
+            .trusted_issuers
             .iter()
-            .find(|k| k.kid == kid)
-            .ok_or(Error::invalid_key_id())?;
+            .find(|trusted_issuer| trusted_issuer.configuration.issuer == issuer)
+            .ok_or(Error::untrusted_oidc_issuer())?;
+
+        let jwk = self.get_jwk(trusted_issuer, &kid).await?;
 
         let modulus = jwk.n.as_str();
         let exponent = jwk.e.as_str();
@@ -51,14 +144,341 @@ impl OidcManager {
         let decoding_key = DecodingKey::from_rsa_components(modulus, exponent)
             .map_err(|_| Error::invalid_key())?;
 
-        let mut validation = Validation::new(Algorithm::RS256);
-        validation.set_audience(&["kiwiDeploy"]);
-        validation.set_issuer(&["https://token.actions.githubusercontent.com"]);
+        let mut validation = Validation::new(header.alg);
+        validation.set_audience(&[&trusted_issuer.configuration.audience]);
+        validation.set_issuer(&[&trusted_issuer.configuration.issuer]);
 
-        let claims = decode::<GithubClaims>(token, &decoding_key, &validation)
+        let claims = decode::<Value>(token, &decoding_key, &validation)
             .map_err(|_| Error::invalid_jwt())?
             .claims;
 
-        Ok(claims)
+        Ok(DeploymentClaims::from_mapped_claims(
+            &trusted_issuer.configuration,
+            &claims,
+        ))
+    }
+
+    async fn get_jwk(&self, trusted_issuer: &TrustedIssuer, kid: &str) -> Result<Jwk, Error> {
+        let mut jwks = trusted_issuer.jwks.lock().await;
+        let is_known_key = jwks.keys.iter().any(|jwk| jwk.kid == kid);
+        if jwks.needs_refresh() || (!is_known_key && jwks.can_refetch()) {
+            self.refresh_jwks(&trusted_issuer.configuration, &mut jwks)
+                .await;
+        }
+
+        jwks.keys
+            .iter()
+            .find(|jwk| jwk.kid == kid)
+            .cloned()
+            .ok_or(Error::invalid_key_id())
+    }
+
+    async fn refresh_jwks(&self, issuer: &OidcIssuer, jwks: &mut JwksCache) {
+        jwks.last_fetch_attempt = Some(Instant::now());
+        match fetch_jwks(&self.client, issuer).await {
+            Ok((keys, time_to_live)) => {
+                jwks.replace_keys(keys, time_to_live);
+                tracing::info!("refreshed {} jwks", issuer.name);
+                if let Err(error) = store_jwks(&self.jwks_folder_path, issuer, &jwks.keys).await {
+                    tracing::error!("failed to store {} jwks: {}", issuer.name, error);
+                }
+            }
+            Err(error) => {
+                tracing::error!("failed to refresh {} jwks: {}", issuer.name, error);
+            }
+        }
+    }
+}
+
+async fn fetch_jwks(client: &Client, issuer: &OidcIssuer) -> Result<(Vec<Jwk>, Duration), Error> {
+    let jwks_uri = match &issuer.jwks_uri {
+        Some(jwks_uri) => jwks_uri.clone(),
+        None => {
+            let configuration: OpenIdConfiguration = client
+                .get(issuer.get_jwks_discovery_uri())
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await?;
+            configuration.jwks_uri
+        }
+    };
+
+    let response = client.get(jwks_uri).send().await?.error_for_status()?;
+    let time_to_live = response
+        .headers()
+        .get(CACHE_CONTROL)
+        .and_then(|value| value.to_str().ok())
+        .and_then(get_max_age)
+        .unwrap_or(DEFAULT_JWKS_TIME_TO_LIVE)
+        .clamp(MIN_JWKS_TIME_TO_LIVE, MAX_JWKS_TIME_TO_LIVE);
+    let jwks: JwksResponse = response.json().await?;
+
+    let keys = jwks
+        .keys
+        .into_iter()
+        .filter(|jwk| jwk.kty == "RSA")
+        .collect();
+    Ok((keys, time_to_live))
+}
+
+fn get_max_age(cache_control: &str) -> Option<Duration> {
+    cache_control
+        .split(',')
+        .filter_map(|directive| directive.trim().strip_prefix("max-age="))
+        .find_map(|seconds| seconds.trim_matches('"').parse().ok())
+        .map(Duration::from_secs)
+}
+
+async fn store_jwks(folder_path: &str, issuer: &OidcIssuer, keys: &[Jwk]) -> Result<(), Error> {
+    let file_path = get_jwks_file_path(folder_path, issuer);
+    let temporary_file_path = format!("{}.tmp", file_path);
+    write(&temporary_file_path, serde_json::to_string(keys)?).await?;
+    rename(temporary_file_path, file_path).await?;
+    Ok(())
+}
+
+async fn load_jwks(folder_path: &str, issuer: &OidcIssuer) -> Result<Vec<Jwk>, Error> {
+    let content = read_to_string(get_jwks_file_path(folder_path, issuer)).await?;
+    Ok(serde_json::from_str(&content)?)
+}
+
+fn get_jwks_file_path(folder_path: &str, issuer: &OidcIssuer) -> String {
+    format!("{}/{}.json", folder_path, issuer.name)
+}
+
+/// Only used to pick the keys to check the signature with.
+fn get_unverified_issuer(token: &str) -> Result<String, Error> {
+    let payload = token.split('.').nth(1).ok_or(Error::invalid_jwt())?;
+    let claims: Value = URL_SAFE_NO_PAD
+        .decode(payload)
+        .ok()
+        .and_then(|payload| serde_json::from_slice(&payload).ok())
+        .ok_or(Error::invalid_jwt())?;
+
+    claims
+        .get("iss")
+        .and_then(|issuer| issuer.as_str())
+        .map(|issuer| issuer.to_string())
+        .ok_or(Error::invalid_jwt())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::OnceLock;
+
+    use axum::{Json, Router, routing::get};
+    use jsonwebtoken::{EncodingKey, Header, encode};
+    use rsa::pkcs1::{EncodeRsaPrivateKey, LineEnding};
+    use rsa::rand_core::OsRng;
+    use rsa::traits::PublicKeyParts;
+    use rsa::{BigUint, RsaPrivateKey};
+    use serde_json::json;
+    use tokio::net::TcpListener;
+    use uuid::Uuid;
+
+    use super::*;
+
+    static KEY_ID: &str = "test-key";
+
+    fn get_private_key() -> &'static RsaPrivateKey {
+        static PRIVATE_KEY: OnceLock<RsaPrivateKey> = OnceLock::new();
+        PRIVATE_KEY.get_or_init(|| RsaPrivateKey::new(&mut OsRng, 2048).unwrap())
+    }
+
+    fn encode_component(component: &BigUint) -> String {
+        URL_SAFE_NO_PAD.encode(component.to_bytes_be())
+    }
+
+    async fn serve_issuer(name: &str) -> OidcIssuer {
+        let private_key = get_private_key();
+        let jwks = json!({
+            "keys": [{
+                "kty": "RSA",
+                "kid": KEY_ID,
+                "alg": "RS256",
+                "n": encode_component(private_key.n()),
+                "e": encode_component(private_key.e()),
+            }]
+        });
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let router = Router::new().route("/jwks", get(move || async move { Json(jwks) }));
+        tokio::spawn(async move { axum::serve(listener, router).await });
+
+        OidcIssuer {
+            name: name.to_string(),
+            issuer: format!("https://{}.example.com", name),
+            jwks_uri: Some(format!("http://{}/jwks", address)),
+            audience: "kiwiDeploy".to_string(),
+            claim_mapping: Default::default(),
+        }
+    }
+
+    fn get_oidc_manager(issuers: Vec<OidcIssuer>) -> OidcManager {
+        let jwks_folder_path = std::env::temp_dir()
+            .join(format!("kiwi-oidc-jwks-{}", Uuid::new_v4().simple()))
+            .to_string_lossy()
+            .to_string();
+        std::fs::create_dir_all(&jwks_folder_path).unwrap();
+
+        OidcManager {
+            client: Client::new(),
+            jwks_folder_path,
+            trusted_issuers: issuers
+                .into_iter()
+                .map(|configuration| TrustedIssuer {
+                    configuration,
+                    jwks: Arc::new(Mutex::new(JwksCache::default())),
+                })
+                .collect(),
+        }
+    }
+
+    fn sign_token(issuer: &OidcIssuer, claims: Value, kid: &str) -> String {
+        let mut header = Header::new(Algorithm::RS256);
+        header.kid = Some(kid.to_string());
+        let pem = get_private_key().to_pkcs1_pem(LineEnding::LF).unwrap();
+        let mut payload = json!({
+            "iss": issuer.issuer,
+            "aud": issuer.audience,
+            "exp": chrono::Utc::now().timestamp() + 300,
+        });
+        payload
+            .as_object_mut()
+            .unwrap()
+            .extend(claims.as_object().unwrap().clone());
+        encode(
+            &header,
+            &payload,
+            &EncodingKey::from_rsa_pem(pem.as_bytes()).unwrap(),
+        )
+        .unwrap()
+    }
+
+    fn github_claims() -> Value {
+        json!({
+            "repository": "owner/repo",
+            "repository_id": "123456",
+            "ref": "refs/heads/main",
+            "actor": "octocat",
+            "environment": "production",
+            "workflow_ref": "owner/repo/.github/workflows/deploy.yaml@refs/heads/main",
+        })
+    }
+
+    #[tokio::test]
+    async fn accepts_token_signed_with_issuer_key() {
+        let issuer = serve_issuer("forgejo").await;
+        let oidc_manager = get_oidc_manager(vec![issuer.clone()]);
+        let token = sign_token(&issuer, github_claims(), KEY_ID);
+
+        let claims = oidc_manager
+            .validate_deployment_token(&token)
+            .await
+            .unwrap();
+        assert_eq!(claims.issuer, "forgejo");
+        assert_eq!(claims.repository, "owner/repo");
+        assert_eq!(claims.repository_id.as_deref(), Some("123456"));
+        assert_eq!(claims.reference, "refs/heads/main");
+        assert_eq!(claims.actor, "octocat");
+        assert_eq!(claims.environment.as_deref(), Some("production"));
+        assert_eq!(
+            claims.get_workflow_path(),
+            Some(".github/workflows/deploy.yaml")
+        );
+    }
+
+    #[tokio::test]
+    async fn rejects_token_of_untrusted_issuer() {
+        let issuer = serve_issuer("forgejo").await;
+        let other_issuer = serve_issuer("gitea").await;
+        let oidc_manager = get_oidc_manager(vec![issuer]);
+        let token = sign_token(&other_issuer, github_claims(), KEY_ID);
+
+        let error = oidc_manager
+            .validate_deployment_token(&token)
+            .await
+            .unwrap_err();
+        assert_eq!(error.message, Error::untrusted_oidc_issuer().message);
+    }
+
+    #[tokio::test]
+    async fn rejects_token_with_wrong_audience() {
+        let issuer = serve_issuer("forgejo").await;
+        let oidc_manager = get_oidc_manager(vec![issuer.clone()]);
+        let token = sign_token(&issuer, json!({ "aud": "someoneElse" }), KEY_ID);
+
+        let error = oidc_manager
+            .validate_deployment_token(&token)
+            .await
+            .unwrap_err();
+        assert_eq!(error.message, Error::invalid_jwt().message);
+    }
+
+    #[tokio::test]
+    async fn rejects_expired_token() {
+        let issuer = serve_issuer("forgejo").await;
+        let oidc_manager = get_oidc_manager(vec![issuer.clone()]);
+        let expired_at = chrono::Utc::now().timestamp() - 3600;
+        let token = sign_token(&issuer, json!({ "exp": expired_at }), KEY_ID);
+
+        let error = oidc_manager
+            .validate_deployment_token(&token)
+            .await
+            .unwrap_err();
+        assert_eq!(error.message, Error::invalid_jwt().message);
+    }
+
+    #[tokio::test]
+    async fn rejects_token_with_unknown_key_id() {
+        let issuer = serve_issuer("forgejo").await;
+        let oidc_manager = get_oidc_manager(vec![issuer.clone()]);
+        let token = sign_token(&issuer, github_claims(), "unknown-key");
+
+        let error = oidc_manager
+            .validate_deployment_token(&token)
+            .await
+            .unwrap_err();
+        assert_eq!(error.message, Error::invalid_key_id().message);
+    }
+
+    #[tokio::test]
+    async fn rejects_token_with_tampered_payload() {
+        let issuer = serve_issuer("forgejo").await;
+        let oidc_manager = get_oidc_manager(vec![issuer.clone()]);
+        let token = sign_token(&issuer, github_claims(), KEY_ID);
+        let forged_token = sign_token(&issuer, json!({ "repository": "attacker/repo" }), KEY_ID);
+        let (header, signature) = token.split_once('.').unwrap();
+        let signature = signature.rsplit_once('.').unwrap().1;
+        let forged_payload = forged_token.split('.').nth(1).unwrap();
+        let tampered_token = format!("{}.{}.{}", header, forged_payload, signature);
+
+        let error = oidc_manager
+            .validate_deployment_token(&tampered_token)
+            .await
+            .unwrap_err();
+        assert_eq!(error.message, Error::invalid_jwt().message);
+    }
+
+    #[tokio::test]
+    async fn rejects_symmetric_token() {
+        let issuer = serve_issuer("forgejo").await;
+        let oidc_manager = get_oidc_manager(vec![issuer.clone()]);
+        let mut header = Header::new(Algorithm::HS256);
+        header.kid = Some(KEY_ID.to_string());
+        let token = encode(
+            &header,
+            &json!({ "iss": issuer.issuer, "aud": issuer.audience }),
+            &EncodingKey::from_secret(b"secret"),
+        )
+        .unwrap();
+
+        let error = oidc_manager
+            .validate_deployment_token(&token)
+            .await
+            .unwrap_err();
+        assert_eq!(error.message, Error::invalid_header().message);
     }
 }
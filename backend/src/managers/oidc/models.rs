@@ -1,12 +1,21 @@
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::time::{Duration, Instant};
+
+pub static GITHUB_ISSUER_NAME: &str = "github";
+pub static DEFAULT_DEPLOYMENT_AUDIENCE: &str = "kiwiDeploy";
+static JWKS_REFETCH_INTERVAL: Duration = Duration::from_secs(60);
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Jwk {
     pub kty: String,
     pub use_: Option<String>,
+    #[serde(default)]
     pub kid: String,
     pub alg: Option<String>,
+    #[serde(default)]
     pub n: String,
+    #[serde(default)]
     pub e: String,
     pub x5c: Option<Vec<String>>,
     pub x5t: Option<String>,
@@ -14,13 +23,301 @@ pub struct Jwk {
 }
 
 #[derive(Serialize, Deserialize)]
-pub struct GithubJwksResponse {
+pub struct JwksResponse {
+    pub keys: Vec<Jwk>,
+}
+
+#[derive(Default)]
+pub struct JwksCache {
     pub keys: Vec<Jwk>,
+    pub expires_at: Option<Instant>,
+    pub last_fetch_attempt: Option<Instant>,
+}
+
+impl JwksCache {
+    pub fn replace_keys(&mut self, keys: Vec<Jwk>, time_to_live: Duration) {
+        self.keys = keys;
+        self.expires_at = Some(Instant::now() + time_to_live);
+    }
+
+    pub fn needs_refresh(&self) -> bool {
+        self.expires_at
+            .is_none_or(|expires_at| expires_at <= Instant::now())
+            && self.can_refetch()
+    }
+
+    /// Rate-limits refetches, so that forged tokens can't be used to hammer the issuer.
+    pub fn can_refetch(&self) -> bool {
+        self.last_fetch_attempt
+            .is_none_or(|last_fetch_attempt| last_fetch_attempt.elapsed() >= JWKS_REFETCH_INTERVAL)
+    }
 }
 
 #[derive(Serialize, Deserialize)]
-pub struct GithubClaims {
+pub struct OpenIdConfiguration {
+    pub jwks_uri: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct OidcIssuer {
+    pub name: String,
+    pub issuer: String,
+    pub jwks_uri: Option<String>,
+    #[serde(default = "default_deployment_audience")]
+    pub audience: String,
+    #[serde(default)]
+    pub claim_mapping: ClaimMapping,
+}
+
+impl OidcIssuer {
+    pub fn github() -> Self {
+        Self {
+            name: GITHUB_ISSUER_NAME.to_string(),
+            issuer: "https://token.actions.githubusercontent.com".to_string(),
+            jwks_uri: Some(
+                "https://token.actions.githubusercontent.com/.well-known/jwks".to_string(),
+            ),
+            audience: default_deployment_audience(),
+            claim_mapping: ClaimMapping::default(),
+        }
+    }
+
+    pub fn has_valid_name(&self) -> bool {
+        !self.name.is_empty()
+            && self
+                .name
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+    }
+
+    pub fn get_jwks_discovery_uri(&self) -> String {
+        format!(
+            "{}/.well-known/openid-configuration",
+            self.issuer.trim_end_matches('/')
+        )
+    }
+}
+
+fn default_deployment_audience() -> String {
+    DEFAULT_DEPLOYMENT_AUDIENCE.to_string()
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ClaimMapping {
     pub repository: String,
-    #[serde(rename = "ref")]
+    pub repository_id: String,
     pub reference: String,
+    pub actor: String,
+    pub environment: String,
+    pub workflow: String,
+}
+
+impl Default for ClaimMapping {
+    fn default() -> Self {
+        Self {
+            repository: "repository".to_string(),
+            repository_id: "repository_id".to_string(),
+            reference: "ref".to_string(),
+            actor: "actor".to_string(),
+            environment: "environment".to_string(),
+            workflow: "workflow_ref".to_string(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct DeploymentClaims {
+    pub issuer: String,
+    pub repository: String,
+    pub repository_id: Option<String>,
+    pub reference: String,
+    pub actor: String,
+    pub environment: Option<String>,
+    pub workflow_ref: String,
+}
+
+impl DeploymentClaims {
+    pub fn from_mapped_claims(issuer: &OidcIssuer, claims: &Value) -> Self {
+        let mapping = &issuer.claim_mapping;
+        let get_claim = |name: &str| match claims.get(name) {
+            Some(Value::String(value)) => Some(value.clone()),
+            Some(Value::Number(value)) => Some(value.to_string()),
+            _ => None,
+        };
+
+        Self {
+            issuer: issuer.name.clone(),
+            repository: get_claim(&mapping.repository).unwrap_or_default(),
+            repository_id: get_claim(&mapping.repository_id).filter(|value| !value.is_empty()),
+            reference: get_claim(&mapping.reference).unwrap_or_default(),
+            actor: get_claim(&mapping.actor).unwrap_or_default(),
+            environment: get_claim(&mapping.environment).filter(|value| !value.is_empty()),
+            workflow_ref: get_claim(&mapping.workflow).unwrap_or_default(),
+        }
+    }
+
+    pub fn get_repository_identifier(&self) -> String {
+        format!(
+            "{}:{}",
+            self.issuer,
+            self.repository_id.as_ref().unwrap_or(&self.repository)
+        )
+    }
+
+    pub fn get_workflow_path(&self) -> Option<&str> {
+        let (workflow, _) = self.workflow_ref.rsplit_once('@')?;
+        let (_, path) = workflow.split_once(&format!("{}/", self.repository))?;
+        Some(path.trim_start_matches('/'))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn missing_repository_id_claim_stays_unset() {
+        let claims = json!({ "repository": "owner/repo", "repository_id": "" });
+        let deployment_claims =
+            DeploymentClaims::from_mapped_claims(&OidcIssuer::github(), &claims);
+        assert_eq!(deployment_claims.repository_id, None);
+
+        let claims = json!({ "repository": "owner/repo" });
+        let deployment_claims =
+            DeploymentClaims::from_mapped_claims(&OidcIssuer::github(), &claims);
+        assert_eq!(deployment_claims.repository_id, None);
+    }
+
+    fn gitlab_issuer() -> OidcIssuer {
+        OidcIssuer {
+            name: "gitlab".to_string(),
+            issuer: "https://gitlab.com".to_string(),
+            jwks_uri: None,
+            audience: default_deployment_audience(),
+            claim_mapping: ClaimMapping {
+                repository: "project_path".to_string(),
+                repository_id: "project_id".to_string(),
+                reference: "ref_path".to_string(),
+                actor: "user_login".to_string(),
+                environment: "environment".to_string(),
+                workflow: "ci_config_ref_uri".to_string(),
+            },
+        }
+    }
+
+    fn deployment_claims(repository: &str, workflow_ref: &str) -> DeploymentClaims {
+        DeploymentClaims {
+            issuer: GITHUB_ISSUER_NAME.to_string(),
+            repository: repository.to_string(),
+            repository_id: None,
+            reference: "refs/heads/main".to_string(),
+            actor: "octocat".to_string(),
+            environment: None,
+            workflow_ref: workflow_ref.to_string(),
+        }
+    }
+
+    #[test]
+    fn claims_are_read_through_issuer_mapping() {
+        let claims = json!({
+            "project_path": "group/project",
+            "project_id": 42,
+            "ref_path": "refs/tags/v1.0.0",
+            "user_login": "maintainer",
+            "environment": "",
+            "ci_config_ref_uri": "gitlab.com/group/project//.gitlab-ci.yml@refs/tags/v1.0.0",
+            "repository": "ignored/repo",
+        });
+        let deployment_claims = DeploymentClaims::from_mapped_claims(&gitlab_issuer(), &claims);
+        assert_eq!(deployment_claims.issuer, "gitlab");
+        assert_eq!(deployment_claims.repository, "group/project");
+        assert_eq!(deployment_claims.repository_id.as_deref(), Some("42"));
+        assert_eq!(deployment_claims.reference, "refs/tags/v1.0.0");
+        assert_eq!(deployment_claims.actor, "maintainer");
+        assert_eq!(deployment_claims.environment, None);
+        assert_eq!(
+            deployment_claims.get_workflow_path(),
+            Some(".gitlab-ci.yml")
+        );
+    }
+
+    #[test]
+    fn non_string_claims_are_ignored() {
+        let claims = json!({ "repository": ["owner/repo"], "actor": null, "ref": true });
+        let deployment_claims =
+            DeploymentClaims::from_mapped_claims(&OidcIssuer::github(), &claims);
+        assert_eq!(deployment_claims.repository, "");
+        assert_eq!(deployment_claims.actor, "");
+        assert_eq!(deployment_claims.reference, "");
+    }
+
+    #[test]
+    fn workflow_path_is_read_from_github_workflow_ref() {
+        let deployment_claims = deployment_claims(
+            "owner/repo",
+            "owner/repo/.github/workflows/deploy.yaml@refs/heads/main",
+        );
+        assert_eq!(
+            deployment_claims.get_workflow_path(),
+            Some(".github/workflows/deploy.yaml")
+        );
+    }
+
+    #[test]
+    fn workflow_path_keeps_at_signs_before_the_ref() {
+        let deployment_claims = deployment_claims(
+            "owner/repo",
+            "owner/repo/.github/workflows/deploy@v2.yaml@refs/heads/main",
+        );
+        assert_eq!(
+            deployment_claims.get_workflow_path(),
+            Some(".github/workflows/deploy@v2.yaml")
+        );
+    }
+
+    #[test]
+    fn workflow_path_requires_repository_and_ref() {
+        let other_repository = deployment_claims(
+            "owner/repo",
+            "owner/other/.github/workflows/deploy.yaml@refs/heads/main",
+        );
+        assert_eq!(other_repository.get_workflow_path(), None);
+
+        let missing_ref =
+            deployment_claims("owner/repo", "owner/repo/.github/workflows/deploy.yaml");
+        assert_eq!(missing_ref.get_workflow_path(), None);
+    }
+
+    #[test]
+    fn issuer_names_are_restricted_to_safe_characters() {
+        let issuer = |name: &str| OidcIssuer {
+            name: name.to_string(),
+            ..gitlab_issuer()
+        };
+        assert!(issuer("gitlab").has_valid_name());
+        assert!(issuer("self-hosted-2").has_valid_name());
+        assert!(!issuer("").has_valid_name());
+        assert!(!issuer("GitLab").has_valid_name());
+        assert!(!issuer("../../etc/passwd").has_valid_name());
+        assert!(!issuer("git lab").has_valid_name());
+    }
+
+    #[test]
+    fn repository_is_identified_by_id_when_known() {
+        let mut deployment_claims = deployment_claims("owner/repo", "");
+        assert_eq!(
+            deployment_claims.get_repository_identifier(),
+            "github:owner/repo"
+        );
+
+        deployment_claims.repository_id = Some("123456".to_string());
+        assert_eq!(
+            deployment_claims.get_repository_identifier(),
+            "github:123456"
+        );
+    }
 }
@@ -0,0 +1,18 @@
+use crate::error::Error;
+use axum::http::StatusCode;
+
+impl Error {
+    pub fn invalid_signing_key() -> Self {
+        Self {
+            code: StatusCode::INTERNAL_SERVER_ERROR,
+            message: "invalid oidc signing key".to_string(),
+        }
+    }
+
+    pub fn cannot_sign_token() -> Self {
+        Self {
+            code: StatusCode::INTERNAL_SERVER_ERROR,
+            message: "cannot sign token".to_string(),
+        }
+    }
+}
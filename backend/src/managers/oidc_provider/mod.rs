@@ -0,0 +1,87 @@
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use jsonwebtoken::{Algorithm, EncodingKey, Header, encode};
+use rcgen::KeyPair;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::error::Error;
+use crate::managers::oidc_provider::models::{EcJwk, JwksResponse};
+
+mod error;
+pub mod models;
+
+#[derive(Clone)]
+pub struct OidcProviderManager {
+    encoding_key: EncodingKey,
+    jwk: EcJwk,
+}
+
+impl OidcProviderManager {
+    pub fn new(signing_key_pem: &str) -> Result<Self, Error> {
+        let key_pair = KeyPair::from_pem(signing_key_pem)?;
+        let encoding_key = EncodingKey::from_ec_pem(signing_key_pem.as_bytes())
+            .map_err(|_| Error::invalid_signing_key())?;
+
+        // uncompressed P-256 point: 0x04 || x (32 bytes) || y (32 bytes)
+        let public_key = key_pair.public_key_raw();
+        if public_key.len() != 65 {
+            return Err(Error::invalid_signing_key());
+        }
+        let kid = URL_SAFE_NO_PAD.encode(&Sha256::digest(public_key)[..16]);
+
+        let jwk = EcJwk {
+            kty: "EC".to_string(),
+            crv: "P-256".to_string(),
+            use_: "sig".to_string(),
+            kid,
+            alg: "ES256".to_string(),
+            x: URL_SAFE_NO_PAD.encode(&public_key[1..33]),
+            y: URL_SAFE_NO_PAD.encode(&public_key[33..65]),
+        };
+
+        tracing::info!("oidc provider manager initialised with key {}", jwk.kid);
+
+        Ok(Self { encoding_key, jwk })
+    }
+
+    pub fn generate_signing_key() -> Result<String, Error> {
+        Ok(KeyPair::generate()?.serialize_pem())
+    }
+
+    pub fn sign<Claims: Serialize>(&self, claims: &Claims) -> Result<String, Error> {
+        self.sign_with_type(claims, "JWT")
+    }
+
+    pub fn sign_identity_assertion<Claims: Serialize>(
+        &self,
+        claims: &Claims,
+    ) -> Result<String, Error> {
+        self.sign_with_type(claims, "kiwi-identity+jwt")
+    }
+
+    pub fn jwks(&self) -> JwksResponse {
+        JwksResponse {
+            keys: vec![self.jwk.clone()],
+        }
+    }
+
+    pub fn get_issuer(domain: &str) -> String {
+        format!("https://auth.{}/api/oidc", domain)
+    }
+
+    pub fn matches_pkce_challenge(code_verifier: &str, code_challenge: &str) -> bool {
+        URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes())) == code_challenge
+    }
+
+    fn sign_with_type<Claims: Serialize>(
+        &self,
+        claims: &Claims,
+        token_type: &str,
+    ) -> Result<String, Error> {
+        let mut header = Header::new(Algorithm::ES256);
+        header.kid = Some(self.jwk.kid.clone());
+        header.typ = Some(token_type.to_string());
+        encode(&header, claims, &self.encoding_key).map_err(|_| Error::cannot_sign_token())
+    }
+}
@@ -0,0 +1,57 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct EcJwk {
+    pub kty: String,
+    pub crv: String,
+    #[serde(rename = "use")]
+    pub use_: String,
+    pub kid: String,
+    pub alg: String,
+    pub x: String,
+    pub y: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct JwksResponse {
+    pub keys: Vec<EcJwk>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct IdTokenClaims {
+    pub iss: String,
+    pub sub: String,
+    pub aud: String,
+    pub exp: i64,
+    pub iat: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
+    pub preferred_username: String,
+    pub role: String,
+    pub groups: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct IdentityAssertionClaims {
+    pub iss: String,
+    pub sub: String,
+    pub aud: String,
+    pub exp: i64,
+    pub iat: i64,
+    pub preferred_username: String,
+    pub role: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub custom_role: Option<String>,
+    pub groups: Vec<String>,
+    pub permissions: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ServiceIdentityAssertionClaims {
+    pub iss: String,
+    pub sub: String,
+    pub aud: String,
+    pub exp: i64,
+    pub iat: i64,
+    pub service: String,
+}
@@ -2,8 +2,14 @@ use std::str::FromStr;
 
 use fred::types::Expiration;
 use time::Duration;
+use urlencoding::{decode, encode};
+use uuid::Uuid;
 
-use crate::{error::Error, models::UserRole};
+use crate::{
+    error::Error,
+    managers::container::models::ServiceAccessList,
+    models::{Permission, UserAuthorisation, UserRole},
+};
 
 pub trait RedisItem: Sized {
     fn to_redis_key_suffix(&self) -> String;
@@ -178,6 +184,39 @@ impl RedisItem for RedisRefreshToken {
     }
 }
 
+pub struct RedisUserSession {
+    pub user_id: i64,
+    pub token_key: String,
+}
+
+impl RedisItem for RedisUserSession {
+    fn to_redis_key_suffix(&self) -> String {
+        format!("user_sessions:{}", self.user_id)
+    }
+
+    fn to_redis_value(&self) -> String {
+        self.token_key.clone()
+    }
+
+    fn get_expiration(&self) -> Option<Expiration> {
+        Some(Expiration::EX(Duration::days(14).whole_seconds()))
+    }
+
+    fn from_redis_key_suffix_and_value(key_suffix: String, value: String) -> Result<Self, Error> {
+        let mut consumed_key = key_suffix.clone();
+        if !consumed_key.starts_with("user_sessions:") {
+            return Err(Error::serialisation());
+        }
+        consumed_key = consumed_key[14..].to_string();
+        let user_id: i64 = consumed_key.parse().map_err(|_| Error::serialisation())?;
+
+        Ok(RedisUserSession {
+            user_id,
+            token_key: value,
+        })
+    }
+}
+
 pub struct RedisServicePort {
     pub service_name: String,
     pub port: Option<i32>,
@@ -220,6 +259,7 @@ impl RedisItem for RedisServicePort {
 pub struct RedisServiceAuthorisation {
     pub service_name: String,
     pub required_role: Option<UserRole>,
+    pub access_list: Option<ServiceAccessList>,
 }
 
 impl RedisItem for RedisServiceAuthorisation {
@@ -228,9 +268,19 @@ impl RedisItem for RedisServiceAuthorisation {
     }
 
     fn to_redis_value(&self) -> String {
-        match &self.required_role {
+        let required_role = match &self.required_role {
             Some(role) => role.to_string(),
             None => "".to_string(),
+        };
+        match &self.access_list {
+            Some(access_list) => format!(
+                "{}:{}:{}:{}",
+                required_role,
+                encode(&access_list.usernames.join(",")),
+                encode(&access_list.groups.join(",")),
+                encode(&access_list.services.join(",")),
+            ),
+            None => required_role,
         }
     }
 
@@ -246,15 +296,86 @@ impl RedisItem for RedisServiceAuthorisation {
         consumed_key = consumed_key[22..].to_string();
         let service_name = consumed_key;
 
-        let required_role = if value.is_empty() {
-            None
-        } else {
-            Some(UserRole::from_str(&value)?)
+        let values: Vec<String> = value.split(":").map(|value| value.to_string()).collect();
+
+        let required_role = match values.first() {
+            Some(role) if !role.is_empty() => Some(UserRole::from_str(role)?),
+            _ => None,
+        };
+        let access_list = match (values.get(1), values.get(2)) {
+            (Some(usernames), Some(groups)) => Some(ServiceAccessList {
+                usernames: decode_list(usernames)?,
+                groups: decode_list(groups)?,
+                services: match values.get(3) {
+                    Some(services) => decode_list(services)?,
+                    None => vec![],
+                },
+            }),
+            _ => None,
         };
 
         Ok(RedisServiceAuthorisation {
             service_name,
             required_role,
+            access_list,
+        })
+    }
+}
+
+pub struct RedisUserAuthorisation {
+    pub user_id: i64,
+    pub authorisation: UserAuthorisation,
+}
+
+impl RedisItem for RedisUserAuthorisation {
+    fn to_redis_key_suffix(&self) -> String {
+        format!("user_authorisation:{}", self.user_id)
+    }
+
+    fn to_redis_value(&self) -> String {
+        let permissions: Vec<String> = self
+            .authorisation
+            .permissions
+            .iter()
+            .map(|permission| permission.to_string())
+            .collect();
+        format!(
+            "{}:{}:{}",
+            self.authorisation.custom_role.clone().unwrap_or_default(),
+            encode(&permissions.join(",")),
+            encode(&self.authorisation.groups.join(",")),
+        )
+    }
+
+    fn get_expiration(&self) -> Option<Expiration> {
+        Some(Expiration::EX(Duration::minutes(5).whole_seconds()))
+    }
+
+    fn from_redis_key_suffix_and_value(key_suffix: String, value: String) -> Result<Self, Error> {
+        let mut consumed_key = key_suffix.clone();
+        if !consumed_key.starts_with("user_authorisation:") {
+            return Err(Error::serialisation());
+        }
+        consumed_key = consumed_key[19..].to_string();
+        let user_id: i64 = consumed_key.parse().map_err(|_| Error::serialisation())?;
+
+        let values: Vec<String> = value.split(":").map(|value| value.to_string()).collect();
+
+        let custom_role = values.first().ok_or(Error::serialisation())?.clone();
+        let permissions: Result<Vec<Permission>, Error> =
+            decode_list(values.get(1).ok_or(Error::serialisation())?)?
+                .iter()
+                .map(|permission| Permission::from_str(permission))
+                .collect();
+        let groups = decode_list(values.get(2).ok_or(Error::serialisation())?)?;
+
+        Ok(RedisUserAuthorisation {
+            user_id,
+            authorisation: UserAuthorisation {
+                custom_role: Some(custom_role).filter(|role| !role.is_empty()),
+                permissions: permissions?,
+                groups,
+            },
         })
     }
 }
@@ -280,3 +401,235 @@ impl RedisItem for RedisLastCertificateOrderUrl {
         Ok(RedisLastCertificateOrderUrl { order_url: value })
     }
 }
+
+pub struct RedisOidcAuthorisationCode {
+    pub code: String,
+    pub client_id: Uuid,
+    pub redirect_uri: String,
+    pub user_id: i64,
+    pub username: String,
+    pub role: UserRole,
+    pub nonce: Option<String>,
+    pub code_challenge: Option<String>,
+}
+
+impl RedisItem for RedisOidcAuthorisationCode {
+    fn to_redis_key_suffix(&self) -> String {
+        format!("oidc_authorisation_code:{}", self.code)
+    }
+
+    fn to_redis_value(&self) -> String {
+        format!(
+            "{}:{}:{}:{}:{}:{}:{}",
+            self.client_id,
+            encode(&self.redirect_uri),
+            self.user_id,
+            self.username,
+            self.role,
+            encode(self.nonce.as_deref().unwrap_or_default()),
+            self.code_challenge.clone().unwrap_or_default(),
+        )
+    }
+
+    fn get_expiration(&self) -> Option<Expiration> {
+        Some(Expiration::EX(Duration::minutes(1).whole_seconds()))
+    }
+
+    fn from_redis_key_suffix_and_value(key_suffix: String, value: String) -> Result<Self, Error> {
+        let mut consumed_key = key_suffix.clone();
+        if !consumed_key.starts_with("oidc_authorisation_code:") {
+            return Err(Error::serialisation());
+        }
+        consumed_key = consumed_key[24..].to_string();
+
+        let values: Vec<String> = value.split(":").map(|value| value.to_string()).collect();
+
+        let client_id = Uuid::parse_str(values.first().ok_or(Error::serialisation())?)
+            .map_err(|_| Error::serialisation())?;
+        let redirect_uri = decode(values.get(1).ok_or(Error::serialisation())?)
+            .map_err(|_| Error::serialisation())?
+            .to_string();
+        let user_id: i64 = values
+            .get(2)
+            .ok_or(Error::serialisation())?
+            .parse()
+            .map_err(|_| Error::serialisation())?;
+        let username = values.get(3).ok_or(Error::serialisation())?.clone();
+        let role = UserRole::from_str(values.get(4).ok_or(Error::serialisation())?)?;
+        let nonce = decode(values.get(5).ok_or(Error::serialisation())?)
+            .map_err(|_| Error::serialisation())?
+            .to_string();
+        let code_challenge = values.get(6).ok_or(Error::serialisation())?.clone();
+
+        Ok(RedisOidcAuthorisationCode {
+            code: consumed_key,
+            client_id,
+            redirect_uri,
+            user_id,
+            username,
+            role,
+            nonce: Some(nonce).filter(|nonce| !nonce.is_empty()),
+            code_challenge: Some(code_challenge).filter(|challenge| !challenge.is_empty()),
+        })
+    }
+}
+
+pub struct RedisOidcAccessToken {
+    pub access_token: String,
+    pub client_id: Uuid,
+    pub user_id: i64,
+    pub username: String,
+    pub role: UserRole,
+}
+
+impl RedisItem for RedisOidcAccessToken {
+    fn to_redis_key_suffix(&self) -> String {
+        format!("oidc_access_token:{}", self.access_token)
+    }
+
+    fn to_redis_value(&self) -> String {
+        format!(
+            "{}:{}:{}:{}",
+            self.client_id, self.user_id, self.username, self.role
+        )
+    }
+
+    fn get_expiration(&self) -> Option<Expiration> {
+        Some(Expiration::EX(Duration::minutes(15).whole_seconds()))
+    }
+
+    fn from_redis_key_suffix_and_value(key_suffix: String, value: String) -> Result<Self, Error> {
+        let mut consumed_key = key_suffix.clone();
+        if !consumed_key.starts_with("oidc_access_token:") {
+            return Err(Error::serialisation());
+        }
+        consumed_key = consumed_key[18..].to_string();
+
+        let values: Vec<String> = value.split(":").map(|value| value.to_string()).collect();
+
+        let client_id = Uuid::parse_str(values.first().ok_or(Error::serialisation())?)
+            .map_err(|_| Error::serialisation())?;
+        let user_id: i64 = values
+            .get(1)
+            .ok_or(Error::serialisation())?
+            .parse()
+            .map_err(|_| Error::serialisation())?;
+        let username = values.get(2).ok_or(Error::serialisation())?.clone();
+        let role = UserRole::from_str(values.get(3).ok_or(Error::serialisation())?)?;
+
+        Ok(RedisOidcAccessToken {
+            access_token: consumed_key,
+            client_id,
+            user_id,
+            username,
+            role,
+        })
+    }
+}
+
+pub struct RedisServiceAccessToken {
+    pub access_token: String,
+    pub service_name: String,
+}
+
+impl RedisItem for RedisServiceAccessToken {
+    fn to_redis_key_suffix(&self) -> String {
+        format!("service_access_token:{}", self.access_token)
+    }
+
+    fn to_redis_value(&self) -> String {
+        self.service_name.clone()
+    }
+
+    fn get_expiration(&self) -> Option<Expiration> {
+        Some(Expiration::EX(Duration::minutes(15).whole_seconds()))
+    }
+
+    fn from_redis_key_suffix_and_value(key_suffix: String, value: String) -> Result<Self, Error> {
+        let mut consumed_key = key_suffix.clone();
+        if !consumed_key.starts_with("service_access_token:") {
+            return Err(Error::serialisation());
+        }
+        consumed_key = consumed_key[21..].to_string();
+
+        Ok(RedisServiceAccessToken {
+            access_token: consumed_key,
+            service_name: value,
+        })
+    }
+}
+
+pub struct RedisTarballUpload {
+    pub upload_id: Uuid,
+    pub repository: String,
+}
+
+impl RedisItem for RedisTarballUpload {
+    fn to_redis_key_suffix(&self) -> String {
+        format!("tarball_upload:{}", self.upload_id)
+    }
+
+    fn to_redis_value(&self) -> String {
+        self.repository.clone()
+    }
+
+    fn get_expiration(&self) -> Option<Expiration> {
+        Some(Expiration::EX(Duration::days(1).whole_seconds()))
+    }
+
+    fn from_redis_key_suffix_and_value(key_suffix: String, value: String) -> Result<Self, Error> {
+        let mut consumed_key = key_suffix.clone();
+        if !consumed_key.starts_with("tarball_upload:") {
+            return Err(Error::serialisation());
+        }
+        consumed_key = consumed_key[15..].to_string();
+        let upload_id = Uuid::parse_str(&consumed_key).map_err(|_| Error::serialisation())?;
+
+        Ok(RedisTarballUpload {
+            upload_id,
+            repository: value,
+        })
+    }
+}
+
+pub struct RedisRegistryUpload {
+    pub upload_id: Uuid,
+    pub repository: String,
+}
+
+impl RedisItem for RedisRegistryUpload {
+    fn to_redis_key_suffix(&self) -> String {
+        format!("registry_upload:{}", self.upload_id)
+    }
+
+    fn to_redis_value(&self) -> String {
+        self.repository.clone()
+    }
+
+    fn get_expiration(&self) -> Option<Expiration> {
+        Some(Expiration::EX(Duration::days(1).whole_seconds()))
+    }
+
+    fn from_redis_key_suffix_and_value(key_suffix: String, value: String) -> Result<Self, Error> {
+        let mut consumed_key = key_suffix.clone();
+        if !consumed_key.starts_with("registry_upload:") {
+            return Err(Error::serialisation());
+        }
+        consumed_key = consumed_key[16..].to_string();
+        let upload_id = Uuid::parse_str(&consumed_key).map_err(|_| Error::serialisation())?;
+
+        Ok(RedisRegistryUpload {
+            upload_id,
+            repository: value,
+        })
+    }
+}
+
+fn decode_list(value: &str) -> Result<Vec<String>, Error> {
+    let decoded_value = decode(value).map_err(|_| Error::serialisation())?;
+    Ok(decoded_value
+        .split(",")
+        .filter(|item| !item.is_empty())
+        .map(|item| item.to_string())
+        .collect())
+}
@@ -1,8 +1,12 @@
-use fred::prelude::{AclInterface, KeysInterface, TransactionInterface};
+use fred::prelude::{AclInterface, KeysInterface, SetsInterface, TransactionInterface};
+use fred::types::Expiration;
 
 use crate::error::Error;
+use crate::managers::container::models::ServiceAccessList;
 use crate::managers::redis::models::{
-    RedisLastCertificateOrderUrl, RedisServiceAuthorisation, RedisServicePort,
+    RedisLastCertificateOrderUrl, RedisOidcAccessToken, RedisOidcAuthorisationCode,
+    RedisRegistryUpload, RedisServiceAccessToken, RedisServiceAuthorisation, RedisServicePort,
+    RedisTarballUpload, RedisUserAuthorisation, RedisUserSession,
 };
 use crate::managers::redis::{
     RedisManager,
@@ -11,7 +15,8 @@ use crate::managers::redis::{
         RedisRefreshTokenKind, RedisRefreshedRefreshToken,
     },
 };
-use crate::models::UserRole;
+use crate::models::{UserAuthorisation, UserRole};
+use uuid::Uuid;
 
 impl RedisManager {
     pub async fn store_active_auth_tokens(
@@ -59,6 +64,24 @@ impl RedisManager {
                 false,
             )
             .await?;
+        let user_session_item = RedisUserSession {
+            user_id,
+            token_key: String::new(),
+        };
+        let _: () = transaction
+            .sadd(
+                user_session_item.to_redis_key(),
+                vec![
+                    access_token_item.to_redis_key(),
+                    refresh_token_item.to_redis_key(),
+                ],
+            )
+            .await?;
+        if let Some(Expiration::EX(seconds)) = user_session_item.get_expiration() {
+            let _: () = transaction
+                .expire(user_session_item.to_redis_key(), seconds, None)
+                .await?;
+        }
         let _: () = transaction.exec(true).await?;
 
         Ok(())
@@ -172,6 +195,43 @@ impl RedisManager {
                 false,
             )
             .await?;
+        let user_session_item = RedisUserSession {
+            user_id,
+            token_key: String::new(),
+        };
+        let _: () = transaction
+            .sadd(
+                user_session_item.to_redis_key(),
+                vec![
+                    refreshed_refresh_token_item.to_redis_key(),
+                    access_token_item.to_redis_key(),
+                    refresh_token_item.to_redis_key(),
+                ],
+            )
+            .await?;
+        if let Some(Expiration::EX(seconds)) = user_session_item.get_expiration() {
+            let _: () = transaction
+                .expire(user_session_item.to_redis_key(), seconds, None)
+                .await?;
+        }
+        let _: () = transaction.exec(true).await?;
+
+        Ok(())
+    }
+
+    pub async fn erase_user_sessions(&self, user_id: i64) -> Result<(), Error> {
+        let user_session_item = RedisUserSession {
+            user_id,
+            token_key: String::new(),
+        };
+        let session_key = user_session_item.to_redis_key();
+        let token_keys: Vec<String> = self.client.smembers(session_key.clone()).await?;
+
+        let transaction = self.client.multi();
+        for token_key in token_keys {
+            let _: () = transaction.del(token_key).await?;
+        }
+        let _: () = transaction.del(session_key).await?;
         let _: () = transaction.exec(true).await?;
 
         Ok(())
@@ -206,6 +266,18 @@ impl RedisManager {
         Ok(())
     }
 
+    pub async fn add_user_password(&self, username: &str, password: &str) -> Result<(), Error> {
+        let rules = vec![format!(">{}", password)];
+        self.client.acl_setuser(username, rules).await?;
+        Ok(())
+    }
+
+    pub async fn remove_user_password(&self, username: &str, password: &str) -> Result<(), Error> {
+        let rules = vec![format!("<{}", password)];
+        self.client.acl_setuser(username, rules).await?;
+        Ok(())
+    }
+
     pub async fn delete_user(&self, username: &str) -> Result<(), Error> {
         let _: () = self.client.acl_deluser(username).await?;
         Ok(())
@@ -255,6 +327,7 @@ impl RedisManager {
         let item = RedisServiceAuthorisation {
             service_name: service_name.to_string(),
             required_role: None,
+            access_list: None,
         };
         let key = item.to_redis_key();
 
@@ -277,10 +350,72 @@ impl RedisManager {
         &self,
         service_name: &str,
         required_role: Option<UserRole>,
+        access_list: Option<ServiceAccessList>,
     ) -> Result<(), Error> {
         let item = RedisServiceAuthorisation {
             service_name: service_name.to_string(),
             required_role,
+            access_list,
+        };
+
+        let _: () = self
+            .client
+            .set(
+                item.to_redis_key(),
+                item.to_redis_value(),
+                item.get_expiration(),
+                None,
+                false,
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn purge_service_authorisation(&self, service_name: &str) -> Result<(), Error> {
+        let item = RedisServiceAuthorisation {
+            service_name: service_name.to_string(),
+            required_role: None,
+            access_list: None,
+        };
+
+        let _: () = self.client.del(item.to_redis_key()).await?;
+
+        Ok(())
+    }
+
+    pub async fn get_user_authorisation(
+        &self,
+        user_id: i64,
+    ) -> Result<Option<UserAuthorisation>, Error> {
+        let item = RedisUserAuthorisation {
+            user_id,
+            authorisation: UserAuthorisation {
+                custom_role: None,
+                permissions: vec![],
+                groups: vec![],
+            },
+        };
+        let key = item.to_redis_key();
+
+        let value: Option<String> = self.client.get(key.clone()).await?;
+
+        let item = if let Some(value) = value {
+            Some(RedisUserAuthorisation::from_redis_item(key, value)?.authorisation)
+        } else {
+            None
+        };
+        Ok(item)
+    }
+
+    pub async fn store_user_authorisation(
+        &self,
+        user_id: i64,
+        authorisation: UserAuthorisation,
+    ) -> Result<(), Error> {
+        let item = RedisUserAuthorisation {
+            user_id,
+            authorisation,
         };
 
         let _: () = self
@@ -297,6 +432,21 @@ impl RedisManager {
         Ok(())
     }
 
+    pub async fn purge_user_authorisation(&self, user_id: i64) -> Result<(), Error> {
+        let item = RedisUserAuthorisation {
+            user_id,
+            authorisation: UserAuthorisation {
+                custom_role: None,
+                permissions: vec![],
+                groups: vec![],
+            },
+        };
+
+        let _: () = self.client.del(item.to_redis_key()).await?;
+
+        Ok(())
+    }
+
     pub async fn purge_service_port(&self, service_name: &str) -> Result<(), Error> {
         let item = RedisServicePort {
             service_name: service_name.to_string(),
@@ -358,4 +508,226 @@ impl RedisManager {
 
         Ok(())
     }
+
+    pub async fn store_oidc_authorisation_code(
+        &self,
+        item: &RedisOidcAuthorisationCode,
+    ) -> Result<(), Error> {
+        let _: () = self
+            .client
+            .set(
+                item.to_redis_key(),
+                item.to_redis_value(),
+                item.get_expiration(),
+                None,
+                false,
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn consume_oidc_authorisation_code(
+        &self,
+        code: &str,
+    ) -> Result<Option<RedisOidcAuthorisationCode>, Error> {
+        let item = RedisOidcAuthorisationCode {
+            code: code.to_string(),
+            client_id: Uuid::nil(),
+            redirect_uri: String::new(),
+            user_id: 0,
+            username: String::new(),
+            role: UserRole::Customer,
+            nonce: None,
+            code_challenge: None,
+        };
+        let key = item.to_redis_key();
+
+        let value: Option<String> = self.client.getdel(key.clone()).await?;
+
+        let item = if let Some(value) = value {
+            Some(RedisOidcAuthorisationCode::from_redis_item(key, value)?)
+        } else {
+            None
+        };
+        Ok(item)
+    }
+
+    pub async fn store_oidc_access_token(&self, item: &RedisOidcAccessToken) -> Result<(), Error> {
+        let transaction = self.client.multi();
+        let _: () = transaction
+            .set(
+                item.to_redis_key(),
+                item.to_redis_value(),
+                item.get_expiration(),
+                None,
+                false,
+            )
+            .await?;
+        // Tracked with the user sessions so that erasing them also revokes the token
+        let user_session_item = RedisUserSession {
+            user_id: item.user_id,
+            token_key: String::new(),
+        };
+        let _: () = transaction
+            .sadd(user_session_item.to_redis_key(), item.to_redis_key())
+            .await?;
+        if let Some(Expiration::EX(seconds)) = user_session_item.get_expiration() {
+            let _: () = transaction
+                .expire(user_session_item.to_redis_key(), seconds, None)
+                .await?;
+        }
+        let _: () = transaction.exec(true).await?;
+
+        Ok(())
+    }
+
+    pub async fn get_oidc_access_token_item(
+        &self,
+        access_token: &str,
+    ) -> Result<Option<RedisOidcAccessToken>, Error> {
+        let key = RedisOidcAccessToken {
+            access_token: access_token.to_string(),
+            client_id: Uuid::nil(),
+            user_id: 0,
+            username: String::new(),
+            role: UserRole::Customer,
+        }
+        .to_redis_key();
+        let value: Option<String> = self.client.get(key.clone()).await?;
+
+        let item = if let Some(value) = value {
+            Some(RedisOidcAccessToken::from_redis_item(key, value)?)
+        } else {
+            None
+        };
+        Ok(item)
+    }
+
+    pub async fn store_service_access_token(
+        &self,
+        item: &RedisServiceAccessToken,
+    ) -> Result<(), Error> {
+        let _: () = self
+            .client
+            .set(
+                item.to_redis_key(),
+                item.to_redis_value(),
+                item.get_expiration(),
+                None,
+                false,
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn get_service_access_token_item(
+        &self,
+        access_token: &str,
+    ) -> Result<Option<RedisServiceAccessToken>, Error> {
+        let key = RedisServiceAccessToken {
+            access_token: access_token.to_string(),
+            service_name: String::new(),
+        }
+        .to_redis_key();
+        let value: Option<String> = self.client.get(key.clone()).await?;
+
+        let item = if let Some(value) = value {
+            Some(RedisServiceAccessToken::from_redis_item(key, value)?)
+        } else {
+            None
+        };
+        Ok(item)
+    }
+
+    pub async fn store_tarball_upload(&self, item: &RedisTarballUpload) -> Result<(), Error> {
+        let _: () = self
+            .client
+            .set(
+                item.to_redis_key(),
+                item.to_redis_value(),
+                item.get_expiration(),
+                None,
+                false,
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn get_tarball_upload(
+        &self,
+        upload_id: &Uuid,
+    ) -> Result<Option<RedisTarballUpload>, Error> {
+        let key = RedisTarballUpload {
+            upload_id: *upload_id,
+            repository: String::new(),
+        }
+        .to_redis_key();
+        let value: Option<String> = self.client.get(key.clone()).await?;
+
+        let item = if let Some(value) = value {
+            Some(RedisTarballUpload::from_redis_item(key, value)?)
+        } else {
+            None
+        };
+        Ok(item)
+    }
+
+    pub async fn purge_tarball_upload(&self, upload_id: &Uuid) -> Result<(), Error> {
+        let item = RedisTarballUpload {
+            upload_id: *upload_id,
+            repository: String::new(),
+        };
+
+        let _: () = self.client.del(item.to_redis_key()).await?;
+
+        Ok(())
+    }
+
+    pub async fn store_registry_upload(&self, item: &RedisRegistryUpload) -> Result<(), Error> {
+        let _: () = self
+            .client
+            .set(
+                item.to_redis_key(),
+                item.to_redis_value(),
+                item.get_expiration(),
+                None,
+                false,
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn get_registry_upload(
+        &self,
+        upload_id: &Uuid,
+    ) -> Result<Option<RedisRegistryUpload>, Error> {
+        let key = RedisRegistryUpload {
+            upload_id: *upload_id,
+            repository: String::new(),
+        }
+        .to_redis_key();
+        let value: Option<String> = self.client.get(key.clone()).await?;
+
+        let item = if let Some(value) = value {
+            Some(RedisRegistryUpload::from_redis_item(key, value)?)
+        } else {
+            None
+        };
+        Ok(item)
+    }
+
+    pub async fn purge_registry_upload(&self, upload_id: &Uuid) -> Result<(), Error> {
+        let item = RedisRegistryUpload {
+            upload_id: *upload_id,
+            repository: String::new(),
+        };
+
+        let _: () = self.client.del(item.to_redis_key()).await?;
+
+        Ok(())
+    }
 }
@@ -0,0 +1,39 @@
+use crate::error::Error;
+use axum::http::StatusCode;
+
+impl Error {
+    pub fn invalid_digest() -> Self {
+        Self {
+            code: StatusCode::BAD_REQUEST,
+            message: "invalid digest, only sha256 digests are supported".to_string(),
+        }
+    }
+
+    pub fn invalid_manifest_reference() -> Self {
+        Self {
+            code: StatusCode::BAD_REQUEST,
+            message: "manifest reference must be a tag or a sha256 digest".to_string(),
+        }
+    }
+
+    pub fn digest_mismatch() -> Self {
+        Self {
+            code: StatusCode::BAD_REQUEST,
+            message: "uploaded content does not match the digest".to_string(),
+        }
+    }
+
+    pub fn upload_not_found() -> Self {
+        Self {
+            code: StatusCode::NOT_FOUND,
+            message: "blob upload not found".to_string(),
+        }
+    }
+
+    pub fn upload_interrupted() -> Self {
+        Self {
+            code: StatusCode::BAD_REQUEST,
+            message: "blob upload was interrupted".to_string(),
+        }
+    }
+}
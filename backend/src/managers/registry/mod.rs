@@ -0,0 +1,214 @@
+use std::collections::HashSet;
+use std::fs::create_dir_all;
+use std::io::ErrorKind;
+use std::time::{Duration, SystemTime};
+
+use axum::body::Body;
+use futures::StreamExt;
+use sha2::{Digest, Sha256};
+use tokio::fs::{File, OpenOptions, metadata, read_dir, remove_file, rename};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use uuid::Uuid;
+
+use crate::error::Error;
+use crate::managers::crypto::hex_encode;
+use crate::managers::registry::models::is_valid_digest;
+use crate::settings::Settings;
+
+pub mod error;
+pub mod models;
+
+static BLOBS_FOLDER_NAME: &str = "blobs";
+static UPLOADS_FOLDER_NAME: &str = "uploads";
+static UNREFERENCED_BLOB_GRACE_PERIOD: Duration = Duration::from_secs(60 * 60);
+static ABANDONED_UPLOAD_EXPIRY: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Clone)]
+pub struct RegistryManager {
+    folder_path: String,
+}
+
+impl RegistryManager {
+    pub fn new(settings: &Settings) -> Result<Self, Error> {
+        let folder_path = settings.registry_folder_path();
+        create_dir_all(format!("{}/{}", folder_path, BLOBS_FOLDER_NAME))?;
+        create_dir_all(format!("{}/{}", folder_path, UPLOADS_FOLDER_NAME))?;
+
+        tracing::info!(
+            "registry manager initialised with storage in {}",
+            folder_path
+        );
+
+        Ok(Self { folder_path })
+    }
+
+    pub async fn get_blob_size(&self, digest: &str) -> Result<Option<u64>, Error> {
+        get_file_size(&self.get_blob_path(digest)?).await
+    }
+
+    pub async fn open_blob(&self, digest: &str) -> Result<File, Error> {
+        Ok(File::open(self.get_blob_path(digest)?).await?)
+    }
+
+    pub async fn read_blob(&self, digest: &str) -> Result<Vec<u8>, Error> {
+        let mut content = vec![];
+        self.open_blob(digest)
+            .await?
+            .read_to_end(&mut content)
+            .await?;
+        Ok(content)
+    }
+
+    pub async fn store_blob(&self, content: &[u8]) -> Result<String, Error> {
+        let digest = format!("sha256:{}", hex_encode(&Sha256::digest(content)));
+        let id = Uuid::new_v4();
+        let upload_path = self.get_upload_path(&id);
+        let mut file = File::create(&upload_path).await?;
+        file.write_all(content).await?;
+        file.flush().await?;
+        rename(upload_path, self.get_blob_path(&digest)?).await?;
+
+        Ok(digest)
+    }
+
+    pub async fn create_upload(&self) -> Result<Uuid, Error> {
+        let id = Uuid::new_v4();
+        File::create(self.get_upload_path(&id)).await?;
+        Ok(id)
+    }
+
+    pub async fn get_upload_size(&self, id: &Uuid) -> Result<Option<u64>, Error> {
+        get_file_size(&self.get_upload_path(id)).await
+    }
+
+    pub async fn append_to_upload(&self, id: &Uuid, body: Body) -> Result<u64, Error> {
+        let mut file = match OpenOptions::new()
+            .append(true)
+            .open(self.get_upload_path(id))
+            .await
+        {
+            Ok(file) => file,
+            Err(error) if error.kind() == ErrorKind::NotFound => {
+                return Err(Error::upload_not_found());
+            }
+            Err(error) => return Err(error.into()),
+        };
+
+        let mut stream = body.into_data_stream();
+        let mut stream_result = Ok(());
+        while let Some(chunk) = stream.next().await {
+            match chunk {
+                Ok(chunk) => file.write_all(&chunk).await?,
+                Err(_) => {
+                    stream_result = Err(Error::upload_interrupted());
+                    break;
+                }
+            }
+        }
+        file.flush().await?;
+        stream_result?;
+
+        Ok(file.metadata().await?.len())
+    }
+
+    pub async fn complete_upload(&self, id: &Uuid, digest: &str) -> Result<u64, Error> {
+        let blob_path = self.get_blob_path(digest)?;
+        let upload_path = self.get_upload_path(id);
+        let mut file = self.open_upload(id).await?;
+
+        let mut hasher = Sha256::new();
+        let mut buffer = vec![0u8; 1024 * 1024];
+        let mut size = 0;
+        loop {
+            let read_bytes_count = file.read(&mut buffer).await?;
+            if read_bytes_count == 0 {
+                break;
+            }
+            hasher.update(&buffer[..read_bytes_count]);
+            size += read_bytes_count as u64;
+        }
+
+        if format!("sha256:{}", hex_encode(&hasher.finalize())) != digest {
+            remove_file(&upload_path).await?;
+            return Err(Error::digest_mismatch());
+        }
+        rename(&upload_path, blob_path).await?;
+
+        Ok(size)
+    }
+
+    pub async fn open_upload(&self, id: &Uuid) -> Result<File, Error> {
+        match File::open(self.get_upload_path(id)).await {
+            Ok(file) => Ok(file),
+            Err(error) if error.kind() == ErrorKind::NotFound => Err(Error::upload_not_found()),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    pub async fn delete_upload(&self, id: &Uuid) -> Result<bool, Error> {
+        match remove_file(self.get_upload_path(id)).await {
+            Ok(()) => Ok(true),
+            Err(error) if error.kind() == ErrorKind::NotFound => Ok(false),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    /// Recent blobs are kept, as they may belong to a push whose manifest is not uploaded yet.
+    pub async fn collect_garbage(
+        &self,
+        referenced_digests: &HashSet<String>,
+    ) -> Result<(usize, usize), Error> {
+        let now = SystemTime::now();
+
+        let mut removed_blobs_count = 0;
+        let mut blobs = read_dir(format!("{}/{}", self.folder_path, BLOBS_FOLDER_NAME)).await?;
+        while let Some(blob) = blobs.next_entry().await? {
+            let digest = format!("sha256:{}", blob.file_name().to_string_lossy());
+            let age = now
+                .duration_since(blob.metadata().await?.modified()?)
+                .unwrap_or_default();
+            if !referenced_digests.contains(&digest) && age > UNREFERENCED_BLOB_GRACE_PERIOD {
+                remove_file(blob.path()).await?;
+                removed_blobs_count += 1;
+            }
+        }
+
+        let mut removed_uploads_count = 0;
+        let mut uploads = read_dir(format!("{}/{}", self.folder_path, UPLOADS_FOLDER_NAME)).await?;
+        while let Some(upload) = uploads.next_entry().await? {
+            let age = now
+                .duration_since(upload.metadata().await?.modified()?)
+                .unwrap_or_default();
+            if age > ABANDONED_UPLOAD_EXPIRY {
+                remove_file(upload.path()).await?;
+                removed_uploads_count += 1;
+            }
+        }
+
+        Ok((removed_blobs_count, removed_uploads_count))
+    }
+
+    fn get_blob_path(&self, digest: &str) -> Result<String, Error> {
+        if !is_valid_digest(digest) {
+            return Err(Error::invalid_digest());
+        }
+        let hex = digest.trim_start_matches("sha256:");
+
+        Ok(format!(
+            "{}/{}/{}",
+            self.folder_path, BLOBS_FOLDER_NAME, hex
+        ))
+    }
+
+    fn get_upload_path(&self, id: &Uuid) -> String {
+        format!("{}/{}/{}", self.folder_path, UPLOADS_FOLDER_NAME, id)
+    }
+}
+
+async fn get_file_size(path: &str) -> Result<Option<u64>, Error> {
+    match metadata(path).await {
+        Ok(metadata) => Ok(Some(metadata.len())),
+        Err(error) if error.kind() == ErrorKind::NotFound => Ok(None),
+        Err(error) => Err(error.into()),
+    }
+}
@@ -0,0 +1,70 @@
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::error::Error;
+
+pub enum ManifestReference {
+    Tag(String),
+    Digest(String),
+}
+
+impl TryFrom<&str> for ManifestReference {
+    type Error = Error;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        if is_valid_digest(value) {
+            return Ok(Self::Digest(value.to_string()));
+        }
+        let tag_regex = Regex::new(r"^[a-zA-Z0-9_][a-zA-Z0-9._-]{0,127}$")?;
+        if tag_regex.is_match(value) {
+            Ok(Self::Tag(value.to_string()))
+        } else {
+            Err(Error::invalid_manifest_reference())
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct Descriptor {
+    pub digest: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ManifestDescriptors {
+    #[serde(default)]
+    pub media_type: Option<String>,
+    #[serde(default)]
+    pub config: Option<Descriptor>,
+    #[serde(default)]
+    pub layers: Vec<Descriptor>,
+    #[serde(default)]
+    pub manifests: Vec<Descriptor>,
+}
+
+impl ManifestDescriptors {
+    pub fn referenced_digests(&self) -> Vec<String> {
+        self.config
+            .iter()
+            .chain(self.layers.iter())
+            .chain(self.manifests.iter())
+            .map(|descriptor| descriptor.digest.clone())
+            .collect()
+    }
+}
+
+pub fn is_valid_digest(value: &str) -> bool {
+    value.strip_prefix("sha256:").is_some_and(|hex| {
+        hex.len() == 64
+            && hex
+                .chars()
+                .all(|character| matches!(character, '0'..='9' | 'a'..='f'))
+    })
+}
+
+pub fn is_valid_repository_name(value: &str) -> bool {
+    let component_regex = Regex::new(r"^[a-z0-9]+((\.|_|__|-+)[a-z0-9]+)*$");
+    component_regex.is_ok_and(|component_regex| {
+        value.len() <= 255 && value.split('/').all(|part| component_regex.is_match(part))
+    })
+}
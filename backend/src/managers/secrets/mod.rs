@@ -7,7 +7,9 @@ use tokio::io::AsyncReadExt;
 use tokio::io::AsyncWriteExt;
 
 use crate::error::Error;
+use crate::managers::encryption::EncryptionManager;
 use crate::managers::secrets::models::DynamicDnsApiConfiguration;
+use crate::managers::secrets::models::EncryptedSecrets;
 use crate::managers::secrets::models::Secret;
 use crate::managers::secrets::models::Secrets;
 use crate::settings::Settings;
@@ -17,10 +19,14 @@ pub mod models;
 pub struct SecretsManager {
     secrets: Secrets,
     secrets_file_path: String,
+    encryption_manager: EncryptionManager,
 }
 
 impl SecretsManager {
-    pub async fn new_with_loaded_or_created_secrets(settings: &Settings) -> Result<Self, Error> {
+    pub async fn new_with_loaded_or_created_secrets(
+        settings: &Settings,
+        encryption_manager: EncryptionManager,
+    ) -> Result<Self, Error> {
         let secrets_file_path = settings.secrets_file_path();
         let secrets_file = File::open(&secrets_file_path).await;
 
@@ -29,7 +35,13 @@ impl SecretsManager {
                 let mut raw_text = vec![];
                 secrets_file.read_to_end(&mut raw_text).await?;
                 let raw_json = String::from_utf8(raw_text)?;
-                serde_json::from_str(&raw_json)?
+                // Secrets files written before encryption was introduced are plain JSON
+                match serde_json::from_str::<EncryptedSecrets>(&raw_json) {
+                    Ok(encrypted_secrets) => serde_json::from_str(
+                        &encryption_manager.decrypt(&encrypted_secrets.encrypted_secrets)?,
+                    )?,
+                    Err(_) => serde_json::from_str(&raw_json)?,
+                }
             }
             Err(error) if error.kind() == ErrorKind::NotFound => Secrets::default(),
             Err(error) => {
@@ -37,16 +49,34 @@ impl SecretsManager {
             }
         };
 
-        store_secrets(&secrets_file_path, &secrets).await?;
+        // Storing them right away also re-encrypts them with the current master key
+        store_secrets(&secrets_file_path, &secrets, &encryption_manager).await?;
 
         tracing::info!("secrets loaded and generated");
 
         Ok(Self {
             secrets,
             secrets_file_path,
+            encryption_manager,
         })
     }
 
+    pub fn secrets(&self) -> Secrets {
+        self.secrets.clone()
+    }
+
+    pub async fn replace_secrets(&mut self, secrets: Secrets) -> Result<(), Error> {
+        self.secrets = secrets;
+        store_secrets(
+            &self.secrets_file_path,
+            &self.secrets,
+            &self.encryption_manager,
+        )
+        .await?;
+
+        Ok(())
+    }
+
     pub fn crypto_pepper(&self) -> String {
         self.secrets.crypto_pepper.get()
     }
@@ -72,7 +102,12 @@ impl SecretsManager {
         configuration: Option<DynamicDnsApiConfiguration>,
     ) -> Result<(), Error> {
         self.secrets.dynamic_dns_api_configuration = configuration;
-        store_secrets(&self.secrets_file_path, &self.secrets).await?;
+        store_secrets(
+            &self.secrets_file_path,
+            &self.secrets,
+            &self.encryption_manager,
+        )
+        .await?;
 
         Ok(())
     }
@@ -86,19 +121,49 @@ impl SecretsManager {
 
     pub async fn set_lets_encrypt_credentials(&mut self, credentials: String) -> Result<(), Error> {
         self.secrets.lets_encrypt_credentials = Some(Secret::from_str(&credentials)?);
-        store_secrets(&self.secrets_file_path, &self.secrets).await?;
+        store_secrets(
+            &self.secrets_file_path,
+            &self.secrets,
+            &self.encryption_manager,
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    pub fn oidc_signing_key(&self) -> Option<String> {
+        self.secrets
+            .oidc_signing_key
+            .clone()
+            .map(|value| value.get())
+    }
+
+    pub async fn set_oidc_signing_key(&mut self, signing_key: String) -> Result<(), Error> {
+        self.secrets.oidc_signing_key = Some(Secret::from_str(&signing_key)?);
+        store_secrets(
+            &self.secrets_file_path,
+            &self.secrets,
+            &self.encryption_manager,
+        )
+        .await?;
 
         Ok(())
     }
 }
 
-async fn store_secrets(secrets_file_path: &String, secrets: &Secrets) -> Result<(), Error> {
+async fn store_secrets(
+    secrets_file_path: &String,
+    secrets: &Secrets,
+    encryption_manager: &EncryptionManager,
+) -> Result<(), Error> {
     let secrets_file_path_parts: Vec<&str> = secrets_file_path.split("/").collect();
     let config_folder_path = secrets_file_path_parts[..secrets_file_path_parts.len() - 1].join("/");
     create_dir_all(config_folder_path).await?;
 
     let mut secrets_file = File::create(&secrets_file_path).await?;
-    let json_string = serde_json::to_string(secrets)?;
+    let json_string = serde_json::to_string(&EncryptedSecrets {
+        encrypted_secrets: encryption_manager.encrypt(&serde_json::to_string(secrets)?)?,
+    })?;
     secrets_file.write_all(json_string.as_bytes()).await?;
     secrets_file.flush().await?;
 
@@ -65,7 +65,7 @@ pub struct DynamicDnsApiConfiguration {
     pub domain: Secret,
 }
 
-#[derive(Serialize, Deserialize, Default)]
+#[derive(Serialize, Deserialize, Default, Clone)]
 #[serde(rename_all = "camelCase")]
 #[serde(default)]
 pub struct Secrets {
@@ -75,4 +75,11 @@ pub struct Secrets {
     pub redis_admin_password: Secret,
     pub dynamic_dns_api_configuration: Option<DynamicDnsApiConfiguration>,
     pub lets_encrypt_credentials: Option<Secret>,
+    pub oidc_signing_key: Option<Secret>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EncryptedSecrets {
+    pub encrypted_secrets: String,
 }
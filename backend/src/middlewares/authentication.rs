@@ -1,21 +1,113 @@
+use std::str::FromStr;
+
 use axum::{
     extract::{Request, State},
-    http::HeaderValue,
+    http::{HeaderValue, Method, header::AUTHORIZATION},
     middleware::Next,
     response::{IntoResponse, Redirect, Response},
 };
 use axum_extra::extract::CookieJar;
+use chrono::Utc;
 use urlencoding::encode;
 
 use crate::{
-    constants::{ACCESS_TOKEN_COOKIE_NAME, KIWI_USER_ID_HEADER_NAME, KIWI_USERNAME_HEADER_NAME},
+    constants::{
+        ACCESS_TOKEN_COOKIE_NAME, KIWI_CALLING_SERVICE_HEADER_NAME,
+        KIWI_IDENTITY_ASSERTION_HEADER_NAME, KIWI_USER_CUSTOM_ROLE_HEADER_NAME,
+        KIWI_USER_GROUPS_HEADER_NAME, KIWI_USER_ID_HEADER_NAME, KIWI_USER_PERMISSIONS_HEADER_NAME,
+        KIWI_USER_ROLE_HEADER_NAME, KIWI_USERNAME_HEADER_NAME, SERVICE_ACCESS_TOKEN_PREFIX,
+    },
     error::Error,
     extractors::{Domain, DomainAndSubdomain, FullOriginalUri},
-    managers::redis::models::RedisServiceAuthorisation,
-    models::UserRole,
+    managers::{
+        container::models::ServiceAccessList,
+        oidc_provider::{
+            OidcProviderManager,
+            models::{IdentityAssertionClaims, ServiceIdentityAssertionClaims},
+        },
+        redis::models::RedisServiceAuthorisation,
+    },
+    models::{AccessLevel, AdminArea, ApiTokenCredentials, UserAuthorisation, UserRole},
     state::AppState,
 };
 
+static IDENTITY_ASSERTION_DURATION_SECONDS: i64 = 60;
+
+enum AdminRequirement {
+    Session,
+    AnyPermission,
+    Permission(AdminArea, AccessLevel),
+    FullAccess,
+}
+
+enum ServiceRequirement {
+    Admin(AdminRequirement),
+    Service {
+        required_role: Option<UserRole>,
+        access_list: Option<ServiceAccessList>,
+    },
+}
+
+impl ServiceRequirement {
+    fn requires_authentication(&self) -> bool {
+        match self {
+            Self::Admin(_) => true,
+            Self::Service {
+                required_role,
+                access_list,
+            } => required_role.is_some() || access_list.is_some(),
+        }
+    }
+
+    fn allows_service(&self, calling_service: &String) -> bool {
+        match self {
+            Self::Admin(_) => false,
+            Self::Service { access_list, .. } => {
+                !self.requires_authentication()
+                    || access_list
+                        .as_ref()
+                        .is_some_and(|access_list| access_list.allows_service(calling_service))
+            }
+        }
+    }
+
+    fn is_satisfied_by(&self, user: &AuthenticatedUser) -> bool {
+        match self {
+            Self::Admin(AdminRequirement::Session) => {
+                !user.is_api_token && user.authorisation.has_any_permission()
+            }
+            Self::Admin(AdminRequirement::AnyPermission) => user.authorisation.has_any_permission(),
+            Self::Admin(AdminRequirement::Permission(area, access_level)) => {
+                user.authorisation.has_permission(*area, *access_level)
+            }
+            Self::Admin(AdminRequirement::FullAccess) => {
+                matches!(user.role, UserRole::Admin) && user.authorisation.has_full_access()
+            }
+            Self::Service {
+                required_role,
+                access_list,
+            } => {
+                let has_role = required_role
+                    .as_ref()
+                    .is_none_or(|required_role| user.role.has_permissions(required_role));
+                let is_allowed = matches!(user.role, UserRole::Admin)
+                    || access_list.as_ref().is_none_or(|access_list| {
+                        access_list.allows(&user.username, &user.authorisation.groups)
+                    });
+                has_role && is_allowed
+            }
+        }
+    }
+}
+
+pub struct AuthenticatedUser {
+    pub user_id: i64,
+    pub username: String,
+    pub role: UserRole,
+    pub authorisation: UserAuthorisation,
+    pub is_api_token: bool,
+}
+
 pub async fn authentication_middleware(
     State(state): State<AppState>,
     cookie_jar: CookieJar,
@@ -26,30 +118,57 @@ pub async fn authentication_middleware(
     next: Next,
 ) -> Response {
     // Remove any abused auth header
-    request.headers_mut().remove(KIWI_USER_ID_HEADER_NAME);
-    request.headers_mut().remove(KIWI_USERNAME_HEADER_NAME);
+    for header_name in [
+        KIWI_USER_ID_HEADER_NAME,
+        KIWI_USERNAME_HEADER_NAME,
+        KIWI_USER_ROLE_HEADER_NAME,
+        KIWI_USER_CUSTOM_ROLE_HEADER_NAME,
+        KIWI_USER_GROUPS_HEADER_NAME,
+        KIWI_USER_PERMISSIONS_HEADER_NAME,
+        KIWI_IDENTITY_ASSERTION_HEADER_NAME,
+        KIWI_CALLING_SERVICE_HEADER_NAME,
+    ] {
+        request.headers_mut().remove(header_name);
+    }
 
     let service = request
         .uri()
         .path()
         .split("/")
         .find(|part| !part.is_empty())
-        .unwrap_or_default();
+        .unwrap_or_default()
+        .to_string();
 
-    let required_role = if service == "admin" {
-        Some(UserRole::Admin)
+    let service_requirement = if service == "admin" {
+        ServiceRequirement::Admin(get_admin_requirement(
+            request.uri().path(),
+            request.method(),
+        ))
     } else {
-        match state.redis_manager.get_service_authorisation(service).await {
+        match state
+            .redis_manager
+            .get_service_authorisation(&service)
+            .await
+        {
             Ok(Some(RedisServiceAuthorisation {
                 service_name: _,
                 required_role,
-            })) => required_role,
-            Ok(None) => match state.db_manager.get_service_data(service).await {
+                access_list,
+            })) => ServiceRequirement::Service {
+                required_role,
+                access_list,
+            },
+            Ok(None) => match state.db_manager.get_service_data(&service).await {
                 Ok(Some(service_data)) => {
                     let required_role = service_data.container_configuration.required_role;
+                    let access_list = service_data.container_configuration.access_list;
                     if let Err(error) = state
                         .redis_manager
-                        .store_service_authorisation(service, required_role.clone())
+                        .store_service_authorisation(
+                            &service,
+                            required_role.clone(),
+                            access_list.clone(),
+                        )
                         .await
                     {
                         tracing::error!(
@@ -57,9 +176,15 @@ pub async fn authentication_middleware(
                             error
                         );
                     }
-                    required_role
+                    ServiceRequirement::Service {
+                        required_role,
+                        access_list,
+                    }
                 }
-                Ok(None) => None,
+                Ok(None) => ServiceRequirement::Service {
+                    required_role: None,
+                    access_list: None,
+                },
                 Err(_) => return Error::internal_authorisation_failure().into_response(),
             },
             Err(_) => return Error::internal_authorisation_failure().into_response(),
@@ -79,35 +204,103 @@ pub async fn authentication_middleware(
         Ok(None)
     };
 
+    if service == "admin"
+        && access_token.is_none()
+        && let Some(bearer_token) = get_bearer_token(&request)
+    {
+        let authenticated_user = match authenticate_api_token(&state, &bearer_token).await {
+            Ok(Some(authenticated_user)) => authenticated_user,
+            Ok(None) => return Error::unauthorised().into_response(),
+            Err(_) => return Error::internal_authorisation_failure().into_response(),
+        };
+        if !service_requirement.is_satisfied_by(&authenticated_user) {
+            return Error::bad_permissions().into_response();
+        }
+        return forward_authenticated_request(
+            &state,
+            &domain,
+            &service,
+            authenticated_user,
+            request,
+            next,
+        )
+        .await;
+    }
+
+    if service != "admin"
+        && access_token.is_none()
+        && let Some(bearer_token) = get_bearer_token(&request)
+        && bearer_token.starts_with(SERVICE_ACCESS_TOKEN_PREFIX)
+    {
+        let calling_service = match state
+            .redis_manager
+            .get_service_access_token_item(&bearer_token)
+            .await
+        {
+            Ok(item) => item.map(|item| item.service_name),
+            Err(_) => return Error::internal_authorisation_failure().into_response(),
+        };
+        match calling_service {
+            Some(calling_service) if service_requirement.allows_service(&calling_service) => {
+                return forward_service_request(
+                    &state,
+                    &domain,
+                    &service,
+                    calling_service,
+                    request,
+                    next,
+                )
+                .await;
+            }
+            Some(_) => return Error::bad_permissions().into_response(),
+            None if service_requirement.requires_authentication() => {
+                return Error::unauthorised().into_response();
+            }
+            None => {}
+        }
+    }
+
     let original_uri = original_uri.to_string();
     let encoded_original_uri = encode(&original_uri);
 
-    match (required_role, access_token, access_token_item) {
-        (required_role, Some(_), Ok(Some(access_token_item))) => {
-            let user_id_string = access_token_item.user_id.to_string();
+    match (
+        service_requirement.requires_authentication(),
+        access_token,
+        access_token_item,
+    ) {
+        (_, Some(_), Ok(Some(access_token_item))) => {
+            let authorisation =
+                match get_user_authorisation(&state, access_token_item.user_id).await {
+                    Ok(Some(user_authorisation)) => user_authorisation,
+                    Ok(None) if !service_requirement.requires_authentication() => {
+                        return next.run(request).await;
+                    }
+                    Ok(None) => return Error::unauthorised().into_response(),
+                    Err(_) => return Error::internal_authorisation_failure().into_response(),
+                };
+            let authenticated_user = AuthenticatedUser {
+                user_id: access_token_item.user_id,
+                username: access_token_item.username,
+                role: access_token_item.role,
+                authorisation,
+                is_api_token: false,
+            };
 
-            if let Some(required_role) = required_role
-                && !access_token_item.role.has_permissions(&required_role)
-            {
+            if !service_requirement.is_satisfied_by(&authenticated_user) {
                 return Error::bad_permissions().into_response();
             }
 
-            if let (Ok(user_id_header_value), Ok(username_header_value)) = (
-                HeaderValue::from_str(&user_id_string),
-                HeaderValue::from_str(&access_token_item.username),
-            ) {
-                request
-                    .headers_mut()
-                    .append(KIWI_USER_ID_HEADER_NAME, user_id_header_value);
-                request
-                    .headers_mut()
-                    .append(KIWI_USERNAME_HEADER_NAME, username_header_value);
-                next.run(request).await
-            } else {
-                Error::serialisation().into_response()
-            }
+            forward_authenticated_request(
+                &state,
+                &domain,
+                &service,
+                authenticated_user,
+                request,
+                next,
+            )
+            .await
         }
-        (Some(_), None, Ok(_)) => {
+        (true, None, Ok(_)) => {
             let redirect_uri = format!(
                 "https://auth.{}/login?return_uri={}",
                 domain, encoded_original_uri
@@ -125,7 +318,264 @@ pub async fn authentication_middleware(
                 Redirect::temporary(&redirect_uri).into_response()
             }
         }
-        (None, None, Ok(_)) => next.run(request).await,
+        (false, None, Ok(_)) => next.run(request).await,
         (_, _, Err(error)) => error.into_response(),
     }
 }
+
+fn get_admin_requirement(path: &str, method: &Method) -> AdminRequirement {
+    let access_level = if matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS) {
+        AccessLevel::Read
+    } else {
+        AccessLevel::Write
+    };
+    let parts: Vec<&str> = path.split("/").filter(|part| !part.is_empty()).collect();
+
+    match parts.as_slice() {
+        ["admin", "api", "users", "me"] => AdminRequirement::AnyPermission,
+        ["admin", "api", "tokens", ..] => AdminRequirement::Session,
+        [
+            "admin",
+            "api",
+            "services" | "secrets" | "volumes" | "registries",
+            ..,
+        ] => AdminRequirement::Permission(AdminArea::Services, access_level),
+        // Roles grant permissions, so writing or assigning them must not let users admins
+        // give themselves more than they hold
+        ["admin", "api", "roles", ..] | ["admin", "api", "users", "custom-role"]
+            if access_level == AccessLevel::Write =>
+        {
+            AdminRequirement::FullAccess
+        }
+        ["admin", "api", "users" | "roles" | "groups", ..] => {
+            AdminRequirement::Permission(AdminArea::Users, access_level)
+        }
+        ["admin", "api", "certificates", ..] => {
+            AdminRequirement::Permission(AdminArea::Certificates, access_level)
+        }
+        ["admin", "api", "dynamic-dns", ..] => {
+            AdminRequirement::Permission(AdminArea::DynamicDns, access_level)
+        }
+        ["admin", "api", ..] => AdminRequirement::FullAccess,
+        _ => AdminRequirement::AnyPermission,
+    }
+}
+
+async fn get_user_authorisation(
+    state: &AppState,
+    user_id: i64,
+) -> Result<Option<UserAuthorisation>, Error> {
+    if let Some(user_authorisation) = state.redis_manager.get_user_authorisation(user_id).await? {
+        return Ok(Some(user_authorisation));
+    }
+
+    let user_authorisation = state.db_manager.get_user_authorisation(&user_id).await?;
+    if let Some(user_authorisation) = &user_authorisation
+        && let Err(error) = state
+            .redis_manager
+            .store_user_authorisation(user_id, user_authorisation.clone())
+            .await
+    {
+        tracing::error!(
+            "failed to store user authorisation information on Redis: {}",
+            error
+        );
+    }
+
+    Ok(user_authorisation)
+}
+
+async fn forward_authenticated_request(
+    state: &AppState,
+    domain: &str,
+    service: &str,
+    user: AuthenticatedUser,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let user_id_string = user.user_id.to_string();
+    let permissions: Vec<String> = user
+        .authorisation
+        .permissions
+        .iter()
+        .map(|permission| permission.to_string())
+        .collect();
+
+    let now = Utc::now().timestamp();
+    let identity_assertion =
+        match state
+            .oidc_provider_manager
+            .sign_identity_assertion(&IdentityAssertionClaims {
+                iss: OidcProviderManager::get_issuer(domain),
+                sub: user_id_string.clone(),
+                aud: service.to_string(),
+                exp: now + IDENTITY_ASSERTION_DURATION_SECONDS,
+                iat: now,
+                preferred_username: user.username.clone(),
+                role: user.role.to_string(),
+                custom_role: user.authorisation.custom_role.clone(),
+                groups: user.authorisation.groups.clone(),
+                permissions: permissions.clone(),
+            }) {
+            Ok(identity_assertion) => identity_assertion,
+            Err(error) => return error.into_response(),
+        };
+
+    let mut identity_headers = vec![
+        (KIWI_USER_ID_HEADER_NAME, user_id_string),
+        (KIWI_USERNAME_HEADER_NAME, user.username),
+        (KIWI_USER_ROLE_HEADER_NAME, user.role.to_string()),
+        (
+            KIWI_USER_GROUPS_HEADER_NAME,
+            user.authorisation.groups.join(","),
+        ),
+        (KIWI_USER_PERMISSIONS_HEADER_NAME, permissions.join(",")),
+        (KIWI_IDENTITY_ASSERTION_HEADER_NAME, identity_assertion),
+    ];
+    if let Some(custom_role) = user.authorisation.custom_role {
+        identity_headers.push((KIWI_USER_CUSTOM_ROLE_HEADER_NAME, custom_role));
+    }
+
+    for (header_name, header_value) in identity_headers {
+        match HeaderValue::from_str(&header_value) {
+            Ok(header_value) => {
+                request.headers_mut().append(header_name, header_value);
+            }
+            Err(_) => return Error::serialisation().into_response(),
+        }
+    }
+    next.run(request).await
+}
+
+async fn forward_service_request(
+    state: &AppState,
+    domain: &str,
+    service: &str,
+    calling_service: String,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let now = Utc::now().timestamp();
+    let identity_assertion =
+        match state
+            .oidc_provider_manager
+            .sign_identity_assertion(&ServiceIdentityAssertionClaims {
+                iss: OidcProviderManager::get_issuer(domain),
+                sub: format!("service:{}", calling_service),
+                aud: service.to_string(),
+                exp: now + IDENTITY_ASSERTION_DURATION_SECONDS,
+                iat: now,
+                service: calling_service.clone(),
+            }) {
+            Ok(identity_assertion) => identity_assertion,
+            Err(error) => return error.into_response(),
+        };
+
+    // The bearer token is only meant for Kiwi, never let the target service replay it
+    request.headers_mut().remove(AUTHORIZATION);
+    for (header_name, header_value) in [
+        (KIWI_CALLING_SERVICE_HEADER_NAME, calling_service),
+        (KIWI_IDENTITY_ASSERTION_HEADER_NAME, identity_assertion),
+    ] {
+        match HeaderValue::from_str(&header_value) {
+            Ok(header_value) => {
+                request.headers_mut().append(header_name, header_value);
+            }
+            Err(_) => return Error::serialisation().into_response(),
+        }
+    }
+    next.run(request).await
+}
+
+fn get_bearer_token(request: &Request) -> Option<String> {
+    request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(|value| value.to_string())
+}
+
+pub async fn authenticate_api_token(
+    state: &AppState,
+    bearer_token: &str,
+) -> Result<Option<AuthenticatedUser>, Error> {
+    let Ok(credentials) = ApiTokenCredentials::from_str(bearer_token) else {
+        return Ok(None);
+    };
+    let Some(api_token) = state.db_manager.get_api_token(&credentials.id).await? else {
+        return Ok(None);
+    };
+    if !state
+        .crypto_manager
+        .matches(&credentials.secret, &api_token.token_hash)
+        .unwrap_or(false)
+    {
+        return Ok(None);
+    }
+
+    let Some(user_data) = state
+        .db_manager
+        .get_user_data_from_id(&api_token.user_id)
+        .await?
+    else {
+        return Ok(None);
+    };
+    let Some(mut authorisation) = get_user_authorisation(state, user_data.id).await? else {
+        return Ok(None);
+    };
+
+    // A token can never grant more than what its owner currently holds
+    authorisation.permissions = api_token
+        .permissions
+        .into_iter()
+        .filter(|permission| authorisation.has_permission(permission.area, permission.access_level))
+        .collect();
+    state
+        .db_manager
+        .mark_api_token_as_used(&api_token.id)
+        .await?;
+
+    Ok(Some(AuthenticatedUser {
+        user_id: user_data.id,
+        username: user_data.username,
+        role: user_data.role,
+        authorisation,
+        is_api_token: true,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requires_full_access_to_write_roles() {
+        for (path, method) in [
+            ("/admin/api/roles/editor", Method::PUT),
+            ("/admin/api/roles/editor", Method::DELETE),
+            ("/admin/api/users/custom-role", Method::PUT),
+        ] {
+            assert!(matches!(
+                get_admin_requirement(path, &method),
+                AdminRequirement::FullAccess
+            ));
+        }
+    }
+
+    #[test]
+    fn requires_users_permission_for_other_users_routes() {
+        assert!(matches!(
+            get_admin_requirement("/admin/api/roles", &Method::GET),
+            AdminRequirement::Permission(AdminArea::Users, AccessLevel::Read)
+        ));
+        assert!(matches!(
+            get_admin_requirement("/admin/api/users/password-reset", &Method::POST),
+            AdminRequirement::Permission(AdminArea::Users, AccessLevel::Write)
+        ));
+        assert!(matches!(
+            get_admin_requirement("/admin/api/groups/editors", &Method::PUT),
+            AdminRequirement::Permission(AdminArea::Users, AccessLevel::Write)
+        ));
+    }
+}
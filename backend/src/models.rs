@@ -1,9 +1,16 @@
 use std::str::FromStr;
 
+use axum::body::Bytes;
+use chrono::NaiveDateTime;
+use futures::stream::BoxStream;
 use postgres_types::{FromSql, ToSql};
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
 
 use crate::error::Error;
+use crate::managers::secrets::models::Secrets;
+
+pub type ByteStream = BoxStream<'static, Result<Bytes, Error>>;
 
 #[derive(Clone, Debug, FromSql, ToSql, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -43,6 +50,176 @@ impl std::fmt::Display for UserRole {
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AdminArea {
+    Services,
+    Users,
+    Certificates,
+    DynamicDns,
+}
+
+impl AdminArea {
+    pub fn all() -> Vec<Self> {
+        vec![
+            Self::Services,
+            Self::Users,
+            Self::Certificates,
+            Self::DynamicDns,
+        ]
+    }
+}
+
+impl FromStr for AdminArea {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "services" => Ok(Self::Services),
+            "users" => Ok(Self::Users),
+            "certificates" => Ok(Self::Certificates),
+            "dynamicDns" => Ok(Self::DynamicDns),
+            _ => Err(Error::serialisation()),
+        }
+    }
+}
+
+impl std::fmt::Display for AdminArea {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Services => write!(f, "services"),
+            Self::Users => write!(f, "users"),
+            Self::Certificates => write!(f, "certificates"),
+            Self::DynamicDns => write!(f, "dynamicDns"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AccessLevel {
+    Read,
+    Write,
+}
+
+impl FromStr for AccessLevel {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "read" => Ok(Self::Read),
+            "write" => Ok(Self::Write),
+            _ => Err(Error::serialisation()),
+        }
+    }
+}
+
+impl std::fmt::Display for AccessLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Read => write!(f, "read"),
+            Self::Write => write!(f, "write"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Permission {
+    pub area: AdminArea,
+    pub access_level: AccessLevel,
+}
+
+impl Permission {
+    pub fn allows(&self, area: AdminArea, access_level: AccessLevel) -> bool {
+        self.area == area
+            && (self.access_level == AccessLevel::Write || access_level == AccessLevel::Read)
+    }
+}
+
+impl FromStr for Permission {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (area, access_level) = value.split_once(":").ok_or(Error::serialisation())?;
+        Ok(Self {
+            area: AdminArea::from_str(area)?,
+            access_level: AccessLevel::from_str(access_level)?,
+        })
+    }
+}
+
+impl std::fmt::Display for Permission {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.area, self.access_level)
+    }
+}
+
+#[derive(Clone)]
+pub struct UserAuthorisation {
+    pub custom_role: Option<String>,
+    pub permissions: Vec<Permission>,
+    pub groups: Vec<String>,
+}
+
+impl UserAuthorisation {
+    pub fn has_permission(&self, area: AdminArea, access_level: AccessLevel) -> bool {
+        self.permissions
+            .iter()
+            .any(|permission| permission.allows(area, access_level))
+    }
+
+    pub fn has_any_permission(&self) -> bool {
+        !self.permissions.is_empty()
+    }
+
+    pub fn has_full_access(&self) -> bool {
+        AdminArea::all()
+            .into_iter()
+            .all(|area| self.has_permission(area, AccessLevel::Write))
+    }
+}
+
+pub struct ApiTokenCredentials {
+    pub id: Uuid,
+    pub secret: String,
+}
+
+impl FromStr for ApiTokenCredentials {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (id, secret) = value
+            .strip_prefix("kiwi_")
+            .and_then(|value| value.split_once("_"))
+            .ok_or(Error::serialisation())?;
+        Ok(Self {
+            id: Uuid::try_parse(id).map_err(|_| Error::serialisation())?,
+            secret: secret.to_string(),
+        })
+    }
+}
+
+impl std::fmt::Display for ApiTokenCredentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "kiwi_{}_{}", self.id.simple(), self.secret)
+    }
+}
+
+/// Values encrypted in the database stay encrypted with `master_key`, the master key of the
+/// exporting instance.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InstanceBackup {
+    pub version: u32,
+    pub created_at: NaiveDateTime,
+    pub master_key: String,
+    pub secrets: Secrets,
+    pub tls_private_key: String,
+    pub tls_public_certificate: String,
+    pub volume_ids: Vec<String>,
+}
+
 pub enum ServerAction {
     RestartWithoutDependenciesInit,
     CloseDueToUnexpectedError,
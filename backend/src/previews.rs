@@ -0,0 +1,211 @@
+use crate::deployments::{
+    deploy_new_service, find_free_port, get_used_service_ports, redeploy_service, tear_down_service,
+};
+use crate::error::Error;
+use crate::managers::container::models::{
+    ContainerConfiguration, DeploymentTrigger, ExposedPort, ImageSha,
+};
+use crate::managers::db::models::{PreviewEnvironment, ServiceData};
+use crate::state::AppState;
+
+static FIRST_PREVIEW_PORT: u16 = 40000;
+static LAST_PREVIEW_PORT: u16 = 40999;
+static MAX_SERVICE_NAME_LENGTH: usize = 32;
+
+pub fn get_preview_name(parent_service_name: &str, pull_request: i32) -> String {
+    format!("pr-{}-{}", pull_request, parent_service_name)
+}
+
+pub async fn deploy_preview(
+    state: &AppState,
+    parent_service: &ServiceData,
+    pull_request: i32,
+    image_sha: ImageSha,
+) -> Result<PreviewEnvironment, Error> {
+    if pull_request <= 0 {
+        return Err(Error::invalid_pull_request());
+    }
+    let parent_configuration = &parent_service.container_configuration;
+    let name = get_preview_name(&parent_configuration.name, pull_request);
+    if name.len() > MAX_SERVICE_NAME_LENGTH {
+        return Err(Error::preview_name_too_long(&name));
+    }
+
+    let existing_service = state.db_manager.get_service_data(&name).await?;
+    let existing_preview = state.db_manager.get_preview_environment(&name).await?;
+    match (existing_service, existing_preview) {
+        (Some(service), Some(_)) => {
+            let configuration = derive_preview_configuration(
+                parent_configuration,
+                &name,
+                image_sha,
+                service.container_configuration.exposed_port.external,
+            );
+            let updated_service = state
+                .db_manager
+                .update_service(&service, &configuration)
+                .await?;
+            redeploy_service(state, &updated_service.container_configuration).await?;
+            state
+                .db_manager
+                .create_deployment(
+                    &name,
+                    &configuration.image_sha.get_value(),
+                    Some(&service.container_configuration.image_sha.get_value()),
+                    &DeploymentTrigger::Preview,
+                )
+                .await?;
+        }
+        (Some(_), None) => return Err(Error::preview_name_taken(&name)),
+        (None, _) => {
+            let used_ports = get_used_service_ports(state).await?;
+            let external_port = find_free_port(FIRST_PREVIEW_PORT..=LAST_PREVIEW_PORT, &used_ports)
+                .ok_or(Error::no_free_preview_port())?;
+            let configuration =
+                derive_preview_configuration(parent_configuration, &name, image_sha, external_port);
+            deploy_new_service(state, &configuration, DeploymentTrigger::Preview).await?;
+        }
+    }
+
+    state
+        .db_manager
+        .save_preview_environment(&name, &parent_configuration.name, pull_request)
+        .await
+}
+
+pub async fn tear_down_preview(state: &AppState, name: &str) -> Result<(), Error> {
+    tear_down_service(state, name, true).await?;
+    tracing::info!("tore down preview environment {}", name);
+
+    Ok(())
+}
+
+pub async fn tear_down_service_previews(
+    state: &AppState,
+    parent_service_name: &str,
+) -> Result<(), Error> {
+    for preview_environment in state
+        .db_manager
+        .get_service_preview_environments(parent_service_name)
+        .await?
+    {
+        tear_down_preview(state, &preview_environment.name).await?;
+    }
+
+    Ok(())
+}
+
+/// Previews run untrusted code, so they get neither the secrets, external volumes nor private
+/// network of their parent.
+fn derive_preview_configuration(
+    parent_configuration: &ContainerConfiguration,
+    name: &str,
+    image_sha: ImageSha,
+    external_port: u16,
+) -> ContainerConfiguration {
+    ContainerConfiguration {
+        name: name.to_string(),
+        image_sha,
+        exposed_port: ExposedPort {
+            internal: parent_configuration.exposed_port.internal,
+            external: external_port,
+        },
+        secrets: vec![],
+        shared_secrets: parent_configuration
+            .deploy_policy
+            .as_ref()
+            .map(|deploy_policy| deploy_policy.preview_shared_secrets.clone())
+            .unwrap_or_default(),
+        internal_secrets: vec![],
+        github_repository: None,
+        source_repository: None,
+        backup_policy: None,
+        shared_volumes: vec![],
+        bind_mounts: vec![],
+        deploy_policy: None,
+        image_update_policy: None,
+        scheduled_jobs: vec![],
+        private_network: None,
+        ..parent_configuration.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::managers::container::models::{
+        DeployPolicy, EnvironmentVariable, PrivateNetworkMembership, SharedSecretReference,
+    };
+
+    use super::*;
+
+    fn shared_secret_reference(name: &str, secret_name: &str) -> SharedSecretReference {
+        SharedSecretReference {
+            name: name.to_string(),
+            secret_name: secret_name.to_string(),
+            version: None,
+            file: None,
+        }
+    }
+
+    fn parent_configuration() -> ContainerConfiguration {
+        ContainerConfiguration {
+            name: "service".to_string(),
+            secrets: vec![EnvironmentVariable::new(
+                "API_KEY",
+                "production".to_string(),
+            )],
+            shared_secrets: vec![shared_secret_reference("PAYMENTS_KEY", "payments")],
+            private_network: Some(PrivateNetworkMembership {
+                name: "project".to_string(),
+                alias: "service".to_string(),
+            }),
+            deploy_policy: Some(DeployPolicy {
+                preview_refs: vec!["refs/pull/*/merge".to_string()],
+                preview_shared_secrets: vec![shared_secret_reference("API_KEY", "sandbox")],
+                ..Default::default()
+            }),
+            ..ContainerConfiguration::get_redis_configuration("password").unwrap()
+        }
+    }
+
+    #[test]
+    fn gives_previews_only_their_own_secrets() {
+        let parent_configuration = parent_configuration();
+        let image_sha = parent_configuration.image_sha.clone();
+        let configuration =
+            derive_preview_configuration(&parent_configuration, "pr-1-service", image_sha, 40000);
+
+        assert!(configuration.secrets.is_empty());
+        assert!(configuration.internal_secrets.is_empty());
+        let shared_secret_names: Vec<&str> = configuration
+            .shared_secrets
+            .iter()
+            .map(|reference| reference.secret_name.as_str())
+            .collect();
+        assert_eq!(shared_secret_names, ["sandbox"]);
+    }
+
+    #[test]
+    fn keeps_previews_out_of_the_private_network() {
+        let parent_configuration = parent_configuration();
+        let image_sha = parent_configuration.image_sha.clone();
+        let configuration =
+            derive_preview_configuration(&parent_configuration, "pr-1-service", image_sha, 40000);
+
+        assert!(configuration.private_network.is_none());
+    }
+
+    #[test]
+    fn gives_previews_no_secrets_without_deploy_policy() {
+        let parent_configuration = ContainerConfiguration {
+            deploy_policy: None,
+            ..parent_configuration()
+        };
+        let image_sha = parent_configuration.image_sha.clone();
+        let configuration =
+            derive_preview_configuration(&parent_configuration, "pr-1-service", image_sha, 40000);
+
+        assert!(configuration.secrets.is_empty());
+        assert!(configuration.shared_secrets.is_empty());
+    }
+}
@@ -0,0 +1,11 @@
+use crate::error::Error;
+use axum::http::StatusCode;
+
+impl Error {
+    pub fn group_member_not_found() -> Self {
+        Self {
+            code: StatusCode::NOT_FOUND,
+            message: "cannot find queried group or user".to_string(),
+        }
+    }
+}
@@ -0,0 +1,87 @@
+use axum::extract::{Path, State};
+use axum::routing::{delete, get, post, put};
+use axum::{Json, Router};
+use regex::Regex;
+
+use crate::error::Error;
+use crate::routes::admin::api::groups::models::{CreateGroupRequest, GetGroupsResponse};
+use crate::state::AppState;
+
+mod error;
+mod models;
+
+pub fn create_router() -> Router<AppState> {
+    Router::new()
+        .route("/", get(get_groups))
+        .route("/", post(create_group))
+        .route("/{name}", delete(delete_group))
+        .route("/{name}/members/{username}", put(add_group_member))
+        .route("/{name}/members/{username}", delete(remove_group_member))
+}
+
+async fn get_groups(State(state): State<AppState>) -> Result<Json<GetGroupsResponse>, Error> {
+    let groups = state.db_manager.get_groups().await?;
+
+    Ok(Json(groups))
+}
+
+async fn create_group(
+    State(state): State<AppState>,
+    Json(payload): Json<CreateGroupRequest>,
+) -> Result<(), Error> {
+    let name_regex = Regex::new(r"^[a-zA-Z0-9-_]{3,32}$")?;
+    if !name_regex.is_match(&payload.name) {
+        return Err(Error::invalid_name());
+    }
+
+    state.db_manager.create_group(&payload.name).await?;
+
+    Ok(())
+}
+
+async fn delete_group(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<(), Error> {
+    let user_ids = state.db_manager.delete_group(&name).await?;
+    for user_id in user_ids {
+        state
+            .redis_manager
+            .purge_user_authorisation(user_id)
+            .await?;
+    }
+
+    Ok(())
+}
+
+async fn add_group_member(
+    State(state): State<AppState>,
+    Path((name, username)): Path<(String, String)>,
+) -> Result<(), Error> {
+    let user_id = state.db_manager.add_group_member(&name, &username).await?;
+    if let Some(user_id) = user_id {
+        state
+            .redis_manager
+            .purge_user_authorisation(user_id)
+            .await?;
+    }
+
+    Ok(())
+}
+
+async fn remove_group_member(
+    State(state): State<AppState>,
+    Path((name, username)): Path<(String, String)>,
+) -> Result<(), Error> {
+    let user_id = state
+        .db_manager
+        .remove_group_member(&name, &username)
+        .await?
+        .ok_or(Error::group_member_not_found())?;
+    state
+        .redis_manager
+        .purge_user_authorisation(user_id)
+        .await?;
+
+    Ok(())
+}
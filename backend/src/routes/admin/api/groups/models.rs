@@ -0,0 +1,11 @@
+use serde::{Deserialize, Serialize};
+
+use crate::managers::db::models::Group;
+
+pub type GetGroupsResponse = Vec<Group>;
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateGroupRequest {
+    pub name: String,
+}
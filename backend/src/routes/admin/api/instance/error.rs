@@ -0,0 +1,11 @@
+use crate::error::Error;
+use axum::http::StatusCode;
+
+impl Error {
+    pub fn weak_instance_backup_passphrase() -> Self {
+        Self {
+            code: StatusCode::BAD_REQUEST,
+            message: "instance backup passphrase must be at least 16 characters long".to_string(),
+        }
+    }
+}
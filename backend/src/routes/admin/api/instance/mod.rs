@@ -0,0 +1,52 @@
+use axum::{
+    Json, Router,
+    body::Body,
+    extract::State,
+    http::header::{CONTENT_DISPOSITION, CONTENT_TYPE},
+    response::IntoResponse,
+    routing::post,
+};
+use chrono::Utc;
+use tokio_util::io::ReaderStream;
+
+use crate::{
+    error::Error, instance_backups::export_instance,
+    routes::admin::api::instance::models::ExportInstanceRequest, state::AppState,
+};
+
+mod error;
+mod models;
+
+static MIN_PASSPHRASE_LENGTH: usize = 16;
+
+pub fn create_router() -> Router<AppState> {
+    Router::new().route("/export", post(export))
+}
+
+async fn export(
+    State(state): State<AppState>,
+    Json(payload): Json<ExportInstanceRequest>,
+) -> Result<impl IntoResponse, Error> {
+    if payload.passphrase.chars().count() < MIN_PASSPHRASE_LENGTH {
+        return Err(Error::weak_instance_backup_passphrase());
+    }
+
+    let instance_backup = export_instance(
+        &state,
+        &payload.passphrase,
+        state.backup_manager.folder_path(),
+    )
+    .await?;
+    let content_disposition = format!(
+        "attachment; filename=\"kiwi-{}.backup\"",
+        Utc::now().format("%Y%m%dT%H%M%S")
+    );
+
+    Ok((
+        [
+            (CONTENT_TYPE, "application/octet-stream".to_string()),
+            (CONTENT_DISPOSITION, content_disposition),
+        ],
+        Body::from_stream(ReaderStream::new(instance_backup)),
+    ))
+}
@@ -4,13 +4,27 @@ use crate::state::AppState;
 
 mod certificates;
 mod dynamic_dns;
+mod groups;
+mod instance;
+mod registries;
+mod roles;
+mod secrets;
 mod services;
+mod tokens;
 mod users;
+mod volumes;
 
 pub fn create_router() -> Router<AppState> {
     Router::new()
         .nest("/dynamic-dns", dynamic_dns::create_router())
         .nest("/services", services::create_router())
+        .nest("/secrets", secrets::create_router())
+        .nest("/volumes", volumes::create_router())
+        .nest("/registries", registries::create_router())
         .nest("/certificates", certificates::create_router())
         .nest("/users", users::create_router())
+        .nest("/roles", roles::create_router())
+        .nest("/groups", groups::create_router())
+        .nest("/tokens", tokens::create_router())
+        .nest("/instance", instance::create_router())
 }
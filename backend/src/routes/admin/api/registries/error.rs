@@ -0,0 +1,18 @@
+use crate::error::Error;
+use axum::http::StatusCode;
+
+impl Error {
+    pub fn invalid_registry_host() -> Self {
+        Self {
+            code: StatusCode::BAD_REQUEST,
+            message: "invalid registry host".to_string(),
+        }
+    }
+
+    pub fn registry_credential_not_found() -> Self {
+        Self {
+            code: StatusCode::NOT_FOUND,
+            message: "registry credentials not found".to_string(),
+        }
+    }
+}
@@ -0,0 +1,64 @@
+use axum::extract::{Path, State};
+use axum::routing::{delete, get, put};
+use axum::{Json, Router};
+use regex::Regex;
+
+use crate::error::Error;
+use crate::managers::container::models::normalise_registry_host;
+use crate::routes::admin::api::registries::models::{
+    GetRegistryCredentialsResponse, UpdateRegistryCredentialRequest,
+    UpdateRegistryCredentialResponse,
+};
+use crate::state::AppState;
+
+mod error;
+mod models;
+
+pub fn create_router() -> Router<AppState> {
+    Router::new()
+        .route("/", get(get_registry_credentials))
+        .route("/{registry}", put(update_registry_credential))
+        .route("/{registry}", delete(delete_registry_credential))
+}
+
+async fn get_registry_credentials(
+    State(state): State<AppState>,
+) -> Result<Json<GetRegistryCredentialsResponse>, Error> {
+    let registry_credentials = state.db_manager.get_registry_credentials().await?;
+
+    Ok(Json(registry_credentials))
+}
+
+async fn update_registry_credential(
+    State(state): State<AppState>,
+    Path(registry): Path<String>,
+    Json(payload): Json<UpdateRegistryCredentialRequest>,
+) -> Result<Json<UpdateRegistryCredentialResponse>, Error> {
+    let registry = normalise_registry_host(&registry);
+    let registry_regex = Regex::new(r"^[a-z0-9]([a-z0-9.-]*[a-z0-9])?(:[0-9]{1,5})?$")?;
+    if !registry_regex.is_match(&registry) {
+        return Err(Error::invalid_registry_host());
+    }
+
+    let registry_credential = state
+        .db_manager
+        .upsert_registry_credential(&registry, &payload.username, &payload.password)
+        .await?;
+
+    Ok(Json(registry_credential))
+}
+
+async fn delete_registry_credential(
+    State(state): State<AppState>,
+    Path(registry): Path<String>,
+) -> Result<(), Error> {
+    let is_deleted = state
+        .db_manager
+        .delete_registry_credential(&normalise_registry_host(&registry))
+        .await?;
+    if !is_deleted {
+        return Err(Error::registry_credential_not_found());
+    }
+
+    Ok(())
+}
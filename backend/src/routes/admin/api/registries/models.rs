@@ -0,0 +1,14 @@
+use serde::{Deserialize, Serialize};
+
+use crate::managers::db::models::RegistryCredential;
+
+pub type GetRegistryCredentialsResponse = Vec<RegistryCredential>;
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateRegistryCredentialRequest {
+    pub username: String,
+    pub password: String,
+}
+
+pub type UpdateRegistryCredentialResponse = RegistryCredential;
@@ -0,0 +1,59 @@
+use axum::extract::{Path, State};
+use axum::routing::{delete, get, put};
+use axum::{Json, Router};
+use regex::Regex;
+
+use crate::error::Error;
+use crate::routes::admin::api::roles::models::{GetRolesResponse, UpsertRoleRequest};
+use crate::state::AppState;
+
+mod models;
+
+pub fn create_router() -> Router<AppState> {
+    Router::new()
+        .route("/", get(get_roles))
+        .route("/{name}", put(upsert_role))
+        .route("/{name}", delete(delete_role))
+}
+
+async fn get_roles(State(state): State<AppState>) -> Result<Json<GetRolesResponse>, Error> {
+    let roles = state.db_manager.get_custom_roles().await?;
+
+    Ok(Json(roles))
+}
+
+async fn upsert_role(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Json(payload): Json<UpsertRoleRequest>,
+) -> Result<(), Error> {
+    let name_regex = Regex::new(r"^[a-zA-Z0-9-_]{3,32}$")?;
+    if !name_regex.is_match(&name) {
+        return Err(Error::invalid_name());
+    }
+
+    let user_ids = state
+        .db_manager
+        .upsert_custom_role(&name, &payload.permissions)
+        .await?;
+    for user_id in user_ids {
+        state
+            .redis_manager
+            .purge_user_authorisation(user_id)
+            .await?;
+    }
+
+    Ok(())
+}
+
+async fn delete_role(State(state): State<AppState>, Path(name): Path<String>) -> Result<(), Error> {
+    let user_ids = state.db_manager.delete_custom_role(&name).await?;
+    for user_id in user_ids {
+        state
+            .redis_manager
+            .purge_user_authorisation(user_id)
+            .await?;
+    }
+
+    Ok(())
+}
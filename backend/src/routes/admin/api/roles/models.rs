@@ -0,0 +1,12 @@
+use serde::{Deserialize, Serialize};
+
+use crate::managers::db::models::CustomRole;
+use crate::models::Permission;
+
+pub type GetRolesResponse = Vec<CustomRole>;
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpsertRoleRequest {
+    pub permissions: Vec<Permission>,
+}
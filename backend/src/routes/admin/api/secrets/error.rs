@@ -0,0 +1,11 @@
+use crate::error::Error;
+use axum::http::StatusCode;
+
+impl Error {
+    pub fn shared_secret_in_use(services: &[String]) -> Self {
+        Self {
+            code: StatusCode::CONFLICT,
+            message: format!("shared secret used by {}", services.join(", ")),
+        }
+    }
+}
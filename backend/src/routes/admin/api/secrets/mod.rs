@@ -0,0 +1,100 @@
+use axum::extract::{Path, State};
+use axum::routing::{delete, get, put};
+use axum::{Json, Router};
+use regex::Regex;
+
+use crate::deployments::redeploy_service;
+use crate::error::Error;
+use crate::routes::admin::api::secrets::models::{
+    GetSharedSecretsResponse, UpdateSharedSecretRequest, UpdateSharedSecretResponse,
+};
+use crate::state::AppState;
+
+mod error;
+mod models;
+
+pub fn create_router() -> Router<AppState> {
+    Router::new()
+        .route("/", get(get_shared_secrets))
+        .route("/{name}", put(update_shared_secret))
+        .route("/{name}", delete(delete_shared_secret))
+}
+
+async fn get_shared_secrets(
+    State(state): State<AppState>,
+) -> Result<Json<GetSharedSecretsResponse>, Error> {
+    let shared_secrets = state.db_manager.get_shared_secrets().await?;
+
+    Ok(Json(shared_secrets))
+}
+
+async fn update_shared_secret(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Json(payload): Json<UpdateSharedSecretRequest>,
+) -> Result<Json<UpdateSharedSecretResponse>, Error> {
+    let name_regex = Regex::new(r"^[a-zA-Z0-9-_]{3,32}$")?;
+    if !name_regex.is_match(&name) {
+        return Err(Error::invalid_name());
+    }
+
+    let shared_secret = state
+        .db_manager
+        .create_shared_secret_version(&name, &payload.value)
+        .await?;
+    let dependent_services = state
+        .db_manager
+        .get_shared_secret_dependent_services(&name)
+        .await?;
+
+    let mut redeployed_services = vec![];
+    if payload.redeploy {
+        for service_name in dependent_services.iter() {
+            let Some(service) = state.db_manager.get_service_data(service_name).await? else {
+                continue;
+            };
+            let follows_latest_version = service
+                .container_configuration
+                .shared_secrets
+                .iter()
+                .any(|reference| reference.follows_latest_version_of(&name));
+            if !follows_latest_version {
+                continue;
+            }
+
+            redeploy_service(&state, &service.container_configuration).await?;
+            tracing::info!(
+                "redeployed service {} after update of shared secret {}",
+                service_name,
+                name
+            );
+            redeployed_services.push(service_name.clone());
+        }
+    }
+
+    Ok(Json(UpdateSharedSecretResponse {
+        version: shared_secret.version,
+        dependent_services,
+        redeployed_services,
+    }))
+}
+
+async fn delete_shared_secret(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<(), Error> {
+    let dependent_services = state
+        .db_manager
+        .get_shared_secret_dependent_services(&name)
+        .await?;
+    if !dependent_services.is_empty() {
+        return Err(Error::shared_secret_in_use(&dependent_services));
+    }
+
+    let is_deleted = state.db_manager.delete_shared_secret(&name).await?;
+    if !is_deleted {
+        return Err(Error::shared_secret_not_found(&name));
+    }
+
+    Ok(())
+}
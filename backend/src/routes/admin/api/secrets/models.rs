@@ -0,0 +1,21 @@
+use serde::{Deserialize, Serialize};
+
+use crate::managers::db::models::SharedSecret;
+
+pub type GetSharedSecretsResponse = Vec<SharedSecret>;
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateSharedSecretRequest {
+    pub value: String,
+    #[serde(default)]
+    pub redeploy: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateSharedSecretResponse {
+    pub version: i32,
+    pub dependent_services: Vec<String>,
+    pub redeployed_services: Vec<String>,
+}
@@ -36,4 +36,185 @@ impl Error {
             message: "service port cannot be changed".to_string(),
         }
     }
+
+    pub fn invalid_redirect_uri(uri: &str) -> Self {
+        Self {
+            code: StatusCode::BAD_REQUEST,
+            message: format!("invalid redirect uri {}", uri),
+        }
+    }
+
+    pub fn invalid_secret_file(path: &str) -> Self {
+        Self {
+            code: StatusCode::BAD_REQUEST,
+            message: format!("invalid secret file {}", path),
+        }
+    }
+
+    pub fn invalid_shared_secret_reference(name: &str) -> Self {
+        Self {
+            code: StatusCode::BAD_REQUEST,
+            message: format!("invalid shared secret reference {}", name),
+        }
+    }
+
+    pub fn invalid_backup_policy() -> Self {
+        Self {
+            code: StatusCode::BAD_REQUEST,
+            message: "backup interval and retention count must be positive".to_string(),
+        }
+    }
+
+    pub fn invalid_deploy_policy() -> Self {
+        Self {
+            code: StatusCode::BAD_REQUEST,
+            message: "deploy policy patterns cannot be empty and repository id must be numeric"
+                .to_string(),
+        }
+    }
+
+    pub fn invalid_source_repository() -> Self {
+        Self {
+            code: StatusCode::BAD_REQUEST,
+            message: "source repository must be a valid path on a trusted oidc issuer".to_string(),
+        }
+    }
+
+    pub fn backup_not_found() -> Self {
+        Self {
+            code: StatusCode::NOT_FOUND,
+            message: "backup not found".to_string(),
+        }
+    }
+
+    pub fn volume_not_found(path: &str) -> Self {
+        Self {
+            code: StatusCode::NOT_FOUND,
+            message: format!("volume {} not found", path),
+        }
+    }
+
+    pub fn volume_snapshot_not_found() -> Self {
+        Self {
+            code: StatusCode::NOT_FOUND,
+            message: "volume snapshot not found".to_string(),
+        }
+    }
+
+    pub fn deleted_volume_not_found() -> Self {
+        Self {
+            code: StatusCode::NOT_FOUND,
+            message: "deleted volume not found".to_string(),
+        }
+    }
+
+    pub fn invalid_shared_volume_mount(name: &str) -> Self {
+        Self {
+            code: StatusCode::BAD_REQUEST,
+            message: format!("shared volume {} does not exist", name),
+        }
+    }
+
+    pub fn invalid_mount_path(path: &str) -> Self {
+        Self {
+            code: StatusCode::BAD_REQUEST,
+            message: format!("invalid or duplicate mount path {}", path),
+        }
+    }
+
+    pub fn invalid_image_update_policy() -> Self {
+        Self {
+            code: StatusCode::BAD_REQUEST,
+            message: "image update policy needs an image name, a valid tag and a positive interval"
+                .to_string(),
+        }
+    }
+
+    pub fn image_update_policy_not_set() -> Self {
+        Self {
+            code: StatusCode::BAD_REQUEST,
+            message: "service does not follow an image tag".to_string(),
+        }
+    }
+
+    pub fn no_free_rotation_port() -> Self {
+        Self {
+            code: StatusCode::SERVICE_UNAVAILABLE,
+            message: "no free port is left to redeploy the service with rotated credentials"
+                .to_string(),
+        }
+    }
+
+    pub fn tracked_image_not_found() -> Self {
+        Self {
+            code: StatusCode::NOT_FOUND,
+            message: "image tag followed by the service was not resolved yet".to_string(),
+        }
+    }
+
+    pub fn invalid_private_network() -> Self {
+        Self {
+            code: StatusCode::BAD_REQUEST,
+            message: "invalid private network name or alias".to_string(),
+        }
+    }
+
+    pub fn invalid_compose_file(message: &str) -> Self {
+        Self {
+            code: StatusCode::BAD_REQUEST,
+            message: format!("invalid compose file: {}", message),
+        }
+    }
+
+    pub fn no_free_compose_port() -> Self {
+        Self {
+            code: StatusCode::SERVICE_UNAVAILABLE,
+            message: "no free port is left for imported services".to_string(),
+        }
+    }
+
+    pub fn service_already_exists(name: &str) -> Self {
+        Self {
+            code: StatusCode::CONFLICT,
+            message: format!("service {} already exists", name),
+        }
+    }
+
+    pub fn invalid_sidecar(name: &str) -> Self {
+        Self {
+            code: StatusCode::BAD_REQUEST,
+            message: format!("invalid or duplicate sidecar {}", name),
+        }
+    }
+
+    pub fn sidecar_not_found(name: &str) -> Self {
+        Self {
+            code: StatusCode::NOT_FOUND,
+            message: format!("sidecar {} not found", name),
+        }
+    }
+
+    pub fn invalid_scheduled_job(name: &str) -> Self {
+        Self {
+            code: StatusCode::BAD_REQUEST,
+            message: format!(
+                "invalid or duplicate scheduled job {}, schedules are five fields cron expressions",
+                name
+            ),
+        }
+    }
+
+    pub fn scheduled_job_not_found(name: &str) -> Self {
+        Self {
+            code: StatusCode::NOT_FOUND,
+            message: format!("scheduled job {} not found", name),
+        }
+    }
+
+    pub fn job_run_not_found() -> Self {
+        Self {
+            code: StatusCode::NOT_FOUND,
+            message: "job run not found".to_string(),
+        }
+    }
 }
@@ -1,7 +1,16 @@
 use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
 
-use crate::managers::{container::models::Log, db::models::ServiceData};
+use uuid::Uuid;
+
+use crate::compose_import::UnsupportedComposeFeature;
+use crate::managers::{
+    container::models::{ContainerConfiguration, Log},
+    db::models::{
+        Backup, DeletedVolume, Deployment, JobRun, OidcClient, PreviewEnvironment, ServiceData,
+        TrackedImageTag, VolumeSnapshot,
+    },
+};
 
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -21,6 +30,96 @@ pub struct GetServiceResponse {
 pub struct GetLogsQuery {
     pub from_date: NaiveDateTime,
     pub to_date: NaiveDateTime,
+    pub sidecar: Option<String>,
 }
 
 pub type GetLogsResponse = Vec<Log>;
+
+pub type GetOidcClientsResponse = Vec<OidcClient>;
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateOidcClientRequest {
+    pub redirect_uris: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateOidcClientResponse {
+    pub client_id: Uuid,
+    pub client_secret: String,
+}
+
+pub type GetBackupsResponse = Vec<Backup>;
+
+pub type CreateBackupResponse = Backup;
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RestoreBackupResponse {
+    pub pre_restore_backup: Backup,
+}
+
+pub type GetVolumeSnapshotsResponse = Vec<VolumeSnapshot>;
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateVolumeSnapshotRequest {
+    pub volume_path: String,
+}
+
+pub type CreateVolumeSnapshotResponse = VolumeSnapshot;
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeleteServiceQuery {
+    #[serde(default)]
+    pub delete_backups: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportVolumeSnapshotQuery {
+    pub volume_path: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RestoreVolumeSnapshotResponse {
+    pub pre_restore_snapshot: VolumeSnapshot,
+}
+
+pub type GetDeletedVolumesResponse = Vec<DeletedVolume>;
+
+pub type GetPreviewEnvironmentsResponse = Vec<PreviewEnvironment>;
+
+pub type GetDeploymentsResponse = Vec<Deployment>;
+
+pub type GetTrackedImageTagResponse = TrackedImageTag;
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportComposeFileQuery {
+    pub project_name: Option<String>,
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportComposeFileResponse {
+    pub services: Vec<ContainerConfiguration>,
+    pub shared_volumes: Vec<String>,
+    pub unsupported_features: Vec<UnsupportedComposeFeature>,
+    pub created: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetJobRunsQuery {
+    pub job_name: Option<String>,
+}
+
+pub type GetJobRunsResponse = Vec<JobRun>;
+
+pub type RunScheduledJobResponse = JobRun;
@@ -0,0 +1,32 @@
+use crate::error::Error;
+use axum::http::StatusCode;
+
+impl Error {
+    pub fn invalid_api_token_name() -> Self {
+        Self {
+            code: StatusCode::BAD_REQUEST,
+            message: "api token name must be between 1 and 64 characters".to_string(),
+        }
+    }
+
+    pub fn invalid_api_token_expiration() -> Self {
+        Self {
+            code: StatusCode::BAD_REQUEST,
+            message: "api token must expire within 1 to 365 days".to_string(),
+        }
+    }
+
+    pub fn excessive_api_token_permissions() -> Self {
+        Self {
+            code: StatusCode::FORBIDDEN,
+            message: "api token cannot grant permissions you do not hold".to_string(),
+        }
+    }
+
+    pub fn api_token_not_found() -> Self {
+        Self {
+            code: StatusCode::NOT_FOUND,
+            message: "cannot find queried api token".to_string(),
+        }
+    }
+}
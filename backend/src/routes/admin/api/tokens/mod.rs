@@ -0,0 +1,105 @@
+use axum::extract::{Path, State};
+use axum::http::HeaderMap;
+use axum::routing::{delete, get, post};
+use axum::{Json, Router};
+use chrono::{Duration, Utc};
+use uuid::Uuid;
+
+use crate::constants::KIWI_USER_ID_HEADER_NAME;
+use crate::error::Error;
+use crate::managers::secrets::models::Secret;
+use crate::models::ApiTokenCredentials;
+use crate::routes::admin::api::tokens::models::{
+    CreateApiTokenRequest, CreateApiTokenResponse, GetApiTokensResponse,
+};
+use crate::state::AppState;
+
+mod error;
+mod models;
+
+pub fn create_router() -> Router<AppState> {
+    Router::new()
+        .route("/", get(get_api_tokens))
+        .route("/", post(create_api_token))
+        .route("/{id}", delete(delete_api_token))
+}
+
+async fn get_api_tokens(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<GetApiTokensResponse>, Error> {
+    let user_id = get_current_user_id(&headers)?;
+    let api_tokens = state.db_manager.get_user_api_tokens(&user_id).await?;
+
+    Ok(Json(api_tokens))
+}
+
+async fn create_api_token(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(payload): Json<CreateApiTokenRequest>,
+) -> Result<Json<CreateApiTokenResponse>, Error> {
+    let user_id = get_current_user_id(&headers)?;
+    if payload.name.trim().is_empty() || payload.name.len() > 64 {
+        return Err(Error::invalid_api_token_name());
+    }
+    if !(1..=365).contains(&payload.expires_in_days) {
+        return Err(Error::invalid_api_token_expiration());
+    }
+
+    let user_authorisation = state
+        .db_manager
+        .get_user_authorisation(&user_id)
+        .await?
+        .ok_or(Error::unauthorised())?;
+    if payload.permissions.iter().any(|permission| {
+        !user_authorisation.has_permission(permission.area, permission.access_level)
+    }) {
+        return Err(Error::excessive_api_token_permissions());
+    }
+
+    let secret = Secret::default().get();
+    let token_hash = state.crypto_manager.generate_hash(&secret)?;
+    let expires_at = (Utc::now() + Duration::days(payload.expires_in_days)).naive_utc();
+    let api_token = state
+        .db_manager
+        .create_api_token(
+            &user_id,
+            &payload.name,
+            &token_hash,
+            &payload.permissions,
+            &expires_at,
+        )
+        .await?;
+
+    Ok(Json(CreateApiTokenResponse {
+        id: api_token.id,
+        token: ApiTokenCredentials {
+            id: api_token.id,
+            secret,
+        }
+        .to_string(),
+        expires_at: api_token.expires_at,
+    }))
+}
+
+async fn delete_api_token(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<Uuid>,
+) -> Result<(), Error> {
+    let user_id = get_current_user_id(&headers)?;
+    if !state.db_manager.delete_api_token(&user_id, &id).await? {
+        return Err(Error::api_token_not_found());
+    }
+
+    Ok(())
+}
+
+fn get_current_user_id(headers: &HeaderMap) -> Result<i64, Error> {
+    headers
+        .get(KIWI_USER_ID_HEADER_NAME)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<i64>().ok())
+        .ok_or(Error::serialisation())
+}
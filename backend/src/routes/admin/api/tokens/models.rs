@@ -0,0 +1,24 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::managers::db::models::ApiToken;
+use crate::models::Permission;
+
+pub type GetApiTokensResponse = Vec<ApiToken>;
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateApiTokenRequest {
+    pub name: String,
+    pub permissions: Vec<Permission>,
+    pub expires_in_days: i64,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateApiTokenResponse {
+    pub id: Uuid,
+    pub token: String,
+    pub expires_at: NaiveDateTime,
+}
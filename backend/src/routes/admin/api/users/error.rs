@@ -8,4 +8,18 @@ impl Error {
             message: "cannot delete active user".to_string(),
         }
     }
+
+    pub fn user_not_found() -> Self {
+        Self {
+            code: StatusCode::NOT_FOUND,
+            message: "cannot find queried user".to_string(),
+        }
+    }
+
+    pub fn custom_role_not_found() -> Self {
+        Self {
+            code: StatusCode::NOT_FOUND,
+            message: "cannot find queried custom role".to_string(),
+        }
+    }
 }
@@ -1,14 +1,20 @@
+use std::str::FromStr;
+
 use axum::extract::State;
 use axum::http::HeaderMap;
-use axum::routing::{delete, get, post};
+use axum::routing::{delete, get, post, put};
 use axum::{Json, Router};
 
-use crate::constants::KIWI_USER_ID_HEADER_NAME;
+use crate::constants::{
+    KIWI_USER_ID_HEADER_NAME, KIWI_USER_PERMISSIONS_HEADER_NAME, KIWI_USER_ROLE_HEADER_NAME,
+};
 use crate::error::Error;
 use crate::managers::db::DbManager;
+use crate::models::{Permission, UserAuthorisation, UserRole};
 use crate::routes::admin::api::users::models::{
-    CreateUserInvitationRequest, CreateUserInvitationResponse, DeleteUserRequest, GetMeResponse,
-    GetUsersResponse, User,
+    CreatePasswordResetRequest, CreatePasswordResetResponse, CreateUserInvitationRequest,
+    CreateUserInvitationResponse, DeleteUserRequest, GetMeResponse, GetUsersResponse,
+    SetCustomRoleRequest, User,
 };
 use crate::state::AppState;
 
@@ -21,6 +27,8 @@ pub fn create_router() -> Router<AppState> {
         .route("/", post(create_user_invitation))
         .route("/", delete(delete_user))
         .route("/me", get(get_me))
+        .route("/password-reset", post(create_password_reset))
+        .route("/custom-role", put(set_custom_role))
 }
 
 async fn get_users(State(state): State<AppState>) -> Result<Json<GetUsersResponse>, Error> {
@@ -30,6 +38,7 @@ async fn get_users(State(state): State<AppState>) -> Result<Json<GetUsersRespons
         .map(|user_data| User {
             username: user_data.username,
             role: user_data.role,
+            custom_role: user_data.custom_role,
         })
         .collect();
 
@@ -45,7 +54,16 @@ async fn delete_user(
     if user.username == payload.username {
         Err(Error::cannot_delete_active_user())
     } else {
+        let user_data = state
+            .db_manager
+            .get_user_data(&payload.username)
+            .await?
+            .ok_or(Error::user_not_found())?;
         state.db_manager.delete_user(&payload.username).await?;
+        state
+            .redis_manager
+            .purge_user_authorisation(user_data.id)
+            .await?;
         Ok(())
     }
 }
@@ -61,8 +79,10 @@ async fn get_me(
 
 async fn create_user_invitation(
     State(state): State<AppState>,
+    headers: HeaderMap,
     Json(payload): Json<CreateUserInvitationRequest>,
 ) -> Result<Json<CreateUserInvitationResponse>, Error> {
+    authorise_admin_target(&headers, &payload.role)?;
     let user_invitation = state
         .db_manager
         .create_user_invitation(payload.role)
@@ -73,6 +93,86 @@ async fn create_user_invitation(
     }))
 }
 
+async fn create_password_reset(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(payload): Json<CreatePasswordResetRequest>,
+) -> Result<Json<CreatePasswordResetResponse>, Error> {
+    let user_data = state
+        .db_manager
+        .get_user_data(&payload.username)
+        .await?
+        .ok_or(Error::user_not_found())?;
+    authorise_admin_target(&headers, &user_data.role)?;
+
+    let password_reset = state
+        .db_manager
+        .create_user_password_reset(&payload.username)
+        .await?
+        .ok_or(Error::user_not_found())?;
+    state
+        .redis_manager
+        .erase_user_sessions(password_reset.user_id)
+        .await?;
+    state
+        .db_manager
+        .delete_user_api_tokens(&password_reset.user_id)
+        .await?;
+
+    Ok(Json(CreatePasswordResetResponse {
+        password_reset_id: password_reset.id,
+    }))
+}
+
+async fn set_custom_role(
+    State(state): State<AppState>,
+    Json(payload): Json<SetCustomRoleRequest>,
+) -> Result<(), Error> {
+    if let Some(custom_role) = &payload.custom_role {
+        let custom_roles = state.db_manager.get_custom_roles().await?;
+        if !custom_roles.iter().any(|role| &role.name == custom_role) {
+            return Err(Error::custom_role_not_found());
+        }
+    }
+
+    let user_id = state
+        .db_manager
+        .set_user_custom_role(&payload.username, &payload.custom_role)
+        .await?
+        .ok_or(Error::user_not_found())?;
+    state
+        .redis_manager
+        .purge_user_authorisation(user_id)
+        .await?;
+
+    Ok(())
+}
+
+fn authorise_admin_target(headers: &HeaderMap, target_role: &UserRole) -> Result<(), Error> {
+    let role = headers
+        .get(KIWI_USER_ROLE_HEADER_NAME)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| UserRole::from_str(value).ok());
+    let permissions = headers
+        .get(KIWI_USER_PERMISSIONS_HEADER_NAME)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default()
+        .split(",")
+        .filter_map(|permission| Permission::from_str(permission).ok())
+        .collect();
+    let authorisation = UserAuthorisation {
+        custom_role: None,
+        permissions,
+        groups: vec![],
+    };
+
+    match (target_role, role) {
+        (UserRole::Admin, Some(UserRole::Admin)) if authorisation.has_full_access() => Ok(()),
+        (UserRole::Admin, _) => Err(Error::bad_permissions()),
+        (UserRole::Customer, _) => Ok(()),
+    }
+}
+
 async fn get_current_user(db_manager: &DbManager, headers: HeaderMap) -> Result<User, Error> {
     let user_id = headers
         .get(KIWI_USER_ID_HEADER_NAME)
@@ -87,5 +187,48 @@ async fn get_current_user(db_manager: &DbManager, headers: HeaderMap) -> Result<
     Ok(User {
         username: user_data.username,
         role: user_data.role,
+        custom_role: user_data.custom_role,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build_headers(role: &str, permissions: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(KIWI_USER_ROLE_HEADER_NAME, role.parse().unwrap());
+        headers.insert(
+            KIWI_USER_PERMISSIONS_HEADER_NAME,
+            permissions.parse().unwrap(),
+        );
+        headers
+    }
+
+    #[test]
+    fn lets_full_admins_target_admins() {
+        let headers = build_headers(
+            "Admin",
+            "services:write,users:write,certificates:write,dynamicDns:write",
+        );
+
+        assert!(authorise_admin_target(&headers, &UserRole::Admin).is_ok());
+        assert!(authorise_admin_target(&headers, &UserRole::Customer).is_ok());
+    }
+
+    #[test]
+    fn prevents_users_admins_from_targeting_admins() {
+        for headers in [
+            build_headers("Customer", "users:write"),
+            build_headers(
+                "Customer",
+                "services:write,users:write,certificates:write,dynamicDns:write",
+            ),
+            build_headers("Admin", "users:write"),
+            HeaderMap::new(),
+        ] {
+            assert!(authorise_admin_target(&headers, &UserRole::Admin).is_err());
+            assert!(authorise_admin_target(&headers, &UserRole::Customer).is_ok());
+        }
+    }
+}
@@ -8,6 +8,7 @@ use crate::models::UserRole;
 pub struct User {
     pub username: String,
     pub role: UserRole,
+    pub custom_role: Option<String>,
 }
 pub type GetUsersResponse = Vec<User>;
 pub type GetMeResponse = User;
@@ -29,3 +30,22 @@ pub struct CreateUserInvitationRequest {
 pub struct CreateUserInvitationResponse {
     pub invitation_id: Uuid,
 }
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreatePasswordResetRequest {
+    pub username: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreatePasswordResetResponse {
+    pub password_reset_id: Uuid,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetCustomRoleRequest {
+    pub username: String,
+    pub custom_role: Option<String>,
+}
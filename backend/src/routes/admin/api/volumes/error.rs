@@ -0,0 +1,18 @@
+use crate::error::Error;
+use axum::http::StatusCode;
+
+impl Error {
+    pub fn shared_volume_not_found() -> Self {
+        Self {
+            code: StatusCode::NOT_FOUND,
+            message: "shared volume not found".to_string(),
+        }
+    }
+
+    pub fn shared_volume_in_use(services: &[String]) -> Self {
+        Self {
+            code: StatusCode::CONFLICT,
+            message: format!("shared volume used by {}", services.join(", ")),
+        }
+    }
+}
@@ -0,0 +1,71 @@
+use axum::extract::{Path, State};
+use axum::routing::{delete, get, put};
+use axum::{Json, Router};
+use regex::Regex;
+
+use crate::error::Error;
+use crate::managers::container::models::get_shared_volume_id;
+use crate::routes::admin::api::volumes::models::{
+    CreateSharedVolumeResponse, GetSharedVolumesResponse,
+};
+use crate::state::AppState;
+
+mod error;
+mod models;
+
+pub fn create_router() -> Router<AppState> {
+    Router::new()
+        .route("/", get(get_shared_volumes))
+        .route("/{name}", put(create_shared_volume))
+        .route("/{name}", delete(delete_shared_volume))
+}
+
+async fn get_shared_volumes(
+    State(state): State<AppState>,
+) -> Result<Json<GetSharedVolumesResponse>, Error> {
+    let shared_volumes = state.db_manager.get_shared_volumes().await?;
+
+    Ok(Json(shared_volumes))
+}
+
+async fn create_shared_volume(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<Json<CreateSharedVolumeResponse>, Error> {
+    let name_regex = Regex::new(r"^[a-zA-Z0-9-_]{3,32}$")?;
+    if !name_regex.is_match(&name) {
+        return Err(Error::invalid_name());
+    }
+
+    state
+        .container_manager
+        .create_volume_if_missing(&get_shared_volume_id(&name))
+        .await?;
+    let shared_volume = state.db_manager.create_shared_volume(&name).await?;
+
+    Ok(Json(shared_volume))
+}
+
+async fn delete_shared_volume(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<(), Error> {
+    let dependent_services = state
+        .db_manager
+        .get_shared_volume_dependent_services(&name)
+        .await?;
+    if !dependent_services.is_empty() {
+        return Err(Error::shared_volume_in_use(&dependent_services));
+    }
+
+    let is_deleted = state.db_manager.delete_shared_volume(&name).await?;
+    if !is_deleted {
+        return Err(Error::shared_volume_not_found());
+    }
+    state
+        .container_manager
+        .remove_volume(&get_shared_volume_id(&name))
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,5 @@
+use crate::managers::db::models::SharedVolume;
+
+pub type GetSharedVolumesResponse = Vec<SharedVolume>;
+
+pub type CreateSharedVolumeResponse = SharedVolume;
@@ -20,20 +20,25 @@ use crate::managers::secrets::models::Secret;
 use crate::models::UserRole;
 use crate::routes::auth::api::constants::CREDENTIALS_DURATION;
 use crate::routes::auth::api::models::{
-    CreateUserRequest, GetSealingKeyResponse, RefreshCredentialsQuery,
+    ChangePasswordRequest, CreateUserRequest, GetSealingKeyResponse, RefreshCredentialsQuery,
+    ResetPasswordRequest,
 };
 use crate::state::AppState;
 
 mod constants;
 mod error;
 mod models;
+mod oidc;
 
 pub fn create_router() -> Router<AppState> {
     Router::new()
+        .route("/change-password", post(change_password))
         .route("/create-user", post(create_user))
         .route("/login", post(login))
+        .nest("/oidc", oidc::create_router())
         .route("/logout", post(logout))
         .route("/refresh-credentials", any(refresh_credentials))
+        .route("/reset-password", post(reset_password))
         .route("/sealing-key", get(get_sealing_key))
 }
 
@@ -89,6 +94,113 @@ async fn login(
         Err(Error::bad_credentials())?
     }
 
+    if state
+        .crypto_manager
+        .needs_rehash(&user_data.password_hash)?
+    {
+        let password_hash = state.crypto_manager.generate_hash(&payload.password_hash)?;
+        state
+            .db_manager
+            .update_user_password_hash(&user_data.id, &password_hash)
+            .await?;
+        tracing::info!("upgraded password hash of user {}", user_data.id);
+    }
+
+    let sealing_key = Secret::generate(32 + 16).get(); // AES-CBC key + iv
+
+    generate_and_store_tokens(
+        cookie_jar,
+        domain,
+        state.redis_manager,
+        user_data.id,
+        user_data.username,
+        sealing_key,
+        user_data.role,
+        None,
+    )
+    .await
+}
+
+async fn change_password(
+    cookie_jar: CookieJar,
+    Domain(domain): Domain,
+    State(state): State<AppState>,
+    Json(payload): Json<ChangePasswordRequest>,
+) -> Result<CookieJar, Error> {
+    let access_token = cookie_jar
+        .get(ACCESS_TOKEN_COOKIE_NAME)
+        .map(|cookie| cookie.value().to_owned())
+        .ok_or(Error::bad_credentials())?;
+    let access_token_item = state
+        .redis_manager
+        .get_access_token_item(&access_token)
+        .await?
+        .ok_or(Error::bad_credentials())?;
+    let user_data = state
+        .db_manager
+        .get_user_data_from_id(&access_token_item.user_id)
+        .await?
+        .ok_or(Error::bad_credentials())?;
+    let valid_password = state
+        .crypto_manager
+        .matches(&payload.old_password_hash, &user_data.password_hash)?;
+
+    if !valid_password {
+        Err(Error::bad_credentials())?
+    }
+
+    let password_hash = state
+        .crypto_manager
+        .generate_hash(&payload.new_password_hash)?;
+    state
+        .db_manager
+        .update_user_password_hash(&user_data.id, &password_hash)
+        .await?;
+    state
+        .db_manager
+        .delete_user_api_tokens(&user_data.id)
+        .await?;
+    state
+        .redis_manager
+        .erase_user_sessions(user_data.id)
+        .await?;
+
+    let sealing_key = Secret::generate(32 + 16).get(); // AES-CBC key + iv
+
+    generate_and_store_tokens(
+        cookie_jar,
+        domain,
+        state.redis_manager,
+        user_data.id,
+        user_data.username,
+        sealing_key,
+        user_data.role,
+        None,
+    )
+    .await
+}
+
+async fn reset_password(
+    cookie_jar: CookieJar,
+    Domain(domain): Domain,
+    State(state): State<AppState>,
+    Json(payload): Json<ResetPasswordRequest>,
+) -> Result<CookieJar, Error> {
+    let password_hash = state.crypto_manager.generate_hash(&payload.password_hash)?;
+    let user_data = state
+        .db_manager
+        .reset_user_password(&payload.password_reset_id, &password_hash)
+        .await?
+        .ok_or(Error::bad_credentials())?;
+    state
+        .redis_manager
+        .erase_user_sessions(user_data.id)
+        .await?;
+    state
+        .db_manager
+        .delete_user_api_tokens(&user_data.id)
+        .await?;
+
     let sealing_key = Secret::generate(32 + 16).get(); // AES-CBC key + iv
 
     generate_and_store_tokens(
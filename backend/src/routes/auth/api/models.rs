@@ -28,3 +28,17 @@ pub struct CreateUserRequest {
     pub password_hash: String,
     pub invitation_id: Uuid,
 }
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangePasswordRequest {
+    pub old_password_hash: String,
+    pub new_password_hash: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResetPasswordRequest {
+    pub password_hash: String,
+    pub password_reset_id: Uuid,
+}
@@ -0,0 +1,39 @@
+use crate::error::Error;
+use axum::http::StatusCode;
+
+impl Error {
+    pub fn invalid_oidc_client() -> Self {
+        Self {
+            code: StatusCode::UNAUTHORIZED,
+            message: "invalid_client".to_string(),
+        }
+    }
+
+    pub fn invalid_oidc_redirect_uri() -> Self {
+        Self {
+            code: StatusCode::BAD_REQUEST,
+            message: "redirect uri not registered for this client".to_string(),
+        }
+    }
+
+    pub fn invalid_oidc_grant() -> Self {
+        Self {
+            code: StatusCode::BAD_REQUEST,
+            message: "invalid_grant".to_string(),
+        }
+    }
+
+    pub fn unsupported_oidc_grant_type() -> Self {
+        Self {
+            code: StatusCode::BAD_REQUEST,
+            message: "unsupported_grant_type".to_string(),
+        }
+    }
+
+    pub fn invalid_oidc_token() -> Self {
+        Self {
+            code: StatusCode::UNAUTHORIZED,
+            message: "invalid_token".to_string(),
+        }
+    }
+}
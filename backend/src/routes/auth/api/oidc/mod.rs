@@ -0,0 +1,404 @@
+use axum::extract::{Query, State};
+use axum::http::header::{AUTHORIZATION, CACHE_CONTROL};
+use axum::http::{HeaderMap, HeaderName};
+use axum::response::{IntoResponse, Redirect};
+use axum::routing::{get, post};
+use axum::{Form, Json, Router};
+use axum_extra::extract::CookieJar;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use chrono::Utc;
+use subtle::ConstantTimeEq;
+use urlencoding::{decode, encode};
+use uuid::Uuid;
+
+use crate::constants::{ACCESS_TOKEN_COOKIE_NAME, SERVICE_ACCESS_TOKEN_PREFIX};
+use crate::error::Error;
+use crate::extractors::{Domain, FullOriginalUri};
+use crate::managers::oidc_provider::OidcProviderManager;
+use crate::managers::oidc_provider::models::{IdTokenClaims, JwksResponse};
+use crate::managers::redis::models::{
+    RedisOidcAccessToken, RedisOidcAuthorisationCode, RedisServiceAccessToken,
+};
+use crate::managers::secrets::models::Secret;
+use crate::models::UserRole;
+use crate::routes::auth::api::oidc::models::{
+    AuthoriseQuery, OpenIdConfigurationResponse, TokenRequest, TokenResponse, UserInfoResponse,
+};
+use crate::state::AppState;
+
+mod error;
+mod models;
+
+static TOKEN_DURATION_SECONDS: i64 = 15 * 60;
+
+type HeaderMapping = [(HeaderName, &'static str); 1];
+
+pub fn create_router() -> Router<AppState> {
+    Router::new()
+        .route(
+            "/.well-known/openid-configuration",
+            get(get_openid_configuration),
+        )
+        .route("/jwks", get(get_jwks))
+        .route("/authorize", get(authorise))
+        .route("/token", post(exchange_token))
+        .route("/userinfo", get(get_user_info).post(get_user_info))
+}
+
+async fn get_openid_configuration(Domain(domain): Domain) -> Json<OpenIdConfigurationResponse> {
+    let issuer = OidcProviderManager::get_issuer(&domain);
+    let to_strings = |values: &[&str]| values.iter().map(|value| value.to_string()).collect();
+
+    Json(OpenIdConfigurationResponse {
+        authorization_endpoint: format!("{}/authorize", issuer),
+        token_endpoint: format!("{}/token", issuer),
+        userinfo_endpoint: format!("{}/userinfo", issuer),
+        jwks_uri: format!("{}/jwks", issuer),
+        issuer,
+        response_types_supported: to_strings(&["code"]),
+        grant_types_supported: to_strings(&["authorization_code", "client_credentials"]),
+        subject_types_supported: to_strings(&["public"]),
+        id_token_signing_alg_values_supported: to_strings(&["ES256"]),
+        scopes_supported: to_strings(&["openid", "profile"]),
+        token_endpoint_auth_methods_supported: to_strings(&[
+            "client_secret_basic",
+            "client_secret_post",
+        ]),
+        claims_supported: to_strings(&[
+            "iss",
+            "sub",
+            "aud",
+            "exp",
+            "iat",
+            "nonce",
+            "preferred_username",
+            "role",
+            "groups",
+        ]),
+        code_challenge_methods_supported: to_strings(&["S256"]),
+    })
+}
+
+async fn get_jwks(State(state): State<AppState>) -> Json<JwksResponse> {
+    Json(state.oidc_provider_manager.jwks())
+}
+
+async fn authorise(
+    cookie_jar: CookieJar,
+    Domain(domain): Domain,
+    FullOriginalUri(original_uri): FullOriginalUri,
+    State(state): State<AppState>,
+    Query(query): Query<AuthoriseQuery>,
+) -> Result<Redirect, Error> {
+    let client = state
+        .db_manager
+        .get_oidc_client(&query.client_id)
+        .await?
+        .ok_or(Error::invalid_oidc_client())?;
+    if !client.redirect_uris.contains(&query.redirect_uri) {
+        return Err(Error::invalid_oidc_redirect_uri());
+    }
+
+    let error_redirect = |error: &str| {
+        redirect_with_parameters(
+            &query.redirect_uri,
+            &[
+                ("error", Some(error.to_string())),
+                ("state", query.state.clone()),
+            ],
+        )
+    };
+
+    if query.response_type != "code" {
+        return Ok(error_redirect("unsupported_response_type"));
+    }
+    if !query.scope.split(' ').any(|scope| scope == "openid") {
+        return Ok(error_redirect("invalid_scope"));
+    }
+    if query.code_challenge.is_some()
+        && query.code_challenge_method.as_deref().unwrap_or("plain") != "S256"
+    {
+        return Ok(error_redirect("invalid_request"));
+    }
+
+    let access_token = cookie_jar
+        .get(ACCESS_TOKEN_COOKIE_NAME)
+        .map(|cookie| cookie.value().to_owned());
+    let encoded_original_uri = encode(&original_uri);
+
+    let access_token_item = match access_token {
+        Some(access_token) => state
+            .redis_manager
+            .get_access_token_item(&access_token)
+            .await?
+            .ok_or(Redirect::temporary(&format!(
+                "https://auth.{}/api/refresh-credentials?return_uri={}",
+                domain, encoded_original_uri
+            ))),
+        None => Err(Redirect::to(&format!(
+            "https://auth.{}/login?return_uri={}",
+            domain, encoded_original_uri
+        ))),
+    };
+    let access_token_item = match access_token_item {
+        Ok(access_token_item) => access_token_item,
+        Err(redirect) => return Ok(redirect),
+    };
+
+    let container_configuration = state
+        .db_manager
+        .get_service_data(&client.service_name)
+        .await?
+        .map(|service| service.container_configuration);
+    let (required_role, access_list) = match container_configuration {
+        Some(container_configuration) => (
+            container_configuration.required_role,
+            container_configuration.access_list,
+        ),
+        None => (None, None),
+    };
+    if let Some(required_role) = required_role
+        && !access_token_item.role.has_permissions(&required_role)
+    {
+        return Ok(error_redirect("access_denied"));
+    }
+    if let Some(access_list) = access_list
+        && !matches!(access_token_item.role, UserRole::Admin)
+    {
+        let groups = get_user_groups(&state, access_token_item.user_id).await?;
+        if !access_list.allows(&access_token_item.username, &groups) {
+            return Ok(error_redirect("access_denied"));
+        }
+    }
+
+    let code = Secret::default().get();
+    state
+        .redis_manager
+        .store_oidc_authorisation_code(&RedisOidcAuthorisationCode {
+            code: code.clone(),
+            client_id: client.id,
+            redirect_uri: query.redirect_uri.clone(),
+            user_id: access_token_item.user_id,
+            username: access_token_item.username,
+            role: access_token_item.role,
+            nonce: query.nonce.clone(),
+            code_challenge: query.code_challenge.clone(),
+        })
+        .await?;
+
+    Ok(redirect_with_parameters(
+        &query.redirect_uri,
+        &[("code", Some(code)), ("state", query.state.clone())],
+    ))
+}
+
+async fn exchange_token(
+    Domain(domain): Domain,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Form(payload): Form<TokenRequest>,
+) -> Result<impl IntoResponse, Error> {
+    let (client_id, client_secret) = match get_basic_credentials(&headers) {
+        Some(credentials) => credentials,
+        None => (
+            payload
+                .client_id
+                .clone()
+                .ok_or(Error::invalid_oidc_client())?,
+            payload
+                .client_secret
+                .clone()
+                .ok_or(Error::invalid_oidc_client())?,
+        ),
+    };
+
+    match payload.grant_type.as_str() {
+        "authorization_code" => {
+            exchange_authorisation_code(&domain, &state, &client_id, &client_secret, payload).await
+        }
+        "client_credentials" => {
+            exchange_service_credentials(&state, &client_id, &client_secret).await
+        }
+        _ => Err(Error::unsupported_oidc_grant_type()),
+    }
+}
+
+async fn exchange_authorisation_code(
+    domain: &str,
+    state: &AppState,
+    client_id: &str,
+    client_secret: &str,
+    payload: TokenRequest,
+) -> Result<(HeaderMapping, Json<TokenResponse>), Error> {
+    let client_id = Uuid::parse_str(client_id).map_err(|_| Error::invalid_oidc_client())?;
+    let client = state
+        .db_manager
+        .get_oidc_client(&client_id)
+        .await?
+        .ok_or(Error::invalid_oidc_client())?;
+    if !state
+        .crypto_manager
+        .matches(client_secret, &client.client_secret_hash)?
+    {
+        return Err(Error::invalid_oidc_client());
+    }
+
+    let authorisation_code = payload.code.ok_or(Error::invalid_oidc_grant())?;
+    let redirect_uri = payload.redirect_uri.ok_or(Error::invalid_oidc_grant())?;
+    let code = state
+        .redis_manager
+        .consume_oidc_authorisation_code(&authorisation_code)
+        .await?
+        .ok_or(Error::invalid_oidc_grant())?;
+    if code.client_id != client.id || code.redirect_uri != redirect_uri {
+        return Err(Error::invalid_oidc_grant());
+    }
+    if let Some(code_challenge) = &code.code_challenge {
+        let code_verifier = payload
+            .code_verifier
+            .as_deref()
+            .ok_or(Error::invalid_oidc_grant())?;
+        if !OidcProviderManager::matches_pkce_challenge(code_verifier, code_challenge) {
+            return Err(Error::invalid_oidc_grant());
+        }
+    }
+
+    let access_token = Secret::default().get();
+    state
+        .redis_manager
+        .store_oidc_access_token(&RedisOidcAccessToken {
+            access_token: access_token.clone(),
+            client_id: client.id,
+            user_id: code.user_id,
+            username: code.username.clone(),
+            role: code.role.clone(),
+        })
+        .await?;
+
+    let groups = get_user_groups(state, code.user_id).await?;
+    let now = Utc::now().timestamp();
+    let id_token = state.oidc_provider_manager.sign(&IdTokenClaims {
+        iss: OidcProviderManager::get_issuer(domain),
+        sub: code.user_id.to_string(),
+        aud: client.id.to_string(),
+        exp: now + TOKEN_DURATION_SECONDS,
+        iat: now,
+        nonce: code.nonce,
+        preferred_username: code.username,
+        role: code.role.to_string(),
+        groups,
+    })?;
+
+    Ok((
+        [(CACHE_CONTROL, "no-store")],
+        Json(TokenResponse {
+            access_token,
+            token_type: "Bearer".to_string(),
+            expires_in: TOKEN_DURATION_SECONDS,
+            id_token: Some(id_token),
+        }),
+    ))
+}
+
+async fn exchange_service_credentials(
+    state: &AppState,
+    client_id: &str,
+    client_secret: &str,
+) -> Result<(HeaderMapping, Json<TokenResponse>), Error> {
+    let service = state
+        .db_manager
+        .get_service_data(client_id)
+        .await?
+        .ok_or(Error::invalid_oidc_client())?;
+    let is_valid_secret: bool = service
+        .internal_configuration
+        .client_secret
+        .as_bytes()
+        .ct_eq(client_secret.as_bytes())
+        .into();
+    if !is_valid_secret {
+        return Err(Error::invalid_oidc_client());
+    }
+
+    let access_token = format!("{}{}", SERVICE_ACCESS_TOKEN_PREFIX, Secret::default().get());
+    state
+        .redis_manager
+        .store_service_access_token(&RedisServiceAccessToken {
+            access_token: access_token.clone(),
+            service_name: service.container_configuration.name,
+        })
+        .await?;
+
+    Ok((
+        [(CACHE_CONTROL, "no-store")],
+        Json(TokenResponse {
+            access_token,
+            token_type: "Bearer".to_string(),
+            expires_in: TOKEN_DURATION_SECONDS,
+            id_token: None,
+        }),
+    ))
+}
+
+async fn get_user_info(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<UserInfoResponse>, Error> {
+    let access_token = headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .ok_or(Error::invalid_oidc_token())?;
+    let access_token_item = state
+        .redis_manager
+        .get_oidc_access_token_item(access_token)
+        .await?
+        .ok_or(Error::invalid_oidc_token())?;
+
+    let groups = get_user_groups(&state, access_token_item.user_id).await?;
+
+    Ok(Json(UserInfoResponse {
+        sub: access_token_item.user_id.to_string(),
+        preferred_username: access_token_item.username,
+        role: access_token_item.role.to_string(),
+        groups,
+    }))
+}
+
+async fn get_user_groups(state: &AppState, user_id: i64) -> Result<Vec<String>, Error> {
+    Ok(state
+        .db_manager
+        .get_user_authorisation(&user_id)
+        .await?
+        .map(|user_authorisation| user_authorisation.groups)
+        .unwrap_or_default())
+}
+
+fn get_basic_credentials(headers: &HeaderMap) -> Option<(String, String)> {
+    let encoded_credentials = headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Basic "))?;
+    let credentials = String::from_utf8(STANDARD.decode(encoded_credentials).ok()?).ok()?;
+    let (client_id, client_secret) = credentials.split_once(':')?;
+    let client_id = decode(client_id).ok()?.to_string();
+    let client_secret = decode(client_secret).ok()?.to_string();
+
+    Some((client_id, client_secret))
+}
+
+fn redirect_with_parameters(redirect_uri: &str, parameters: &[(&str, Option<String>)]) -> Redirect {
+    let query = parameters
+        .iter()
+        .filter_map(|(name, value)| {
+            value
+                .as_ref()
+                .map(|value| format!("{}={}", name, encode(value)))
+        })
+        .collect::<Vec<String>>()
+        .join("&");
+    let separator = if redirect_uri.contains('?') { "&" } else { "?" };
+
+    Redirect::to(&format!("{}{}{}", redirect_uri, separator, query))
+}
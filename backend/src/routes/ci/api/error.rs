@@ -12,7 +12,7 @@ impl Error {
     pub fn invalid_branch() -> Self {
         Self {
             code: StatusCode::FORBIDDEN,
-            message: "only 'main' is accepted as valid deployment branch".to_string(),
+            message: "this ref is not allowed to deploy the service".to_string(),
         }
     }
 
@@ -22,4 +22,85 @@ impl Error {
             message: "service not found or not bound to this repo".to_string(),
         }
     }
+
+    pub fn invalid_deployment_environment() -> Self {
+        Self {
+            code: StatusCode::FORBIDDEN,
+            message: "this environment is not allowed to deploy the service".to_string(),
+        }
+    }
+
+    pub fn invalid_deployment_workflow() -> Self {
+        Self {
+            code: StatusCode::FORBIDDEN,
+            message: "this workflow is not allowed to deploy the service".to_string(),
+        }
+    }
+
+    pub fn actor_not_allowed_to_deploy() -> Self {
+        Self {
+            code: StatusCode::FORBIDDEN,
+            message: "this actor is not allowed to deploy the service".to_string(),
+        }
+    }
+
+    pub fn image_sha_mismatch(loaded_image_ids: &[String]) -> Self {
+        Self {
+            code: StatusCode::BAD_REQUEST,
+            message: format!(
+                "tarball does not contain the expected image, loaded {}",
+                loaded_image_ids.join(", ")
+            ),
+        }
+    }
+
+    pub fn previews_disabled() -> Self {
+        Self {
+            code: StatusCode::FORBIDDEN,
+            message: "preview environments are not enabled for this service".to_string(),
+        }
+    }
+
+    pub fn pull_request_mismatch() -> Self {
+        Self {
+            code: StatusCode::FORBIDDEN,
+            message: "this ref is not allowed to deploy the preview of another pull request"
+                .to_string(),
+        }
+    }
+
+    pub fn invalid_pull_request() -> Self {
+        Self {
+            code: StatusCode::BAD_REQUEST,
+            message: "pull request number must be positive".to_string(),
+        }
+    }
+
+    pub fn preview_name_too_long(name: &str) -> Self {
+        Self {
+            code: StatusCode::BAD_REQUEST,
+            message: format!("preview name {} is longer than 32 characters", name),
+        }
+    }
+
+    pub fn preview_name_taken(name: &str) -> Self {
+        Self {
+            code: StatusCode::CONFLICT,
+            message: format!("service {} already exists and is not a preview", name),
+        }
+    }
+
+    pub fn preview_not_found() -> Self {
+        Self {
+            code: StatusCode::NOT_FOUND,
+            message: "preview environment not found".to_string(),
+        }
+    }
+
+    pub fn no_free_preview_port() -> Self {
+        Self {
+            code: StatusCode::SERVICE_UNAVAILABLE,
+            message: "no free port is left for preview environments".to_string(),
+        }
+    }
 }
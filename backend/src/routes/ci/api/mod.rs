@@ -1,13 +1,32 @@
+use std::collections::HashSet;
+
 use axum::{
     Json, Router,
+    body::{Body, Bytes},
     extract::{Multipart, Path, State},
-    routing::post,
+    http::{
+        HeaderMap,
+        header::{AUTHORIZATION, CONTENT_RANGE},
+    },
+    routing::{delete, get, post},
 };
+use futures::{SinkExt, channel::mpsc::channel, join};
+use uuid::Uuid;
 
 use crate::{
+    deployments::deploy_image,
     error::Error,
-    managers::container::models::{GithubRepository, ImageSha},
-    routes::ci::api::models::DeployServiceRequest,
+    managers::{
+        container::models::{DeploymentTrigger, ImageSha},
+        db::models::ServiceData,
+        oidc::models::DeploymentClaims,
+        redis::models::RedisTarballUpload,
+    },
+    previews::{deploy_preview, get_preview_name, tear_down_preview},
+    routes::ci::api::models::{
+        DeployPreviewRequest, DeployPreviewResponse, DeployServiceRequest,
+        LoadTarballUploadRequest, PushTarballResponse, TarballUploadResponse,
+    },
     state::AppState,
 };
 
@@ -17,7 +36,23 @@ mod models;
 pub fn create_router() -> Router<AppState> {
     Router::new()
         .route("/deploy/{service_name}", post(deploy_service))
+        .route("/previews/{service_name}", post(deploy_service_preview))
+        .route(
+            "/previews/{service_name}/{pull_request}",
+            delete(delete_service_preview),
+        )
         .route("/push-tarball", post(push_tarball))
+        .route("/tarball-uploads", post(create_tarball_upload))
+        .route(
+            "/tarball-uploads/{upload_id}",
+            get(get_tarball_upload)
+                .patch(append_to_tarball_upload)
+                .delete(delete_tarball_upload),
+        )
+        .route(
+            "/tarball-uploads/{upload_id}/load",
+            post(load_tarball_upload),
+        )
 }
 
 async fn deploy_service(
@@ -33,51 +68,93 @@ async fn deploy_service(
 
     let token = state
         .oidc_manager
-        .validate_github_oidc_token(&payload.oidc_token)
+        .validate_deployment_token(&payload.oidc_token)
         .await?;
-    let github_repo = GithubRepository::try_from(token.repository)?;
-
-    if token.reference != "refs/heads/main" {
-        return Err(Error::invalid_branch());
-    }
-
-    match &service_data.container_configuration.github_repository {
-        Some(required_repo) if *required_repo == github_repo => {}
-        _ => return Err(Error::invalid_repo_for_service()),
+    let container_configuration = &service_data.container_configuration;
+    match container_configuration.get_linked_repository() {
+        Some(required_repo) => container_configuration
+            .deploy_policy
+            .clone()
+            .unwrap_or_default()
+            .authorises(&required_repo, &token)?,
+        None => return Err(Error::invalid_repo_for_service()),
     }
 
-    let mut new_container_configuration = service_data.container_configuration.clone();
     let image_sha = payload.image_sha.trim_start_matches("sha256:").to_string();
-    new_container_configuration.image_sha = ImageSha::new(image_sha)?;
+    deploy_image(
+        &state,
+        &service_data,
+        ImageSha::new(image_sha)?,
+        DeploymentTrigger::Ci,
+    )
+    .await
+}
 
-    state
+async fn deploy_service_preview(
+    State(state): State<AppState>,
+    Path(service_name): Path<String>,
+    Json(payload): Json<DeployPreviewRequest>,
+) -> Result<Json<DeployPreviewResponse>, Error> {
+    let parent_service = state
         .db_manager
-        .update_service(&service_data, &new_container_configuration)
-        .await?;
-    state
-        .container_manager
-        .stop_and_remove_container(&service_name)
-        .await?;
-    state
-        .container_manager
-        .start_container(&new_container_configuration)
-        .await?;
-    state
-        .container_manager
-        .create_and_attach_network_for_container(&new_container_configuration)
-        .await?;
+        .get_service_data(&service_name)
+        .await?
+        .ok_or(Error::service_not_found())?;
+    authorise_preview_workflow(
+        &state,
+        &parent_service,
+        &payload.oidc_token,
+        payload.pull_request,
+    )
+    .await?;
 
-    Ok(())
+    let image_sha = parse_image_sha(payload.image_sha)?;
+    let preview_environment =
+        deploy_preview(&state, &parent_service, payload.pull_request, image_sha).await?;
+
+    Ok(Json(preview_environment))
+}
+
+async fn delete_service_preview(
+    State(state): State<AppState>,
+    Path((service_name, pull_request)): Path<(String, i32)>,
+    headers: HeaderMap,
+) -> Result<(), Error> {
+    let parent_service = state
+        .db_manager
+        .get_service_data(&service_name)
+        .await?
+        .ok_or(Error::service_not_found())?;
+    authorise_preview_workflow(
+        &state,
+        &parent_service,
+        &get_oidc_token(&headers)?,
+        pull_request,
+    )
+    .await?;
+
+    let preview_name = get_preview_name(&service_name, pull_request);
+    match state
+        .db_manager
+        .get_preview_environment(&preview_name)
+        .await?
+    {
+        Some(preview_environment) if preview_environment.parent_service_name == service_name => {
+            tear_down_preview(&state, &preview_name).await
+        }
+        _ => Err(Error::preview_not_found()),
+    }
 }
 
 async fn push_tarball(
     State(state): State<AppState>,
     mut multipart: Multipart,
-) -> Result<(), Error> {
+) -> Result<Json<PushTarballResponse>, Error> {
     let mut oidc_token: Option<String> = None;
-    let mut tarball: Option<Vec<u8>> = None;
+    let mut image_sha: Option<String> = None;
 
-    while let Some(field) = multipart
+    // The tarball is streamed to Docker as it arrives, so every other field must come first
+    while let Some(mut field) = multipart
         .next_field()
         .await
         .map_err(|_| Error::invalid_ci_payload("malformed multipart payload"))?
@@ -91,49 +168,309 @@ async fn push_tarball(
                         .map_err(|_| Error::invalid_ci_payload("cannot parse oidc_token field"))?,
                 );
             }
-            Some("tarball") => {
-                tarball = Some(
+            Some("imageSha") => {
+                image_sha = Some(
                     field
-                        .bytes()
+                        .text()
                         .await
-                        .map_err(|_| Error::invalid_ci_payload("cannot parse tarball field"))?
-                        .to_vec(),
+                        .map_err(|_| Error::invalid_ci_payload("cannot parse image_sha field"))?,
                 );
             }
+            Some("tarball") => {
+                let oidc_token = oidc_token
+                    .as_ref()
+                    .ok_or(Error::invalid_ci_payload("missing oidc_token form field"))?;
+                authorise_ci_workflow(&state, oidc_token).await?;
+                let expected_image_sha = image_sha.map(parse_image_sha).transpose()?;
+                let existing_image_ids = state.container_manager.get_image_ids().await?;
+
+                let (mut sender, receiver) = channel::<Result<Bytes, std::io::Error>>(4);
+                let forward_tarball = async move {
+                    loop {
+                        match field.chunk().await {
+                            Ok(Some(chunk)) => {
+                                if sender.send(Ok(chunk)).await.is_err() {
+                                    return Ok(());
+                                }
+                            }
+                            Ok(None) => return Ok(()),
+                            Err(_) => {
+                                let _ = sender
+                                    .send(Err(std::io::Error::other("tarball upload interrupted")))
+                                    .await;
+                                return Err(Error::invalid_ci_payload(
+                                    "cannot parse tarball field",
+                                ));
+                            }
+                        }
+                    }
+                };
+                let (forward_result, load_result) = join!(
+                    forward_tarball,
+                    state
+                        .container_manager
+                        .load_image_tarball(reqwest::Body::wrap_stream(receiver))
+                );
+                forward_result?;
+
+                let loaded_image_ids = load_result?;
+                verify_loaded_images(
+                    &state,
+                    &existing_image_ids,
+                    &loaded_image_ids,
+                    expected_image_sha.as_ref(),
+                )
+                .await?;
+                return Ok(Json(PushTarballResponse { loaded_image_ids }));
+            }
             _ => {}
         }
     }
 
-    let oidc_token =
-        oidc_token.ok_or(Error::invalid_ci_payload("missing oidc_token form field"))?;
-    let tarball = tarball.ok_or(Error::invalid_ci_payload("missing tarball form field"))?;
-    if tarball.is_empty() {
-        return Err(Error::invalid_ci_payload("tarball cannot be empty"));
+    Err(Error::invalid_ci_payload("missing tarball form field"))
+}
+
+async fn create_tarball_upload(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<TarballUploadResponse>, Error> {
+    let token = authorise_ci_workflow(&state, &get_oidc_token(&headers)?).await?;
+    let upload_id = state.registry_manager.create_upload().await?;
+    state
+        .redis_manager
+        .store_tarball_upload(&RedisTarballUpload {
+            upload_id,
+            repository: token.get_repository_identifier(),
+        })
+        .await?;
+
+    Ok(Json(TarballUploadResponse { upload_id, size: 0 }))
+}
+
+async fn get_tarball_upload(
+    State(state): State<AppState>,
+    Path(upload_id): Path<Uuid>,
+    headers: HeaderMap,
+) -> Result<Json<TarballUploadResponse>, Error> {
+    authorise_tarball_upload_owner(&state, &headers, &upload_id).await?;
+    let size = state
+        .registry_manager
+        .get_upload_size(&upload_id)
+        .await?
+        .ok_or(Error::upload_not_found())?;
+
+    Ok(Json(TarballUploadResponse { upload_id, size }))
+}
+
+async fn append_to_tarball_upload(
+    State(state): State<AppState>,
+    Path(upload_id): Path<Uuid>,
+    headers: HeaderMap,
+    body: Body,
+) -> Result<Json<TarballUploadResponse>, Error> {
+    let tarball_upload = authorise_tarball_upload_owner(&state, &headers, &upload_id).await?;
+    let size = state
+        .registry_manager
+        .get_upload_size(&upload_id)
+        .await?
+        .ok_or(Error::upload_not_found())?;
+
+    let chunk_start = headers
+        .get(CONTENT_RANGE)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.trim_start_matches("bytes").trim())
+        .and_then(|value| value.split_once('-'))
+        .map(|(start, _)| start.parse::<u64>());
+    match chunk_start {
+        None => {}
+        Some(Ok(chunk_start)) if chunk_start == size => {}
+        Some(_) => return Err(Error::invalid_content_range()),
+    }
+
+    let size = state
+        .registry_manager
+        .append_to_upload(&upload_id, body)
+        .await?;
+    // Uploads in progress don't expire
+    state
+        .redis_manager
+        .store_tarball_upload(&tarball_upload)
+        .await?;
+
+    Ok(Json(TarballUploadResponse { upload_id, size }))
+}
+
+async fn load_tarball_upload(
+    State(state): State<AppState>,
+    Path(upload_id): Path<Uuid>,
+    headers: HeaderMap,
+    Json(payload): Json<LoadTarballUploadRequest>,
+) -> Result<Json<PushTarballResponse>, Error> {
+    authorise_tarball_upload_owner(&state, &headers, &upload_id).await?;
+    let expected_image_sha = payload.image_sha.map(parse_image_sha).transpose()?;
+
+    let tarball = state.registry_manager.open_upload(&upload_id).await?;
+    let existing_image_ids = state.container_manager.get_image_ids().await?;
+    let loaded_image_ids = state
+        .container_manager
+        .load_image_tarball(reqwest::Body::from(tarball))
+        .await?;
+    state.registry_manager.delete_upload(&upload_id).await?;
+    state.redis_manager.purge_tarball_upload(&upload_id).await?;
+
+    verify_loaded_images(
+        &state,
+        &existing_image_ids,
+        &loaded_image_ids,
+        expected_image_sha.as_ref(),
+    )
+    .await?;
+    Ok(Json(PushTarballResponse { loaded_image_ids }))
+}
+
+async fn delete_tarball_upload(
+    State(state): State<AppState>,
+    Path(upload_id): Path<Uuid>,
+    headers: HeaderMap,
+) -> Result<(), Error> {
+    authorise_tarball_upload_owner(&state, &headers, &upload_id).await?;
+    state.redis_manager.purge_tarball_upload(&upload_id).await?;
+    if !state.registry_manager.delete_upload(&upload_id).await? {
+        return Err(Error::upload_not_found());
     }
 
+    Ok(())
+}
+
+async fn authorise_ci_workflow(
+    state: &AppState,
+    oidc_token: &str,
+) -> Result<DeploymentClaims, Error> {
     let token = state
         .oidc_manager
-        .validate_github_oidc_token(&oidc_token)
+        .validate_deployment_token(oidc_token)
         .await?;
-    let github_repo = GithubRepository::try_from(token.repository)?;
 
-    if token.reference != "refs/heads/main" {
-        return Err(Error::invalid_branch());
+    let mut authorisation_result = Err(Error::invalid_repo_for_service());
+    for service in state.db_manager.get_services_data().await? {
+        let container_configuration = service.container_configuration;
+        let Some(required_repo) = container_configuration.get_linked_repository() else {
+            continue;
+        };
+        let deploy_policy = container_configuration.deploy_policy.unwrap_or_default();
+        if deploy_policy.matches_repository(&required_repo, &token) {
+            authorisation_result =
+                deploy_policy
+                    .authorises(&required_repo, &token)
+                    .or_else(|error| {
+                        if deploy_policy.preview_refs.is_empty() {
+                            Err(error)
+                        } else {
+                            deploy_policy.authorises_preview(&required_repo, &token, None)
+                        }
+                    });
+            if authorisation_result.is_ok() {
+                break;
+            }
+        }
     }
 
-    let services = state.db_manager.get_services_data().await?;
-    let is_authorised_repo = services.iter().any(|service| {
-        service
-            .container_configuration
-            .github_repository
-            .as_ref()
-            .is_some_and(|repo| *repo == github_repo)
-    });
-    if !is_authorised_repo {
-        return Err(Error::invalid_repo_for_service());
+    authorisation_result.map(|_| token)
+}
+
+async fn authorise_tarball_upload_owner(
+    state: &AppState,
+    headers: &HeaderMap,
+    upload_id: &Uuid,
+) -> Result<RedisTarballUpload, Error> {
+    let token = authorise_ci_workflow(state, &get_oidc_token(headers)?).await?;
+    state
+        .redis_manager
+        .get_tarball_upload(upload_id)
+        .await?
+        .filter(|tarball_upload| tarball_upload.repository == token.get_repository_identifier())
+        .ok_or(Error::upload_not_found())
+}
+
+async fn authorise_preview_workflow(
+    state: &AppState,
+    parent_service: &ServiceData,
+    oidc_token: &str,
+    pull_request: i32,
+) -> Result<(), Error> {
+    let token = state
+        .oidc_manager
+        .validate_deployment_token(oidc_token)
+        .await?;
+    let container_configuration = &parent_service.container_configuration;
+    match container_configuration.get_linked_repository() {
+        Some(required_repo) => container_configuration
+            .deploy_policy
+            .clone()
+            .unwrap_or_default()
+            .authorises_preview(&required_repo, &token, Some(pull_request)),
+        None => Err(Error::invalid_repo_for_service()),
     }
+}
 
-    state.container_manager.load_image_tarball(tarball).await?;
+fn get_oidc_token(headers: &HeaderMap) -> Result<String, Error> {
+    headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(|value| value.to_string())
+        .ok_or(Error::unauthorised())
+}
 
-    Ok(())
+fn parse_image_sha(image_sha: String) -> Result<ImageSha, Error> {
+    ImageSha::new(image_sha.trim().trim_start_matches("sha256:").to_string())
+}
+
+/// Images that existed before the load are kept, as services may run them.
+async fn verify_loaded_images(
+    state: &AppState,
+    existing_image_ids: &HashSet<String>,
+    loaded_image_ids: &[String],
+    expected_image_sha: Option<&ImageSha>,
+) -> Result<(), Error> {
+    match expected_image_sha {
+        Some(expected_image_sha)
+            if !loaded_image_ids.iter().any(|image_id| {
+                image_id.trim_start_matches("sha256:") == expected_image_sha.get_value()
+            }) =>
+        {
+            let new_image_ids = get_new_image_ids(existing_image_ids, loaded_image_ids);
+            if let Err(error) = state.container_manager.remove_images(&new_image_ids).await {
+                tracing::error!("failed to remove mismatched loaded images: {}", error);
+            }
+            Err(Error::image_sha_mismatch(loaded_image_ids))
+        }
+        _ => Ok(()),
+    }
+}
+
+fn get_new_image_ids(
+    existing_image_ids: &HashSet<String>,
+    loaded_image_ids: &[String],
+) -> Vec<String> {
+    loaded_image_ids
+        .iter()
+        .filter(|image_id| !existing_image_ids.contains(*image_id))
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_images_that_existed_before_the_load() {
+        let existing_image_ids = HashSet::from(["sha256:a".to_string()]);
+        let loaded_image_ids = vec!["sha256:a".to_string(), "sha256:b".to_string()];
+        assert_eq!(
+            get_new_image_ids(&existing_image_ids, &loaded_image_ids),
+            vec!["sha256:b".to_string()]
+        );
+    }
 }
@@ -1,4 +1,7 @@
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::managers::db::models::PreviewEnvironment;
 
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -6,3 +9,32 @@ pub struct DeployServiceRequest {
     pub oidc_token: String,
     pub image_sha: String,
 }
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeployPreviewRequest {
+    pub oidc_token: String,
+    pub image_sha: String,
+    pub pull_request: i32,
+}
+
+pub type DeployPreviewResponse = PreviewEnvironment;
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PushTarballResponse {
+    pub loaded_image_ids: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TarballUploadResponse {
+    pub upload_id: Uuid,
+    pub size: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LoadTarballUploadRequest {
+    pub image_sha: Option<String>,
+}
@@ -17,6 +17,7 @@ pub mod admin;
 pub mod auth;
 pub mod ci;
 mod error;
+pub mod registry;
 pub mod status;
 
 pub fn create_router(settings: &Settings) -> Router<AppState> {
@@ -24,6 +25,7 @@ pub fn create_router(settings: &Settings) -> Router<AppState> {
         .nest("/admin", admin::create_router(settings))
         .nest("/auth", auth::create_router(settings))
         .nest("/ci", ci::create_router())
+        .nest("/registry", registry::create_router())
         .nest("/status", status::create_router())
         .route("/{service}", any(forward_to_service_root))
         .route("/{service}/{*path}", any(forward_to_service))
@@ -0,0 +1,60 @@
+use crate::error::Error;
+use axum::http::StatusCode;
+
+impl Error {
+    pub fn invalid_repository_name() -> Self {
+        Self {
+            code: StatusCode::BAD_REQUEST,
+            message: "invalid repository name".to_string(),
+        }
+    }
+
+    pub fn invalid_manifest() -> Self {
+        Self {
+            code: StatusCode::BAD_REQUEST,
+            message: "invalid manifest".to_string(),
+        }
+    }
+
+    pub fn manifest_blob_unknown(digest: &str) -> Self {
+        Self {
+            code: StatusCode::BAD_REQUEST,
+            message: format!("manifest references unknown blob {}", digest),
+        }
+    }
+
+    pub fn manifest_not_found() -> Self {
+        Self {
+            code: StatusCode::NOT_FOUND,
+            message: "manifest not found".to_string(),
+        }
+    }
+
+    pub fn blob_not_found() -> Self {
+        Self {
+            code: StatusCode::NOT_FOUND,
+            message: "blob not found".to_string(),
+        }
+    }
+
+    pub fn invalid_content_range() -> Self {
+        Self {
+            code: StatusCode::RANGE_NOT_SATISFIABLE,
+            message: "chunk does not start at the end of the upload".to_string(),
+        }
+    }
+
+    pub fn unsupported_registry_operation() -> Self {
+        Self {
+            code: StatusCode::METHOD_NOT_ALLOWED,
+            message: "unsupported registry operation".to_string(),
+        }
+    }
+
+    pub fn repository_access_denied() -> Self {
+        Self {
+            code: StatusCode::FORBIDDEN,
+            message: "no access to this repository".to_string(),
+        }
+    }
+}
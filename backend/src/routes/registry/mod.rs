@@ -0,0 +1,632 @@
+use axum::{
+    Json, Router,
+    body::{Body, to_bytes},
+    extract::{Path, Query, State},
+    http::{
+        HeaderMap, HeaderName, HeaderValue, Method, StatusCode, Uri,
+        header::{
+            AUTHORIZATION, CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE, LOCATION, RANGE,
+            WWW_AUTHENTICATE,
+        },
+    },
+    response::{IntoResponse, Response},
+    routing::{any, get},
+};
+use base64::{Engine, engine::general_purpose::STANDARD};
+use tokio_util::io::ReaderStream;
+use uuid::Uuid;
+
+use crate::{
+    error::Error,
+    managers::{
+        redis::models::RedisRegistryUpload,
+        registry::models::{
+            ManifestDescriptors, ManifestReference, is_valid_digest, is_valid_repository_name,
+        },
+    },
+    middlewares::authentication::authenticate_api_token,
+    models::{AccessLevel, AdminArea},
+    routes::registry::models::{
+        CompleteUploadQuery, ListTagsQuery, ListTagsResponse, RegistryIdentity, StartUploadQuery,
+    },
+    state::AppState,
+};
+
+mod error;
+mod models;
+
+static API_VERSION_HEADER_NAME: &str = "Docker-Distribution-API-Version";
+static API_VERSION: &str = "registry/2.0";
+static CONTENT_DIGEST_HEADER_NAME: &str = "Docker-Content-Digest";
+static UPLOAD_UUID_HEADER_NAME: &str = "Docker-Upload-UUID";
+static MAX_MANIFEST_SIZE_BYTES: usize = 4 * 1024 * 1024;
+static MAX_LISTED_TAGS_COUNT: i64 = 1000;
+
+pub fn create_router() -> Router<AppState> {
+    Router::new()
+        .route("/v2", get(check_api_version))
+        .route("/v2/{*path}", any(handle_registry_request))
+}
+
+async fn check_api_version(State(state): State<AppState>, headers: HeaderMap) -> Response {
+    match authenticate(&state, &headers).await {
+        Ok(Some(_)) => registry_response(StatusCode::OK, &[], Json(serde_json::json!({}))),
+        Ok(None) => unauthorised_response(),
+        Err(error) => error.into_response(),
+    }
+}
+
+async fn handle_registry_request(
+    State(state): State<AppState>,
+    Path(path): Path<String>,
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+    body: Body,
+) -> Response {
+    let identity = match authenticate(&state, &headers).await {
+        Ok(Some(identity)) => identity,
+        Ok(None) => return unauthorised_response(),
+        Err(error) => return error.into_response(),
+    };
+
+    match dispatch(&state, &identity, &path, method, &uri, &headers, body).await {
+        Ok(response) => response,
+        Err(error) => {
+            let mut response = error.into_response();
+            response.headers_mut().insert(
+                API_VERSION_HEADER_NAME,
+                HeaderValue::from_static(API_VERSION),
+            );
+            response
+        }
+    }
+}
+
+async fn dispatch(
+    state: &AppState,
+    identity: &RegistryIdentity,
+    path: &str,
+    method: Method,
+    uri: &Uri,
+    headers: &HeaderMap,
+    body: Body,
+) -> Result<Response, Error> {
+    let path = path.trim_end_matches('/');
+    let access_level = if matches!(method, Method::GET | Method::HEAD) {
+        AccessLevel::Read
+    } else {
+        AccessLevel::Write
+    };
+
+    if let Some(repository) = path.strip_suffix("/tags/list") {
+        authorise(state, identity, repository, access_level).await?;
+        return match method {
+            Method::GET => list_tags(state, repository, uri).await,
+            _ => Err(Error::unsupported_registry_operation()),
+        };
+    }
+
+    if let Some((repository, reference)) = path.rsplit_once("/manifests/") {
+        authorise(state, identity, repository, access_level).await?;
+        let reference = ManifestReference::try_from(reference)?;
+        return match method {
+            Method::GET => get_manifest(state, repository, reference, true).await,
+            Method::HEAD => get_manifest(state, repository, reference, false).await,
+            Method::PUT => put_manifest(state, repository, reference, headers, body).await,
+            Method::DELETE => delete_manifest(state, repository, reference).await,
+            _ => Err(Error::unsupported_registry_operation()),
+        };
+    }
+
+    if let Some((repository, upload)) = path.split_once("/blobs/uploads") {
+        authorise(state, identity, repository, access_level).await?;
+        let upload = upload.trim_start_matches('/');
+        if upload.is_empty() {
+            return match method {
+                Method::POST => start_upload(state, identity, repository, uri, body).await,
+                _ => Err(Error::unsupported_registry_operation()),
+            };
+        }
+        let id = Uuid::try_parse(upload).map_err(|_| Error::upload_not_found())?;
+        authorise_upload(state, repository, &id).await?;
+        return match method {
+            Method::GET => get_upload_status(state, repository, &id).await,
+            Method::PATCH => patch_upload(state, repository, &id, headers, body).await,
+            Method::PUT => complete_upload(state, repository, &id, uri, body).await,
+            Method::DELETE => delete_upload(state, &id).await,
+            _ => Err(Error::unsupported_registry_operation()),
+        };
+    }
+
+    if let Some((repository, digest)) = path.rsplit_once("/blobs/") {
+        authorise(state, identity, repository, access_level).await?;
+        return match method {
+            Method::GET => get_blob(state, repository, digest, true).await,
+            Method::HEAD => get_blob(state, repository, digest, false).await,
+            _ => Err(Error::unsupported_registry_operation()),
+        };
+    }
+
+    Err(Error::unsupported_registry_operation())
+}
+
+async fn authenticate(
+    state: &AppState,
+    headers: &HeaderMap,
+) -> Result<Option<RegistryIdentity>, Error> {
+    let Some(password) = headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Basic "))
+        .and_then(|value| STANDARD.decode(value).ok())
+        .and_then(|value| String::from_utf8(value).ok())
+        .and_then(|value| {
+            value
+                .split_once(':')
+                .map(|(_, password)| password.to_string())
+        })
+    else {
+        return Ok(None);
+    };
+
+    if password.starts_with("kiwi_") {
+        return Ok(authenticate_api_token(state, &password)
+            .await?
+            .map(RegistryIdentity::User));
+    }
+
+    let Ok(claims) = state
+        .oidc_manager
+        .validate_deployment_token(&password)
+        .await
+    else {
+        return Ok(None);
+    };
+    Ok(Some(RegistryIdentity::CiWorkflow(claims)))
+}
+
+async fn authorise(
+    state: &AppState,
+    identity: &RegistryIdentity,
+    repository: &str,
+    access_level: AccessLevel,
+) -> Result<(), Error> {
+    if !is_valid_repository_name(repository) {
+        return Err(Error::invalid_repository_name());
+    }
+
+    let is_allowed = match identity {
+        RegistryIdentity::User(user) => user
+            .authorisation
+            .has_permission(AdminArea::Services, access_level),
+        RegistryIdentity::CiWorkflow(claims) => {
+            let service_name = repository.split('/').next().unwrap_or_default();
+            state
+                .db_manager
+                .get_service_data(service_name)
+                .await?
+                .map(|service_data| service_data.container_configuration)
+                .is_some_and(|container_configuration| {
+                    container_configuration
+                        .get_linked_repository()
+                        .is_some_and(|required_repo| {
+                            let deploy_policy =
+                                container_configuration.deploy_policy.unwrap_or_default();
+                            deploy_policy.authorises(&required_repo, claims).is_ok()
+                                || deploy_policy
+                                    .authorises_preview(&required_repo, claims, None)
+                                    .is_ok()
+                        })
+                })
+        }
+    };
+
+    if is_allowed {
+        Ok(())
+    } else {
+        Err(Error::repository_access_denied())
+    }
+}
+
+async fn authorise_upload(state: &AppState, repository: &str, id: &Uuid) -> Result<(), Error> {
+    state
+        .redis_manager
+        .get_registry_upload(id)
+        .await?
+        .filter(|registry_upload| registry_upload.repository == repository)
+        .ok_or(Error::upload_not_found())?;
+
+    Ok(())
+}
+
+async fn get_blob(
+    state: &AppState,
+    repository: &str,
+    digest: &str,
+    include_content: bool,
+) -> Result<Response, Error> {
+    if !is_valid_digest(digest) {
+        return Err(Error::invalid_digest());
+    }
+    if !state
+        .db_manager
+        .registry_blob_exists(repository, digest)
+        .await?
+    {
+        return Err(Error::blob_not_found());
+    }
+    let size = state
+        .registry_manager
+        .get_blob_size(digest)
+        .await?
+        .ok_or(Error::blob_not_found())?;
+
+    let headers = [
+        (CONTENT_LENGTH.as_str(), size.to_string()),
+        (
+            CONTENT_TYPE.as_str(),
+            "application/octet-stream".to_string(),
+        ),
+        (CONTENT_DIGEST_HEADER_NAME, digest.to_string()),
+    ];
+    let body = if include_content {
+        let file = state.registry_manager.open_blob(digest).await?;
+        Body::from_stream(ReaderStream::new(file))
+    } else {
+        Body::empty()
+    };
+
+    Ok(registry_response(StatusCode::OK, &headers, body))
+}
+
+async fn start_upload(
+    state: &AppState,
+    identity: &RegistryIdentity,
+    repository: &str,
+    uri: &Uri,
+    body: Body,
+) -> Result<Response, Error> {
+    let query = Query::<StartUploadQuery>::try_from_uri(uri)
+        .map_err(|_| Error::invalid_digest())?
+        .0;
+
+    if let (Some(digest), Some(from)) = (&query.mount, &query.from)
+        && is_valid_digest(digest)
+        && authorise(state, identity, from, AccessLevel::Read)
+            .await
+            .is_ok()
+        && state.db_manager.registry_blob_exists(from, digest).await?
+        && let Some(size) = state.registry_manager.get_blob_size(digest).await?
+    {
+        state
+            .db_manager
+            .link_registry_blob(repository, digest, size as i64)
+            .await?;
+        return Ok(created_blob_response(repository, digest));
+    }
+
+    let id = state.registry_manager.create_upload().await?;
+
+    if let Some(digest) = &query.digest {
+        state.registry_manager.append_to_upload(&id, body).await?;
+        let size = state.registry_manager.complete_upload(&id, digest).await?;
+        state
+            .db_manager
+            .link_registry_blob(repository, digest, size as i64)
+            .await?;
+        return Ok(created_blob_response(repository, digest));
+    }
+
+    state
+        .redis_manager
+        .store_registry_upload(&RedisRegistryUpload {
+            upload_id: id,
+            repository: repository.to_string(),
+        })
+        .await?;
+
+    Ok(upload_progress_response(
+        StatusCode::ACCEPTED,
+        repository,
+        &id,
+        0,
+    ))
+}
+
+async fn get_upload_status(
+    state: &AppState,
+    repository: &str,
+    id: &Uuid,
+) -> Result<Response, Error> {
+    let size = state
+        .registry_manager
+        .get_upload_size(id)
+        .await?
+        .ok_or(Error::upload_not_found())?;
+
+    Ok(upload_progress_response(
+        StatusCode::NO_CONTENT,
+        repository,
+        id,
+        size,
+    ))
+}
+
+async fn patch_upload(
+    state: &AppState,
+    repository: &str,
+    id: &Uuid,
+    headers: &HeaderMap,
+    body: Body,
+) -> Result<Response, Error> {
+    let size = state
+        .registry_manager
+        .get_upload_size(id)
+        .await?
+        .ok_or(Error::upload_not_found())?;
+
+    let chunk_start = headers
+        .get(CONTENT_RANGE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split_once('-'))
+        .map(|(start, _)| start.trim().parse::<u64>());
+    match chunk_start {
+        None => {}
+        Some(Ok(chunk_start)) if chunk_start == size => {}
+        Some(_) => return Err(Error::invalid_content_range()),
+    }
+
+    let size = state.registry_manager.append_to_upload(id, body).await?;
+    // Uploads in progress don't expire
+    state
+        .redis_manager
+        .store_registry_upload(&RedisRegistryUpload {
+            upload_id: *id,
+            repository: repository.to_string(),
+        })
+        .await?;
+
+    Ok(upload_progress_response(
+        StatusCode::ACCEPTED,
+        repository,
+        id,
+        size,
+    ))
+}
+
+async fn complete_upload(
+    state: &AppState,
+    repository: &str,
+    id: &Uuid,
+    uri: &Uri,
+    body: Body,
+) -> Result<Response, Error> {
+    let query = Query::<CompleteUploadQuery>::try_from_uri(uri)
+        .map_err(|_| Error::invalid_digest())?
+        .0;
+    if !is_valid_digest(&query.digest) {
+        return Err(Error::invalid_digest());
+    }
+
+    state.registry_manager.append_to_upload(id, body).await?;
+    let size = state
+        .registry_manager
+        .complete_upload(id, &query.digest)
+        .await?;
+    state.redis_manager.purge_registry_upload(id).await?;
+    state
+        .db_manager
+        .link_registry_blob(repository, &query.digest, size as i64)
+        .await?;
+
+    Ok(created_blob_response(repository, &query.digest))
+}
+
+async fn delete_upload(state: &AppState, id: &Uuid) -> Result<Response, Error> {
+    state.redis_manager.purge_registry_upload(id).await?;
+    if !state.registry_manager.delete_upload(id).await? {
+        return Err(Error::upload_not_found());
+    }
+
+    Ok(registry_response(
+        StatusCode::NO_CONTENT,
+        &[],
+        Body::empty(),
+    ))
+}
+
+async fn get_manifest(
+    state: &AppState,
+    repository: &str,
+    reference: ManifestReference,
+    include_content: bool,
+) -> Result<Response, Error> {
+    let manifest = match reference {
+        ManifestReference::Tag(tag) => {
+            state
+                .db_manager
+                .get_tagged_registry_manifest(repository, &tag)
+                .await?
+        }
+        ManifestReference::Digest(digest) => {
+            state
+                .db_manager
+                .get_registry_manifest(repository, &digest)
+                .await?
+        }
+    }
+    .ok_or(Error::manifest_not_found())?;
+    let content = state.registry_manager.read_blob(&manifest.digest).await?;
+
+    let headers = [
+        (CONTENT_LENGTH.as_str(), content.len().to_string()),
+        (CONTENT_TYPE.as_str(), manifest.media_type),
+        (CONTENT_DIGEST_HEADER_NAME, manifest.digest),
+    ];
+    let body = if include_content {
+        Body::from(content)
+    } else {
+        Body::empty()
+    };
+
+    Ok(registry_response(StatusCode::OK, &headers, body))
+}
+
+async fn put_manifest(
+    state: &AppState,
+    repository: &str,
+    reference: ManifestReference,
+    headers: &HeaderMap,
+    body: Body,
+) -> Result<Response, Error> {
+    let content = to_bytes(body, MAX_MANIFEST_SIZE_BYTES)
+        .await
+        .map_err(|_| Error::invalid_manifest())?;
+    let descriptors: ManifestDescriptors =
+        serde_json::from_slice(&content).map_err(|_| Error::invalid_manifest())?;
+    let media_type = descriptors
+        .media_type
+        .clone()
+        .or(headers
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.to_string()))
+        .ok_or(Error::invalid_manifest())?;
+
+    let referenced_digests = descriptors.referenced_digests();
+    for referenced_digest in &referenced_digests {
+        if !is_valid_digest(referenced_digest)
+            || !state
+                .db_manager
+                .registry_blob_exists(repository, referenced_digest)
+                .await?
+        {
+            return Err(Error::manifest_blob_unknown(referenced_digest));
+        }
+    }
+
+    let digest = state.registry_manager.store_blob(&content).await?;
+    let tag = match &reference {
+        ManifestReference::Tag(tag) => Some(tag.as_str()),
+        ManifestReference::Digest(expected_digest) if *expected_digest == digest => None,
+        ManifestReference::Digest(_) => return Err(Error::digest_mismatch()),
+    };
+    state
+        .db_manager
+        .put_registry_manifest(repository, &digest, &media_type, &referenced_digests, tag)
+        .await?;
+
+    let headers = [
+        (
+            LOCATION.as_str(),
+            format!("/v2/{}/manifests/{}", repository, digest),
+        ),
+        (CONTENT_DIGEST_HEADER_NAME, digest),
+    ];
+    Ok(registry_response(
+        StatusCode::CREATED,
+        &headers,
+        Body::empty(),
+    ))
+}
+
+async fn delete_manifest(
+    state: &AppState,
+    repository: &str,
+    reference: ManifestReference,
+) -> Result<Response, Error> {
+    let ManifestReference::Digest(digest) = reference else {
+        return Err(Error::unsupported_registry_operation());
+    };
+    if !state
+        .db_manager
+        .delete_registry_manifest(repository, &digest)
+        .await?
+    {
+        return Err(Error::manifest_not_found());
+    }
+
+    Ok(registry_response(StatusCode::ACCEPTED, &[], Body::empty()))
+}
+
+async fn list_tags(state: &AppState, repository: &str, uri: &Uri) -> Result<Response, Error> {
+    let query = Query::<ListTagsQuery>::try_from_uri(uri)
+        .map_err(|_| Error::unsupported_registry_operation())?
+        .0;
+    let limit = query
+        .n
+        .unwrap_or(MAX_LISTED_TAGS_COUNT)
+        .clamp(1, MAX_LISTED_TAGS_COUNT);
+    let tags = state
+        .db_manager
+        .get_registry_tags(repository, limit, &query.last.unwrap_or_default())
+        .await?;
+
+    Ok(registry_response(
+        StatusCode::OK,
+        &[],
+        Json(ListTagsResponse {
+            name: repository.to_string(),
+            tags,
+        }),
+    ))
+}
+
+fn created_blob_response(repository: &str, digest: &str) -> Response {
+    let headers = [
+        (
+            LOCATION.as_str(),
+            format!("/v2/{}/blobs/{}", repository, digest),
+        ),
+        (CONTENT_DIGEST_HEADER_NAME, digest.to_string()),
+    ];
+    registry_response(StatusCode::CREATED, &headers, Body::empty())
+}
+
+fn upload_progress_response(
+    status: StatusCode,
+    repository: &str,
+    id: &Uuid,
+    size: u64,
+) -> Response {
+    let headers = [
+        (
+            LOCATION.as_str(),
+            format!("/v2/{}/blobs/uploads/{}", repository, id),
+        ),
+        (RANGE.as_str(), format!("0-{}", size.saturating_sub(1))),
+        (UPLOAD_UUID_HEADER_NAME, id.to_string()),
+        (CONTENT_LENGTH.as_str(), "0".to_string()),
+    ];
+    registry_response(status, &headers, Body::empty())
+}
+
+fn unauthorised_response() -> Response {
+    let headers = [(
+        WWW_AUTHENTICATE.as_str(),
+        "Basic realm=\"kiwi-registry\"".to_string(),
+    )];
+    registry_response(StatusCode::UNAUTHORIZED, &headers, Error::unauthorised())
+}
+
+fn registry_response(
+    status: StatusCode,
+    headers: &[(&str, String)],
+    body: impl IntoResponse,
+) -> Response {
+    let mut response = (status, body).into_response();
+    response.headers_mut().insert(
+        API_VERSION_HEADER_NAME,
+        HeaderValue::from_static(API_VERSION),
+    );
+    for (name, value) in headers {
+        if let (Ok(name), Ok(value)) = (
+            HeaderName::from_bytes(name.as_bytes()),
+            HeaderValue::from_str(value),
+        ) {
+            response.headers_mut().insert(name, value);
+        }
+    }
+    response
+}
@@ -0,0 +1,33 @@
+use serde::{Deserialize, Serialize};
+
+use crate::managers::oidc::models::DeploymentClaims;
+use crate::middlewares::authentication::AuthenticatedUser;
+
+pub enum RegistryIdentity {
+    User(AuthenticatedUser),
+    CiWorkflow(DeploymentClaims),
+}
+
+#[derive(Deserialize)]
+pub struct StartUploadQuery {
+    pub digest: Option<String>,
+    pub mount: Option<String>,
+    pub from: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct CompleteUploadQuery {
+    pub digest: String,
+}
+
+#[derive(Deserialize)]
+pub struct ListTagsQuery {
+    pub n: Option<i64>,
+    pub last: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct ListTagsResponse {
+    pub name: String,
+    pub tags: Vec<String>,
+}
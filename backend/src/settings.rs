@@ -7,20 +7,60 @@ use crate::error::Error;
 
 #[derive(Parser, Debug)]
 pub struct Settings {
+    #[arg(long, value_delimiter = ',')]
+    pub allowed_bind_mount_paths: Vec<String>,
+    #[arg(long, env = "KIWI_BACKUPS_S3_ACCESS_KEY_ID", hide_env_values = true)]
+    pub backups_s3_access_key_id: Option<String>,
+    #[arg(long)]
+    pub backups_s3_bucket: Option<String>,
+    #[arg(long)]
+    pub backups_s3_endpoint: Option<String>,
+    #[arg(long, default_value = "us-east-1")]
+    pub backups_s3_region: String,
+    #[arg(
+        long,
+        env = "KIWI_BACKUPS_S3_SECRET_ACCESS_KEY",
+        hide_env_values = true
+    )]
+    pub backups_s3_secret_access_key: Option<String>,
     #[arg(long, default_value_t = default_config_folder_path())]
     config_folder_path: String,
+    #[arg(long, default_value = "0")]
+    pub credentials_rotation_interval_days: u32,
     #[arg(long, default_value = "3000")]
     pub dev_frontend_server_port: i32,
     #[arg(long, default_value = "127.0.0.1")]
     host: String,
+    #[arg(long)]
+    pub instance_backup_import_path: Option<String>,
+    #[arg(long, env = "KIWI_INSTANCE_BACKUP_PASSPHRASE", hide_env_values = true)]
+    pub instance_backup_passphrase: Option<String>,
     #[arg(long, default_value = "staging")]
     lets_encrypt_environment: LetsEncryptEnvironment,
     #[arg(long, default_value = "info")]
     pub log_level: tracing::Level,
+    #[arg(long)]
+    master_key_file_path: Option<String>,
+    #[arg(long, env = "KIWI_MASTER_PASSPHRASE", hide_env_values = true)]
+    pub master_passphrase: Option<String>,
+    #[arg(long)]
+    pub previous_master_key_file_path: Option<String>,
+    #[arg(long, env = "KIWI_PREVIOUS_MASTER_PASSPHRASE", hide_env_values = true)]
+    pub previous_master_passphrase: Option<String>,
+    #[arg(long)]
+    pub oidc_issuers_file_path: Option<String>,
     #[arg(long, default_value = "5000")]
     port: i32,
+    #[arg(long, default_value = "72")]
+    pub preview_environment_ttl_hours: u32,
+    #[arg(long)]
+    secret_files_host_path: Option<String>,
+    #[arg(long, default_value = "/dev/shm/kiwi-secrets")]
+    pub secret_files_path: String,
     #[arg(long, default_value = "/path")]
     pub static_files_path: String,
+    #[arg(long, default_value = "72")]
+    pub volume_deletion_grace_period_hours: u32,
 }
 
 impl Settings {
@@ -32,10 +72,38 @@ impl Settings {
         cfg!(debug_assertions)
     }
 
+    pub fn backups_folder_path(&self) -> String {
+        format!("{}/backups", self.config_folder_path)
+    }
+
+    pub fn oidc_jwks_folder_path(&self) -> String {
+        format!("{}/oidc_jwks", self.config_folder_path)
+    }
+
+    pub fn registry_folder_path(&self) -> String {
+        format!("{}/registry", self.config_folder_path)
+    }
+
     pub fn secrets_file_path(&self) -> String {
         format!("{}/secrets.json", self.config_folder_path)
     }
 
+    pub fn master_key_file_path(&self) -> String {
+        self.master_key_file_path
+            .clone()
+            .unwrap_or(format!("{}/master.key", self.config_folder_path))
+    }
+
+    pub fn master_key_salt_path(&self) -> String {
+        format!("{}/master_key_salt", self.config_folder_path)
+    }
+
+    pub fn secret_files_host_path(&self) -> String {
+        self.secret_files_host_path
+            .clone()
+            .unwrap_or(self.secret_files_path.clone())
+    }
+
     pub fn tls_public_certificate_path(&self) -> String {
         format!("{}/tls_public_certificate.pem", self.config_folder_path)
     }
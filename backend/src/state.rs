@@ -3,9 +3,11 @@ use std::sync::Arc;
 use tokio::sync::Mutex;
 
 use crate::managers::{
-    container::ContainerManager, crypto::CryptoManager, db::DbManager,
-    dynamic_dns::DynamicDnsManager, lets_encrypt::LetsEncryptManager, local_http::LocalHttpManager,
-    oidc::OidcManager, redis::RedisManager, secrets::SecretsManager,
+    backup::BackupManager, container::ContainerManager, crypto::CryptoManager, db::DbManager,
+    dynamic_dns::DynamicDnsManager, encryption::EncryptionManager,
+    lets_encrypt::LetsEncryptManager, local_http::LocalHttpManager, oidc::OidcManager,
+    oidc_provider::OidcProviderManager, redis::RedisManager, registry::RegistryManager,
+    secrets::SecretsManager,
 };
 
 #[derive(Clone)]
@@ -16,7 +18,11 @@ pub struct AppState {
     pub redis_manager: RedisManager,
     pub local_http_manager: LocalHttpManager,
     pub oidc_manager: OidcManager,
+    pub oidc_provider_manager: OidcProviderManager,
     pub dynamic_dns_manager: Arc<Mutex<Option<DynamicDnsManager>>>,
     pub secrets_manager: Arc<Mutex<SecretsManager>>,
     pub lets_encrypt_manager: Arc<Mutex<LetsEncryptManager>>,
+    pub backup_manager: BackupManager,
+    pub encryption_manager: EncryptionManager,
+    pub registry_manager: RegistryManager,
 }
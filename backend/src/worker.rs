@@ -1,37 +1,52 @@
-use std::sync::Arc;
-
 use tokio::{
     select,
-    sync::Mutex,
     time::{Duration, sleep},
 };
 
 use crate::{
-    managers::{dynamic_dns::DynamicDnsManager, lets_encrypt::LetsEncryptManager},
+    backups::{apply_backup_retention, create_service_backup},
+    deployments::{check_image_update, rotate_service_credentials},
+    error::Error,
+    jobs::run_due_scheduled_job,
+    managers::backup::models::BackupTrigger,
     models::ServerAction,
+    previews::tear_down_preview,
+    settings::Settings,
+    state::AppState,
 };
 
+static REGISTRY_BLOB_GRACE_PERIOD_MINUTES: i32 = 60;
+
 pub struct Worker {
-    dynamic_dns_manager: Arc<Mutex<Option<DynamicDnsManager>>>,
-    lets_encrypt_manager: Arc<Mutex<LetsEncryptManager>>,
+    state: AppState,
+    credentials_rotation_interval_days: u32,
+    preview_environment_ttl_hours: u32,
+    volume_deletion_grace_period_hours: u32,
 }
 
 impl Worker {
-    pub fn new(
-        dynamic_dns_manager: Arc<Mutex<Option<DynamicDnsManager>>>,
-        lets_encrypt_manager: Arc<Mutex<LetsEncryptManager>>,
-    ) -> Self {
+    pub fn new(state: AppState, settings: &Settings) -> Self {
         tracing::info!("side worker initialised");
 
         Self {
-            dynamic_dns_manager,
-            lets_encrypt_manager,
+            state,
+            credentials_rotation_interval_days: settings.credentials_rotation_interval_days,
+            preview_environment_ttl_hours: settings.preview_environment_ttl_hours,
+            volume_deletion_grace_period_hours: settings.volume_deletion_grace_period_hours,
         }
     }
 
     pub async fn start(&self) -> ServerAction {
         select! {
             _ = self.refresh_dns() => ServerAction::CloseDueToUnexpectedError,
+            _ = self.rotate_services_credentials() => ServerAction::CloseDueToUnexpectedError,
+            _ = self.back_up_services() => ServerAction::CloseDueToUnexpectedError,
+            _ = self.purge_deleted_volumes() => ServerAction::CloseDueToUnexpectedError,
+            _ = self.tear_down_expired_previews() => ServerAction::CloseDueToUnexpectedError,
+            _ = self.check_image_updates() => ServerAction::CloseDueToUnexpectedError,
+            _ = self.run_scheduled_jobs() => ServerAction::CloseDueToUnexpectedError,
+            _ = self.collect_registry_garbage() => ServerAction::CloseDueToUnexpectedError,
+            _ = self.refresh_oidc_jwks() => ServerAction::CloseDueToUnexpectedError,
             worker_return_action = self.refresh_tls_certificates() => worker_return_action,
         }
     }
@@ -39,7 +54,9 @@ impl Worker {
     async fn refresh_dns(&self) {
         loop {
             sleep(Duration::from_secs(60)).await;
-            if let Some(mut dynamic_dns_manager) = self.dynamic_dns_manager.lock().await.take() {
+            if let Some(mut dynamic_dns_manager) =
+                self.state.dynamic_dns_manager.lock().await.take()
+            {
                 let refresh_result = dynamic_dns_manager.refresh().await;
 
                 if let Err(error) = refresh_result {
@@ -51,10 +68,18 @@ impl Worker {
         }
     }
 
+    async fn refresh_oidc_jwks(&self) {
+        loop {
+            sleep(Duration::from_secs(60)).await;
+            self.state.oidc_manager.refresh_expired_jwks().await;
+        }
+    }
+
     async fn refresh_tls_certificates(&self) -> ServerAction {
         loop {
             sleep(Duration::from_secs(60)).await;
             match self
+                .state
                 .lets_encrypt_manager
                 .lock()
                 .await
@@ -72,4 +97,235 @@ impl Worker {
             }
         }
     }
+
+    async fn rotate_services_credentials(&self) {
+        loop {
+            sleep(Duration::from_secs(3600)).await;
+            if self.credentials_rotation_interval_days == 0 {
+                continue;
+            }
+
+            let service_names = match self
+                .state
+                .db_manager
+                .get_services_due_for_credentials_rotation(
+                    self.credentials_rotation_interval_days as i32,
+                )
+                .await
+            {
+                Ok(service_names) => service_names,
+                Err(error) => {
+                    tracing::error!("rotate credentials job failed: {}", error);
+                    continue;
+                }
+            };
+
+            for service_name in service_names {
+                if let Err(error) = rotate_service_credentials(&self.state, &service_name).await {
+                    tracing::error!(
+                        "failed to rotate credentials of service {}: {}",
+                        service_name,
+                        error
+                    );
+                }
+            }
+        }
+    }
+
+    async fn back_up_services(&self) {
+        loop {
+            sleep(Duration::from_secs(600)).await;
+            let service_names = match self.state.db_manager.get_services_due_for_backup().await {
+                Ok(service_names) => service_names,
+                Err(error) => {
+                    tracing::error!("back up services job failed: {}", error);
+                    continue;
+                }
+            };
+
+            for service_name in service_names {
+                if let Err(error) = self.back_up_service(&service_name).await {
+                    tracing::error!("failed to back up service {}: {}", service_name, error);
+                }
+            }
+        }
+    }
+
+    async fn back_up_service(&self, service_name: &str) -> Result<(), Error> {
+        let backup_policy = self
+            .state
+            .db_manager
+            .get_service_data(service_name)
+            .await?
+            .and_then(|service| service.container_configuration.backup_policy);
+        if let Some(backup_policy) = backup_policy {
+            create_service_backup(&self.state, service_name, BackupTrigger::Scheduled).await?;
+            apply_backup_retention(&self.state, service_name, backup_policy.retention_count)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn purge_deleted_volumes(&self) {
+        loop {
+            sleep(Duration::from_secs(600)).await;
+            let deleted_volumes = match self
+                .state
+                .db_manager
+                .get_expired_deleted_volumes(self.volume_deletion_grace_period_hours as i32)
+                .await
+            {
+                Ok(deleted_volumes) => deleted_volumes,
+                Err(error) => {
+                    tracing::error!("purge deleted volumes job failed: {}", error);
+                    continue;
+                }
+            };
+
+            for deleted_volume in deleted_volumes {
+                let purge_result = match self
+                    .state
+                    .container_manager
+                    .remove_volume(&deleted_volume.volume_id)
+                    .await
+                {
+                    Ok(()) => {
+                        self.state
+                            .db_manager
+                            .remove_deleted_volume(
+                                &deleted_volume.service_name,
+                                &deleted_volume.volume_id,
+                            )
+                            .await
+                    }
+                    Err(error) => Err(error),
+                };
+                match purge_result {
+                    Ok(_) => tracing::info!("purged volume {}", deleted_volume.volume_id),
+                    Err(error) => tracing::error!(
+                        "failed to purge volume {}: {}",
+                        deleted_volume.volume_id,
+                        error
+                    ),
+                }
+            }
+        }
+    }
+
+    async fn tear_down_expired_previews(&self) {
+        loop {
+            sleep(Duration::from_secs(600)).await;
+            let preview_environments = match self
+                .state
+                .db_manager
+                .get_expired_preview_environments(self.preview_environment_ttl_hours as i32)
+                .await
+            {
+                Ok(preview_environments) => preview_environments,
+                Err(error) => {
+                    tracing::error!("tear down expired previews job failed: {}", error);
+                    continue;
+                }
+            };
+
+            for preview_environment in preview_environments {
+                if let Err(error) = tear_down_preview(&self.state, &preview_environment.name).await
+                {
+                    tracing::error!(
+                        "failed to tear down preview environment {}: {}",
+                        preview_environment.name,
+                        error
+                    );
+                }
+            }
+        }
+    }
+
+    async fn check_image_updates(&self) {
+        loop {
+            sleep(Duration::from_secs(60)).await;
+            let service_names = match self
+                .state
+                .db_manager
+                .get_services_due_for_image_update_check()
+                .await
+            {
+                Ok(service_names) => service_names,
+                Err(error) => {
+                    tracing::error!("check image updates job failed: {}", error);
+                    continue;
+                }
+            };
+
+            for service_name in service_names {
+                if let Err(error) = check_image_update(&self.state, &service_name).await {
+                    tracing::error!(
+                        "failed to check image update of service {}: {}",
+                        service_name,
+                        error
+                    );
+                }
+            }
+        }
+    }
+
+    async fn run_scheduled_jobs(&self) {
+        loop {
+            sleep(Duration::from_secs(60)).await;
+            let services = match self.state.db_manager.get_services_data().await {
+                Ok(services) => services,
+                Err(error) => {
+                    tracing::error!("run scheduled jobs job failed: {}", error);
+                    continue;
+                }
+            };
+
+            for service in services {
+                for job in service.container_configuration.scheduled_jobs.iter() {
+                    if let Err(error) = run_due_scheduled_job(&self.state, &service, job).await {
+                        tracing::error!(
+                            "failed to run job {} of service {}: {}",
+                            job.name,
+                            service.container_configuration.name,
+                            error
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    async fn collect_registry_garbage(&self) {
+        loop {
+            sleep(Duration::from_secs(3600)).await;
+            let referenced_digests = match self
+                .state
+                .db_manager
+                .prune_registry_blobs(REGISTRY_BLOB_GRACE_PERIOD_MINUTES)
+                .await
+            {
+                Ok(referenced_digests) => referenced_digests,
+                Err(error) => {
+                    tracing::error!("registry garbage collection job failed: {}", error);
+                    continue;
+                }
+            };
+
+            match self
+                .state
+                .registry_manager
+                .collect_garbage(&referenced_digests)
+                .await
+            {
+                Ok((0, 0)) => {}
+                Ok((removed_blobs_count, removed_uploads_count)) => tracing::info!(
+                    "registry garbage collection removed {} blobs and {} abandoned uploads",
+                    removed_blobs_count,
+                    removed_uploads_count
+                ),
+                Err(error) => tracing::error!("registry garbage collection job failed: {}", error),
+            }
+        }
+    }
 }
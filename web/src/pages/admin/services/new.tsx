@@ -21,6 +21,7 @@ export const AdminServicesNew: Component = () => {
 			statefulVolumePaths: [],
 			githubRepository: null,
 			requiredRole: null,
+			accessList: null,
 		},
 	);
 
@@ -6,10 +6,56 @@ export enum Role {
 export type User = {
 	username: string;
 	role: Role;
+	customRole: string | null;
 };
 
-type EnvironmentVariable = { name: string; value: string };
+type SecretFile = { path: string; mode?: number; ownerId?: number | null };
+type EnvironmentVariable = { name: string; value: string; file?: SecretFile };
+type SharedSecretReference = {
+	name: string;
+	secretName: string;
+	version?: number | null;
+	file?: SecretFile;
+};
+type BackupPolicy = { intervalHours: number; retentionCount: number };
+type SharedVolumeMount = { name: string; path: string; readOnly?: boolean };
+type BindMount = { hostPath: string; path: string; readOnly?: boolean };
 type GithubRepository = { name: string; owner: string };
+type SourceRepository = { issuer: string; path: string };
+type DeployPolicy = {
+	allowedRefs?: string[];
+	environment?: string | null;
+	workflowPath?: string | null;
+	allowedActors?: string[];
+	repositoryId?: string | null;
+	previewRefs?: string[];
+	previewSharedSecrets?: SharedSecretReference[];
+};
+type ImageUpdatePolicy = {
+	tag: string;
+	mode: "notify" | "deploy";
+	checkIntervalMinutes?: number;
+};
+type PrivateNetworkMembership = { name: string; alias: string };
+type Sidecar = {
+	name: string;
+	imageName?: string | null;
+	imageSha?: { value: string } | null;
+	command?: string[] | null;
+	environmentVariables?: EnvironmentVariable[];
+};
+type ScheduledJob = {
+	name: string;
+	schedule: string;
+	command?: string[] | null;
+	timeoutSeconds?: number;
+	concurrencyPolicy?: "allow" | "forbid" | "replace";
+};
+type ServiceAccessList = {
+	usernames: string[];
+	groups: string[];
+	services: string[];
+};
 
 export type ContainerConfiguration = {
 	name: string;
@@ -18,10 +64,24 @@ export type ContainerConfiguration = {
 	exposedPort: { internal: number; external: number };
 	environmentVariables: EnvironmentVariable[];
 	secrets: EnvironmentVariable[];
+	sharedSecrets?: SharedSecretReference[];
 	internalSecrets: EnvironmentVariable[];
 	statefulVolumePaths: string[];
 	githubRepository: GithubRepository | null;
+	sourceRepository?: SourceRepository | null;
 	requiredRole: Role | null;
+	accessList: ServiceAccessList | null;
+	internalSecretsAsFiles?: boolean;
+	internalSecretsOwnerId?: number | null;
+	backupPolicy?: BackupPolicy | null;
+	sharedVolumes?: SharedVolumeMount[];
+	bindMounts?: BindMount[];
+	deployPolicy?: DeployPolicy | null;
+	pullPolicy?: "always" | "ifNotPresent";
+	imageUpdatePolicy?: ImageUpdatePolicy | null;
+	privateNetwork?: PrivateNetworkMembership | null;
+	sidecars?: Sidecar[];
+	scheduledJobs?: ScheduledJob[];
 };
 
 export type Service = {
@@ -29,6 +89,50 @@ export type Service = {
 	createdAt: Date;
 	lastModifiedAt: Date;
 	lastDeployedAt: Date;
+	credentialsRotatedAt: Date;
+};
+
+export type PreviewEnvironment = {
+	name: string;
+	parentServiceName: string;
+	pullRequest: number;
+	createdAt: Date;
+	lastDeployedAt: Date;
+};
+
+export type Deployment = {
+	id: string;
+	serviceName: string;
+	imageSha: string;
+	previousImageSha: string | null;
+	trigger: "manual" | "ci" | "preview" | "imageUpdate";
+	createdAt: Date;
+};
+
+export type JobRun = {
+	id: string;
+	serviceName: string;
+	jobName: string;
+	status:
+		| "running"
+		| "succeeded"
+		| "failed"
+		| "timedOut"
+		| "cancelled"
+		| "skipped";
+	exitCode: number | null;
+	error: string | null;
+	scheduledAt: Date | null;
+	startedAt: Date;
+	finishedAt: Date | null;
+};
+
+export type TrackedImageTag = {
+	serviceName: string;
+	tag: string;
+	imageSha: string | null;
+	error: string | null;
+	checkedAt: Date;
 };
 
 export enum ContainerLogType {